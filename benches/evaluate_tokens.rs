@@ -0,0 +1,77 @@
+#![allow(clippy::needless_return)]
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use tazor::evaluator::{evaluate_tokens, BuiltinEvaluator};
+use tazor::token::{tokenize, Token};
+use tazor::value::EvaluateValue;
+
+/// Terms of a scalar expression, read by `evaluate_tokens`
+const SCALAR_TERMS: [&str; 4] = [
+    "(1.5 * -2 + 3)",
+    "4 / 2 ^ 2",
+    "-(7 - 2.25)",
+    "0.5 * (3 + 1)",
+];
+
+/// Terms of an expression calling functions on vectors, which is not scalar and thus evaluated by parser
+const FUNCTION_TERMS: [&str; 4] = [
+    "(1.5 * -2 + 3)",
+    "len([1, 2, 3, 4]) / 2 ^ 2",
+    "-(7 - dot([1, 2], [0.25, 1]))",
+    "0.5 * (3 + 1)",
+];
+
+/// Build a synthetic expression of about 1 MB mixing given terms with operators
+fn large_expression(terms: &[&str]) -> String {
+    let mut expression: String = String::from("1");
+    let mut id: usize = 0;
+
+    while expression.len() < 1_000_000 {
+        expression.push_str(" + ");
+        expression.push_str(terms[id % terms.len()]);
+        id += 1;
+    }
+
+    return expression;
+}
+
+fn bench_evaluate_tokens(c: &mut Criterion) {
+    let expression: String = large_expression(&SCALAR_TERMS);
+    let tokens: Vec<Token> = tokenize(expression.as_str());
+    let evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+
+    let mut group = c.benchmark_group("large expression");
+    group.sample_size(10);
+
+    group.bench_function("evaluate_tokens", |b| {
+        b.iter(|| evaluate_tokens(black_box(tokens.as_slice())))
+    });
+    group.bench_function("tokenize and evaluate_tokens", |b| {
+        b.iter(|| evaluate_tokens(&tokenize(black_box(expression.as_str()))))
+    });
+    group.bench_function("BuiltinEvaluator", |b| {
+        b.iter(|| evaluator.evaluate(black_box(expression.as_str())))
+    });
+
+    group.finish();
+}
+
+fn bench_evaluate_non_scalar(c: &mut Criterion) {
+    let expression: String = large_expression(&FUNCTION_TERMS);
+    let evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+
+    let mut group = c.benchmark_group("large expression calling functions");
+    group.sample_size(10);
+
+    assert!(evaluator.evaluate(expression.as_str()).is_ok());
+
+    group.bench_function("BuiltinEvaluator", |b| {
+        b.iter(|| evaluator.evaluate(black_box(expression.as_str())))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_evaluate_tokens, bench_evaluate_non_scalar);
+criterion_main!(benches);
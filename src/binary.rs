@@ -0,0 +1,697 @@
+//! Compact binary format of calculator state, to save a session without a serialization crate
+//!
+//! All integers are little-endian. A state is written as follows:
+//!
+//! - header `TZB` followed by version byte `3`
+//! - count of variables as `u32`, then each variable as its name and its value
+//! - count of functions as `u32`, then each function as its name, count of its variables
+//!   as `u32`, its variables and its definition
+//! - count of references as `u32`, then each reference as its name and the name of its target
+//! - count of lazy variables as `u32`, then each lazy variable as its name and its definition
+//! - count of constants as `u32`, then name of each constant
+//! - count of ranges as `u32`, then each range as name of its variable, its minimum and maximum
+//!   as `f64`, and its step as byte `0` if it has none or byte `1` followed by step as `f64`
+//!
+//! A string is its length in bytes as `u32` followed by its UTF-8 bytes. A value is a tag byte
+//! followed by its content: `0` number as `f64`, `1` bool as byte `0` or `1`, `2` integer as `i64`,
+//! `3` vector as count of elements `u32` followed by elements `f64`, `4` decimal as 16 bytes given
+//! by `rust_decimal::Decimal::serialize`, `5` complex as real and imaginary parts `f64`.
+//! Definitions are sorted by name, so that a state is always written the same way.
+//! A state of version `1`, without references, lazy variables and constants, or of version `2`,
+//! without ranges, can still be read.
+
+use crate::value::Value;
+use crate::{CalculatorState, VariableRange};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+
+/// Header of binary state, followed by version byte
+const HEADER: &[u8; 3] = b"TZB";
+
+/// Version of format written by this release
+const VERSION: u8 = 3;
+
+/// Version of format without references, lazy variables and constants
+const VERSION_WITHOUT_REFERENCES: u8 = 1;
+
+/// Version of format without ranges
+const VERSION_WITHOUT_RANGES: u8 = 2;
+
+/// Maximal capacity allocated before reading elements, so that a corrupted count cannot give a huge allocation
+const MAX_PREALLOCATION: usize = 1024;
+
+/// Tags of value types
+const NUMBER_TAG: u8 = 0;
+const BOOL_TAG: u8 = 1;
+const INTEGER_TAG: u8 = 2;
+const VECTOR_TAG: u8 = 3;
+#[cfg(feature = "decimal")]
+const DECIMAL_TAG: u8 = 4;
+#[cfg(feature = "complex")]
+const COMPLEX_TAG: u8 = 5;
+
+/// Write a state in binary format, see `Calculator::save_binary`
+pub(crate) fn write<W: Write>(state: &CalculatorState, mut writer: W) -> io::Result<()> {
+    let mut variable_names: Vec<&String> = state.variables.keys().collect();
+    variable_names.sort_unstable();
+
+    let mut function_names: Vec<&String> = state.functions.keys().collect();
+    function_names.sort_unstable();
+
+    let mut reference_names: Vec<&String> = state.references.keys().collect();
+    reference_names.sort_unstable();
+
+    let mut lazy_names: Vec<&String> = state.lazy_definitions.keys().collect();
+    lazy_names.sort_unstable();
+
+    let mut constant_names: Vec<&String> = state.constants.iter().collect();
+    constant_names.sort_unstable();
+
+    let mut range_names: Vec<&String> = state.variable_ranges.keys().collect();
+    range_names.sort_unstable();
+
+    writer.write_all(HEADER)?;
+    writer.write_all(&[VERSION])?;
+
+    write_length(&mut writer, variable_names.len())?;
+
+    for name in variable_names {
+        write_string(&mut writer, name)?;
+        write_value(&mut writer, &state.variables[name])?;
+    }
+
+    write_length(&mut writer, function_names.len())?;
+
+    for name in function_names {
+        let (variables, definition) = &state.functions[name];
+
+        write_string(&mut writer, name)?;
+        write_length(&mut writer, variables.len())?;
+
+        for variable in variables {
+            write_string(&mut writer, variable)?;
+        }
+
+        write_string(&mut writer, definition)?;
+    }
+
+    write_length(&mut writer, reference_names.len())?;
+
+    for name in reference_names {
+        write_string(&mut writer, name)?;
+        write_string(&mut writer, &state.references[name])?;
+    }
+
+    write_length(&mut writer, lazy_names.len())?;
+
+    for name in lazy_names {
+        write_string(&mut writer, name)?;
+        write_string(&mut writer, &state.lazy_definitions[name])?;
+    }
+
+    write_length(&mut writer, constant_names.len())?;
+
+    for name in constant_names {
+        write_string(&mut writer, name)?;
+    }
+
+    write_length(&mut writer, range_names.len())?;
+
+    for name in range_names {
+        let range: &VariableRange = &state.variable_ranges[name];
+
+        write_string(&mut writer, name)?;
+        write_float(&mut writer, range.min)?;
+        write_float(&mut writer, range.max)?;
+
+        match range.step {
+            Some(step) => {
+                writer.write_all(&[1])?;
+                write_float(&mut writer, step)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+    }
+
+    return writer.flush();
+}
+
+/// Read a state written by `write`, see `Calculator::load_binary`
+///
+/// A name written twice or data after the state gives an error. Names and definitions are not checked,
+/// see `validation::check_state`.
+pub(crate) fn read<R: Read>(mut reader: R) -> io::Result<CalculatorState> {
+    let mut header: [u8; 4] = [0; 4];
+    reader.read_exact(&mut header)?;
+
+    if &header[..3] != HEADER {
+        return Err(invalid_data("The data is not a binary state of calculator"));
+    }
+
+    if ![VERSION, VERSION_WITHOUT_RANGES, VERSION_WITHOUT_REFERENCES].contains(&header[3]) {
+        return Err(invalid_data(
+            format!("The binary state has an unknown version {}", header[3]).as_str(),
+        ));
+    }
+
+    let variable_count: usize = read_length(&mut reader)?;
+    let mut variables: HashMap<String, Value> =
+        HashMap::with_capacity(variable_count.min(MAX_PREALLOCATION));
+
+    for _ in 0..variable_count {
+        let name: String = read_string(&mut reader)?;
+        let value: Value = read_value(&mut reader)?;
+
+        if variables.contains_key(&name) {
+            return Err(invalid_data(
+                format!("The variable {} is written twice in binary state", name).as_str(),
+            ));
+        }
+
+        variables.insert(name, value);
+    }
+
+    let function_count: usize = read_length(&mut reader)?;
+    let mut functions: HashMap<String, (Vec<String>, String)> =
+        HashMap::with_capacity(function_count.min(MAX_PREALLOCATION));
+
+    for _ in 0..function_count {
+        let name: String = read_string(&mut reader)?;
+
+        let variable_count: usize = read_length(&mut reader)?;
+        let mut function_variables: Vec<String> =
+            Vec::with_capacity(variable_count.min(MAX_PREALLOCATION));
+
+        for _ in 0..variable_count {
+            function_variables.push(read_string(&mut reader)?);
+        }
+
+        let definition: String = read_string(&mut reader)?;
+
+        if functions.contains_key(&name) {
+            return Err(invalid_data(
+                format!("The function {} is written twice in binary state", name).as_str(),
+            ));
+        }
+
+        functions.insert(name, (function_variables, definition));
+    }
+
+    let reference_count: usize = match header[3] {
+        VERSION_WITHOUT_REFERENCES => 0,
+        _ => read_length(&mut reader)?,
+    };
+    let mut references: HashMap<String, String> =
+        HashMap::with_capacity(reference_count.min(MAX_PREALLOCATION));
+
+    for _ in 0..reference_count {
+        let name: String = read_string(&mut reader)?;
+        let target: String = read_string(&mut reader)?;
+
+        if references.contains_key(&name) {
+            return Err(invalid_data(
+                format!("The reference {} is written twice in binary state", name).as_str(),
+            ));
+        }
+
+        references.insert(name, target);
+    }
+
+    let lazy_count: usize = match header[3] {
+        VERSION_WITHOUT_REFERENCES => 0,
+        _ => read_length(&mut reader)?,
+    };
+    let mut lazy_definitions: HashMap<String, String> =
+        HashMap::with_capacity(lazy_count.min(MAX_PREALLOCATION));
+
+    for _ in 0..lazy_count {
+        let name: String = read_string(&mut reader)?;
+        let definition: String = read_string(&mut reader)?;
+
+        if lazy_definitions.contains_key(&name) {
+            return Err(invalid_data(
+                format!(
+                    "The lazy variable {} is written twice in binary state",
+                    name
+                )
+                .as_str(),
+            ));
+        }
+
+        lazy_definitions.insert(name, definition);
+    }
+
+    let constant_count: usize = match header[3] {
+        VERSION_WITHOUT_REFERENCES => 0,
+        _ => read_length(&mut reader)?,
+    };
+    let mut constants: HashSet<String> =
+        HashSet::with_capacity(constant_count.min(MAX_PREALLOCATION));
+
+    for _ in 0..constant_count {
+        let name: String = read_string(&mut reader)?;
+
+        if !constants.insert(name.clone()) {
+            return Err(invalid_data(
+                format!("The constant {} is written twice in binary state", name).as_str(),
+            ));
+        }
+    }
+
+    let range_count: usize = match header[3] {
+        VERSION_WITHOUT_REFERENCES | VERSION_WITHOUT_RANGES => 0,
+        _ => read_length(&mut reader)?,
+    };
+    let mut variable_ranges: HashMap<String, VariableRange> =
+        HashMap::with_capacity(range_count.min(MAX_PREALLOCATION));
+
+    for _ in 0..range_count {
+        let name: String = read_string(&mut reader)?;
+        let min: f64 = read_float(&mut reader)?;
+        let max: f64 = read_float(&mut reader)?;
+
+        let mut has_step: [u8; 1] = [0; 1];
+        reader.read_exact(&mut has_step)?;
+
+        let step: Option<f64> = match has_step[0] {
+            0 => None,
+            1 => Some(read_float(&mut reader)?),
+            byte => {
+                return Err(invalid_data(
+                    format!("The byte {} is not a valid step marker", byte).as_str(),
+                ));
+            }
+        };
+
+        if variable_ranges.contains_key(&name) {
+            return Err(invalid_data(
+                format!("The range of {} is written twice in binary state", name).as_str(),
+            ));
+        }
+
+        variable_ranges.insert(name, VariableRange { min, max, step });
+    }
+
+    if reader.read(&mut [0; 1])? != 0 {
+        return Err(invalid_data(
+            "The binary state is followed by unexpected data",
+        ));
+    }
+
+    return Ok(CalculatorState {
+        variables,
+        functions,
+        references,
+        lazy_definitions,
+        constants,
+        variable_ranges,
+    });
+}
+
+/// Build an error of invalid data with given message
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+/// Write a length as `u32`, a length which does not fit gives an error
+fn write_length<W: Write>(writer: &mut W, length: usize) -> io::Result<()> {
+    let length: u32 = u32::try_from(length)
+        .map_err(|_| invalid_data("The length is too large for binary state"))?;
+
+    return writer.write_all(&length.to_le_bytes());
+}
+
+/// Read a length written by `write_length`
+fn read_length<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let mut bytes: [u8; 4] = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    return Ok(u32::from_le_bytes(bytes) as usize);
+}
+
+/// Write a string as its length followed by its bytes
+fn write_string<W: Write>(writer: &mut W, text: &str) -> io::Result<()> {
+    write_length(writer, text.len())?;
+
+    return writer.write_all(text.as_bytes());
+}
+
+/// Read a string written by `write_string`
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let length: usize = read_length(reader)?;
+
+    // Bytes are read through take so that a corrupted length stops at end of data
+    let mut bytes: Vec<u8> = Vec::with_capacity(length.min(MAX_PREALLOCATION));
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+
+    if bytes.len() != length {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    return String::from_utf8(bytes).map_err(|_| invalid_data("The string is not valid UTF-8"));
+}
+
+/// Write a float as `f64`
+fn write_float<W: Write>(writer: &mut W, float: f64) -> io::Result<()> {
+    return writer.write_all(&float.to_le_bytes());
+}
+
+/// Read a float written by `write_float`
+fn read_float<R: Read>(reader: &mut R) -> io::Result<f64> {
+    let mut bytes: [u8; 8] = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    return Ok(f64::from_le_bytes(bytes));
+}
+
+/// Write a value as its tag followed by its content
+fn write_value<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Number(number) => {
+            writer.write_all(&[NUMBER_TAG])?;
+            write_float(writer, *number)?;
+        }
+        Value::Bool(boolean) => {
+            writer.write_all(&[BOOL_TAG, u8::from(*boolean)])?;
+        }
+        Value::Integer(integer) => {
+            writer.write_all(&[INTEGER_TAG])?;
+            writer.write_all(&integer.to_le_bytes())?;
+        }
+        Value::Vector(elements) => {
+            writer.write_all(&[VECTOR_TAG])?;
+            write_length(writer, elements.len())?;
+
+            for element in elements {
+                write_float(writer, *element)?;
+            }
+        }
+        #[cfg(feature = "decimal")]
+        Value::Decimal(decimal) => {
+            writer.write_all(&[DECIMAL_TAG])?;
+            writer.write_all(&decimal.serialize())?;
+        }
+        #[cfg(feature = "complex")]
+        Value::Complex(complex) => {
+            writer.write_all(&[COMPLEX_TAG])?;
+            write_float(writer, complex.re)?;
+            write_float(writer, complex.im)?;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Read a value written by `write_value`
+fn read_value<R: Read>(reader: &mut R) -> io::Result<Value> {
+    let mut tag: [u8; 1] = [0; 1];
+    reader.read_exact(&mut tag)?;
+
+    return match tag[0] {
+        NUMBER_TAG => Ok(Value::Number(read_float(reader)?)),
+        BOOL_TAG => {
+            let mut boolean: [u8; 1] = [0; 1];
+            reader.read_exact(&mut boolean)?;
+
+            match boolean[0] {
+                0 => Ok(Value::Bool(false)),
+                1 => Ok(Value::Bool(true)),
+                byte => Err(invalid_data(
+                    format!("The byte {} is not a valid bool", byte).as_str(),
+                )),
+            }
+        }
+        INTEGER_TAG => {
+            let mut bytes: [u8; 8] = [0; 8];
+            reader.read_exact(&mut bytes)?;
+
+            Ok(Value::Integer(i64::from_le_bytes(bytes)))
+        }
+        VECTOR_TAG => {
+            let count: usize = read_length(reader)?;
+            let mut elements: Vec<f64> = Vec::with_capacity(count.min(MAX_PREALLOCATION));
+
+            for _ in 0..count {
+                elements.push(read_float(reader)?);
+            }
+
+            Ok(Value::Vector(elements))
+        }
+        #[cfg(feature = "decimal")]
+        DECIMAL_TAG => {
+            let mut bytes: [u8; 16] = [0; 16];
+            reader.read_exact(&mut bytes)?;
+
+            Ok(Value::Decimal(rust_decimal::Decimal::deserialize(bytes)))
+        }
+        #[cfg(feature = "complex")]
+        COMPLEX_TAG => {
+            let re: f64 = read_float(reader)?;
+            let im: f64 = read_float(reader)?;
+
+            Ok(Value::Complex(num_complex::Complex64::new(re, im)))
+        }
+        tag => Err(invalid_data(
+            format!("The value tag {} is unknown", tag).as_str(),
+        )),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Build a state with each kind of value and functions
+    fn sample_state() -> CalculatorState {
+        let mut state: CalculatorState = CalculatorState::default();
+
+        state
+            .variables
+            .insert(String::from("x"), Value::Number(-2.5));
+        state
+            .variables
+            .insert(String::from("flag"), Value::Bool(true));
+        state
+            .variables
+            .insert(String::from("n"), Value::Integer(i64::MIN));
+        state.variables.insert(
+            String::from("v"),
+            Value::Vector(vec![1.0, f64::INFINITY, -0.0]),
+        );
+        state
+            .variables
+            .insert(String::from("empty"), Value::Vector(Vec::new()));
+        state
+            .variables
+            .insert(String::from("énergie"), Value::Number(1.0 / 3.0));
+
+        state.functions.insert(
+            String::from("f"),
+            (
+                vec![String::from("a"), String::from("b")],
+                String::from("a * x + b"),
+            ),
+        );
+        state
+            .functions
+            .insert(String::from("one"), (Vec::new(), String::from("1")));
+
+        state
+            .references
+            .insert(String::from("r"), String::from("x"));
+        state
+            .lazy_definitions
+            .insert(String::from("x"), String::from("-5 / 2"));
+        state.constants.insert(String::from("n"));
+
+        state.variable_ranges.insert(
+            String::from("x"),
+            VariableRange {
+                min: -10.0,
+                max: 10.0,
+                step: Some(0.5),
+            },
+        );
+        state.variable_ranges.insert(
+            String::from("speed"),
+            VariableRange {
+                min: 0.0,
+                max: f64::INFINITY,
+                step: None,
+            },
+        );
+
+        return state;
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let state: CalculatorState = sample_state();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write(&state, &mut buffer).unwrap();
+
+        assert!(buffer.starts_with(b"TZB\x03"));
+        assert_eq!(read(buffer.as_slice()).unwrap(), state);
+
+        // Same state is always written the same way
+        let mut other_buffer: Vec<u8> = Vec::new();
+        write(&state.clone(), &mut other_buffer).unwrap();
+
+        assert_eq!(buffer, other_buffer);
+    }
+
+    #[cfg(all(feature = "decimal", feature = "complex"))]
+    #[test]
+    fn test_binary_round_trip_decimal_complex() {
+        let mut state: CalculatorState = CalculatorState::default();
+        state.variables.insert(
+            String::from("price"),
+            Value::Decimal(rust_decimal::Decimal::new(-1999, 2)),
+        );
+        state.variables.insert(
+            String::from("z"),
+            Value::Complex(num_complex::Complex64::new(1.5, -2.0)),
+        );
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write(&state, &mut buffer).unwrap();
+
+        assert_eq!(read(buffer.as_slice()).unwrap(), state);
+    }
+
+    #[test]
+    fn test_binary_layout() {
+        let mut state: CalculatorState = CalculatorState::default();
+        state.variables.insert(String::from("x"), Value::Bool(true));
+        state.functions.insert(
+            String::from("f"),
+            (vec![String::from("y")], String::from("y")),
+        );
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write(&state, &mut buffer).unwrap();
+
+        assert_eq!(
+            buffer,
+            b"TZB\x03\x01\x00\x00\x00\x01\x00\x00\x00x\x01\x01\x01\x00\x00\x00\x01\x00\x00\x00f\x01\x00\x00\x00\x01\x00\x00\x00y\x01\x00\x00\x00y\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
+        );
+
+        // A state of version 2 has no ranges
+        assert_eq!(
+            read(b"TZB\x02\x01\x00\x00\x00\x01\x00\x00\x00x\x01\x01\x01\x00\x00\x00\x01\x00\x00\x00f\x01\x00\x00\x00\x01\x00\x00\x00y\x01\x00\x00\x00y\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".as_slice())
+                .unwrap(),
+            state
+        );
+
+        // A state of version 1 has no references
+        assert_eq!(
+            read(b"TZB\x01\x01\x00\x00\x00\x01\x00\x00\x00x\x01\x01\x01\x00\x00\x00\x01\x00\x00\x00f\x01\x00\x00\x00\x01\x00\x00\x00y\x01\x00\x00\x00y".as_slice())
+                .unwrap(),
+            state
+        );
+    }
+
+    #[test]
+    fn test_binary_invalid() {
+        let mut buffer: Vec<u8> = Vec::new();
+        write(&sample_state(), &mut buffer).unwrap();
+
+        // Every truncation of data gives an error
+        for length in 0..buffer.len() {
+            assert!(read(&buffer[..length]).is_err());
+        }
+
+        let error: io::Error = read(b"TZX\x01".as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "The data is not a binary state of calculator"
+        );
+
+        assert_eq!(
+            read(b"TZB\x04".as_slice()).unwrap_err().to_string(),
+            "The binary state has an unknown version 4"
+        );
+
+        assert_eq!(
+            read(b"TZB\x01\x01\x00\x00\x00\x01\x00\x00\x00x\x09".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The value tag 9 is unknown"
+        );
+
+        assert_eq!(
+            read(b"TZB\x01\x01\x00\x00\x00\x01\x00\x00\x00\xff\x01\x01".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The string is not valid UTF-8"
+        );
+
+        assert_eq!(
+            read(b"TZB\x01\x02\x00\x00\x00\x01\x00\x00\x00x\x01\x01\x01\x00\x00\x00x\x01\x00\x00\x00\x00\x00".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The variable x is written twice in binary state"
+        );
+
+        assert_eq!(
+            read(b"TZB\x01\x00\x00\x00\x00\x02\x00\x00\x00\x01\x00\x00\x00f\x00\x00\x00\x00\x01\x00\x00\x001\x01\x00\x00\x00f\x00\x00\x00\x00\x01\x00\x00\x002".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The function f is written twice in binary state"
+        );
+
+        assert_eq!(
+            read(b"TZB\x02\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x01\x00\x00\x00r\x01\x00\x00\x00x\x01\x00\x00\x00r\x01\x00\x00\x00y\x00\x00\x00\x00\x00\x00\x00\x00".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The reference r is written twice in binary state"
+        );
+
+        assert_eq!(
+            read(b"TZB\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x01\x00\x00\x00x\x01\x00\x00\x001\x01\x00\x00\x00x\x01\x00\x00\x002\x00\x00\x00\x00".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The lazy variable x is written twice in binary state"
+        );
+
+        assert_eq!(
+            read(b"TZB\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x01\x00\x00\x00x\x01\x00\x00\x00x".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The constant x is written twice in binary state"
+        );
+
+        assert_eq!(
+            read(b"TZB\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x01\x00\x00\x00x\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xf0\x3f\x02".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The byte 2 is not a valid step marker"
+        );
+
+        assert_eq!(
+            read(b"TZB\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x01\x00\x00\x00x\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xf0\x3f\x00\x01\x00\x00\x00x\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xf0\x3f\x00".as_slice())
+                .unwrap_err()
+                .to_string(),
+            "The range of x is written twice in binary state"
+        );
+
+        let mut trailing_buffer: Vec<u8> = buffer.clone();
+        trailing_buffer.push(0);
+
+        assert_eq!(
+            read(trailing_buffer.as_slice()).unwrap_err().to_string(),
+            "The binary state is followed by unexpected data"
+        );
+
+        // A huge length does not allocate before data ends
+        assert_eq!(
+            read(b"TZB\x01\xff\xff\xff\xff\xff\xff\xff\xffx".as_slice())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
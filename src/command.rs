@@ -0,0 +1,278 @@
+use crate::locale::NumberLocale;
+use crate::notation::Notation;
+use crate::validation;
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// Names of commands provided by calculator, without leading `:`
+pub const BUILTIN_COMMANDS: [&str; 11] = [
+    "at",
+    "auto",
+    "eng",
+    "fix",
+    "help",
+    "metrics",
+    "preview",
+    "results",
+    "save",
+    "sci",
+    "snapshots",
+];
+
+/// Usage and description of each command provided by calculator, shown by `:help commands`
+pub const BUILTIN_COMMAND_USAGES: [(&str, &str); 11] = [
+    (
+        ":at name expression",
+        "evaluate expression against a saved snapshot",
+    ),
+    (":auto", "write results in shortest decimal form"),
+    (":eng", "write results in engineering notation"),
+    (":fix n", "write results with n decimals"),
+    (":help [topic]", "show help on a topic, or list topics"),
+    (":metrics name", "show size and references of a function"),
+    (
+        ":preview expression",
+        "show effect of an expression without processing it",
+    ),
+    (":results", "list numbered results of raw expressions"),
+    (":save name", "save a snapshot of variables and functions"),
+    (":sci", "write results in scientific notation"),
+    (":snapshots", "list saved snapshots"),
+];
+
+/// Maximal number of decimals of command `:fix`, enough to write any 64-bit float between -1 and 1 exactly
+pub const MAX_FIXED_DECIMALS: usize = 17;
+
+/// Topics of command `:help`
+pub const HELP_TOPICS: [&str; 4] = ["commands", "functions", "operators", "variables"];
+
+/// Handler of a command registered by a frontend
+///
+/// It receives a context giving access to calculator and arguments of command,
+/// it returns the result string of command or an error message.
+/// It is `Send`, so that calculator can be moved to another thread. Calculator keeps it behind
+/// a mutex, thus it does not need to be `Sync` for calculator to be shared behind a lock.
+pub type CommandHandler =
+    Box<dyn FnMut(&mut CommandContext, &[&str]) -> Result<String, String> + Send>;
+
+/// Context given to a registered command handler
+///
+/// Variables and functions can be read and modified, names are validated before any modification.
+/// History of processed inputs and configuration of calculator can only be read.
+///
+pub struct CommandContext<'a> {
+    variables: &'a mut HashMap<String, Value>, // variables defined in calculator
+    functions: &'a mut HashMap<String, (Vec<String>, String)>, // functions defined in calculator
+    history: &'a [String],                     // inputs processed successfully by calculator
+    notation: Notation,                        // notation used to write numbers of results
+    number_locale: NumberLocale, // convention used to read numbers of expression and write results
+    strict_mode: bool,           // if true, expression is validated before its process
+}
+
+impl<'a> CommandContext<'a> {
+    /// Construct a context from parts of calculator
+    pub(crate) fn new(
+        variables: &'a mut HashMap<String, Value>,
+        functions: &'a mut HashMap<String, (Vec<String>, String)>,
+        history: &'a [String],
+        notation: Notation,
+        number_locale: NumberLocale,
+        strict_mode: bool,
+    ) -> Self {
+        return Self {
+            variables,
+            functions,
+            history,
+            notation,
+            number_locale,
+            strict_mode,
+        };
+    }
+
+    /// Get variables defined in calculator
+    pub fn variables(&self) -> &HashMap<String, Value> {
+        return self.variables;
+    }
+
+    /// Define or redefine a variable, its name must be a valid identifier
+    pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), String> {
+        if !validation::is_identifier(name) {
+            return Err(format!("The variable name {} is not valid", name));
+        }
+
+        self.variables.insert(String::from(name), value);
+
+        return Ok(());
+    }
+
+    /// Remove a variable and get its value, none if it is not defined
+    pub fn remove_variable(&mut self, name: &str) -> Option<Value> {
+        return self.variables.remove(name);
+    }
+
+    /// Get functions defined in calculator, value is a pair containing name of variables and definition
+    pub fn functions(&self) -> &HashMap<String, (Vec<String>, String)> {
+        return self.functions;
+    }
+
+    /// Define or redefine a function, its name and names of its variables must be valid identifiers
+    pub fn set_function(
+        &mut self,
+        name: &str,
+        variables: &[&str],
+        definition: &str,
+    ) -> Result<(), String> {
+        if !validation::is_identifier(name) {
+            return Err(format!("The function name {} is not valid", name));
+        }
+
+        if let Some(variable) = variables
+            .iter()
+            .find(|variable| !validation::is_identifier(variable))
+        {
+            return Err(format!("The variable name {} is not valid", variable));
+        }
+
+        self.functions.insert(
+            String::from(name),
+            (
+                variables
+                    .iter()
+                    .map(|variable| String::from(*variable))
+                    .collect(),
+                String::from(definition),
+            ),
+        );
+
+        return Ok(());
+    }
+
+    /// Remove a function, it returns true if function was defined
+    pub fn remove_function(&mut self, name: &str) -> bool {
+        return self.functions.remove(name).is_some();
+    }
+
+    /// Get inputs processed successfully by calculator, from oldest to newest
+    pub fn history(&self) -> &[String] {
+        return self.history;
+    }
+
+    /// Get notation used to write numbers of results
+    pub fn notation(&self) -> Notation {
+        return self.notation;
+    }
+
+    /// Get convention used to read numbers of expression and write results
+    pub fn number_locale(&self) -> NumberLocale {
+        return self.number_locale;
+    }
+
+    /// Check if calculator validates expressions before their process
+    pub fn strict_mode(&self) -> bool {
+        return self.strict_mode;
+    }
+}
+
+/// Write a help topic given its entries, each entry is a syntax followed by its description
+///
+/// Descriptions are aligned after the longest syntax, as
+/// ```text
+/// help on commands:
+///   :auto          write results in shortest decimal form
+///   :help [topic]  show help on a topic, or list topics
+/// ```
+pub fn write_help(topic: &str, entries: &[(String, String)]) -> String {
+    let syntax_width: usize = entries
+        .iter()
+        .map(|(syntax, _)| syntax.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut help: String = format!("help on {}:", topic);
+
+    for (syntax, description) in entries {
+        help.push_str(format!("\n  {:<2$}  {}", syntax, description, syntax_width).as_str());
+    }
+
+    return help;
+}
+
+/// Split a command into its name and its arguments
+///
+/// The leading `:` is removed and arguments are separated by whitespaces,
+/// except inside double quotes, thus `:plot "f(x) + 1" 0 10` has three arguments.
+/// Double quotes surrounding an argument are removed.
+pub fn split_command(command: &str) -> (String, Vec<String>) {
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None;
+    let mut is_quoted: bool = false;
+
+    for c in command.trim_start().trim_start_matches(':').chars() {
+        match c {
+            '"' => {
+                is_quoted = !is_quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !is_quoted => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    let mut words = words.into_iter();
+    let name: String = words.next().unwrap_or_default();
+
+    return (name, words.collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_help() {
+        let entries: Vec<(String, String)> = vec![
+            (String::from("x + y"), String::from("addition")),
+            (String::from("len(v)"), String::from("length of vector")),
+        ];
+
+        assert_eq!(
+            write_help("operators", &entries),
+            "help on operators:\n  x + y   addition\n  len(v)  length of vector"
+        );
+
+        assert_eq!(write_help("commands", &[]), "help on commands:");
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(":plot \"f(x) + 1\"  0 10"),
+            (
+                String::from("plot"),
+                vec![
+                    String::from("f(x) + 1"),
+                    String::from("0"),
+                    String::from("10")
+                ]
+            )
+        );
+
+        assert_eq!(
+            split_command(" :echo \"\" a\"b c\""),
+            (
+                String::from("echo"),
+                vec![String::new(), String::from("ab c")]
+            )
+        );
+
+        assert_eq!(split_command(":"), (String::new(), Vec::new()));
+    }
+}
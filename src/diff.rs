@@ -0,0 +1,293 @@
+use crate::value::Value;
+use crate::CalculatorState;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Change of a variable or a function between two states of calculator
+///
+/// Added and Removed contain name and definition, Modified contains name, old and new definitions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<Definition> {
+    Added(String, Definition),
+    Removed(String, Definition),
+    Modified(String, Definition, Definition),
+}
+
+impl<Definition> Change<Definition> {
+    /// Get name of changed variable or function
+    pub fn name(&self) -> &str {
+        return match self {
+            Self::Added(name, _) | Self::Removed(name, _) | Self::Modified(name, _, _) => {
+                name.as_str()
+            }
+        };
+    }
+}
+
+/// Differences between two states of calculator, as the ones before and after replay of a script
+///
+/// Changes of variables, references, lazy variables and functions are sorted by name, so that report is deterministic.
+/// Values of variables are compared by exact bit equality, since they are stored values,
+/// thus `0` and `-0` are different whereas two NaN with the same bits are identical.
+///
+/// Its display is a report similar to a unified diff, where each removed definition is written
+/// after `-` and each added definition after `+`, a modified definition giving both lines.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    pub variables: Vec<Change<Value>>, // changes of variables, sorted by name
+    pub references: Vec<Change<String>>, // changes of references, sorted by name, definition is name of target
+    pub lazy_definitions: Vec<Change<String>>, // changes of definitions of lazy variables, sorted by name
+    pub functions: Vec<Change<(Vec<String>, String)>>, // changes of functions, sorted by name, definition is (variables, definition)
+}
+
+impl StateDiff {
+    /// Compute differences from old state to new state given in arguments
+    pub fn between(old_state: &CalculatorState, new_state: &CalculatorState) -> Self {
+        return Self {
+            variables: changes(&old_state.variables, &new_state.variables, is_same_value),
+            references: changes(&old_state.references, &new_state.references, |old, new| {
+                old == new
+            }),
+            lazy_definitions: changes(
+                &old_state.lazy_definitions,
+                &new_state.lazy_definitions,
+                |old, new| old == new,
+            ),
+            functions: changes(&old_state.functions, &new_state.functions, |old, new| {
+                old == new
+            }),
+        };
+    }
+
+    /// Check if states are identical
+    pub fn is_empty(&self) -> bool {
+        return self.variables.is_empty()
+            && self.references.is_empty()
+            && self.lazy_definitions.is_empty()
+            && self.functions.is_empty();
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_variable = |name: &str, value: &Value| format!("{} = {}", name, value);
+        let write_reference =
+            |name: &str, target: &String| format!("reference {} = {}", name, target);
+        let write_lazy =
+            |name: &str, definition: &String| format!("lazy {} = {}", name, definition);
+        let write_function = |name: &str, (variables, definition): &(Vec<String>, String)| {
+            format!("{}: {} = {}", name, variables.join(", "), definition)
+        };
+
+        let mut lines: Vec<String> = Vec::new();
+        lines.extend(report_lines(&self.variables, write_variable));
+        lines.extend(report_lines(&self.references, write_reference));
+        lines.extend(report_lines(&self.lazy_definitions, write_lazy));
+        lines.extend(report_lines(&self.functions, write_function));
+
+        return write!(f, "{}", lines.join("\n"));
+    }
+}
+
+/// Compute changes of definitions between old and new maps, sorted by name
+fn changes<Definition, IsSame>(
+    old_definitions: &HashMap<String, Definition>,
+    new_definitions: &HashMap<String, Definition>,
+    is_same: IsSame,
+) -> Vec<Change<Definition>>
+where
+    Definition: Clone,
+    IsSame: Fn(&Definition, &Definition) -> bool,
+{
+    let mut changes: Vec<Change<Definition>> = Vec::new();
+
+    for (name, old_definition) in old_definitions {
+        match new_definitions.get(name) {
+            Some(new_definition) if is_same(old_definition, new_definition) => {}
+            Some(new_definition) => changes.push(Change::Modified(
+                name.clone(),
+                old_definition.clone(),
+                new_definition.clone(),
+            )),
+            None => changes.push(Change::Removed(name.clone(), old_definition.clone())),
+        }
+    }
+
+    for (name, new_definition) in new_definitions {
+        if !old_definitions.contains_key(name) {
+            changes.push(Change::Added(name.clone(), new_definition.clone()));
+        }
+    }
+
+    changes.sort_unstable_by(|lhs, rhs| lhs.name().cmp(rhs.name()));
+
+    return changes;
+}
+
+/// Write lines of report for changes given in argument, each definition is written by function given in argument
+fn report_lines<Definition, WriteDefinition>(
+    changes: &[Change<Definition>],
+    write_definition: WriteDefinition,
+) -> Vec<String>
+where
+    WriteDefinition: Fn(&str, &Definition) -> String,
+{
+    let mut lines: Vec<String> = Vec::with_capacity(changes.len());
+
+    for change in changes {
+        match change {
+            Change::Added(name, definition) => {
+                lines.push(format!("+{}", write_definition(name, definition)));
+            }
+            Change::Removed(name, definition) => {
+                lines.push(format!("-{}", write_definition(name, definition)));
+            }
+            Change::Modified(name, old_definition, new_definition) => {
+                lines.push(format!("-{}", write_definition(name, old_definition)));
+                lines.push(format!("+{}", write_definition(name, new_definition)));
+            }
+        }
+    }
+
+    return lines;
+}
+
+/// Check if two values are identical, numbers being compared by exact bit equality
+fn is_same_value(lhs: &Value, rhs: &Value) -> bool {
+    return match (lhs, rhs) {
+        (Value::Number(lhs), Value::Number(rhs)) => lhs.to_bits() == rhs.to_bits(),
+        (Value::Vector(lhs), Value::Vector(rhs)) => {
+            lhs.len() == rhs.len()
+                && lhs
+                    .iter()
+                    .zip(rhs.iter())
+                    .all(|(lhs, rhs)| lhs.to_bits() == rhs.to_bits())
+        }
+        #[cfg(feature = "complex")]
+        (Value::Complex(lhs), Value::Complex(rhs)) => {
+            lhs.re.to_bits() == rhs.re.to_bits() && lhs.im.to_bits() == rhs.im.to_bits()
+        }
+        _ => lhs == rhs,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn state(variables: &[(&str, f64)], functions: &[(&str, &[&str], &str)]) -> CalculatorState {
+        return CalculatorState {
+            variables: variables
+                .iter()
+                .map(|(name, value)| (String::from(*name), Value::Number(*value)))
+                .collect(),
+            functions: functions
+                .iter()
+                .map(|(name, variables, definition)| {
+                    let variables: Vec<String> = variables
+                        .iter()
+                        .map(|variable| String::from(*variable))
+                        .collect();
+                    return (String::from(*name), (variables, String::from(*definition)));
+                })
+                .collect(),
+            references: HashMap::new(),
+            lazy_definitions: HashMap::new(),
+            constants: HashSet::new(),
+            variable_ranges: HashMap::new(),
+        };
+    }
+
+    #[test]
+    fn test_state_diff_changes() {
+        let old_state: CalculatorState = state(
+            &[("a", 1.0), ("b", 2.0), ("zero", 0.0)],
+            &[("f", &["x"], "x + 1"), ("g", &["x"], "x * 2")],
+        );
+
+        let new_state: CalculatorState = state(
+            &[("b", 3.0), ("c", 4.0), ("zero", -0.0)],
+            &[
+                ("f", &["x"], "x + 1"),
+                ("g", &["x", "y"], "x * y"),
+                ("h", &["t"], "t"),
+            ],
+        );
+
+        let diff: StateDiff = StateDiff::between(&old_state, &new_state);
+
+        assert_eq!(
+            diff.variables,
+            vec![
+                Change::Removed(String::from("a"), Value::Number(1.0)),
+                Change::Modified(String::from("b"), Value::Number(2.0), Value::Number(3.0)),
+                Change::Added(String::from("c"), Value::Number(4.0)),
+                Change::Modified(
+                    String::from("zero"),
+                    Value::Number(0.0),
+                    Value::Number(-0.0)
+                ),
+            ]
+        );
+
+        assert_eq!(
+            diff.functions,
+            vec![
+                Change::Modified(
+                    String::from("g"),
+                    (vec![String::from("x")], String::from("x * 2")),
+                    (
+                        vec![String::from("x"), String::from("y")],
+                        String::from("x * y")
+                    )
+                ),
+                Change::Added(
+                    String::from("h"),
+                    (vec![String::from("t")], String::from("t"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_state_diff_identical_states() {
+        let old_state: CalculatorState = state(&[("a", f64::NAN)], &[("f", &["x"], "x + 1")]);
+        let diff: StateDiff = StateDiff::between(&old_state, &old_state.clone());
+
+        assert!(diff.is_empty());
+        assert_eq!(format!("{}", diff), "");
+    }
+
+    #[test]
+    fn test_state_diff_report() {
+        let old_state: CalculatorState = state(
+            &[("y", 1.0), ("x", 1.0)],
+            &[("g", &["t"], "t"), ("f", &["t"], "t")],
+        );
+        let new_state: CalculatorState =
+            state(&[("y", 2.0), ("w", 5.0)], &[("f", &["t"], "2 * t")]);
+
+        for _ in 0..10 {
+            assert_eq!(
+                format!("{}", StateDiff::between(&old_state, &new_state)),
+                "+w = 5\n-x = 1\n-y = 1\n+y = 2\n-f: t = t\n+f: t = 2 * t\n-g: t = t"
+            );
+        }
+
+        let mut referencing_state: CalculatorState = new_state.clone();
+        referencing_state
+            .references
+            .insert(String::from("z"), String::from("w"));
+
+        let diff: StateDiff = StateDiff::between(&new_state, &referencing_state);
+        assert_eq!(
+            diff.references,
+            vec![Change::Added(String::from("z"), String::from("w"))]
+        );
+        assert_eq!(format!("{}", diff), "+reference z = w");
+    }
+}
@@ -0,0 +1,317 @@
+use crate::expression::{self, Expression, InputKind};
+use crate::validation;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// Cause of an error, when calculator identifies it
+///
+/// EmptyInput indicates that input is empty or contains only whitespaces,
+/// see `Calculator::set_empty_input_policy`.
+///
+/// EmptyExpansion indicates that expression given to evaluator is syntactically empty,
+/// i.e. it contains only whitespaces and parenthesis. It contains name of stored function
+/// whose substitution gives empty text, none if input itself is syntactically empty, as `( )`.
+///
+/// UnsafeCharacter indicates that input contains a control or bidirectional character rejected
+/// by calculator, see `validation::is_unsafe_character`. It contains this character and its column.
+///
+/// TooManyExpansions indicates that maximal number of function expansions of a processed expression
+/// is reached, see `Limits::max_function_expansions`. It contains name of outermost function
+/// being expanded, number of performed expansions and the limit.
+///
+/// TokenTooLong indicates that a single token of input is longer than `Limits::max_token_length`.
+/// It contains column where this token starts and the limit.
+///
+/// ExpressionTooLong indicates that input is longer than `Limits::max_expression_length`.
+/// It contains length of input and the limit.
+///
+/// ExpansionTooLong indicates that substitution of functions and variables is stopped since expression
+/// becomes longer than `Limits::max_expression_length`. It contains name of outermost function
+/// being expanded, none if variables are substituted, and the limit.
+///
+/// Evaluator indicates that evaluator fails, it contains error given by evaluator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cause {
+    EmptyInput,
+    EmptyExpansion(Option<String>),
+    UnsafeCharacter(char, usize),
+    TooManyExpansions {
+        function: String,
+        performed: usize,
+        limit: usize,
+    },
+    TokenTooLong {
+        column: usize,
+        limit: usize,
+    },
+    ExpressionTooLong {
+        length: usize,
+        limit: usize,
+    },
+    ExpansionTooLong {
+        function: Option<String>,
+        limit: usize,
+    },
+    Evaluator(EvaluatorError),
+}
+
+/// Error given by evaluator, kept with its original type
+///
+/// Its message is the one written by evaluator, and original error can be retrieved
+/// with `downcast_ref` given error type of evaluator, as `EvaluateValue::Error`.
+/// Two errors are equal if their messages are equal.
+#[derive(Clone)]
+pub struct EvaluatorError {
+    message: Arc<str>,                 // message written by evaluator error
+    error: Arc<dyn Any + Send + Sync>, // original error of evaluator
+}
+
+impl EvaluatorError {
+    /// Construct from an error of evaluator
+    pub fn new<E>(error: E) -> Self
+    where
+        E: fmt::Display + Send + Sync + 'static,
+    {
+        return Self {
+            message: Arc::from(error.to_string()),
+            error: Arc::new(error),
+        };
+    }
+
+    /// Get message written by evaluator error
+    pub fn message(&self) -> &str {
+        return &self.message;
+    }
+
+    /// Get original error of evaluator, none if it is not of given type
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: 'static,
+    {
+        return self.error.downcast_ref::<E>();
+    }
+}
+
+impl fmt::Debug for EvaluatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f
+            .debug_tuple("EvaluatorError")
+            .field(&self.message)
+            .finish();
+    }
+}
+
+impl PartialEq for EvaluatorError {
+    fn eq(&self, other: &Self) -> bool {
+        return self.message == other.message;
+    }
+}
+
+impl fmt::Display for EvaluatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.message);
+    }
+}
+
+/// Failure of process inside calculator, before it becomes an `Error` knowing processed input
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Failure {
+    pub message: String,                // message describing error
+    pub cause: Option<Cause>,           // cause of error, if identified
+    pub legacy_message: Option<String>, // message given by String-based API when it differs from message
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        return Self {
+            message,
+            cause: None,
+            legacy_message: None,
+        };
+    }
+}
+
+impl From<EvaluatorError> for Failure {
+    fn from(error: EvaluatorError) -> Self {
+        return Self {
+            message: String::from(error.message()),
+            cause: Some(Cause::Evaluator(error)),
+            legacy_message: None,
+        };
+    }
+}
+
+impl From<Failure> for String {
+    fn from(failure: Failure) -> Self {
+        return failure.legacy_message.unwrap_or(failure.message);
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.message);
+    }
+}
+
+/// Error occurring during process of an expression
+///
+/// Besides its message, it carries processed input, kind of expression detected by calculator
+/// and name of defined variable or function, if any. When this name is not a valid identifier,
+/// expression has probably not been interpreted as user intended, thus a hint is displayed
+/// after message, as for `y - 2 = x` interpreted as definition of variable `y - 2`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    message: String,                // message describing error
+    input: String,                  // processed input giving error
+    kind: InputKind,                // kind of expression detected
+    name: Option<String>,           // name of defined variable or function
+    cause: Option<Box<Cause>>,      // cause of error, if identified by calculator
+    legacy_message: Option<String>, // message given by String-based API when it differs from message
+}
+
+impl Error {
+    /// Construct an error given its message and expression whose process gives it
+    pub fn new(message: &str, expression_str: &str) -> Self {
+        let kind: InputKind = expression::input_kind(expression_str);
+
+        let mut expression: Expression = Expression::new(expression_str);
+        expression.remove_freeze_marker();
+
+        let name: Option<String> = match expression {
+            Expression::Variable(name, _) | Expression::Function(name, _, _) => Some(name),
+            Expression::Raw(_) => None,
+        };
+
+        return Self {
+            message: String::from(message),
+            input: String::from(expression_str),
+            kind,
+            name,
+            cause: None,
+            legacy_message: None,
+        };
+    }
+
+    /// Construct an error from a failure of process and expression whose process gives it
+    pub(crate) fn from_failure(failure: Failure, expression_str: &str) -> Self {
+        let mut error: Self = Self::new(failure.message.as_str(), expression_str);
+        error.cause = failure.cause.map(Box::new);
+        error.legacy_message = failure.legacy_message;
+
+        return error;
+    }
+
+    /// Get processed input giving error
+    pub fn input(&self) -> &str {
+        return self.input.as_str();
+    }
+
+    /// Get cause of error, none if calculator does not identify it
+    pub fn cause(&self) -> Option<&Cause> {
+        return self.cause.as_deref();
+    }
+
+    /// Get message describing error, without hint
+    pub fn message(&self) -> &str {
+        return self.message.as_str();
+    }
+
+    /// Get kind of expression detected
+    pub fn kind(&self) -> InputKind {
+        return self.kind;
+    }
+
+    /// Get name of defined variable or function, none for other kinds of expression
+    pub fn name(&self) -> Option<&str> {
+        return self.name.as_deref();
+    }
+
+    /// Get message as given by String-based API of calculator, as `Calculator::process`
+    ///
+    /// It is the message followed by hint, if any. Its text is kept unchanged across releases,
+    /// so that code matching messages of `process` keeps working while migrating to `Error`.
+    /// Thus for an empty expression, it is always `The expression is empty`, without name of
+    /// function whose definition is empty, and evaluator is never called to build it.
+    pub fn legacy_message(&self) -> String {
+        let message: &str = self.legacy_message.as_deref().unwrap_or(&self.message);
+
+        return match self.hint() {
+            Some(hint) => format!("{} (hint: {})", message, hint),
+            None => String::from(message),
+        };
+    }
+
+    /// Get hint explaining how expression has been interpreted, when its name is not a valid identifier
+    pub fn hint(&self) -> Option<String> {
+        let name: &str = self.name()?;
+
+        if validation::is_identifier(name) {
+            return None;
+        }
+
+        return match self.kind {
+            InputKind::Variable => Some(format!(
+                "this was interpreted as a variable definition for '{}'; if you meant a comparison, use '=='",
+                name
+            )),
+            InputKind::Function => Some(format!(
+                "this was interpreted as a function definition for '{}'",
+                name
+            )),
+            _ => None,
+        };
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.legacy_message());
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_with_hint() {
+        let error: Error = Error::new("Invalid number", "y - 2 = x");
+
+        assert_eq!(error.kind(), InputKind::Variable);
+        assert_eq!(error.name(), Some("y - 2"));
+
+        assert_eq!(
+            format!("{}", error),
+            "Invalid number (hint: this was interpreted as a variable definition for 'y - 2'; if you meant a comparison, use '==')"
+        );
+    }
+
+    #[test]
+    fn test_error_without_hint() {
+        let errors: Vec<(Error, InputKind, Option<&str>)> = vec![
+            (
+                Error::new("Invalid number", "y = x"),
+                InputKind::Variable,
+                Some("y"),
+            ),
+            (Error::new("Invalid number", "1 + x"), InputKind::Raw, None),
+            (
+                Error::new("Invalid number", "g!: x = x + y"),
+                InputKind::Function,
+                Some("g"),
+            ),
+        ];
+
+        for (error, kind, name) in errors {
+            assert_eq!(error.kind(), kind);
+            assert_eq!(error.name(), name);
+            assert_eq!(error.hint(), None);
+            assert_eq!(format!("{}", error), "Invalid number");
+            assert_eq!(error.legacy_message(), "Invalid number");
+        }
+    }
+}
@@ -0,0 +1,1969 @@
+use crate::token::{tokenize, Lexeme, LexemeKind, Lexer, Token};
+use crate::value::{EvaluateValue, Value};
+
+use std::fmt;
+use std::ops::Range;
+
+#[cfg(feature = "complex")]
+use num_complex::Complex64;
+#[cfg(feature = "decimal")]
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Built-in evaluator of arithmetic expressions
+///
+/// It handles numbers, parenthesis, unary `+` and `-` and binary operators `+`, `-`, `*`, `/` and `^`,
+/// where `^` is right associative and has higher precedence than unary minus, thus `-2^2` gives `-4`.
+///
+/// Unary plus does nothing and consecutive unary signs are folded, thus `--3` gives `3`.
+/// A binary operator can be followed by unary signs, as `2 - -3` or `2 * +4`, but not by another
+/// binary operator, thus `2 * / 3` is an error at column of `/`. The same rules are followed
+/// by `validation::is_syntactically_valid` for other evaluators.
+///
+/// When parsing fails, the error names what was expected and what was found with its column,
+/// as `Expected a number, an identifier or '(' after operator '*' at column 5, found end of expression`.
+///
+/// An expression made only of numbers, parenthesis and operators is evaluated in one pass
+/// by `evaluate_tokens`, other expressions and errors are handled by a recursive descent parser.
+///
+/// With implicit multiplication, two operands without operator between them are multiplied,
+/// as `2(3 + 1)`, else it is an error.
+///
+/// A vector is written between brackets, as `[1, 2, 3]`. Binary operators apply element by element,
+/// a number or a vector of length one is broadcast to length of other vector, thus `[1, 2] * 2`
+/// gives `[2, 4]` while `[1, 2] + [1, 2, 3]` is an error. Vector is indexed from 0, thus `[4, 5][1]`
+/// gives `5`. Functions `len` and `dot` give length of a vector and dot product of two vectors.
+///
+/// Comparisons `==`, `!=`, `<`, `<=`, `>` and `>=` of numbers give 1 if they hold, else 0,
+/// they have lower precedence than arithmetic operators. Equality `==` and inequality `!=`
+/// consider two numbers equal when their difference is not greater than comparison epsilon,
+/// by default 0 for exact equality, whereas ordering comparisons are never affected by it.
+/// Function `approx(a, b, eps)` gives 1 if difference of `a` and `b` is not greater than `eps`,
+/// whatever the comparison epsilon.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BuiltinEvaluator {
+    implicit_multiplication: bool, // if true, two consecutive operands are multiplied
+    comparison_epsilon: f64, // maximal difference of two numbers considered equal by == and !=
+}
+
+impl BuiltinEvaluator {
+    /// Construct a built-in evaluator without implicit multiplication
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Enable or disable implicit multiplication between two consecutive operands
+    pub fn set_implicit_multiplication(&mut self, implicit_multiplication: bool) {
+        self.implicit_multiplication = implicit_multiplication;
+    }
+
+    /// Set maximal difference of two numbers considered equal by comparisons `==` and `!=`, 0 by default
+    ///
+    /// Ordering comparisons, as `<`, are not affected.
+    pub fn set_comparison_epsilon(&mut self, comparison_epsilon: f64) {
+        self.comparison_epsilon = comparison_epsilon;
+    }
+}
+
+impl EvaluateValue for BuiltinEvaluator {
+    type Error = ParseError;
+
+    fn evaluate(&self, expression: &str) -> Result<Value, ParseError> {
+        // Evaluation of tokens of a well-formed scalar expression cannot fail,
+        // thus an expression giving an error is only evaluated by parser
+        if is_scalar_expression(expression) {
+            if let Ok(number) =
+                evaluate_tokens_with_epsilon(&tokenize(expression), self.comparison_epsilon)
+            {
+                return Ok(Value::Number(number));
+            }
+        }
+
+        let mut parser: Parser<Array> = Parser::new(expression, self.implicit_multiplication);
+        parser.comparison_epsilon = self.comparison_epsilon;
+
+        return match parser.parse()? {
+            Array::Scalar(number) => Ok(Value::Number(number)),
+            Array::Vector(elements) => Ok(Value::Vector(elements)),
+        };
+    }
+
+    fn syntax_help(&self) -> Vec<(String, String)> {
+        let mut help: Vec<(String, String)> = arithmetic_help(self.implicit_multiplication);
+
+        help.extend(syntax_entries(&[
+            ("[a, b, c]", "vector, operators apply element by element"),
+            ("v[n]", "element of vector v at index n, starting from 0"),
+            ("len(v)", "length of vector v"),
+            ("dot(u, v)", "dot product of vectors u and v"),
+            (
+                "x == y, x != y",
+                "equality and inequality within comparison epsilon, 1 or 0",
+            ),
+            (
+                "x < y, x <= y, x > y, x >= y",
+                "ordering comparison, 1 or 0",
+            ),
+            (
+                "approx(x, y, eps)",
+                "1 if x and y differ by at most eps, else 0",
+            ),
+        ]));
+
+        return help;
+    }
+}
+
+/// Built-in evaluator of arithmetic expressions computing on decimal numbers, available with feature `decimal`
+///
+/// It handles the same expressions as `BuiltinEvaluator`, but numbers are exact decimal numbers,
+/// thus `0.1 + 0.2` gives exactly `0.3`. An exponent must be an integer and a division by zero
+/// or an overflow gives an error.
+///
+/// Result can be rounded to a number of decimals, by default with banker's rounding
+/// where a midpoint is rounded to nearest even digit, thus `0.125` gives `0.12` with 2 decimals.
+///
+#[cfg(feature = "decimal")]
+#[derive(Debug, Clone, Copy)]
+pub struct DecimalEvaluator {
+    implicit_multiplication: bool, // if true, two consecutive operands are multiplied
+    decimals: Option<u32>,         // number of decimals of result, none for no rounding
+    rounding_strategy: RoundingStrategy, // strategy used to round result
+}
+
+#[cfg(feature = "decimal")]
+impl Default for DecimalEvaluator {
+    fn default() -> Self {
+        return Self {
+            implicit_multiplication: false,
+            decimals: None,
+            rounding_strategy: RoundingStrategy::MidpointNearestEven,
+        };
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl DecimalEvaluator {
+    /// Construct a decimal evaluator without implicit multiplication nor rounding
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Enable or disable implicit multiplication between two consecutive operands
+    pub fn set_implicit_multiplication(&mut self, implicit_multiplication: bool) {
+        self.implicit_multiplication = implicit_multiplication;
+    }
+
+    /// Set number of decimals to which result is rounded, none for no rounding
+    pub fn set_decimals(&mut self, decimals: Option<u32>) {
+        self.decimals = decimals;
+    }
+
+    /// Set strategy used to round result, banker's rounding by default
+    pub fn set_rounding_strategy(&mut self, rounding_strategy: RoundingStrategy) {
+        self.rounding_strategy = rounding_strategy;
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl EvaluateValue for DecimalEvaluator {
+    type Error = ParseError;
+
+    fn evaluate(&self, expression: &str) -> Result<Value, ParseError> {
+        let mut parser: Parser<Decimal> = Parser::new(expression, self.implicit_multiplication);
+
+        let value: Decimal = parser.parse()?;
+
+        return match self.decimals {
+            Some(decimals) => Ok(Value::Decimal(
+                value.round_dp_with_strategy(decimals, self.rounding_strategy),
+            )),
+            None => Ok(Value::Decimal(value)),
+        };
+    }
+
+    fn syntax_help(&self) -> Vec<(String, String)> {
+        let mut help: Vec<(String, String)> = arithmetic_help(self.implicit_multiplication);
+
+        if let Some(decimals) = self.decimals {
+            help.push((
+                String::from("rounding"),
+                format!("results are rounded to {} decimals", decimals),
+            ));
+        }
+
+        return help;
+    }
+}
+
+/// Built-in evaluator of arithmetic expressions computing on complex numbers, available with feature `complex`
+///
+/// It handles the same expressions as `BuiltinEvaluator`, with imaginary unit written `i` or `j`.
+/// A number immediately followed by `i` or `j` is imaginary, thus `3 + 4i` or `2.5j`.
+/// Functions `abs`, `arg`, `re`, `im` and `conj` are provided, as `abs(3 + 4i)` giving `5`.
+///
+/// Names `i` and `j` are reserved, calculator does not allow to define them.
+///
+#[cfg(feature = "complex")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ComplexEvaluator {
+    implicit_multiplication: bool, // if true, two consecutive operands are multiplied
+}
+
+#[cfg(feature = "complex")]
+impl ComplexEvaluator {
+    /// Construct a complex evaluator without implicit multiplication
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Enable or disable implicit multiplication between two consecutive operands
+    pub fn set_implicit_multiplication(&mut self, implicit_multiplication: bool) {
+        self.implicit_multiplication = implicit_multiplication;
+    }
+}
+
+#[cfg(feature = "complex")]
+impl EvaluateValue for ComplexEvaluator {
+    type Error = ParseError;
+
+    fn evaluate(&self, expression: &str) -> Result<Value, ParseError> {
+        let mut parser: Parser<Complex64> = Parser::new(expression, self.implicit_multiplication);
+
+        return parser.parse().map(Value::Complex);
+    }
+
+    fn reserved_names(&self) -> &[&str] {
+        return &["i", "j"];
+    }
+
+    fn syntax_help(&self) -> Vec<(String, String)> {
+        let mut help: Vec<(String, String)> = arithmetic_help(self.implicit_multiplication);
+
+        help.extend(syntax_entries(&[
+            ("i, j", "imaginary unit, these names cannot be defined"),
+            (
+                "3 + 4i",
+                "complex number, imaginary part is followed by i or j",
+            ),
+            ("abs(z)", "modulus of z"),
+            ("arg(z)", "argument of z"),
+            ("re(z)", "real part of z"),
+            ("im(z)", "imaginary part of z"),
+            ("conj(z)", "conjugate of z"),
+        ]));
+
+        return help;
+    }
+}
+
+/// Convert syntax entries of help given as string slices
+fn syntax_entries(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+    return entries
+        .iter()
+        .map(|(syntax, description)| (String::from(*syntax), String::from(*description)))
+        .collect();
+}
+
+/// Get syntax entries of help for arithmetic handled by parser
+fn arithmetic_help(implicit_multiplication: bool) -> Vec<(String, String)> {
+    let mut help: Vec<(String, String)> = syntax_entries(&[
+        ("x + y, x - y", "addition and subtraction"),
+        ("x * y, x / y", "multiplication and division"),
+        ("x ^ y", "power, right associative and before unary minus"),
+        ("-x, +x", "unary sign"),
+        ("(x)", "parenthesis"),
+    ]);
+
+    if implicit_multiplication {
+        help.push((
+            String::from("2(x + 1)"),
+            String::from("implicit multiplication of consecutive operands"),
+        ));
+    }
+
+    return help;
+}
+
+/// Number on which parser computes
+trait ParserNumber: Clone {
+    /// Read a number literal
+    fn from_literal(literal: &str) -> Result<Self, String>;
+
+    /// Apply a binary operator among `+`, `-`, `*`, `/` and `^`
+    fn apply(operator: &str, left: Self, right: Self) -> Result<Self, String>;
+
+    /// Get opposite of number
+    fn negate(self) -> Self;
+
+    /// Apply a comparison among `==`, `!=`, `<`, `<=`, `>` and `>=`, where two numbers are equal
+    /// if their difference is not greater than epsilon
+    fn compare(_operator: &str, _left: Self, _right: Self, _epsilon: f64) -> Result<Self, String> {
+        return Err(String::from(
+            "The comparisons are not supported by evaluator",
+        ));
+    }
+
+    /// Get constant named by identifier, none if identifier is not a constant
+    fn constant(_name: &str) -> Option<Self> {
+        return None;
+    }
+
+    /// Get function named by identifier with its number of arguments, none if identifier is not a function
+    fn function(_name: &str) -> Option<(usize, ParserFunction<Self>)> {
+        return None;
+    }
+
+    /// Build a vector from its elements written between brackets, as `[1, 2, 3]`
+    fn from_elements(_elements: Vec<Self>) -> Result<Self, String> {
+        return Err(String::from("The vectors are not supported by evaluator"));
+    }
+
+    /// Get element of a vector at index written between brackets, as `v[1]`
+    fn index(self, _index: Self) -> Result<Self, String> {
+        return Err(String::from("The vectors are not supported by evaluator"));
+    }
+}
+
+/// Function called by parser with its arguments, their number is checked before call
+type ParserFunction<Number> = fn(Vec<Number>) -> Result<Number, String>;
+
+/// Number or vector of numbers on which built-in evaluator computes
+#[derive(Debug, Clone, PartialEq)]
+enum Array {
+    Scalar(f64),
+    Vector(Vec<f64>),
+}
+
+impl ParserNumber for Array {
+    fn from_literal(literal: &str) -> Result<Self, String> {
+        return f64::from_literal(literal).map(Array::Scalar);
+    }
+
+    fn apply(operator: &str, left: Self, right: Self) -> Result<Self, String> {
+        let (left_elements, right_elements): (Vec<f64>, Vec<f64>) = match (left, right) {
+            (Array::Scalar(left), Array::Scalar(right)) => {
+                return f64::apply(operator, left, right).map(Array::Scalar);
+            }
+            (Array::Scalar(left), Array::Vector(right)) => (vec![left], right),
+            (Array::Vector(left), Array::Scalar(right)) => (left, vec![right]),
+            (Array::Vector(left), Array::Vector(right)) => (left, right),
+        };
+
+        // An operand of length one is broadcast to length of other operand
+        let length: usize = match (left_elements.len(), right_elements.len()) {
+            (left_length, right_length) if left_length == right_length => left_length,
+            (1, right_length) => right_length,
+            (left_length, 1) => left_length,
+            (left_length, right_length) => {
+                return Err(format!(
+                    "The vectors of lengths {} and {} cannot be combined by operator '{}'",
+                    left_length, right_length, operator
+                ));
+            }
+        };
+
+        let element = |elements: &[f64], id: usize| -> f64 {
+            return if elements.len() == 1 {
+                elements[0]
+            } else {
+                elements[id]
+            };
+        };
+
+        return (0..length)
+            .map(|id| {
+                return f64::apply(
+                    operator,
+                    element(&left_elements, id),
+                    element(&right_elements, id),
+                );
+            })
+            .collect::<Result<Vec<f64>, String>>()
+            .map(Array::Vector);
+    }
+
+    fn compare(operator: &str, left: Self, right: Self, epsilon: f64) -> Result<Self, String> {
+        return match (left, right) {
+            (Array::Scalar(left), Array::Scalar(right)) => {
+                f64::compare(operator, left, right, epsilon).map(Array::Scalar)
+            }
+            _ => Err(format!(
+                "The vectors cannot be compared by operator '{}'",
+                operator
+            )),
+        };
+    }
+
+    fn negate(self) -> Self {
+        return match self {
+            Array::Scalar(number) => Array::Scalar(-number),
+            Array::Vector(elements) => {
+                Array::Vector(elements.into_iter().map(|element| -element).collect())
+            }
+        };
+    }
+
+    fn function(name: &str) -> Option<(usize, ParserFunction<Self>)> {
+        return match name {
+            "len" => Some((1, |arguments| match arguments.as_slice() {
+                [Array::Vector(elements)] => Ok(Array::Scalar(elements.len() as f64)),
+                _ => Err(String::from("The function len expects a vector")),
+            })),
+            "dot" => Some((2, |arguments| match arguments.as_slice() {
+                [Array::Vector(left), Array::Vector(right)] if left.len() == right.len() => {
+                    Ok(Array::Scalar(
+                        left.iter()
+                            .zip(right)
+                            .map(|(left, right)| left * right)
+                            .sum(),
+                    ))
+                }
+                [Array::Vector(left), Array::Vector(right)] => Err(format!(
+                    "The dot product of vectors of lengths {} and {} is not defined",
+                    left.len(),
+                    right.len()
+                )),
+                _ => Err(String::from("The function dot expects two vectors")),
+            })),
+            "approx" => Some((3, |arguments| match arguments.as_slice() {
+                [Array::Scalar(left), Array::Scalar(right), Array::Scalar(epsilon)] => {
+                    f64::compare("==", *left, *right, *epsilon).map(Array::Scalar)
+                }
+                _ => Err(String::from("The function approx expects three numbers")),
+            })),
+            _ => None,
+        };
+    }
+
+    fn from_elements(elements: Vec<Self>) -> Result<Self, String> {
+        return elements
+            .into_iter()
+            .map(|element| match element {
+                Array::Scalar(number) => Ok(number),
+                Array::Vector(_) => Err(String::from(
+                    "The element of a vector must be a number, not a vector",
+                )),
+            })
+            .collect::<Result<Vec<f64>, String>>()
+            .map(Array::Vector);
+    }
+
+    fn index(self, index: Self) -> Result<Self, String> {
+        let elements: Vec<f64> = match self {
+            Array::Vector(elements) => elements,
+            Array::Scalar(number) => {
+                return Err(format!(
+                    "The number {} is not a vector, it cannot be indexed",
+                    number
+                ));
+            }
+        };
+
+        let index: f64 = match index {
+            Array::Scalar(index) if index >= 0.0 && index.fract() == 0.0 => index,
+            Array::Scalar(index) => {
+                return Err(format!("The index {} is not a non-negative integer", index));
+            }
+            Array::Vector(_) => {
+                return Err(String::from("The index must be a number, not a vector"))
+            }
+        };
+
+        return match elements.get(index as usize) {
+            Some(element) => Ok(Array::Scalar(*element)),
+            None => Err(format!(
+                "The index {} is out of range for a vector of length {}",
+                index,
+                elements.len()
+            )),
+        };
+    }
+}
+
+impl ParserNumber for f64 {
+    fn from_literal(literal: &str) -> Result<Self, String> {
+        return literal
+            .parse::<f64>()
+            .map_err(|_| format!("The number {} is not valid", literal));
+    }
+
+    fn apply(operator: &str, left: Self, right: Self) -> Result<Self, String> {
+        return Ok(match operator {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            _ => left.powf(right),
+        });
+    }
+
+    fn negate(self) -> Self {
+        return -self;
+    }
+
+    fn compare(operator: &str, left: Self, right: Self, epsilon: f64) -> Result<Self, String> {
+        let is_equal: bool = left == right || (left - right).abs() <= epsilon;
+
+        let holds: bool = match operator {
+            "==" => is_equal,
+            "!=" => !is_equal,
+            "<" => left < right,
+            "<=" => left <= right,
+            ">" => left > right,
+            _ => left >= right,
+        };
+
+        return Ok(f64::from(u8::from(holds)));
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl ParserNumber for Decimal {
+    fn from_literal(literal: &str) -> Result<Self, String> {
+        return Decimal::from_str_exact(literal)
+            .or_else(|_| Decimal::from_scientific(literal))
+            .map_err(|_| format!("The number {} is not valid", literal));
+    }
+
+    fn apply(operator: &str, left: Self, right: Self) -> Result<Self, String> {
+        let result: Option<Decimal> = match operator {
+            "+" => left.checked_add(right),
+            "-" => left.checked_sub(right),
+            "*" => left.checked_mul(right),
+            "/" if right.is_zero() => return Err(String::from("Division by zero")),
+            "/" => left.checked_div(right),
+            _ => {
+                if !right.fract().is_zero() {
+                    return Err(format!("The exponent {} is not an integer", right));
+                }
+
+                let exponent: i64 = match i64::try_from(right) {
+                    Ok(exponent) => exponent,
+                    Err(_) => return Err(format!("The exponent {} is too large", right)),
+                };
+
+                // Exponentiation by squaring, a negative exponent gives inverse of power
+                let mut power: Option<Decimal> = Some(Decimal::ONE);
+                let mut base: Option<Decimal> = Some(left);
+                let mut remaining_exponent: u64 = exponent.unsigned_abs();
+
+                while remaining_exponent > 0 {
+                    if remaining_exponent % 2 == 1 {
+                        power = power
+                            .zip(base)
+                            .and_then(|(power, base)| power.checked_mul(base));
+                    }
+
+                    remaining_exponent /= 2;
+
+                    if remaining_exponent > 0 {
+                        base = base.and_then(|base| base.checked_mul(base));
+                    }
+                }
+
+                match power {
+                    Some(power) if exponent < 0 && power.is_zero() => {
+                        return Err(String::from("Division by zero"));
+                    }
+                    Some(power) if exponent < 0 => Decimal::ONE.checked_div(power),
+                    _ => power,
+                }
+            }
+        };
+
+        return result.ok_or_else(|| {
+            return format!("The result of {} {} {} overflows", left, operator, right);
+        });
+    }
+
+    fn negate(self) -> Self {
+        return -self;
+    }
+}
+
+#[cfg(feature = "complex")]
+impl ParserNumber for Complex64 {
+    fn from_literal(literal: &str) -> Result<Self, String> {
+        return f64::from_literal(literal).map(Complex64::from);
+    }
+
+    fn apply(operator: &str, left: Self, right: Self) -> Result<Self, String> {
+        return Ok(match operator {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            // Integer exponent is computed by multiplications to keep exact results as `i^2 = -1`
+            _ if right.im == 0.0
+                && right.re.fract() == 0.0
+                && right.re.abs() <= i32::MAX as f64 =>
+            {
+                left.powi(right.re as i32)
+            }
+            _ => left.powc(right),
+        });
+    }
+
+    fn negate(self) -> Self {
+        return -self;
+    }
+
+    fn constant(name: &str) -> Option<Self> {
+        return match name {
+            "i" | "j" => Some(Complex64::i()),
+            _ => None,
+        };
+    }
+
+    fn function(name: &str) -> Option<(usize, ParserFunction<Self>)> {
+        return match name {
+            "abs" => Some((1, |z| Ok(Complex64::from(z[0].norm())))),
+            "arg" => Some((1, |z| Ok(Complex64::from(z[0].arg())))),
+            "re" => Some((1, |z| Ok(Complex64::from(z[0].re)))),
+            "im" => Some((1, |z| Ok(Complex64::from(z[0].im)))),
+            "conj" => Some((1, |z| Ok(z[0].conj()))),
+            _ => None,
+        };
+    }
+}
+
+/// Maximal nesting depth of parser, as parenthesis, brackets, unary signs and exponents
+///
+/// It prevents a stack overflow on pathological expressions, as a thousand nested parenthesis.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Error of built-in evaluators, with the span of expression where it occurs
+///
+/// Span is given in characters counted from 0, an error at end of expression spans
+/// the character just after it. `render` shows the expression with carets under the span.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String,    // message describing error
+    expression: String, // expression whose evaluation gives error
+    span: Range<usize>, // range of characters of expression where error occurs
+}
+
+impl ParseError {
+    /// Get message describing error
+    pub fn message(&self) -> &str {
+        return self.message.as_str();
+    }
+
+    /// Get expression whose evaluation gives error
+    pub fn expression(&self) -> &str {
+        return self.expression.as_str();
+    }
+
+    /// Get range of characters of expression where error occurs, counted from 0
+    pub fn span(&self) -> Range<usize> {
+        return self.span.clone();
+    }
+
+    /// Write message, then expression with carets under span of error on the next line
+    ///
+    /// Thus `2 * ` gives message, then `2 * ` and `    ^`.
+    pub fn render(&self) -> String {
+        return format!(
+            "{}\n{}\n{}{}",
+            self.message,
+            self.expression,
+            " ".repeat(self.span.start),
+            "^".repeat(self.span.len().max(1))
+        );
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.message);
+    }
+}
+
+impl From<ParseError> for String {
+    fn from(error: ParseError) -> Self {
+        return error.message;
+    }
+}
+
+/// Recursive descent parser evaluating an expression while reading it
+struct Parser<'a, Number: ParserNumber> {
+    expression: &'a str,                      // expression to evaluate
+    lexemes: Vec<Lexeme>,                     // lexemes of expression
+    position: usize,                          // index of next lexeme to read
+    depth: usize,                             // current nesting depth, bounded by MAX_NESTING_DEPTH
+    implicit_multiplication: bool,            // if true, two consecutive operands are multiplied
+    comparison_epsilon: f64, // maximal difference of two numbers considered equal by == and !=
+    number: std::marker::PhantomData<Number>, // number on which parser computes
+}
+
+impl<'a, Number: ParserNumber> Parser<'a, Number> {
+    /// Construct a parser over expression given in argument
+    fn new(expression: &'a str, implicit_multiplication: bool) -> Self {
+        return Self {
+            expression,
+            lexemes: Lexer::new(expression).collect(),
+            position: 0,
+            depth: 0,
+            implicit_multiplication,
+            comparison_epsilon: 0.0,
+            number: std::marker::PhantomData,
+        };
+    }
+
+    /// Parse whole expression and get its value
+    fn parse(&mut self) -> Result<Number, ParseError> {
+        let value: Number = self.parse_comparison()?;
+
+        if self.peek().is_some() {
+            return Err(self.expectation_error("an operator or end of expression"));
+        }
+
+        return Ok(value);
+    }
+
+    /// Get text of lexeme
+    fn text(&self, lexeme: &Lexeme) -> &'a str {
+        return &self.expression[lexeme.range.clone()];
+    }
+
+    /// Get text of next lexeme without reading it
+    fn peek(&self) -> Option<&'a str> {
+        return self
+            .lexemes
+            .get(self.position)
+            .map(|lexeme| self.text(lexeme));
+    }
+
+    /// Check if next lexeme starts an operand, i.e. it is a number, an identifier or `(`
+    fn peek_operand(&self) -> bool {
+        return self.lexemes.get(self.position).is_some_and(|lexeme| {
+            return lexeme.kind != LexemeKind::Symbol || self.text(lexeme) == "(";
+        });
+    }
+
+    /// Get byte range of lexemes from first one to last one included, end of expression if absent
+    fn range(&self, first: usize, last: usize) -> Range<usize> {
+        let end_of_expression: Range<usize> = self.expression.len()..self.expression.len();
+
+        let start: usize = self
+            .lexemes
+            .get(first)
+            .map_or(end_of_expression.start, |lexeme| lexeme.range.start);
+        let end: usize = self
+            .lexemes
+            .get(last)
+            .map_or(end_of_expression.end, |lexeme| lexeme.range.end);
+
+        return start..end;
+    }
+
+    /// Get column of a byte position of expression, counted from 1
+    fn column(&self, byte_position: usize) -> usize {
+        return self.expression[..byte_position].chars().count() + 1;
+    }
+
+    /// Build error given its message and byte range of expression where it occurs
+    fn error(&self, message: String, byte_range: Range<usize>) -> ParseError {
+        let start: usize = self.column(byte_range.start) - 1;
+        let end: usize = self.column(byte_range.end) - 1;
+
+        return ParseError {
+            message,
+            expression: String::from(self.expression),
+            span: start..end.max(start + 1),
+        };
+    }
+
+    /// Describe a lexeme in an error message
+    fn describe(&self, lexeme: &Lexeme) -> String {
+        let text: &str = self.text(lexeme);
+
+        return match lexeme.kind {
+            LexemeKind::Number => format!("number '{}'", text),
+            LexemeKind::Identifier => format!("identifier '{}'", text),
+            LexemeKind::Symbol if matches!(text, "+" | "-" | "*" | "/" | "^") => {
+                format!("operator '{}'", text)
+            }
+            LexemeKind::Symbol => format!("'{}'", text),
+        };
+    }
+
+    /// Build error indicating what was expected instead of next lexeme
+    fn expectation_error(&self, expected: &str) -> ParseError {
+        let context: String = match self.position.checked_sub(1) {
+            Some(previous) => format!("after {}", self.describe(&self.lexemes[previous])),
+            None => String::from("at start of expression"),
+        };
+
+        let found: String = match self.lexemes.get(self.position) {
+            Some(lexeme) => self.describe(lexeme),
+            None => String::from("end of expression"),
+        };
+
+        let byte_range: Range<usize> = self.range(self.position, self.position);
+
+        return self.error(
+            format!(
+                "Expected {} {} at column {}, found {}",
+                expected,
+                context,
+                self.column(byte_range.start),
+                found
+            ),
+            byte_range,
+        );
+    }
+
+    /// Parse a comparison of sums, as `a + b < c`, comparisons are left associative
+    fn parse_comparison(&mut self) -> Result<Number, ParseError> {
+        let mut value: Number = self.parse_sum()?;
+
+        while let Some((operator, length)) = self.peek_comparison() {
+            let operator_range: Range<usize> =
+                self.range(self.position, self.position + length - 1);
+            self.position += length;
+
+            let right: Number = self.parse_sum()?;
+            value = Number::compare(operator, value, right, self.comparison_epsilon)
+                .map_err(|message| self.error(message, operator_range))?;
+        }
+
+        return Ok(value);
+    }
+
+    /// Get comparison operator starting at next lexeme with its number of lexemes, none if it is not a comparison
+    ///
+    /// An operator of two characters, as `<=`, is made of two adjacent lexemes.
+    fn peek_comparison(&self) -> Option<(&'static str, usize)> {
+        let first: &str = self.peek()?;
+
+        let second: Option<&str> = self
+            .lexemes
+            .get(self.position + 1)
+            .filter(|lexeme| lexeme.range.start == self.lexemes[self.position].range.end)
+            .map(|lexeme| self.text(lexeme));
+
+        return match (first, second) {
+            ("=", Some("=")) => Some(("==", 2)),
+            ("!", Some("=")) => Some(("!=", 2)),
+            ("<", Some("=")) => Some(("<=", 2)),
+            (">", Some("=")) => Some((">=", 2)),
+            ("<", _) => Some(("<", 1)),
+            (">", _) => Some((">", 1)),
+            _ => None,
+        };
+    }
+
+    /// Parse a sum of terms
+    fn parse_sum(&mut self) -> Result<Number, ParseError> {
+        let mut value: Number = self.parse_product()?;
+
+        while let Some(operator @ ("+" | "-")) = self.peek() {
+            let operator_range: Range<usize> = self.range(self.position, self.position);
+            self.position += 1;
+
+            let term: Number = self.parse_product()?;
+            value = Number::apply(operator, value, term)
+                .map_err(|message| self.error(message, operator_range))?;
+        }
+
+        return Ok(value);
+    }
+
+    /// Parse a product of factors
+    fn parse_product(&mut self) -> Result<Number, ParseError> {
+        let mut value: Number = self.parse_unary()?;
+
+        loop {
+            let operator_range: Range<usize> = self.range(self.position, self.position);
+
+            let operator: &str = match self.peek() {
+                Some(operator @ ("*" | "/")) => {
+                    self.position += 1;
+                    operator
+                }
+                _ if self.implicit_multiplication && self.peek_operand() => "*",
+                _ => return Ok(value),
+            };
+
+            let factor: Number = self.parse_unary()?;
+            value = Number::apply(operator, value, factor)
+                .map_err(|message| self.error(message, operator_range))?;
+        }
+    }
+
+    /// Parse a factor with optional unary sign
+    ///
+    /// Each nested operand goes through it, thus it bounds nesting depth by `MAX_NESTING_DEPTH`.
+    fn parse_unary(&mut self) -> Result<Number, ParseError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            let byte_range: Range<usize> = self.range(self.position, self.position);
+
+            return Err(self.error(
+                format!(
+                    "The expression is nested deeper than {} levels at column {}",
+                    MAX_NESTING_DEPTH,
+                    self.column(byte_range.start)
+                ),
+                byte_range,
+            ));
+        }
+
+        self.depth += 1;
+
+        let value: Result<Number, ParseError> = match self.peek() {
+            Some("-") => {
+                self.position += 1;
+                self.parse_unary().map(Number::negate)
+            }
+            Some("+") => {
+                self.position += 1;
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        };
+
+        self.depth -= 1;
+
+        return value;
+    }
+
+    /// Parse a primary operand with optional exponent
+    fn parse_power(&mut self) -> Result<Number, ParseError> {
+        let base: Number = self.parse_indexing()?;
+
+        if self.peek() == Some("^") {
+            let operator_range: Range<usize> = self.range(self.position, self.position);
+            self.position += 1;
+
+            let exponent: Number = self.parse_unary()?;
+
+            return Number::apply("^", base, exponent)
+                .map_err(|message| self.error(message, operator_range));
+        }
+
+        return Ok(base);
+    }
+
+    /// Parse a primary operand followed by indices between brackets, as `v[1]`
+    fn parse_indexing(&mut self) -> Result<Number, ParseError> {
+        let mut value: Number = self.parse_primary()?;
+
+        while self.peek() == Some("[") {
+            let opening_position: usize = self.position;
+            self.position += 1;
+
+            let index: Number = self.parse_comparison()?;
+
+            if self.peek() != Some("]") {
+                return Err(self.expectation_error("an operator or ']'"));
+            }
+
+            let index_range: Range<usize> = self.range(opening_position, self.position);
+            self.position += 1;
+
+            value = value
+                .index(index)
+                .map_err(|message| self.error(message, index_range))?;
+        }
+
+        return Ok(value);
+    }
+
+    /// Parse expressions separated by commas until closing bracket, opening bracket is already read
+    fn parse_list(&mut self, closing_bracket: &str) -> Result<Vec<Number>, ParseError> {
+        let mut values: Vec<Number> = Vec::new();
+
+        if self.peek() == Some(closing_bracket) {
+            self.position += 1;
+            return Ok(values);
+        }
+
+        loop {
+            values.push(self.parse_comparison()?);
+
+            match self.peek() {
+                Some(",") => self.position += 1,
+                Some(bracket) if bracket == closing_bracket => {
+                    self.position += 1;
+                    return Ok(values);
+                }
+                _ => {
+                    return Err(self.expectation_error(
+                        format!("an operator, ',' or '{}'", closing_bracket).as_str(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Parse a number, a vector, a function call or an expression between parenthesis
+    fn parse_primary(&mut self) -> Result<Number, ParseError> {
+        let lexeme: Lexeme = match self.lexemes.get(self.position) {
+            Some(lexeme) => lexeme.clone(),
+            None => return Err(self.expectation_error("a number, an identifier or '('")),
+        };
+
+        let start_position: usize = self.position;
+        let text: &str = self.text(&lexeme);
+
+        return match lexeme.kind {
+            LexemeKind::Number => {
+                self.position += 1;
+
+                let value: Number = Number::from_literal(text)
+                    .map_err(|message| self.error(message, lexeme.range.clone()))?;
+
+                // A constant written immediately after a number multiplies it, as `4i`
+                match self.lexemes.get(self.position) {
+                    Some(suffix)
+                        if suffix.kind == LexemeKind::Identifier
+                            && suffix.range.start == lexeme.range.end =>
+                    {
+                        match Number::constant(self.text(suffix)) {
+                            Some(constant) => {
+                                self.position += 1;
+                                Number::apply("*", value, constant).map_err(|message| {
+                                    self.error(
+                                        message,
+                                        self.range(start_position, start_position + 1),
+                                    )
+                                })
+                            }
+                            None => Ok(value),
+                        }
+                    }
+                    _ => Ok(value),
+                }
+            }
+            LexemeKind::Identifier => {
+                if let Some(constant) = Number::constant(text) {
+                    self.position += 1;
+                    return Ok(constant);
+                }
+
+                let is_call: bool = self
+                    .lexemes
+                    .get(self.position + 1)
+                    .is_some_and(|next_lexeme| self.text(next_lexeme) == "(");
+
+                match Number::function(text) {
+                    Some((argument_count, function)) if is_call => {
+                        self.position += 2;
+
+                        let arguments: Vec<Number> = self.parse_list(")")?;
+                        let call_range: Range<usize> =
+                            self.range(start_position, self.position - 1);
+
+                        if arguments.len() != argument_count {
+                            return Err(self.error(
+                                format!(
+                                    "The function {} expects {} argument(s), found {}",
+                                    text,
+                                    argument_count,
+                                    arguments.len()
+                                ),
+                                call_range,
+                            ));
+                        }
+
+                        function(arguments).map_err(|message| self.error(message, call_range))
+                    }
+                    _ => Err(self.error(
+                        format!(
+                            "The identifier '{}' at column {} is not defined",
+                            text,
+                            self.column(lexeme.range.start)
+                        ),
+                        lexeme.range.clone(),
+                    )),
+                }
+            }
+            LexemeKind::Symbol if text == "(" => {
+                self.position += 1;
+
+                let value: Number = self.parse_comparison()?;
+
+                if self.peek() != Some(")") {
+                    return Err(self.expectation_error("an operator or ')'"));
+                }
+
+                self.position += 1;
+
+                Ok(value)
+            }
+            LexemeKind::Symbol if text == "[" => {
+                self.position += 1;
+
+                let elements: Vec<Number> = self.parse_list("]")?;
+
+                Number::from_elements(elements).map_err(|message| {
+                    self.error(message, self.range(start_position, self.position - 1))
+                })
+            }
+            LexemeKind::Symbol => Err(self.expectation_error("a number, an identifier or '('")),
+        };
+    }
+}
+
+/// Stack whose first elements are stored inline, it allocates only beyond its inline capacity
+struct InlineStack<T: Copy, const N: usize> {
+    inline: [Option<T>; N], // first elements of stack
+    spilled: Vec<T>,        // elements beyond inline capacity
+    len: usize,             // number of elements of stack
+}
+
+impl<T: Copy, const N: usize> InlineStack<T, N> {
+    /// Construct an empty stack
+    fn new() -> Self {
+        return Self {
+            inline: [None; N],
+            spilled: Vec::new(),
+            len: 0,
+        };
+    }
+
+    /// Push an element on top of stack
+    fn push(&mut self, element: T) {
+        match self.inline.get_mut(self.len) {
+            Some(slot) => *slot = Some(element),
+            None => self.spilled.push(element),
+        }
+
+        self.len += 1;
+    }
+
+    /// Remove element on top of stack and get it, none if stack is empty
+    fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+
+        return match self.inline.get_mut(self.len) {
+            Some(slot) => slot.take(),
+            None => self.spilled.pop(),
+        };
+    }
+
+    /// Get element on top of stack, none if stack is empty
+    fn top(&self) -> Option<T> {
+        let top_id: usize = self.len.checked_sub(1)?;
+
+        return match self.inline.get(top_id) {
+            Some(slot) => *slot,
+            None => self.spilled.last().copied(),
+        };
+    }
+}
+
+/// Inline capacity of stacks of `evaluate_tokens`, deeper nesting allocates
+const TOKEN_STACK_CAPACITY: usize = 32;
+
+/// Operator waiting on operator stack of `evaluate_tokens`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StackOperator {
+    Binary(&'static str, usize), // binary operator with its position of token
+    Negate,                      // unary minus
+    Parenthesis(usize),          // opening parenthesis with its position of token
+}
+
+impl StackOperator {
+    /// Get precedence of operator, comparisons have the lowest one and `^` the highest one
+    fn precedence(&self) -> u8 {
+        return match self {
+            Self::Binary("==" | "!=" | "<" | "<=" | ">" | ">=", _) => 1,
+            Self::Binary("+" | "-", _) => 2,
+            Self::Binary("*" | "/", _) => 3,
+            Self::Negate => 4,
+            Self::Binary(_, _) => 5,
+            Self::Parenthesis(_) => 0,
+        };
+    }
+}
+
+/// Evaluate tokens of an arithmetic expression in one pass, as given by `token::tokenize`
+///
+/// It follows rules of `BuiltinEvaluator` for numbers, without vectors, functions
+/// nor implicit multiplication, thus `-2^2` gives `-4` and `2 - -3` gives `5`.
+/// A comparison is made of consecutive operators as `<` and `=`, equality is exact.
+///
+/// Tokens are read once with an operand stack and an operator stack, as in precedence climbing,
+/// thus no tree is built and tokens produced by a previous pass, as validation, are not lexed again.
+/// Stacks are stored inline up to a nesting depth of 32 and allocate beyond it.
+/// Errors indicate the position of token, counted from 1, as `Expected an operand at token 3, found ')'`.
+/// `BuiltinEvaluator` evaluates this way a well-formed expression made only of numbers, parenthesis
+/// and operators, any other expression being evaluated by its parser.
+pub fn evaluate_tokens(tokens: &[Token]) -> Result<f64, String> {
+    return evaluate_tokens_with_epsilon(tokens, 0.0);
+}
+
+/// Check if an expression is made only of numbers, parenthesis and operators read by `evaluate_tokens`
+/// and is well formed, thus its evaluation by `evaluate_tokens` cannot fail
+///
+/// Lexemes are read without allocation and reading stops at the first one which is not supported.
+/// As for parser of `BuiltinEvaluator`, a comparison operator of two characters, as `<=`,
+/// must be written without whitespace, and two consecutive operands are not well formed,
+/// even with implicit multiplication.
+fn is_scalar_expression(expression: &str) -> bool {
+    let mut lexemes = Lexer::new(expression).peekable();
+    let mut is_operand_expected: bool = true;
+    let mut depth: usize = 0;
+
+    while let Some(lexeme) = lexemes.next() {
+        let text: &str = &expression[lexeme.range.clone()];
+
+        if is_operand_expected {
+            match (lexeme.kind, text) {
+                (LexemeKind::Number, _) if text.parse::<f64>().is_ok() => {
+                    is_operand_expected = false
+                }
+                (LexemeKind::Symbol, "+" | "-") => {}
+                (LexemeKind::Symbol, "(") => depth += 1,
+                _ => return false,
+            }
+
+            continue;
+        }
+
+        match text {
+            ")" if depth > 0 => depth -= 1,
+            "+" | "-" | "*" | "/" | "^" => is_operand_expected = true,
+            "=" | "!" | "<" | ">" => {
+                let is_followed_by_equal: bool = lexemes.peek().is_some_and(|next| {
+                    return next.range.start == lexeme.range.end
+                        && &expression[next.range.clone()] == "=";
+                });
+
+                if is_followed_by_equal {
+                    lexemes.next();
+                } else if matches!(text, "=" | "!") {
+                    return false;
+                }
+
+                is_operand_expected = true;
+            }
+            _ => return false,
+        }
+    }
+
+    return !is_operand_expected && depth == 0;
+}
+
+/// Evaluate tokens as `evaluate_tokens`, two numbers being equal if their difference
+/// is not greater than comparison epsilon
+fn evaluate_tokens_with_epsilon(tokens: &[Token], comparison_epsilon: f64) -> Result<f64, String> {
+    let mut operands: InlineStack<f64, TOKEN_STACK_CAPACITY> = InlineStack::new();
+    let mut operators: InlineStack<StackOperator, TOKEN_STACK_CAPACITY> = InlineStack::new();
+
+    let mut is_operand_expected: bool = true;
+    let mut id: usize = 0;
+
+    while id < tokens.len() {
+        let position: usize = id + 1;
+
+        if is_operand_expected {
+            match &tokens[id] {
+                Token::Number(number) => {
+                    operands.push(*number);
+                    is_operand_expected = false;
+                }
+                Token::Operator('-') => operators.push(StackOperator::Negate),
+                Token::Operator('+') => {}
+                Token::LParen => operators.push(StackOperator::Parenthesis(position)),
+                Token::Identifier(name) => {
+                    return Err(format!(
+                        "The identifier '{}' at token {} is not defined",
+                        name, position
+                    ));
+                }
+                Token::LBracket => {
+                    return Err(String::from("The vectors are not supported by evaluator"));
+                }
+                token => {
+                    return Err(format!(
+                        "Expected an operand at token {}, found {}",
+                        position,
+                        describe_token(token)
+                    ));
+                }
+            }
+
+            id += 1;
+            continue;
+        }
+
+        let (operator, length): (&'static str, usize) = match (&tokens[id], tokens.get(id + 1)) {
+            (Token::RParen, _) => {
+                loop {
+                    match operators.pop() {
+                        Some(StackOperator::Parenthesis(_)) => break,
+                        Some(operator) => {
+                            apply_stack_operator(operator, &mut operands, comparison_epsilon)?
+                        }
+                        None => {
+                            return Err(format!(
+                                "The parenthesis at token {} is not opened",
+                                position
+                            ));
+                        }
+                    }
+                }
+
+                id += 1;
+                continue;
+            }
+            (Token::Operator('='), Some(Token::Operator('='))) => ("==", 2),
+            (Token::Operator('!'), Some(Token::Operator('='))) => ("!=", 2),
+            (Token::Operator('<'), Some(Token::Operator('='))) => ("<=", 2),
+            (Token::Operator('>'), Some(Token::Operator('='))) => (">=", 2),
+            (Token::Operator('<'), _) => ("<", 1),
+            (Token::Operator('>'), _) => (">", 1),
+            (Token::Operator('+'), _) => ("+", 1),
+            (Token::Operator('-'), _) => ("-", 1),
+            (Token::Operator('*'), _) => ("*", 1),
+            (Token::Operator('/'), _) => ("/", 1),
+            (Token::Operator('^'), _) => ("^", 1),
+            (token, _) => {
+                return Err(format!(
+                    "Expected an operator at token {}, found {}",
+                    position,
+                    describe_token(token)
+                ));
+            }
+        };
+
+        let binary_operator: StackOperator = StackOperator::Binary(operator, position);
+
+        // Operators of higher precedence are applied first, `^` is right associative
+        while let Some(top) = operators.top() {
+            let is_applied: bool = match operator {
+                "^" => top.precedence() > binary_operator.precedence(),
+                _ => top.precedence() >= binary_operator.precedence(),
+            };
+
+            if !is_applied {
+                break;
+            }
+
+            operators.pop();
+            apply_stack_operator(top, &mut operands, comparison_epsilon)?;
+        }
+
+        operators.push(binary_operator);
+        is_operand_expected = true;
+        id += length;
+    }
+
+    if is_operand_expected {
+        return Err(format!(
+            "Expected an operand at token {}, found end of expression",
+            tokens.len() + 1
+        ));
+    }
+
+    while let Some(operator) = operators.pop() {
+        if let StackOperator::Parenthesis(position) = operator {
+            return Err(format!(
+                "The parenthesis at token {} is not closed",
+                position
+            ));
+        }
+
+        apply_stack_operator(operator, &mut operands, comparison_epsilon)?;
+    }
+
+    return operands
+        .pop()
+        .ok_or_else(|| String::from("The expression is empty"));
+}
+
+/// Apply an operator of operator stack to operands on top of operand stack
+fn apply_stack_operator(
+    operator: StackOperator,
+    operands: &mut InlineStack<f64, TOKEN_STACK_CAPACITY>,
+    comparison_epsilon: f64,
+) -> Result<(), String> {
+    let missing_operand = || String::from("The operator has no operand");
+
+    let value: f64 = match operator {
+        StackOperator::Negate => operands.pop().ok_or_else(missing_operand)?.negate(),
+        StackOperator::Binary(operator, _) => {
+            let right: f64 = operands.pop().ok_or_else(missing_operand)?;
+            let left: f64 = operands.pop().ok_or_else(missing_operand)?;
+
+            match operator {
+                "+" | "-" | "*" | "/" | "^" => f64::apply(operator, left, right)?,
+                _ => f64::compare(operator, left, right, comparison_epsilon)?,
+            }
+        }
+        StackOperator::Parenthesis(_) => return Ok(()),
+    };
+
+    operands.push(value);
+
+    return Ok(());
+}
+
+/// Describe a token in an error message of `evaluate_tokens`
+fn describe_token(token: &Token) -> String {
+    return match token {
+        Token::Number(number) => format!("number '{}'", number),
+        Token::Identifier(name) => format!("identifier '{}'", name),
+        Token::Operator(operator @ ('+' | '-' | '*' | '/' | '^')) => {
+            format!("operator '{}'", operator)
+        }
+        Token::Operator(symbol) => format!("'{}'", symbol),
+        Token::LParen => String::from("'('"),
+        Token::RParen => String::from("')'"),
+        Token::LBracket => String::from("'['"),
+        Token::RBracket => String::from("']'"),
+        Token::Comma => String::from("','"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Evaluate expression with tokens evaluator
+    fn evaluate_with_tokens(expression: &str) -> Result<f64, String> {
+        return evaluate_tokens(&tokenize(expression));
+    }
+
+    #[test]
+    fn test_evaluate_tokens() {
+        assert_eq!(evaluate_with_tokens("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(evaluate_with_tokens("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(evaluate_with_tokens("-2^2 + 2^3^2"), Ok(508.0));
+        assert_eq!(evaluate_with_tokens("2^-2 * 4"), Ok(1.0));
+        assert_eq!(evaluate_with_tokens("10 / 4 - -1.5e1"), Ok(17.5));
+        assert_eq!(evaluate_with_tokens("1 + 1 <= 2 == 1"), Ok(1.0));
+        assert_eq!(evaluate_with_tokens("--+3 != 3"), Ok(0.0));
+
+        assert_eq!(
+            evaluate_with_tokens("2 * / 3"),
+            Err(String::from(
+                "Expected an operand at token 3, found operator '/'"
+            ))
+        );
+        assert_eq!(
+            evaluate_with_tokens("(1 + 2"),
+            Err(String::from("The parenthesis at token 1 is not closed"))
+        );
+        assert_eq!(
+            evaluate_with_tokens("1 + 2)"),
+            Err(String::from("The parenthesis at token 4 is not opened"))
+        );
+        assert_eq!(
+            evaluate_with_tokens("2 3"),
+            Err(String::from(
+                "Expected an operator at token 2, found number '3'"
+            ))
+        );
+        assert_eq!(
+            evaluate_with_tokens("x + 1"),
+            Err(String::from("The identifier 'x' at token 1 is not defined"))
+        );
+        assert_eq!(
+            evaluate_with_tokens(""),
+            Err(String::from(
+                "Expected an operand at token 1, found end of expression"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_scalar_tokens() {
+        assert!(is_scalar_expression("(1 + 2) * -3 <= 4"));
+        assert!(is_scalar_expression("--+3 != 2^-2"));
+        assert!(!is_scalar_expression("[1, 2]"));
+        assert!(!is_scalar_expression("sqrt(2)"));
+        assert!(!is_scalar_expression("1 < = 2"));
+        assert!(!is_scalar_expression("1 = 2"));
+        assert!(!is_scalar_expression("2 (3)"));
+        assert!(!is_scalar_expression("(1 + 2"));
+        assert!(!is_scalar_expression("1 + 2)"));
+        assert!(!is_scalar_expression("1 *"));
+        assert!(!is_scalar_expression(""));
+
+        // Well-formed scalar expressions give the same result as parser
+        for expression in [
+            "(1 + 2) * -3 <= 4",
+            "-2^2 + 2^3^2",
+            "10 / 4 - -1.5e1 >= 17.5",
+        ] {
+            assert_eq!(
+                evaluate_tokens(&tokenize(expression)).map(Array::Scalar),
+                Parser::<Array>::new(expression, false)
+                    .parse()
+                    .map_err(String::from)
+            );
+        }
+
+        let mut evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+
+        assert_eq!(
+            evaluator.evaluate("1 < = 2").map_err(String::from),
+            Err(String::from(
+                "Expected a number, an identifier or '(' after '<' at column 5, found '='"
+            ))
+        );
+        assert_eq!(
+            evaluator.evaluate("0.1 + 0.2 == 0.3"),
+            Ok(Value::Number(0.0))
+        );
+
+        evaluator.set_comparison_epsilon(1e-9);
+        assert_eq!(
+            evaluator.evaluate("0.1 + 0.2 == 0.3"),
+            Ok(Value::Number(1.0))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_tokens_with_deep_nesting() {
+        // Nesting deeper than inline capacity of stacks
+        let depth: usize = 3 * TOKEN_STACK_CAPACITY;
+        let expression: String = format!("{}1{}", "(1 + ".repeat(depth), ")".repeat(depth));
+
+        assert_eq!(
+            evaluate_with_tokens(expression.as_str()),
+            Ok((depth + 1) as f64)
+        );
+        assert_eq!(
+            evaluate_with_tokens(format!("2{}", "^1".repeat(depth)).as_str()),
+            Ok(2.0)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_tokens_matches_reference_parser() {
+        // Linear congruential generator giving reproducible random expressions
+        let mut seed: u64 = 42;
+        let mut random = |bound: u64| -> u64 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+
+            return (seed >> 33) % bound;
+        };
+
+        let atoms: [&str; 6] = ["1", "2.5", "0", "3e1", "0.25", "7"];
+        let operators: [&str; 10] = [
+            " + ", " - ", " * ", " / ", "^", " < ", " <= ", " == ", " != ", " >= ",
+        ];
+
+        for _ in 0..2000 {
+            let mut expression: String = String::new();
+            let mut open_parenthesis: usize = 0;
+
+            for term_id in 0..(1 + random(8)) {
+                if term_id > 0 {
+                    expression.push_str(operators[random(10) as usize]);
+                }
+
+                // Occasional unary signs, parenthesis and mistakes
+                match random(10) {
+                    0 => expression.push('-'),
+                    1 => expression.push_str("+-"),
+                    2 => {
+                        expression.push('(');
+                        open_parenthesis += 1;
+                    }
+                    3 => expression.push('*'),
+                    _ => {}
+                }
+
+                expression.push_str(atoms[random(6) as usize]);
+
+                if open_parenthesis > 0 && random(3) == 0 {
+                    expression.push(')');
+                    open_parenthesis -= 1;
+                }
+            }
+
+            if random(5) > 0 {
+                expression.push_str(")".repeat(open_parenthesis).as_str());
+            }
+
+            let reference: Result<f64, String> = Parser::<f64>::new(&expression, false)
+                .parse()
+                .map_err(String::from);
+
+            // Built-in evaluator uses tokens evaluator, and parser for its errors
+            let builtin: Result<f64, String> = BuiltinEvaluator::new()
+                .evaluate(expression.as_str())
+                .map(|value| value.to_f64())
+                .map_err(String::from);
+
+            match (&builtin, &reference) {
+                (Ok(value), Ok(reference_value)) => assert!(
+                    value == reference_value || (value.is_nan() && reference_value.is_nan()),
+                    "{} gives {} instead of {}",
+                    expression,
+                    value,
+                    reference_value
+                ),
+                _ => assert_eq!(builtin, reference, "{}", expression),
+            }
+
+            match (evaluate_with_tokens(expression.as_str()), reference) {
+                (Ok(value), Ok(reference_value)) => assert!(
+                    value == reference_value || (value.is_nan() && reference_value.is_nan()),
+                    "{} gives {} instead of {}",
+                    expression,
+                    value,
+                    reference_value
+                ),
+                (value, reference_value) => assert_eq!(
+                    value.is_err(),
+                    reference_value.is_err(),
+                    "{} gives {:?} instead of {:?}",
+                    expression,
+                    value,
+                    reference_value
+                ),
+            }
+        }
+    }
+
+    // Evaluate expression with built-in evaluator and get its number
+    fn evaluate(expression: &str, implicit_multiplication: bool) -> Result<f64, String> {
+        let mut evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+        evaluator.set_implicit_multiplication(implicit_multiplication);
+
+        return evaluator
+            .evaluate(expression)
+            .map(|value| value.to_f64())
+            .map_err(String::from);
+    }
+
+    #[test]
+    fn test_builtin_evaluator_evaluate() {
+        assert_eq!(evaluate("1 + 2 * 3", false), Ok(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3", false), Ok(9.0));
+        assert_eq!(evaluate("-2^2 + 2^3^2", false), Ok(508.0));
+        assert_eq!(evaluate("10 / 4 - -1.5e1", false), Ok(17.5));
+        assert_eq!(evaluate("2(3 + 1)", true), Ok(8.0));
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_vectors() {
+        let evaluate_vector = |expression: &str| -> Result<Value, String> {
+            return BuiltinEvaluator::new()
+                .evaluate(expression)
+                .map_err(String::from);
+        };
+
+        assert_eq!(
+            evaluate_vector("-[1, 2] * 2 + [0.5]"),
+            Ok(Value::Vector(vec![-1.5, -3.5]))
+        );
+        assert_eq!(
+            evaluate_vector("2 ^ [1, 2, 3][2] + len([])"),
+            Ok(Value::Number(8.0))
+        );
+
+        assert_eq!(
+            evaluate_vector("[1, 2][-1]"),
+            Err(String::from("The index -1 is not a non-negative integer"))
+        );
+        assert_eq!(
+            evaluate_vector("[[1], 2]"),
+            Err(String::from(
+                "The element of a vector must be a number, not a vector"
+            ))
+        );
+        assert_eq!(
+            evaluate_vector("[1, 2"),
+            Err(String::from(
+                "Expected an operator, ',' or ']' after number '2' at column 6, found end of expression"
+            ))
+        );
+        assert_eq!(
+            evaluate_vector("dot([1])"),
+            Err(String::from(
+                "The function dot expects 2 argument(s), found 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_comparisons() {
+        let mut evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+
+        assert_eq!(
+            evaluator.evaluate("0.1 + 0.2 == 0.3"),
+            Ok(Value::Number(0.0))
+        );
+        assert_eq!(
+            evaluator.evaluate("0.1 + 0.2 != 0.3"),
+            Ok(Value::Number(1.0))
+        );
+        assert_eq!(
+            evaluator.evaluate("approx(0.1 + 0.2, 0.3, 1e-9)"),
+            Ok(Value::Number(1.0))
+        );
+        assert_eq!(
+            evaluator.evaluate("approx(1, 1.5, 0.1)"),
+            Ok(Value::Number(0.0))
+        );
+
+        evaluator.set_comparison_epsilon(1e-9);
+
+        assert_eq!(
+            evaluator.evaluate("0.1 + 0.2 == 0.3"),
+            Ok(Value::Number(1.0))
+        );
+        assert_eq!(
+            evaluator.evaluate("(1 < 1 + 1e-12) + (2 >= 1)"),
+            Ok(Value::Number(2.0))
+        );
+        assert_eq!(evaluator.evaluate("1 <= 1 - 1e-12"), Ok(Value::Number(0.0)));
+        assert_eq!(
+            evaluator.evaluate("approx(0.1 + 0.2, 0.3, 0)"),
+            Ok(Value::Number(0.0))
+        );
+
+        assert_eq!(
+            evaluator.evaluate("[1] == 1").map_err(String::from),
+            Err(String::from(
+                "The vectors cannot be compared by operator '=='"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_unary_signs() {
+        let accepted_expressions: [(&str, f64); 7] = [
+            ("+5", 5.0),
+            ("--3", 3.0),
+            ("- - -3", -3.0),
+            ("2 - -3", 5.0),
+            ("2 * +4", 8.0),
+            ("2 ^ -+1", 0.5),
+            ("-(+2) * --2", -4.0),
+        ];
+
+        for (expression, value) in accepted_expressions {
+            assert_eq!(evaluate(expression, false), Ok(value), "{}", expression);
+        }
+
+        assert_eq!(
+            evaluate("2 + * 3", false),
+            Err(String::from(
+                "Expected a number, an identifier or '(' after operator '+' at column 5, found operator '*'"
+            ))
+        );
+
+        assert_eq!(
+            evaluate("2 - -", false),
+            Err(String::from(
+                "Expected a number, an identifier or '(' after operator '-' at column 6, found end of expression"
+            ))
+        );
+
+        assert_eq!(
+            evaluate("* 2", false),
+            Err(String::from(
+                "Expected a number, an identifier or '(' at start of expression at column 1, found operator '*'"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_missing_operand() {
+        assert_eq!(
+            evaluate("(1 + 2) *", false),
+            Err(String::from(
+                "Expected a number, an identifier or '(' after operator '*' at column 10, found end of expression"
+            ))
+        );
+
+        assert_eq!(
+            evaluate("2 * / 3", false),
+            Err(String::from(
+                "Expected a number, an identifier or '(' after operator '*' at column 5, found operator '/'"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_missing_operator() {
+        assert_eq!(
+            evaluate("2 3", false),
+            Err(String::from(
+                "Expected an operator or end of expression after number '2' at column 3, found number '3'"
+            ))
+        );
+
+        assert_eq!(
+            evaluate("2(3 + 1)", false),
+            Err(String::from(
+                "Expected an operator or end of expression after number '2' at column 2, found '('"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_unbalanced_parenthesis() {
+        assert_eq!(
+            evaluate("(1 + 2", false),
+            Err(String::from(
+                "Expected an operator or ')' after number '2' at column 7, found end of expression"
+            ))
+        );
+
+        assert_eq!(
+            evaluate("1 + 2)", false),
+            Err(String::from(
+                "Expected an operator or end of expression after number '2' at column 6, found ')'"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_empty_expression() {
+        assert_eq!(
+            evaluate("  ", false),
+            Err(String::from(
+                "Expected a number, an identifier or '(' at start of expression at column 3, found end of expression"
+            ))
+        );
+
+        assert_eq!(
+            evaluate("x + 1", false),
+            Err(String::from(
+                "The identifier 'x' at column 1 is not defined"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builtin_evaluator_error_span() {
+        let evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+
+        let spans: [(&str, Range<usize>); 6] = [
+            ("(1 + 2) * ", 10..11),
+            ("2 * / x", 4..5),
+            ("2 x", 2..3),
+            ("(1 + [2]", 8..9),
+            ("", 0..1),
+            ("2 + [1, 2] + [1, 2, 3]", 11..12),
+        ];
+
+        for (expression, span) in spans {
+            match evaluator.evaluate(expression) {
+                Ok(_) => assert!(false, "{}", expression),
+                Err(error) => {
+                    assert_eq!(error.expression(), expression);
+                    assert_eq!(error.span(), span, "{}", expression);
+                }
+            }
+        }
+
+        match evaluator.evaluate("2 * / x") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.render(),
+                "Expected a number, an identifier or '(' after operator '*' at column 5, found operator '/'\n2 * / x\n    ^"
+            ),
+        }
+
+        match evaluator.evaluate("1 + undefined") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(error.render().lines().last(), Some("    ^^^^^^^^^")),
+        }
+    }
+
+    #[test]
+    fn test_builtin_evaluator_with_deep_nesting() {
+        let evaluator: BuiltinEvaluator = BuiltinEvaluator::new();
+
+        // Expression of numbers is evaluated from its tokens, without recursion
+        let depth: usize = 2 * MAX_NESTING_DEPTH;
+        let expression: String = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(
+            evaluator.evaluate(expression.as_str()),
+            Ok(Value::Number(1.0))
+        );
+
+        let expression: String = format!("{}[1]{}", "(".repeat(depth), ")".repeat(depth));
+
+        match evaluator.evaluate(expression.as_str()) {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(
+                    error.message(),
+                    "The expression is nested deeper than 128 levels at column 129"
+                );
+                assert_eq!(error.span(), 128..129);
+            }
+        }
+
+        let expression: String = format!("{}[1]", "-".repeat(depth));
+        assert!(evaluator.evaluate(expression.as_str()).is_err());
+
+        let expression: String = format!("{}[1]", "-".repeat(MAX_NESTING_DEPTH - 2));
+        assert_eq!(
+            evaluator.evaluate(expression.as_str()),
+            Ok(Value::Vector(vec![1.0]))
+        );
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn test_complex_evaluator_evaluate() {
+        let evaluate_complex = |expression: &str| -> Result<String, String> {
+            return ComplexEvaluator::new()
+                .evaluate(expression)
+                .map(|value| value.to_string())
+                .map_err(String::from);
+        };
+
+        assert_eq!(
+            evaluate_complex("(1 + 2i) * (3 - j)"),
+            Ok(String::from("5 + 5i"))
+        );
+        assert_eq!(
+            evaluate_complex("i^2 + 1.5e1i"),
+            Ok(String::from("-1 + 15i"))
+        );
+        assert_eq!(evaluate_complex("abs(3 + 4i)"), Ok(String::from("5")));
+        assert_eq!(
+            evaluate_complex("re(conj(2 - 3i)) + im(2 - 3i)"),
+            Ok(String::from("-1"))
+        );
+
+        assert_eq!(
+            evaluate_complex("4 k"),
+            Err(String::from(
+                "Expected an operator or end of expression after number '4' at column 3, found identifier 'k'"
+            ))
+        );
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_evaluator_evaluate() {
+        let evaluate_decimal = |expression: &str| -> Result<String, String> {
+            return DecimalEvaluator::new()
+                .evaluate(expression)
+                .map(|value| value.to_string())
+                .map_err(String::from);
+        };
+
+        assert_eq!(evaluate_decimal("0.1 + 0.2"), Ok(String::from("0.3")));
+        assert_eq!(evaluate_decimal("-2^2 + 2^-2"), Ok(String::from("-3.75")));
+        assert_eq!(evaluate_decimal("1.5e1 * 2"), Ok(String::from("30")));
+
+        assert_eq!(
+            evaluate_decimal("2^0.5"),
+            Err(String::from("The exponent 0.5 is not an integer"))
+        );
+        assert_eq!(
+            evaluate_decimal("1 / 0"),
+            Err(String::from("Division by zero"))
+        );
+    }
+}
@@ -1,4 +1,54 @@
+use crate::error::{Cause, Failure};
+use crate::token::{self, IdentifierPredicate, Lexeme, LexemeKind, Lexer};
+use crate::validation;
+
 use std::collections::HashMap;
+use std::fmt;
+
+/// Character escaping a name to prevent its replacement by variable value
+const ESCAPE_CHARACTER: char = '`';
+
+/// Character marking a function definition whose called functions are replaced at definition
+pub(crate) const FREEZE_MARKER: char = '!';
+
+/// Operators written between two operands, they are surrounded by spaces in canonical form
+pub(crate) const BINARY_OPERATORS: [&str; 15] = [
+    "+", "-", "*", "/", "^", "%", "<", ">", "=", "==", "!=", "<=", ">=", "&&", "||",
+];
+
+/// Maximal number of function calls replaced in an expression, it prevents infinite recursion
+pub(crate) const MAX_FUNCTION_EXPANSIONS: usize = 10_000;
+
+/// Statistics about replacement of functions in an expression
+///
+/// A function call written in expression has depth 1,
+/// a function call coming from definition of a function called at depth n has depth n + 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExpansionStatistics {
+    pub expansion_count: usize, // number of replaced function calls
+    pub max_depth: usize,       // maximal depth of replaced function calls
+}
+
+/// Limits of replacement of functions in an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ExpansionLimits {
+    pub max_expansions: usize,     // maximal number of replaced function calls
+    pub max_length: Option<usize>, // maximal number of characters of expression while replacing, none for no limit
+}
+
+/// Order of replacement of functions and variables in an expression
+///
+/// FunctionsFirst replaces function calls, then variables of the expanded expression,
+/// including the ones coming from function definitions.
+///
+/// VariablesFirst replaces variables written in expression, then function calls,
+/// thus variables coming from function definitions are left in expansion.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SubstitutionOrder {
+    #[default]
+    FunctionsFirst,
+    VariablesFirst,
+}
 
 /// Kind of expression that we can parse
 ///
@@ -22,134 +72,465 @@ pub enum Expression {
 
 impl Expression {
     /// Construct an Expression from string
+    ///
+    /// A function definition whose variables are replaced by a number, as `f: 2 = $1 * $1 + $2`,
+    /// has positional variables `$1`, `$2`, ... up to this number.
+    ///
+    /// The left side of `=` is a function header only if each variable after `:` is an identifier,
+    /// else the whole left side is the name of a variable. Thus `note: this is text = 5` defines
+    /// variable `note: this is text`, which is rejected later as its name is not valid.
     pub fn new(expression: &str) -> Self {
-        return match expression.split_once('=') {
+        return Self::with_argument_separator(expression, ',');
+    }
+
+    /// Construct an Expression from string whose variables of function definition
+    /// are separated by given separator, as `f: x; y = x * y` with `;`
+    pub fn with_argument_separator(expression: &str, argument_separator: char) -> Self {
+        return Self::with_identifier_predicate(
+            expression,
+            argument_separator,
+            token::is_identifier_character,
+        );
+    }
+
+    /// Construct an Expression from string as `with_argument_separator`, variables of function definition
+    /// being identifiers made of characters accepted by predicate, as `f: $x = $x * 2` with `$` accepted
+    pub fn with_identifier_predicate(
+        expression: &str,
+        argument_separator: char,
+        is_identifier_character: IdentifierPredicate,
+    ) -> Self {
+        let assignment = assignment_split(expression).map(|(position, is_function)| {
+            (
+                &expression[..position],
+                &expression[(position + 1)..],
+                is_function,
+            )
+        });
+
+        return match assignment {
             // Here the expression define a variable or function
-            Some((name, definition)) => match name.split_once(':') {
-                // Here we have a function
-                Some((fun_name, fun_variables_compact)) => {
-                    let fun_variables: Vec<String> = fun_variables_compact
-                        .split(',')
-                        .map(|fun_variable_name: &str| {
-                            String::from(fun_variable_name.trim_start().trim_end())
-                        })
-                        .collect();
-
-                    return Self::Function(
-                        String::from(fun_name.trim_start().trim_end()),
-                        fun_variables,
+            Some((name, definition, is_function)) => {
+                match split_function_header(name, &[argument_separator], is_identifier_character)
+                    .filter(|_| is_function)
+                {
+                    // Here we have a function
+                    Some((fun_name, fun_variables)) => {
+                        return Self::Function(
+                            String::from(fun_name.trim_start().trim_end()),
+                            fun_variables,
+                            String::from(definition.trim_start().trim_end()),
+                        );
+                    }
+                    // Here we have a variable
+                    None => Self::Variable(
+                        String::from(name.trim_start().trim_end()),
                         String::from(definition.trim_start().trim_end()),
-                    );
+                    ),
                 }
-                // Here we have a variable
-                None => Self::Variable(
-                    String::from(name.trim_start().trim_end()),
-                    String::from(definition.trim_start().trim_end()),
-                ),
-            },
+            }
             // Here we have a raw expression
             None => Self::Raw(String::from(expression)),
         };
     }
 
+    /// Get definition of expression, i.e. the raw expression or the right side of `=`
+    pub fn definition(&self) -> &str {
+        return match self {
+            Self::Raw(raw_expression) => raw_expression,
+            Self::Variable(_, definition) => definition,
+            Self::Function(_, _, definition) => definition,
+        };
+    }
+
+    /// Get names of variables contained in expression which would be replaced by `replace_variables`
+    ///
+    /// Names are distinct and sorted in alphabetical order.
+    pub fn referenced_variables<'a, Value>(
+        &self,
+        variables: &'a HashMap<String, Value>,
+    ) -> Vec<&'a str> {
+        let definition: &str = self.definition();
+
+        let function_variables: &[String] = match self {
+            Self::Function(_, function_variables, _) => function_variables,
+            _ => &[],
+        };
+
+        let mut names: Vec<&str> = token::lex(definition)
+            .into_iter()
+            .filter(|lexeme| lexeme.kind == LexemeKind::Identifier)
+            .filter(|lexeme| !definition[..lexeme.range.start].ends_with(ESCAPE_CHARACTER))
+            .map(|lexeme| &definition[lexeme.range])
+            .filter(|name| !function_variables.iter().any(|variable| variable == name))
+            .filter_map(|name| variables.get_key_value(name))
+            .map(|(name, _)| name.as_str())
+            .collect();
+
+        names.sort_unstable();
+        names.dedup();
+
+        return names;
+    }
+
+    /// Get names of functions contained in expression without being called, as `f` in `f + 2`
+    ///
+    /// A function name is not called when it is not followed by an opening parenthesis.
+    /// Escaped names and variables of a function definition are ignored.
+    /// Names are distinct and sorted in alphabetical order.
+    pub fn uncalled_functions<'a, Function>(
+        &self,
+        functions: &'a HashMap<String, Function>,
+    ) -> Vec<&'a str> {
+        let definition: &str = self.definition();
+
+        let function_variables: &[String] = match self {
+            Self::Function(_, function_variables, _) => function_variables,
+            _ => &[],
+        };
+
+        let lexemes: Vec<Lexeme> = token::lex(definition);
+
+        let mut names: Vec<&str> = lexemes
+            .iter()
+            .enumerate()
+            .filter(|(_, lexeme)| lexeme.kind == LexemeKind::Identifier)
+            .filter(|(_, lexeme)| !definition[..lexeme.range.start].ends_with(ESCAPE_CHARACTER))
+            .filter(|(id, _)| {
+                return lexemes
+                    .get(id + 1)
+                    .is_none_or(|next_lexeme| &definition[next_lexeme.range.clone()] != "(");
+            })
+            .map(|(_, lexeme)| &definition[lexeme.range.clone()])
+            .filter(|name| !function_variables.iter().any(|variable| variable == name))
+            .filter_map(|name| functions.get_key_value(name))
+            .map(|(name, _)| name.as_str())
+            .collect();
+
+        names.sort_unstable();
+        names.dedup();
+
+        return names;
+    }
+
+    /// Replace identifiers contained in definition according to substitution given in argument
+    ///
+    /// The substitution gives the replacement of an identifier, or none to leave it untouched.
+    /// Only whole identifiers are replaced, thus a longer name containing a replaced name
+    /// or a number as `1.5e2` is never modified. An identifier preceded by escape character is left untouched.
+    fn substitute_identifiers<Substitution>(
+        definition: &str,
+        substitution: Substitution,
+        is_identifier_character: IdentifierPredicate,
+    ) -> String
+    where
+        Substitution: Fn(&str) -> Option<String>,
+    {
+        let mut substituted_definition: String = String::with_capacity(definition.len());
+        let mut last_position: usize = 0;
+
+        token::lex_with_identifier_predicate(definition, is_identifier_character)
+            .into_iter()
+            .filter(|lexeme| lexeme.kind == LexemeKind::Identifier)
+            .filter(|lexeme| !definition[..lexeme.range.start].ends_with(ESCAPE_CHARACTER))
+            .for_each(|lexeme| {
+                if let Some(replacement) = substitution(&definition[lexeme.range.clone()]) {
+                    substituted_definition.push_str(&definition[last_position..lexeme.range.start]);
+                    substituted_definition.push_str(replacement.as_str());
+                    last_position = lexeme.range.end;
+                }
+            });
+
+        substituted_definition.push_str(&definition[last_position..]);
+
+        return substituted_definition;
+    }
+
+    /// Remove escape characters directly preceding an identifier, other backticks are kept
+    fn remove_escape_characters(
+        definition: &str,
+        is_identifier_character: IdentifierPredicate,
+    ) -> String {
+        let lexemes: Vec<Lexeme> =
+            token::lex_with_identifier_predicate(definition, is_identifier_character);
+
+        let mut unescaped_definition: String = String::with_capacity(definition.len());
+        let mut last_position: usize = 0;
+
+        for (lexeme, next_lexeme) in lexemes.iter().zip(lexemes.iter().skip(1)) {
+            let is_escape: bool = definition[lexeme.range.clone()].starts_with(ESCAPE_CHARACTER)
+                && next_lexeme.kind == LexemeKind::Identifier
+                && next_lexeme.range.start == lexeme.range.end;
+
+            if is_escape {
+                unescaped_definition.push_str(&definition[last_position..lexeme.range.start]);
+                last_position = lexeme.range.end;
+            }
+        }
+
+        unescaped_definition.push_str(&definition[last_position..]);
+
+        return unescaped_definition;
+    }
+
     /// Replace all variable contained in expression by their value
     ///
     /// The variables are given in argument through HashMap where
     /// pair (key, value) correspond respectively to name and value of variable
+    ///
+    /// A variable name preceded by a backtick, as `` `x ``, is not replaced.
+    /// In raw and variable expressions, the backtick is then removed to pass `x` to evaluator.
+    /// In function expressions, the backtick is kept to protect the name when function is called,
+    /// and nothing is replaced if variables of function are not valid, see `validation::check_function_variables`.
     pub fn replace_variables(&mut self, variables: &HashMap<String, f64>) {
+        self.replace_variables_with(|name| {
+            return variables.get(name).map(|value| format!("{}", value));
+        });
+    }
+
+    /// Replace all variable contained in expression according to substitution given in argument
+    ///
+    /// The substitution gives the string replacing a variable from its name, or none if it is not a variable.
+    /// It behaves as `replace_variables` otherwise, in particular for escaped names and function variables.
+    pub fn replace_variables_with<Substitution>(&mut self, substitution: Substitution)
+    where
+        Substitution: Fn(&str) -> Option<String>,
+    {
+        self.replace_variables_with_predicate(substitution, token::is_identifier_character);
+    }
+
+    /// Replace all variable contained in expression according to substitution given in argument,
+    /// identifiers being made of characters accepted by predicate given in argument
+    ///
+    /// It behaves as `replace_variables_with`, thus with a predicate accepting `$`,
+    /// `$x` is replaced as a whole identifier.
+    pub fn replace_variables_with_predicate<Substitution>(
+        &mut self,
+        substitution: Substitution,
+        is_identifier_character: IdentifierPredicate,
+    ) where
+        Substitution: Fn(&str) -> Option<String>,
+    {
         match self {
             Self::Raw(definition) | Self::Variable(_, definition) => {
-                variables
-                    .iter()
-                    .for_each(|(variable_name, variable_value)| {
-                        let mut replaced_definition: String = definition
-                            .replace(variable_name, format!("{}", variable_value).as_str());
-
-                        core::mem::swap(definition, &mut replaced_definition);
-                    });
+                let replaced_definition: String = Expression::substitute_identifiers(
+                    definition,
+                    substitution,
+                    is_identifier_character,
+                );
+
+                let mut replaced_definition: String = Expression::remove_escape_characters(
+                    replaced_definition.as_str(),
+                    is_identifier_character,
+                );
+
+                core::mem::swap(definition, &mut replaced_definition);
             }
-            Self::Function(_, function_variables, definition) => {
-                variables
+            Self::Function(name, function_variables, definition) => {
+                // Invalid variables cannot tell which names they shadow, thus nothing is replaced
+                if validation::check_function_variables_with_predicate(
+                    name,
+                    function_variables,
+                    definition,
+                    is_identifier_character,
+                )
+                .is_err()
+                {
+                    return;
+                }
+
+                // Variables are compared trimmed, as a variable ` x` built by hand still shadows `x`
+                let parameters: Vec<&str> = function_variables
                     .iter()
-                    .filter(|(variable_name, _)| {
-                        return !function_variables.contains(variable_name);
-                    })
-                    .for_each(|(variable_name, variable_value)| {
-                        let mut replaced_definition: String = definition
-                            .replace(variable_name, format!("{}", variable_value).as_str());
-
-                        core::mem::swap(definition, &mut replaced_definition);
-                    });
+                    .map(|variable| variable.trim())
+                    .collect();
+
+                let mut replaced_definition: String = Expression::substitute_identifiers(
+                    definition,
+                    |name| {
+                        if parameters.contains(&name) {
+                            return None;
+                        }
+
+                        return substitution(name);
+                    },
+                    is_identifier_character,
+                );
+
+                core::mem::swap(definition, &mut replaced_definition);
             }
         };
     }
 
-    /// Recovery positions of function and its parenthesis in expression definition
-    /// Expression definition and function name are given in argument
-    fn get_function_positions(
-        expression_definition: &String,
-        fun_name: &String,
-    ) -> Result<Option<(usize, usize, usize)>, String> {
-        // Get position of function
-        let potential_start_position: Option<usize> = expression_definition.find(fun_name.as_str());
-
-        if potential_start_position.is_none() {
-            return Ok(None);
+    /// Split arguments of a function call on separators which are not nested in parenthesis or brackets
+    fn split_arguments(arguments: &str, argument_separator: char) -> Vec<&str> {
+        let mut split_arguments: Vec<&str> = Vec::new();
+        let mut parenthesis_counter: usize = 0;
+        let mut start_position: usize = 0;
+
+        for (position, c) in arguments.char_indices() {
+            match c {
+                '(' | '[' => parenthesis_counter += 1,
+                ')' | ']' => parenthesis_counter = parenthesis_counter.saturating_sub(1),
+                c if c == argument_separator && parenthesis_counter == 0 => {
+                    split_arguments.push(arguments[start_position..position].trim());
+                    start_position = position + 1;
+                }
+                _ => {}
+            }
         }
 
-        let start_position: usize = potential_start_position.unwrap();
+        split_arguments.push(arguments[start_position..].trim());
+
+        return split_arguments;
+    }
+
+    /// Check if an argument of function call contains an operator outside of brackets,
+    /// in this case it must be surrounded by parenthesis when it replaces a function variable
+    ///
+    /// Any operator is considered, including unary minus as in `-2` or comparison as in `a == b`,
+    /// so that precedence of operators in function definition never applies inside argument.
+    /// An argument already between parenthesis, as `(1 + 2)`, or a call, as `sin(x + 1)`, is not compound.
+    fn is_compound_argument(argument: &str) -> bool {
+        let mut bracket_counter: usize = 0;
+
+        return token::lex(argument).iter().any(|lexeme: &Lexeme| {
+            if lexeme.kind != LexemeKind::Symbol {
+                return false;
+            }
+
+            match &argument[lexeme.range.clone()] {
+                "(" | "[" => bracket_counter += 1,
+                ")" | "]" => bracket_counter = bracket_counter.saturating_sub(1),
+                "`" => {}
+                _ => return bracket_counter == 0,
+            }
+
+            return false;
+        });
+    }
+
+    /// Substitute variables of a function definition by arguments, a missing argument leaves its variable
+    ///
+    /// A compound argument, as `1 + 2`, is put between parentheses (see `is_compound_argument`),
+    /// thus with `x * y` for variables `x` and `y`, arguments `1 + 2` and none give `(1 + 2) * y`.
+    /// Variables and arguments must have the same length. Identifiers are made of characters
+    /// accepted by predicate, see `token::is_identifier_character`.
+    pub(crate) fn substitute_arguments(
+        definition: &str,
+        variables: &[String],
+        arguments: &[Option<&str>],
+        is_identifier_character: IdentifierPredicate,
+    ) -> String {
+        return Expression::substitute_identifiers(
+            definition,
+            |name| {
+                let id: usize = variables.iter().position(|variable| variable == name)?;
+                let argument: &str = arguments[id]?;
+
+                if Expression::is_compound_argument(argument) {
+                    return Some(format!("({})", argument));
+                }
 
-        // Get position of opening parenthesis
-        let start_search_parenthesis_position: usize = start_position + fun_name.len();
+                return Some(String::from(argument));
+            },
+            is_identifier_character,
+        );
+    }
 
-        let potential_opening_parenthesis_position: Option<usize> = expression_definition
-            .chars()
-            .skip(start_search_parenthesis_position)
-            .position(|c| c == '(');
+    /// Check if an expanded definition does not need parentheses, i.e. it is a single token
+    /// which is not an identifier, as `5`, or it is entirely between parentheses, as `(x + 1)`
+    /// but not `(x) + (1)`
+    ///
+    /// An identifier is wrapped since it can be replaced afterwards by the value of a variable,
+    /// as `y` of `f(y) ^ 2` with `y = -3`.
+    fn is_wrapped(definition: &str) -> bool {
+        let lexemes: Vec<Lexeme> = token::lex(definition);
 
-        if potential_opening_parenthesis_position.is_none() {
-            return Ok(None);
+        if lexemes.len() == 1 {
+            return lexemes[0].kind != LexemeKind::Identifier;
         }
 
-        let opening_parenthesis_position: usize =
-            start_search_parenthesis_position + potential_opening_parenthesis_position.unwrap();
+        let mut bracket_counter: usize = 0;
+
+        for (id, lexeme) in lexemes.iter().enumerate() {
+            match &definition[lexeme.range.clone()] {
+                "(" => bracket_counter += 1,
+                ")" => bracket_counter = bracket_counter.saturating_sub(1),
+                _ => {}
+            }
 
-        // To get closing parenthesis, we initialize a counter to 1, then we increment it when we encounter
-        // an opening parenthesis or we decrement it when we encounter a closing parenthesis.
-        // When the counter reach 0, we have on closing parenthesis corresponding to function.
-        let mut parenthesis_counter: u8 = 1;
+            if bracket_counter == 0 {
+                return id > 0 && id + 1 == lexemes.len();
+            }
+        }
 
-        let closing_parenthesis_position: usize = opening_parenthesis_position
-            + 1
-            + expression_definition
-                .chars()
-                .skip(opening_parenthesis_position + 1)
-                .take_while(|c| -> bool {
-                    match c {
-                        '(' => parenthesis_counter += 1,
-                        ')' => parenthesis_counter -= 1,
-                        _ => {}
-                    }
+        return false;
+    }
+
+    /// Recovery first function call in expression definition with positions of function and its parenthesis
+    ///
+    /// Expression definition and functions are given in argument.
+    /// A function call is a function name followed by an opening parenthesis, whitespaces are allowed between them.
+    /// Function names are identifiers made of characters accepted by predicate.
+    fn get_function_positions<'a>(
+        expression_definition: &str,
+        functions: &'a HashMap<String, (Vec<String>, String)>,
+        is_identifier_character: IdentifierPredicate,
+    ) -> Result<Option<(&'a str, usize, usize, usize)>, String> {
+        let lexeme_str = |lexeme: &Lexeme| -> &str { &expression_definition[lexeme.range.clone()] };
+
+        // Lexemes are produced lazily to stop at first function call
+        let mut lexemes =
+            Lexer::with_identifier_predicate(expression_definition, is_identifier_character)
+                .peekable();
+
+        while let Some(lexeme) = lexemes.next() {
+            if lexeme.kind != LexemeKind::Identifier {
+                continue;
+            }
 
-                    return parenthesis_counter > 0;
-                })
-                .count();
+            // Get name of function
+            let fun_name: &str = match functions.get_key_value(lexeme_str(&lexeme)) {
+                Some((fun_name, _)) => fun_name,
+                None => continue,
+            };
+
+            // Get position of opening parenthesis, else it is not a function call
+            let opening_parenthesis_position: usize = match lexemes.peek() {
+                Some(next_lexeme) if lexeme_str(next_lexeme) == "(" => next_lexeme.range.start,
+                _ => continue,
+            };
+
+            // To get closing parenthesis, we initialize a counter to 0, then we increment it when we encounter
+            // an opening parenthesis or we decrement it when we encounter a closing parenthesis.
+            // When the counter reach 0, we have on closing parenthesis corresponding to function.
+            let mut parenthesis_counter: usize = 0;
+
+            for next_lexeme in lexemes.by_ref() {
+                match lexeme_str(&next_lexeme) {
+                    "(" => parenthesis_counter += 1,
+                    ")" => parenthesis_counter -= 1,
+                    _ => {}
+                }
 
-        // Check if we handle a function, else we go to next function name
-        let has_char_between_fun_name_and_first_parenthesis: bool = expression_definition
-            [start_search_parenthesis_position..opening_parenthesis_position]
-            .chars()
-            .any(|c| !c.is_whitespace());
+                if parenthesis_counter == 0 {
+                    return Ok(Some((
+                        fun_name,
+                        lexeme.range.start,
+                        opening_parenthesis_position,
+                        next_lexeme.range.start,
+                    )));
+                }
+            }
 
-        if has_char_between_fun_name_and_first_parenthesis {
-            return Ok(None);
+            return Err(format!(
+                "The closing parenthesis of function {} is missing",
+                fun_name
+            ));
         }
 
-        return Ok(Some((
-            start_position,
-            opening_parenthesis_position,
-            closing_parenthesis_position,
-        )));
+        return Ok(None);
     }
 
     /// Replace all function contained in expression by their definition
@@ -157,145 +538,768 @@ impl Expression {
     /// The function are given in argument through HashMap where
     /// key correspond to name of function and value is a pair containing
     /// name of variables and definition of function
+    ///
+    /// Function calls are replaced from left to right until no call remains,
+    /// thus function calls contained in definition of a function are also replaced.
+    ///
+    /// A function call is a function name followed by an opening parenthesis,
+    /// any whitespace, including tabulation and newline, is allowed between them as `distance (2, 3)`.
+    /// A function name followed by anything else, as `distance 2`, is not a call and is left untouched.
     pub fn replace_functions(
         &mut self,
         functions: &HashMap<String, (Vec<String>, String)>,
     ) -> Result<(), String> {
+        self.replace_functions_with_statistics(functions)?;
+
+        return Ok(());
+    }
+
+    /// Replace all function contained in expression by their definition as `replace_functions`
+    /// and return statistics about replacement
+    pub fn replace_functions_with_statistics(
+        &mut self,
+        functions: &HashMap<String, (Vec<String>, String)>,
+    ) -> Result<ExpansionStatistics, String> {
+        return self.replace_functions_with_separator(functions, ',');
+    }
+
+    /// Replace all function contained in expression by their definition as `replace_functions_with_statistics`,
+    /// arguments of function calls being separated by given separator, as `f(1; 2)` with `;`
+    pub fn replace_functions_with_separator(
+        &mut self,
+        functions: &HashMap<String, (Vec<String>, String)>,
+        argument_separator: char,
+    ) -> Result<ExpansionStatistics, String> {
+        return self
+            .replace_functions_with_limit(
+                functions,
+                argument_separator,
+                false,
+                0,
+                ExpansionLimits {
+                    max_expansions: MAX_FUNCTION_EXPANSIONS,
+                    max_length: None,
+                },
+                token::is_identifier_character,
+            )
+            .map_err(String::from);
+    }
+
+    /// Replace all function contained in expression by their definition as `replace_functions_with_separator`,
+    /// with limits of replacement, maximal number of replaced function calls counting from number of calls already replaced
+    ///
+    /// With minimal parentheses, an expanded definition which is a single number or already between
+    /// parentheses, as `5` or `(x + 1)`, is not wrapped in parentheses.
+    /// Expansion count of returned statistics includes calls already replaced. When maximal number
+    /// is reached, failure has cause `TooManyExpansions` naming outermost function being expanded.
+    /// Expansion stops as soon as expression is longer than maximal length, if any, with cause `ExpansionTooLong`.
+    /// Names of functions and their variables are identifiers made of characters accepted by predicate.
+    pub(crate) fn replace_functions_with_limit(
+        &mut self,
+        functions: &HashMap<String, (Vec<String>, String)>,
+        argument_separator: char,
+        minimal_parentheses: bool,
+        performed_expansions: usize,
+        limits: ExpansionLimits,
+        is_identifier_character: IdentifierPredicate,
+    ) -> Result<ExpansionStatistics, Failure> {
+        let ExpansionLimits {
+            max_expansions,
+            max_length,
+        } = limits;
+
         let definition: &mut String = match self {
             Self::Raw(raw_expression) => raw_expression,
             Self::Variable(_, definition) => definition,
             Self::Function(_, _, definition) => definition,
         };
 
-        for fun_name in functions.keys() {
-            // Get positions of function name and its parenthesis
-            let potential_positions: Option<(usize, usize, usize)> =
-                Expression::get_function_positions(&definition, fun_name)?;
+        let mut statistics: ExpansionStatistics = ExpansionStatistics {
+            expansion_count: performed_expansions,
+            max_depth: 0,
+        };
+
+        // Stack of replaced function calls containing the current position in definition,
+        // each one is given by end position of its replacement, its depth and name of function
+        let mut replacements: Vec<(usize, usize, &str)> = Vec::new();
 
-            if potential_positions.is_none() {
-                // here the functions is not in expression definition
-                continue;
+        // Since calls are replaced from left to right, there is no call before last replaced call
+        let mut search_position: usize = 0;
+
+        // Get name of function and positions of function name and its parenthesis
+        while let Some((
+            fun_name,
+            start_position,
+            opening_parenthesis_position,
+            closing_parenthesis_position,
+        )) = Expression::get_function_positions(
+            &definition[search_position..],
+            functions,
+            is_identifier_character,
+        )? {
+            let start_position: usize = search_position + start_position;
+            let opening_parenthesis_position: usize =
+                search_position + opening_parenthesis_position;
+            let closing_parenthesis_position: usize =
+                search_position + closing_parenthesis_position;
+
+            search_position = start_position;
+
+            // Get depth of function call from replacements containing it
+            while replacements
+                .last()
+                .is_some_and(|(end_position, _, _)| *end_position <= start_position)
+            {
+                replacements.pop();
             }
 
-            let (start_position, opening_parenthesis_position, closing_parenthesis_position) =
-                potential_positions.unwrap();
+            if statistics.expansion_count >= max_expansions {
+                let outermost_name: &str =
+                    replacements.first().map_or(fun_name, |(_, _, name)| name);
+
+                return Err(Failure {
+                    message: format!(
+                        "The maximal number of function expansions ({}) is reached during expansion of function {}",
+                        max_expansions, outermost_name
+                    ),
+                    cause: Some(Cause::TooManyExpansions {
+                        function: String::from(outermost_name),
+                        performed: statistics.expansion_count,
+                        limit: max_expansions,
+                    }),
+                    legacy_message: None,
+                });
+            }
+
+            statistics.expansion_count += 1;
+
+            let depth: usize = replacements.last().map_or(0, |(_, depth, _)| *depth) + 1;
+            statistics.max_depth = statistics.max_depth.max(depth);
 
             // Get value of function variables
-            let variable_values: Vec<&str> = definition
-                [(opening_parenthesis_position + 1)..closing_parenthesis_position]
-                .split(", ")
-                .collect();
+            let variable_values: Vec<&str> = Expression::split_arguments(
+                &definition[(opening_parenthesis_position + 1)..closing_parenthesis_position],
+                argument_separator,
+            );
 
             // Create string to replace function call by function body
-            let variables: &Vec<String> = functions[fun_name].0.as_ref();
-            let mut replaced_fun_definition: String = functions[fun_name].1.clone();
+            let (variables, fun_definition) = &functions[fun_name];
 
             if variables.len() != variable_values.len() {
-                return Err(format!("The number of variables is not consistent"));
+                return Err(Failure::from(String::from(
+                    "The number of variables is not consistent",
+                )));
             }
 
-            let mut id: usize = 0;
+            let arguments: Vec<Option<&str>> = variable_values.into_iter().map(Some).collect();
 
-            for variable in variables {
-                if variable_values[id]
-                    .chars()
-                    .any(|c| c == '+' || c == '*' || c == '-' || c == '/')
-                {
-                    replaced_fun_definition = replaced_fun_definition
-                        .replace(variable, format!("({})", variable_values[id]).as_str());
+            let replaced_fun_definition: String = Expression::substitute_arguments(
+                fun_definition,
+                variables,
+                &arguments,
+                is_identifier_character,
+            );
+
+            let replacement: String =
+                if minimal_parentheses && Expression::is_wrapped(&replaced_fun_definition) {
+                    replaced_fun_definition
                 } else {
-                    replaced_fun_definition =
-                        replaced_fun_definition.replace(variable, variable_values[id]);
-                }
+                    format!("({})", replaced_fun_definition)
+                };
+            let replaced_length: usize = closing_parenthesis_position + 1 - start_position;
+
+            // Replacements containing function call are shifted by difference of length
+            replacements.iter_mut().for_each(|(end_position, _, _)| {
+                *end_position = *end_position + replacement.len() - replaced_length;
+            });
+
+            replacements.push((start_position + replacement.len(), depth, fun_name));
+
+            definition.replace_range(
+                start_position..=closing_parenthesis_position,
+                replacement.as_str(),
+            );
 
-                id += 1;
+            // Number of bytes is an upper bound of number of characters, which are counted only beyond it
+            if let Some(max_length) = max_length {
+                if definition.len() > max_length && definition.chars().count() > max_length {
+                    let outermost_name: &str =
+                        replacements.first().map_or(fun_name, |(_, _, name)| name);
+
+                    return Err(Failure {
+                        message: format!(
+                            "The expansion is too long during expansion of function {} (maximal length is {})",
+                            outermost_name, max_length
+                        ),
+                        cause: Some(Cause::ExpansionTooLong {
+                            function: Some(String::from(outermost_name)),
+                            limit: max_length,
+                        }),
+                        legacy_message: None,
+                    });
+                }
             }
+        }
+
+        return Ok(statistics);
+    }
+
+    /// Count call sites of each function in expression definition, without replacing them
+    ///
+    /// Calls contained in arguments of another call are also counted, as both calls of `f(f(1))`.
+    /// Functions which are not called are absent of result, and the scan stops
+    /// at a call whose closing parenthesis is missing.
+    pub fn call_counts(
+        &self,
+        functions: &HashMap<String, (Vec<String>, String)>,
+    ) -> HashMap<String, usize> {
+        let definition: &str = self.definition();
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut position: usize = 0;
+
+        while let Ok(Some((fun_name, _, opening_position, _))) = Expression::get_function_positions(
+            &definition[position..],
+            functions,
+            token::is_identifier_character,
+        ) {
+            *counts.entry(String::from(fun_name)).or_insert(0) += 1;
+            position += opening_position + 1;
+        }
+
+        return counts;
+    }
+
+    /// Replace calls of given functions by text given by a replacement function
+    ///
+    /// The replacement function receives name of called function and its arguments between parenthesis,
+    /// as `f` and `(1, 2)` for call `f(1, 2)`. Calls are searched from left to right and text of a replacement
+    /// is not searched again, thus calls nested in arguments are part of replaced call.
+    pub fn replace_calls_with<Replace>(
+        &mut self,
+        functions: &HashMap<String, (Vec<String>, String)>,
+        mut replace: Replace,
+    ) -> Result<(), String>
+    where
+        Replace: FnMut(&str, &str) -> Result<String, String>,
+    {
+        let definition: &mut String = match self {
+            Self::Raw(raw_expression) => raw_expression,
+            Self::Variable(_, definition) => definition,
+            Self::Function(_, _, definition) => definition,
+        };
+
+        let mut search_position: usize = 0;
+
+        while let Some((
+            fun_name,
+            start_position,
+            opening_parenthesis_position,
+            closing_parenthesis_position,
+        )) = Expression::get_function_positions(
+            &definition[search_position..],
+            functions,
+            token::is_identifier_character,
+        )? {
+            let start_position: usize = search_position + start_position;
+            let opening_parenthesis_position: usize =
+                search_position + opening_parenthesis_position;
+            let closing_parenthesis_position: usize =
+                search_position + closing_parenthesis_position;
+
+            let replacement: String = replace(
+                fun_name,
+                &definition[opening_parenthesis_position..=closing_parenthesis_position],
+            )?;
 
             definition.replace_range(
                 start_position..=closing_parenthesis_position,
-                format!("({})", replaced_fun_definition).as_str(),
+                replacement.as_str(),
             );
+
+            search_position = start_position + replacement.len();
         }
 
         return Ok(());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Rename calls of a function in expression definition, other occurrences of its name are left untouched
+    ///
+    /// It returns the number of renamed calls. The scan stops at a call whose closing parenthesis is missing.
+    pub fn rename_function_calls(&mut self, from: &str, to: &str) -> usize {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::with_capacity(1);
+        functions.insert(String::from(from), (Vec::new(), String::new()));
 
-    #[test]
-    fn test_expression_new_with_raw_expression() {
-        let expression: String = String::from("1 + 1");
+        let definition: &mut String = match self {
+            Self::Raw(definition) => definition,
+            Self::Variable(_, definition) => definition,
+            Self::Function(_, _, definition) => definition,
+        };
 
-        match Expression::new(expression.as_str()) {
-            Expression::Raw(raw_expression) => assert_eq!(raw_expression, expression),
-            _ => assert!(false),
+        let mut renamed_count: usize = 0;
+        let mut position: usize = 0;
+
+        while let Ok(Some((_, start, opening_position, _))) = Expression::get_function_positions(
+            &definition[position..],
+            &functions,
+            token::is_identifier_character,
+        ) {
+            let name_start: usize = position + start;
+            definition.replace_range(name_start..(name_start + from.len()), to);
+
+            position += opening_position + to.len() - from.len() + 1;
+            renamed_count += 1;
         }
-    }
 
-    #[test]
-    fn test_expression_new_with_variable_definition() {
-        let variable_name: String = String::from("x");
-        let variable_definition: String = String::from("1 + 1");
+        return renamed_count;
+    }
 
-        let expression: String = format!("{} = {}", variable_name, variable_definition);
+    /// Write expression with canonical spacing, as its Display implementation
+    ///
+    /// Only whitespaces are changed, thus `x*x+ y *y` gives `x * x + y * y` and reading
+    /// the result gives the same tokens in the same order.
+    pub fn pretty(&self) -> String {
+        return self.to_string();
+    }
 
-        match Expression::new(expression.as_str()) {
-            Expression::Variable(name, definition) => {
-                assert_eq!(name, variable_name);
-                assert_eq!(definition, variable_definition);
+    /// Remove freeze marker at end of function name, as in `g!: x = f(x) * 2`
+    ///
+    /// It returns true if expression is a function definition with freeze marker.
+    /// In this case, functions called in its definition must be replaced when it is defined,
+    /// instead of when it is called.
+    pub fn remove_freeze_marker(&mut self) -> bool {
+        if let Self::Function(name, _, _) = self {
+            if let Some(unmarked_name) = name.strip_suffix(FREEZE_MARKER) {
+                *name = String::from(unmarked_name.trim_end());
+                return true;
             }
-            _ => assert!(false),
         }
+
+        return false;
     }
+}
 
-    #[test]
-    fn test_expression_new_with_function_definition() {
-        let function_name: String = String::from("distance");
-        let function_variables: Vec<String> =
-            vec![String::from("x"), String::from("y"), String::from("z)")];
-        let function_definition: String = String::from("x * x + y * y + z * z");
+impl fmt::Display for Expression {
+    /// Write expression in canonical form, as `f: x, y = x * (y + 1)`
+    ///
+    /// Binary operators and `=` are surrounded by one space, a comma is followed by one space
+    /// and there is no space inside parenthesis nor after a unary sign.
+    /// Positional variables of a function definition are written by their number, as `f: 2 = $1 * $2`.
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::Raw(raw_expression) => {
+                write!(formatter, "{}", canonical_definition(raw_expression))
+            }
+            Self::Variable(name, definition) => {
+                write!(formatter, "{} = {}", name, canonical_definition(definition))
+            }
+            Self::Function(name, variables, definition) => {
+                let are_positional: bool = variables
+                    .iter()
+                    .enumerate()
+                    .all(|(id, variable)| *variable == format!("${}", id + 1));
 
-        let expression: String = format!(
-            "{}: {}, {}, {} = {}",
-            function_name,
-            function_variables[0],
-            function_variables[1],
-            function_variables[2],
-            function_definition
-        );
+                let variables_str: String = if are_positional && !variables.is_empty() {
+                    format!("{}", variables.len())
+                } else {
+                    variables.join(", ")
+                };
+
+                write!(
+                    formatter,
+                    "{}: {} = {}",
+                    name,
+                    variables_str,
+                    canonical_definition(definition)
+                )
+            }
+        };
+    }
+}
 
-        match Expression::new(&expression.as_str()) {
-            Expression::Function(name, variables, definition) => {
-                assert_eq!(name, function_name);
-                assert_eq!(variables, function_variables);
-                assert_eq!(definition, function_definition);
+/// Write definition of an expression in canonical form, see Display implementation of Expression
+///
+/// Consecutive symbols of a comparison or logical operator, as `<=` or `&&`, are kept together.
+/// Between two tokens which are not operators, as in `2 x`, a space is kept only if there was one.
+fn canonical_definition(definition: &str) -> String {
+    let is_operator_part = |c: char| -> bool { return "<>=!&|".contains(c) };
+
+    // Tokens of definition with a flag indicating if they are preceded by a whitespace
+    let mut tokens: Vec<(LexemeKind, &str, bool)> = Vec::new();
+    let mut previous_end: usize = 0;
+
+    for lexeme in token::lex(definition) {
+        let text: &str = &definition[lexeme.range.clone()];
+        let is_spaced: bool = lexeme.range.start > previous_end;
+
+        match tokens.last_mut() {
+            Some((LexemeKind::Symbol, previous_text, _))
+                if !is_spaced
+                    && text.chars().all(is_operator_part)
+                    && previous_text.chars().all(is_operator_part)
+                    && previous_text.len() < 2 =>
+            {
+                let start: usize = lexeme.range.start - previous_text.len();
+                *previous_text = &definition[start..lexeme.range.end];
             }
-            _ => assert!(false),
+            _ => tokens.push((lexeme.kind, text, is_spaced)),
         }
+
+        previous_end = lexeme.range.end;
     }
 
-    #[test]
-    fn test_expression_replace_variables_in_raw_expression() {
-        let mut variables: HashMap<String, f64> = HashMap::new();
+    let ends_operand = |(kind, text, _): &(LexemeKind, &str, bool)| -> bool {
+        return *kind != LexemeKind::Symbol || matches!(*text, ")" | "]" | "}");
+    };
+
+    // An operator is binary when it follows an operand, else `+` and `-` are unary signs
+    let is_binary: Vec<bool> = tokens
+        .iter()
+        .enumerate()
+        .map(|(id, (kind, text, _))| {
+            return *kind == LexemeKind::Symbol
+                && BINARY_OPERATORS.contains(text)
+                && id > 0
+                && ends_operand(&tokens[id - 1]);
+        })
+        .collect();
+
+    let mut canonical_definition: String = String::with_capacity(definition.len());
+
+    for (id, (_, text, is_spaced)) in tokens.iter().enumerate() {
+        if id > 0 {
+            let previous_text: &str = tokens[id - 1].1;
+            let is_previous_unary: bool = !is_binary[id - 1] && matches!(previous_text, "+" | "-");
+
+            let is_space_needed: bool = if is_binary[id] || is_binary[id - 1] {
+                true
+            } else if matches!(previous_text, "(" | "[" | "{" | "`")
+                || is_previous_unary
+                || matches!(*text, ")" | "]" | "}" | "," | ";")
+            {
+                false
+            } else if matches!(previous_text, "," | ";") {
+                true
+            } else {
+                *is_spaced
+            };
+
+            if is_space_needed {
+                canonical_definition.push(' ');
+            }
+        }
 
-        variables.insert(String::from("x"), 1.0);
-        variables.insert(String::from("velocity"), 3.43);
-        variables.insert(String::from("time"), 5.9954);
+        canonical_definition.push_str(text);
+    }
 
-        let raw_expression: String = String::from("(x - 2.75) + velocity * time");
+    return canonical_definition;
+}
 
-        let replaced_raw_expression: String = format!(
-            "({} - 2.75) + {} * {}",
-            variables["x"], variables["velocity"], variables["time"]
-        );
+/// Format an input in canonical form without replacing functions and variables nor evaluating it
+///
+/// It parses input as an Expression and writes it with its Display implementation,
+/// thus `f:x ,y=x*( y+1 )` gives `f: x, y = x * (y + 1)`. Comments and commands are only trimmed.
+/// An empty input, unbalanced brackets or an invalid name gives an error.
+pub fn format(expression_str: &str) -> Result<String, String> {
+    match input_kind(expression_str) {
+        InputKind::Empty => return Err(String::from("The expression is empty")),
+        InputKind::Comment | InputKind::Command => return Ok(String::from(expression_str.trim())),
+        _ => {}
+    }
 
-        let mut expression: Expression = Expression::new(raw_expression.as_str());
-        expression.replace_variables(&variables);
+    validation::check_brackets(expression_str)?;
 
-        match expression {
+    let expression: Expression = Expression::new(expression_str);
+    validation::check_definition_name(&expression)?;
+
+    return Ok(expression.to_string());
+}
+
+/// Kind of input string detected by `input_kind`
+///
+/// Empty is an input containing only whitespaces, Comment is an input starting with `#`
+/// and Command is an input starting with `:` as `:sci`. Other kinds correspond to those of Expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputKind {
+    Raw,
+    Variable,
+    Function,
+    Empty,
+    Comment,
+    Command,
+}
+
+/// Way to handle an empty or whitespace-only input given to `Calculator::process`
+///
+/// Reject gives an error whose cause is `error::Cause::EmptyInput`, and Ignore gives an empty result,
+/// as for frontends sending blank lines. In both cases, calculator is not modified.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EmptyInputPolicy {
+    #[default]
+    Reject,
+    Ignore,
+}
+
+/// Split header of a function definition, as `f: x, y`, into function name and its variables
+///
+/// Variables are given by their number, as `f: 2` for `$1` and `$2`, or separated by one of separators
+/// and each one must be an identifier or a positional variable. Otherwise, as for `note: this is text`,
+/// the header is not a function header and none is returned.
+fn split_function_header<'a>(
+    header: &'a str,
+    argument_separators: &[char],
+    is_identifier_character: IdentifierPredicate,
+) -> Option<(&'a str, Vec<String>)> {
+    let (fun_name, fun_variables_compact) = header.split_once(':')?;
+
+    let fun_variables: Vec<String> = match fun_variables_compact.trim().parse::<usize>() {
+        Ok(arity) if arity > 0 => (1..=arity).map(|id| format!("${}", id)).collect(),
+        _ => fun_variables_compact
+            .split(argument_separators)
+            .map(|fun_variable_name: &str| String::from(fun_variable_name.trim()))
+            .collect(),
+    };
+
+    let is_variable = |fun_variable_name: &String| -> bool {
+        let is_positional: bool = fun_variable_name
+            .strip_prefix('$')
+            .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()));
+
+        return is_positional
+            || validation::is_identifier_with_predicate(
+                fun_variable_name,
+                is_identifier_character,
+            );
+    };
+
+    if !fun_variables.iter().all(is_variable) {
+        return None;
+    }
+
+    return Some((fun_name.trim(), fun_variables));
+}
+
+/// Split an expression at `=` separating name and definition, none for a raw expression
+///
+/// It gives byte position of `=` and true if left side is a function header, i.e. it contains `:`.
+/// A `=` which is part of a comparison operator `==`, `!=`, `<=` or `>=` is not an assignment,
+/// thus `1 <= 2` is a raw expression and `b = 1 == 2` defines variable `b`.
+/// Variables of a function header are not checked, see `Expression::new`.
+pub fn assignment_split(expression: &str) -> Option<(usize, bool)> {
+    let bytes: &[u8] = expression.as_bytes();
+
+    let position: usize = (0..bytes.len()).find(|&id| is_assignment_sign(bytes, id))?;
+
+    return Some((position, expression[..position].contains(':')));
+}
+
+/// Check if byte at given position of an expression is `=` of an assignment, see `assignment_split`
+fn is_assignment_sign(bytes: &[u8], id: usize) -> bool {
+    return bytes[id] == b'='
+        && (id == 0 || !matches!(bytes[id - 1], b'=' | b'!' | b'<' | b'>'))
+        && bytes.get(id + 1) != Some(&b'=');
+}
+
+/// Get declaration following a keyword, as `x = 1 in [0, 10]` for `slider x = 1 in [0, 10]`
+/// with keyword `slider`, none if expression is not a declaration starting with this keyword
+///
+/// Keyword must be followed by whitespaces and a name, thus `slider = 3` defines variable `slider`
+/// and `slider + 1` is a raw expression.
+pub(crate) fn keyword_declaration<'a>(expression: &'a str, keyword: &str) -> Option<&'a str> {
+    let declaration: &str = expression.trim_start().strip_prefix(keyword)?;
+
+    if !declaration.starts_with(char::is_whitespace)
+        || !declaration
+            .trim_start()
+            .starts_with(|c: char| c.is_alphabetic() || c == '_')
+    {
+        return None;
+    }
+
+    return Some(declaration);
+}
+
+/// Check if an expression is syntactically empty, i.e. it contains only whitespaces and parenthesis
+///
+/// An expression as `( )` is empty, it can be given by a call of function whose definition is empty.
+pub fn is_syntactically_empty(expression: &str) -> bool {
+    return expression
+        .chars()
+        .all(|c| c.is_whitespace() || c == '(' || c == ')');
+}
+
+/// Detect kind of an input string without constructing an Expression
+///
+/// The input is scanned for a leading `#` or `:`, then for first `=` of an assignment and `:`
+/// outside of double quotes. As in `assignment_split`, a comparison as `x <= 1` is not an assignment.
+pub fn input_kind(expression: &str) -> InputKind {
+    let trimmed_expression: &str = expression.trim_start();
+
+    if trimmed_expression.is_empty() {
+        return InputKind::Empty;
+    }
+
+    if trimmed_expression.starts_with('#') {
+        return InputKind::Comment;
+    }
+
+    if trimmed_expression.starts_with(':') {
+        return InputKind::Command;
+    }
+
+    let bytes: &[u8] = trimmed_expression.as_bytes();
+    let mut is_quoted: bool = false;
+    let mut has_colon: bool = false;
+
+    for (position, c) in trimmed_expression.char_indices() {
+        match c {
+            '"' => is_quoted = !is_quoted,
+            ':' if !is_quoted => has_colon = true,
+            '=' if is_quoted || !is_assignment_sign(bytes, position) => {}
+            '=' if has_colon
+                && split_function_header(
+                    &trimmed_expression[..position],
+                    &[',', ';'],
+                    token::is_identifier_character,
+                )
+                .is_some() =>
+            {
+                return InputKind::Function
+            }
+            '=' => return InputKind::Variable,
+            _ => {}
+        }
+    }
+
+    return InputKind::Raw;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignment_split() {
+        assert_eq!(assignment_split("x = 1 == 2"), Some((2, false)));
+        assert_eq!(assignment_split("f: x, y = x <= y"), Some((8, true)));
+        assert_eq!(assignment_split("x <= 1 + (y != 2)"), None);
+        assert_eq!(assignment_split("1 + 2 * 3"), None);
+    }
+
+    #[test]
+    fn test_expression_new_with_raw_expression() {
+        let expression: String = String::from("1 + 1");
+
+        match Expression::new(expression.as_str()) {
+            Expression::Raw(raw_expression) => assert_eq!(raw_expression, expression),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_new_with_variable_definition() {
+        let variable_name: String = String::from("x");
+        let variable_definition: String = String::from("1 + 1");
+
+        let expression: String = format!("{} = {}", variable_name, variable_definition);
+
+        match Expression::new(expression.as_str()) {
+            Expression::Variable(name, definition) => {
+                assert_eq!(name, variable_name);
+                assert_eq!(definition, variable_definition);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_new_with_positional_variables() {
+        match Expression::new("f: 2 = $1 * $1 + $2") {
+            Expression::Function(name, variables, definition) => {
+                assert_eq!(name, "f");
+                assert_eq!(variables, vec![String::from("$1"), String::from("$2")]);
+                assert_eq!(definition, "$1 * $1 + $2");
+            }
+            _ => assert!(false),
+        }
+
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (
+                vec![String::from("$1"), String::from("$2")],
+                String::from("$1 * $1 + $2"),
+            ),
+        );
+
+        let mut expression: Expression = Expression::new("f(3, x + 1)");
+        expression.replace_functions(&functions).unwrap();
+
+        assert_eq!(expression.definition(), "(3 * 3 + (x + 1))");
+    }
+
+    #[test]
+    fn test_expression_new_with_comparison() {
+        match Expression::new("x == 2") {
+            Expression::Raw(raw_expression) => assert_eq!(raw_expression, "x == 2"),
+            _ => assert!(false),
+        }
+
+        match Expression::new("b = x == 2") {
+            Expression::Variable(name, definition) => {
+                assert_eq!(name, "b");
+                assert_eq!(definition, "x == 2");
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_new_with_function_definition() {
+        let function_name: String = String::from("distance");
+        let function_variables: Vec<String> =
+            vec![String::from("x"), String::from("y"), String::from("z")];
+        let function_definition: String = String::from("x * x + y * y + z * z");
+
+        let expression: String = format!(
+            "{}: {}, {}, {} = {}",
+            function_name,
+            function_variables[0],
+            function_variables[1],
+            function_variables[2],
+            function_definition
+        );
+
+        match Expression::new(expression.as_str()) {
+            Expression::Function(name, variables, definition) => {
+                assert_eq!(name, function_name);
+                assert_eq!(variables, function_variables);
+                assert_eq!(definition, function_definition);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_variables_in_raw_expression() {
+        let mut variables: HashMap<String, f64> = HashMap::new();
+
+        variables.insert(String::from("x"), 1.0);
+        variables.insert(String::from("velocity"), 3.43);
+        variables.insert(String::from("time"), 5.9954);
+
+        let raw_expression: String = String::from("(x - 2.75) + velocity * time");
+
+        let replaced_raw_expression: String = format!(
+            "({} - 2.75) + {} * {}",
+            variables["x"], variables["velocity"], variables["time"]
+        );
+
+        let mut expression: Expression = Expression::new(raw_expression.as_str());
+        expression.replace_variables(&variables);
+
+        match expression {
             Expression::Raw(replaced_expression) => {
                 assert_eq!(replaced_raw_expression, replaced_expression)
             }
@@ -329,6 +1333,89 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_expression_replace_variables_in_function_expression() {
+        let mut variables: HashMap<String, f64> = HashMap::new();
+
+        variables.insert(String::from("x"), 1.0);
+        variables.insert(String::from("y"), 2.0);
+
+        let mut expression: Expression = Expression::new("f: x = x + y * xy - x");
+        expression.replace_variables(&variables);
+
+        // Parameter x shadows variable x in body, variable y is replaced
+        match expression {
+            Expression::Function(name, function_variables, definition) => {
+                assert_eq!(name, "f");
+                assert_eq!(function_variables, vec![String::from("x")]);
+                assert_eq!(definition, "x + 2 * xy - x");
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_variables_with_untrimmed_function_variables() {
+        let mut variables: HashMap<String, f64> = HashMap::new();
+        variables.insert(String::from("x"), 1.0);
+        variables.insert(String::from("y"), 2.0);
+
+        let mut expression: Expression = Expression::Function(
+            String::from("f"),
+            vec![String::from(" x"), String::from("y\t")],
+            String::from("x + y + z"),
+        );
+        expression.replace_variables(&variables);
+
+        assert_eq!(expression.definition(), "x + y + z");
+
+        // Nothing is replaced when variables are not valid, as `x y` missing a separator
+        let mut invalid_expression: Expression = Expression::Function(
+            String::from("f"),
+            vec![String::from("x y")],
+            String::from("x + y + z"),
+        );
+        invalid_expression.replace_variables(&variables);
+
+        assert_eq!(invalid_expression.definition(), "x + y + z");
+
+        // Variables are trimmed when a definition is parsed, whatever spaces around them
+        match Expression::new("f:  x ,\ty  = x + y") {
+            Expression::Function(_, function_variables, _) => {
+                assert_eq!(
+                    function_variables,
+                    vec![String::from("x"), String::from("y")]
+                )
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_variables_with_escaped_variable() {
+        let mut variables: HashMap<String, f64> = HashMap::new();
+        variables.insert(String::from("x"), 5.0);
+
+        let raw_expression: String = String::from("`x + x");
+        let replaced_raw_expression: String = String::from("x + 5");
+
+        let mut expression: Expression = Expression::new(raw_expression.as_str());
+        expression.replace_variables(&variables);
+
+        match expression {
+            Expression::Raw(replaced_expression) => {
+                assert_eq!(replaced_raw_expression, replaced_expression)
+            }
+            _ => assert!(false),
+        }
+
+        // Only a backtick directly before an identifier is an escape character
+        let mut expression: Expression = Expression::new("`x + ` x + \"`\" + `2");
+        expression.replace_variables(&variables);
+
+        assert_eq!(expression.definition(), "x + ` 5 + \"`\" + `2");
+    }
+
     #[test]
     fn test_expression_replace_functions_in_raw_expression() {
         let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
@@ -425,4 +1512,636 @@ mod tests {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn test_expression_replace_functions_parenthesizes_compound_arguments() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("square"),
+            (vec![String::from("x")], String::from("x * x")),
+        );
+
+        functions.insert(
+            String::from("pow2"),
+            (vec![String::from("x")], String::from("x ^ 2")),
+        );
+
+        let cases: Vec<(&str, &str)> = vec![
+            ("square(1 + 2)", "((1 + 2) * (1 + 2))"),
+            ("square((1 + 2))", "((1 + 2) * (1 + 2))"),
+            ("square(-2) + pow2(2 ^ 3)", "((-2) * (-2)) + ((2 ^ 3) ^ 2)"),
+            ("pow2(a == b)", "((a == b) ^ 2)"),
+            ("square(sin(x + 1))", "(sin(x + 1) * sin(x + 1))"),
+            (
+                "square(square(1 + 1))",
+                "(((1 + 1) * (1 + 1)) * ((1 + 1) * (1 + 1)))",
+            ),
+        ];
+
+        for (raw_expression, replaced_raw_expression) in cases {
+            let mut expression: Expression = Expression::new(raw_expression);
+            expression.replace_functions(&functions).unwrap();
+
+            match expression {
+                Expression::Raw(replaced_expression) => {
+                    assert_eq!(replaced_raw_expression, replaced_expression)
+                }
+                _ => assert!(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_variables_keeps_numbers_with_exponent() {
+        let mut variables: HashMap<String, f64> = HashMap::new();
+        variables.insert(String::from("e2"), 4.0);
+        variables.insert(String::from("E"), 7.0);
+
+        let raw_expression: String = String::from("1.5e2 + e2 * 2E-3 - E");
+        let replaced_raw_expression: String = String::from("1.5e2 + 4 * 2E-3 - 7");
+
+        let mut expression: Expression = Expression::new(raw_expression.as_str());
+        expression.replace_variables(&variables);
+
+        match expression {
+            Expression::Raw(replaced_expression) => {
+                assert_eq!(replaced_raw_expression, replaced_expression)
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_keeps_numbers_with_exponent() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (
+                vec![String::from("E"), String::from("e2")],
+                String::from("E * 1e2 + e2"),
+            ),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (vec![String::from("e")], String::from("e - 2e-1")),
+        );
+
+        let raw_expression: String = String::from("f(1.5e2, 7e+10) + g(2E-3)");
+        let replaced_raw_expression: String = String::from("(1.5e2 * 1e2 + 7e+10) + (2E-3 - 2e-1)");
+
+        let mut expression: Expression = Expression::new(raw_expression.as_str());
+        expression.replace_functions(&functions).unwrap();
+
+        match expression {
+            Expression::Raw(replaced_expression) => {
+                assert_eq!(replaced_raw_expression, replaced_expression)
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_with_several_calls_and_nested_calls() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (vec![String::from("x")], String::from("f(x) * 2")),
+        );
+
+        let raw_expression: String = String::from("f(1) + g(f(2)) - f (3)");
+        let replaced_raw_expression: String =
+            String::from("(1 + 1) + (((2 + 1) + 1) * 2) - (3 + 1)");
+
+        let mut expression: Expression = Expression::new(raw_expression.as_str());
+        expression.replace_functions(&functions).unwrap();
+
+        match expression {
+            Expression::Raw(replaced_expression) => {
+                assert_eq!(replaced_raw_expression, replaced_expression)
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_with_errors() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("f(x) + 1")),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        let mut recursive_expression: Expression = Expression::new("f(1)");
+        assert!(recursive_expression.replace_functions(&functions).is_err());
+
+        let mut unclosed_expression: Expression = Expression::new("g(1 + 2");
+        assert!(unclosed_expression.replace_functions(&functions).is_err());
+
+        let mut inconsistent_expression: Expression = Expression::new("g(1, 2)");
+        assert!(inconsistent_expression
+            .replace_functions(&functions)
+            .is_err());
+    }
+
+    #[test]
+    fn test_expression_call_counts() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (vec![String::from("x")], String::from("f(x) * 2")),
+        );
+
+        let counts: HashMap<String, usize> =
+            Expression::new("y = f(f(1)) + g (2) + f + h(3)").call_counts(&functions);
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["f"], 2);
+        assert_eq!(counts["g"], 1);
+
+        let counts: HashMap<String, usize> = Expression::new("g(1) + f(2").call_counts(&functions);
+
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["g"], 1);
+    }
+
+    #[test]
+    fn test_expression_rename_function_calls() {
+        let mut expression: Expression = Expression::new("h: f = f(f(1)) + f + ff(2)");
+
+        assert_eq!(expression.rename_function_calls("f", "distance"), 2);
+        assert_eq!(expression.definition(), "distance(distance(1)) + f + ff(2)");
+
+        let mut expression: Expression = Expression::new("long_name(2) * long_name (3)");
+
+        assert_eq!(expression.rename_function_calls("long_name", "g"), 2);
+        assert_eq!(expression.definition(), "g(2) * g (3)");
+    }
+
+    #[test]
+    fn test_expression_remove_freeze_marker() {
+        let mut frozen_expression: Expression = Expression::new("g!: x = f(x) * 2");
+        assert!(frozen_expression.remove_freeze_marker());
+
+        match frozen_expression {
+            Expression::Function(name, _, _) => assert_eq!(name, "g"),
+            _ => assert!(false),
+        }
+
+        let mut expression: Expression = Expression::new("g: x = f(x) * 2");
+        assert!(!expression.remove_freeze_marker());
+
+        match expression {
+            Expression::Function(name, _, _) => assert_eq!(name, "g"),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_with_whitespaces_before_parenthesis() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("distance"),
+            (
+                vec![String::from("x"), String::from("y")],
+                String::from("x * x + y * y"),
+            ),
+        );
+
+        let replaced_raw_expression: String = String::from("(2 * 2 + 3 * 3) + 1");
+
+        for raw_expression in [
+            "distance (2, 3) + 1",
+            "distance  (2,3) + 1",
+            "distance\t(2, 3) + 1",
+            "distance\n(2, 3) + 1",
+            "distance \r\n (2, 3) + 1",
+        ] {
+            let mut expression: Expression = Expression::new(raw_expression);
+            expression.replace_functions(&functions).unwrap();
+
+            match expression {
+                Expression::Raw(replaced_expression) => {
+                    assert_eq!(replaced_raw_expression, replaced_expression)
+                }
+                _ => assert!(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_with_function_name_not_called() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("distance"),
+            (
+                vec![String::from("x"), String::from("y")],
+                String::from("x * x + y * y"),
+            ),
+        );
+
+        for raw_expression in ["distance 2", "distance + (2, 3)", "distance"] {
+            let mut expression: Expression = Expression::new(raw_expression);
+            expression.replace_functions(&functions).unwrap();
+
+            match expression {
+                Expression::Raw(replaced_expression) => {
+                    assert_eq!(raw_expression, replaced_expression)
+                }
+                _ => assert!(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_abutting_previous_token() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        let expressions: Vec<(&str, &str)> = vec![
+            ("2+f(3)", "2+(3 + 1)"),
+            ("(f(3))", "((3 + 1))"),
+            ("-f(3)", "-(3 + 1)"),
+            ("2*f(3)/f(4)", "2*(3 + 1)/(4 + 1)"),
+        ];
+
+        for (raw_expression, expected_expression) in expressions {
+            let mut expression: Expression = Expression::new(raw_expression);
+            expression.replace_functions(&functions).unwrap();
+
+            match expression {
+                Expression::Raw(replaced_expression) => {
+                    assert_eq!(replaced_expression, expected_expression)
+                }
+                _ => assert!(false),
+            }
+        }
+
+        // Function name preceded by alphanumeric character is another identifier
+        for raw_expression in ["gf(3)", "_f(3)", "f2(3)"] {
+            let mut expression: Expression = Expression::new(raw_expression);
+            expression.replace_functions(&functions).unwrap();
+
+            assert_eq!(expression.definition(), raw_expression);
+        }
+    }
+
+    #[test]
+    fn test_expression_replace_functions_spanning_whole_expression() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (
+                vec![String::from("x"), String::from("y")],
+                String::from("x * y"),
+            ),
+        );
+
+        // Call starts at first character and its closing parenthesis is last character
+        assert_eq!(
+            Expression::get_function_positions("f(2)", &functions, token::is_identifier_character),
+            Ok(Some(("f", 0, 1, 3)))
+        );
+        assert_eq!(
+            Expression::get_function_positions(
+                "g(2, 3)",
+                &functions,
+                token::is_identifier_character
+            ),
+            Ok(Some(("g", 0, 1, 6)))
+        );
+
+        let expressions: Vec<(&str, &str)> = vec![
+            ("f(2)", "(2 + 1)"),
+            ("g(2, 3)", "(2 * 3)"),
+            ("g(f(2), f(3))", "((2 + 1) * (3 + 1))"),
+            ("f(g(2, 3))", "((2 * 3) + 1)"),
+            ("y = g(2, 3)", "(2 * 3)"),
+        ];
+
+        for (expression_str, expected_expression) in expressions {
+            let mut expression: Expression = Expression::new(expression_str);
+            expression.replace_functions(&functions).unwrap();
+
+            assert_eq!(expression.definition(), expected_expression);
+        }
+
+        let mut expression: Expression = Expression::new("g(2, 3");
+
+        assert_eq!(
+            expression.replace_functions(&functions),
+            Err(String::from(
+                "The closing parenthesis of function g is missing"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_expression_replace_functions_with_semicolon_separator() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        match Expression::with_argument_separator("f: x; y = max(x; y) + 1", ';') {
+            Expression::Function(name, variables, definition) => {
+                assert_eq!(variables, vec![String::from("x"), String::from("y")]);
+                functions.insert(name, (variables, definition));
+            }
+            _ => assert!(false),
+        }
+
+        let mut expression: Expression = Expression::with_argument_separator("f(1; 2 + 3)", ';');
+
+        match expression.replace_functions_with_separator(&functions, ';') {
+            Ok(statistics) => assert_eq!(statistics.expansion_count, 1),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(expression.definition(), "(max(1; (2 + 3)) + 1)");
+    }
+
+    #[test]
+    fn test_expression_replace_functions_with_statistics() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (vec![String::from("x")], String::from("f(x) * f(2)")),
+        );
+
+        functions.insert(
+            String::from("h"),
+            (vec![String::from("x")], String::from("g(x) - 1")),
+        );
+
+        let mut expression: Expression = Expression::new("h(1) + f(3)");
+
+        match expression.replace_functions_with_statistics(&functions) {
+            Ok(statistics) => {
+                assert_eq!(statistics.expansion_count, 5);
+                assert_eq!(statistics.max_depth, 3);
+            }
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(
+            expression.definition(),
+            "(((1 + 1) * (2 + 1)) - 1) + (3 + 1)"
+        );
+
+        let mut literal_expression: Expression = Expression::new("2 + 3");
+
+        match literal_expression.replace_functions_with_statistics(&functions) {
+            Ok(statistics) => assert_eq!(statistics, ExpansionStatistics::default()),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_referenced_variables() {
+        let mut variables: HashMap<String, f64> = HashMap::new();
+
+        variables.insert(String::from("x"), 1.0);
+        variables.insert(String::from("y"), 2.0);
+        variables.insert(String::from("z"), 3.0);
+
+        let raw_expression: Expression = Expression::new("y * x + x - `z + w");
+        assert_eq!(
+            raw_expression.referenced_variables(&variables),
+            vec!["x", "y"]
+        );
+
+        let function_expression: Expression = Expression::new("f: x = x + y");
+        assert_eq!(
+            function_expression.referenced_variables(&variables),
+            vec!["y"]
+        );
+    }
+
+    #[test]
+    fn test_expression_uncalled_functions() {
+        let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+
+        functions.insert(
+            String::from("f"),
+            (vec![String::from("x")], String::from("x + 1")),
+        );
+
+        functions.insert(
+            String::from("g"),
+            (vec![String::from("x")], String::from("x * 2")),
+        );
+
+        assert_eq!(
+            Expression::new("g + f (2) + f * `g").uncalled_functions(&functions),
+            vec!["f", "g"]
+        );
+
+        assert_eq!(
+            Expression::new("y = f(1) + g").uncalled_functions(&functions),
+            vec!["g"]
+        );
+
+        assert!(Expression::new("h: f, x = f + g(x)")
+            .uncalled_functions(&functions)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_is_syntactically_empty() {
+        assert!(is_syntactically_empty(""));
+        assert!(is_syntactically_empty(" (( ) ) "));
+        assert!(!is_syntactically_empty("(0)"));
+    }
+
+    #[test]
+    fn test_input_kind() {
+        assert_eq!(input_kind("1 + 1"), InputKind::Raw);
+        assert_eq!(input_kind("x = 1 + 1"), InputKind::Variable);
+        assert_eq!(input_kind("f: x, y = x * x + y * y"), InputKind::Function);
+        assert_eq!(input_kind(""), InputKind::Empty);
+        assert_eq!(input_kind(" \t "), InputKind::Empty);
+        assert_eq!(input_kind("# x = 1 + 1"), InputKind::Comment);
+        assert_eq!(input_kind("  #"), InputKind::Comment);
+        assert_eq!(input_kind("\"a:b\" = 2"), InputKind::Variable);
+        assert_eq!(input_kind("\"x = 2\""), InputKind::Raw);
+        assert_eq!(input_kind(" :fix 3"), InputKind::Command);
+        assert_eq!(input_kind("x == 2"), InputKind::Raw);
+        assert_eq!(input_kind("b = x == 2"), InputKind::Variable);
+        assert_eq!(input_kind("x <= 1"), InputKind::Raw);
+        assert_eq!(input_kind("a != b"), InputKind::Raw);
+        assert_eq!(input_kind("x >= 1 && y <= 2"), InputKind::Raw);
+        assert_eq!(input_kind("b = a != 1"), InputKind::Variable);
+        assert_eq!(input_kind("f: x = x <= 1"), InputKind::Function);
+    }
+
+    #[test]
+    fn test_expression_display() {
+        assert_eq!(Expression::new("1+2 *(3-4)").to_string(), "1 + 2 * (3 - 4)");
+        assert_eq!(Expression::new("x=-1").to_string(), "x = -1");
+        assert_eq!(
+            Expression::new("f: 2 = $1*$2").to_string(),
+            "f: 2 = $1 * $2"
+        );
+    }
+
+    #[test]
+    fn test_format_with_messy_definitions() {
+        let inputs_and_formats: [(&str, &str); 8] = [
+            ("  x=1+2*y ", "x = 1 + 2 * y"),
+            ("y =  -( x^2 )/ 3", "y = -(x ^ 2) / 3"),
+            ("b=x==2", "b = x == 2"),
+            ("f:a ,b=  a*( b+1 )", "f: a, b = a * (b + 1)"),
+            ("g : 2 = $1^-$2", "g: 2 = $1 ^ -$2"),
+            ("h!:x=f( x ,-1)+`e", "h!: x = f(x, -1) + `e"),
+            ("max( 1,2 )==3 && y>0", "max(1, 2) == 3 && y > 0"),
+            ("  # a comment  ", "# a comment"),
+        ];
+
+        for (input, formatted_input) in inputs_and_formats {
+            match format(input) {
+                Ok(result) => assert_eq!(result, formatted_input),
+                Err(_) => assert!(false),
+            }
+
+            // Canonical form is kept as is
+            match format(formatted_input) {
+                Ok(result) => assert_eq!(result, formatted_input),
+                Err(_) => assert!(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_with_invalid_input() {
+        assert_eq!(format("   "), Err(String::from("The expression is empty")));
+
+        assert_eq!(
+            format("f: x = (x + 1"),
+            Err(String::from("The bracket '(' at column 8 is not closed"))
+        );
+
+        assert_eq!(
+            format("2x = 3"),
+            Err(String::from("The variable name 2x is not valid"))
+        );
+
+        assert_eq!(
+            format("f: x + 1 = x"),
+            Err(String::from("The variable name f: x + 1 is not valid"))
+        );
+    }
+
+    #[test]
+    fn test_expression_pretty() {
+        let inputs_and_pretty_forms: [(&str, &str); 6] = [
+            ("x*x+ y *y", "x * x + y * y"),
+            ("2*-x", "2 * -x"),
+            ("f( x,g( y ,-2 ) )", "f(x, g(y, -2))"),
+            ("-(-x)^ 2", "-(-x) ^ 2"),
+            ("a ==b", "a == b"),
+            ("n: x = 2 x+`e", "n: x = 2 x + `e"),
+        ];
+
+        // Get tokens of expression to check that only whitespaces are changed
+        let tokens = |expression: &str| -> Vec<String> {
+            return token::lex(expression)
+                .into_iter()
+                .map(|lexeme| String::from(&expression[lexeme.range]))
+                .collect();
+        };
+
+        for (input, pretty_form) in inputs_and_pretty_forms {
+            assert_eq!(Expression::new(input).pretty(), pretty_form);
+            assert_eq!(tokens(pretty_form), tokens(input));
+        }
+    }
+
+    #[test]
+    fn test_expression_new_with_prose_like_header() {
+        match Expression::new("note: this is text = 5") {
+            Expression::Variable(name, definition) => {
+                assert_eq!(name, "note: this is text");
+                assert_eq!(definition, "5");
+            }
+            _ => assert!(false),
+        }
+
+        assert_eq!(input_kind("note: this is text = 5"), InputKind::Variable);
+    }
+
+    #[test]
+    fn test_expression_new_with_url_like_definition() {
+        for expression_str in ["site = http://example.com", "site = a:b = c"] {
+            match Expression::new(expression_str) {
+                Expression::Variable(name, _) => assert_eq!(name, "site"),
+                _ => assert!(false),
+            }
+
+            assert_eq!(input_kind(expression_str), InputKind::Variable);
+        }
+
+        match Expression::new("link: url = http://example.com") {
+            Expression::Function(name, variables, definition) => {
+                assert_eq!(name, "link");
+                assert_eq!(variables, vec![String::from("url")]);
+                assert_eq!(definition, "http://example.com");
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_expression_new_with_valid_headers() {
+        let headers_and_variables: [(&str, Vec<&str>); 4] = [
+            ("f: x = x", vec!["x"]),
+            ("distance : x , y = x * y", vec!["x", "y"]),
+            ("g!: x = f(x)", vec!["x"]),
+            ("p: 2 = $1 * $2", vec!["$1", "$2"]),
+        ];
+
+        for (expression_str, expected_variables) in headers_and_variables {
+            match Expression::new(expression_str) {
+                Expression::Function(_, variables, _) => assert_eq!(variables, expected_variables),
+                _ => assert!(false),
+            }
+
+            assert_eq!(input_kind(expression_str), InputKind::Function);
+        }
+    }
 }
@@ -0,0 +1,85 @@
+/// Options applied by calculator on strings given by `process`
+///
+/// New options may be added in a future release, thus a format is built from default one
+/// with its methods, as `Format::default().max_output_len(80)`.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Format {
+    pub max_output_len: Option<usize>, // maximal number of characters of result and error strings, none for no limit
+}
+
+impl Format {
+    /// Set maximal number of characters of result and error strings
+    ///
+    /// A longer string is truncated at a character boundary and ends with number of removed
+    /// characters, as `… (+120 chars)`, the whole string being at most `max_output_len` characters.
+    pub fn max_output_len(mut self, max_output_len: usize) -> Self {
+        self.max_output_len = Some(max_output_len);
+        return self;
+    }
+
+    /// Truncate a string to maximal output length, indicating number of removed characters
+    ///
+    /// If maximal output length is too small to hold the indication, string is only truncated.
+    pub fn truncate(&self, output: String) -> String {
+        let max_output_len: usize = match self.max_output_len {
+            Some(max_output_len) => max_output_len,
+            None => return output,
+        };
+
+        let output_len: usize = output.chars().count();
+
+        if output_len <= max_output_len {
+            return output;
+        }
+
+        // Length of suffix depends on number of removed characters, thus on number of kept ones
+        let mut kept_len: usize = max_output_len;
+
+        loop {
+            let suffix: String = format!("… (+{} chars)", output_len - kept_len);
+            let suffix_len: usize = suffix.chars().count();
+
+            if suffix_len > max_output_len {
+                return output.chars().take(max_output_len).collect();
+            }
+
+            if kept_len + suffix_len <= max_output_len {
+                let mut truncated_output: String = output.chars().take(kept_len).collect();
+                truncated_output.push_str(suffix.as_str());
+                return truncated_output;
+            }
+
+            kept_len = max_output_len - suffix_len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_truncate() {
+        let format: Format = Format::default().max_output_len(20);
+
+        assert_eq!(format.truncate(String::from("short")), "short");
+        assert_eq!(
+            format.truncate(String::from("f(é) = é * é + é * é + é")),
+            "f(é) = … (+17 chars)"
+        );
+        assert_eq!(
+            format.truncate(String::from("éééééééééééééééééééééééé")),
+            "ééééééé… (+17 chars)"
+        );
+
+        let too_small_format: Format = Format::default().max_output_len(4);
+        assert_eq!(too_small_format.truncate(String::from("é + é + é")), "é + ");
+
+        assert_eq!(
+            Format::default().truncate(String::from("é + é + é")),
+            "é + é + é"
+        );
+    }
+}
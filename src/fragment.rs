@@ -0,0 +1,661 @@
+//! Encoding of calculator state in URL fragment, to share a session by a link
+//!
+//! State is written in a script typing each variable, compressed with zlib and encoded
+//! in URL-safe base64 without padding, after version prefix `v1.`. Checksum of zlib
+//! detects a tampered fragment. It is available with feature `url`.
+
+use crate::token;
+use crate::validation;
+use crate::value::Value;
+use crate::{CalculatorState, VariableRange};
+
+use std::collections::{HashMap, HashSet};
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+/// Version prefix of fragments written by this release
+const VERSION_PREFIX: &str = "v1.";
+
+/// Maximal length of a fragment read by `decode`
+pub const MAX_FRAGMENT_LENGTH: usize = 8192;
+
+/// Maximal length of script decompressed from a fragment, so that a small fragment cannot give a huge text
+pub const MAX_SCRIPT_LENGTH: usize = 65536;
+
+/// Alphabet of URL-safe base64
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Level of zlib compression, the best one since fragments are small
+const COMPRESSION_LEVEL: u8 = 10;
+
+/// Encode a state in a fragment, see `CalculatorState::to_url_fragment`
+///
+/// A fragment which would be longer than `MAX_FRAGMENT_LENGTH`, or whose script would be longer
+/// than `MAX_SCRIPT_LENGTH`, gives an error since `decode` would reject it.
+pub(crate) fn encode(state: &CalculatorState) -> Result<String, String> {
+    let script: String = state_script(state);
+
+    if script.len() > MAX_SCRIPT_LENGTH {
+        return Err(format!(
+            "The state has {} bytes, more than the maximum of {}",
+            script.len(),
+            MAX_SCRIPT_LENGTH
+        ));
+    }
+
+    let compressed: Vec<u8> = compress_to_vec_zlib(script.as_bytes(), COMPRESSION_LEVEL);
+    let fragment: String = format!("{}{}", VERSION_PREFIX, encode_base64(&compressed));
+
+    if fragment.len() > MAX_FRAGMENT_LENGTH {
+        return Err(format!(
+            "The fragment has {} characters, more than the maximum of {}",
+            fragment.len(),
+            MAX_FRAGMENT_LENGTH
+        ));
+    }
+
+    return Ok(fragment);
+}
+
+/// Decode a state from a fragment, see `CalculatorState::from_url_fragment`
+pub(crate) fn decode(fragment: &str) -> Result<CalculatorState, String> {
+    let fragment: &str = fragment.strip_prefix('#').unwrap_or(fragment);
+
+    if fragment.len() > MAX_FRAGMENT_LENGTH {
+        return Err(format!(
+            "The fragment has {} characters, more than the maximum of {}",
+            fragment.len(),
+            MAX_FRAGMENT_LENGTH
+        ));
+    }
+
+    let encoded: &str = fragment
+        .strip_prefix(VERSION_PREFIX)
+        .ok_or(String::from("The fragment has an unknown version"))?;
+
+    let compressed: Vec<u8> =
+        decode_base64(encoded).ok_or(String::from("The fragment is not valid URL-safe base64"))?;
+
+    let script: Vec<u8> = decompress_to_vec_zlib_with_limit(&compressed, MAX_SCRIPT_LENGTH)
+        .map_err(|_| {
+            format!(
+                "The fragment is corrupted or its state exceeds {} bytes",
+                MAX_SCRIPT_LENGTH
+            )
+        })?;
+
+    let script: String =
+        String::from_utf8(script).map_err(|_| String::from("The fragment is not valid UTF-8"))?;
+
+    let state: CalculatorState = parse_state_script(script.as_str())?;
+    validation::check_state(&state, token::is_identifier_character)?;
+
+    return Ok(state);
+}
+
+/// Write a state in a script, variables, functions, references, lazy variables, constants then ranges
+/// sorted by name
+///
+/// Each variable line starts with its type so that value is read again exactly,
+/// as `integer n = 3` or `vector v = [1, 2]`, function line is `function f: x, y = x + y`,
+/// reference line is `reference b = a`, definition of lazy variable is `lazy area = w * h`,
+/// constant line is `constant rate` and range line is `range x = [0, 10] step 0.5`, without step if it has none.
+fn state_script(state: &CalculatorState) -> String {
+    let mut variable_names: Vec<&String> = state.variables.keys().collect();
+    variable_names.sort_unstable();
+
+    let mut function_names: Vec<&String> = state.functions.keys().collect();
+    function_names.sort_unstable();
+
+    let mut reference_names: Vec<&String> = state.references.keys().collect();
+    reference_names.sort_unstable();
+
+    let mut lazy_names: Vec<&String> = state.lazy_definitions.keys().collect();
+    lazy_names.sort_unstable();
+
+    let mut constant_names: Vec<&String> = state.constants.iter().collect();
+    constant_names.sort_unstable();
+
+    let mut range_names: Vec<&String> = state.variable_ranges.keys().collect();
+    range_names.sort_unstable();
+
+    let mut script: String = String::new();
+
+    for name in variable_names {
+        let (kind, value): (&str, String) = match &state.variables[name] {
+            Value::Number(number) => ("number", number.to_string()),
+            Value::Bool(boolean) => ("bool", boolean.to_string()),
+            Value::Integer(integer) => ("integer", integer.to_string()),
+            vector @ Value::Vector(_) => ("vector", vector.to_string()),
+            #[cfg(feature = "decimal")]
+            decimal @ Value::Decimal(_) => ("decimal", decimal.to_string()),
+            #[cfg(feature = "complex")]
+            Value::Complex(complex) => ("complex", format!("{}, {}", complex.re, complex.im)),
+        };
+
+        script.push_str(format!("{} {} = {}\n", kind, name, value).as_str());
+    }
+
+    for name in function_names {
+        let (variables, definition) = &state.functions[name];
+
+        script.push_str(
+            format!(
+                "function {}: {} = {}\n",
+                name,
+                variables.join(", "),
+                definition
+            )
+            .as_str(),
+        );
+    }
+
+    for name in reference_names {
+        script.push_str(format!("reference {} = {}\n", name, state.references[name]).as_str());
+    }
+
+    for name in lazy_names {
+        script.push_str(format!("lazy {} = {}\n", name, state.lazy_definitions[name]).as_str());
+    }
+
+    for name in constant_names {
+        script.push_str(format!("constant {}\n", name).as_str());
+    }
+
+    for name in range_names {
+        let range: &VariableRange = &state.variable_ranges[name];
+
+        script.push_str(format!("range {} = [{}, {}]", name, range.min, range.max).as_str());
+
+        if let Some(step) = range.step {
+            script.push_str(format!(" step {}", step).as_str());
+        }
+
+        script.push('\n');
+    }
+
+    return script;
+}
+
+/// Read a state from a script written by `state_script`
+fn parse_state_script(script: &str) -> Result<CalculatorState, String> {
+    let mut variables: HashMap<String, Value> = HashMap::new();
+    let mut functions: HashMap<String, (Vec<String>, String)> = HashMap::new();
+    let mut references: HashMap<String, String> = HashMap::new();
+    let mut lazy_definitions: HashMap<String, String> = HashMap::new();
+    let mut constants: HashSet<String> = HashSet::new();
+    let mut variable_ranges: HashMap<String, VariableRange> = HashMap::new();
+
+    for (id, line) in script.lines().enumerate() {
+        let invalid_line = || format!("Line {}: The line of state is invalid", id + 1);
+
+        if let Some(name) = line.strip_prefix("constant ") {
+            if !constants.insert(String::from(name)) {
+                return Err(format!(
+                    "Line {}: The constant {} is already defined",
+                    id + 1,
+                    name
+                ));
+            }
+
+            continue;
+        }
+
+        let (kind, rest) = line.split_once(' ').ok_or_else(invalid_line)?;
+        let (head, text) = rest.split_once(" = ").ok_or_else(invalid_line)?;
+
+        if kind == "function" {
+            let (name, variable_list) = head.split_once(": ").unwrap_or((head, ""));
+
+            let function_variables: Vec<String> = variable_list
+                .split(", ")
+                .filter(|variable| !variable.is_empty())
+                .map(String::from)
+                .collect();
+
+            if functions
+                .insert(String::from(name), (function_variables, String::from(text)))
+                .is_some()
+            {
+                return Err(format!(
+                    "Line {}: The function {} is already defined",
+                    id + 1,
+                    name
+                ));
+            }
+
+            continue;
+        }
+
+        if kind == "reference" {
+            if references
+                .insert(String::from(head), String::from(text))
+                .is_some()
+            {
+                return Err(format!(
+                    "Line {}: The reference {} is already defined",
+                    id + 1,
+                    head
+                ));
+            }
+
+            continue;
+        }
+
+        if kind == "lazy" {
+            if lazy_definitions
+                .insert(String::from(head), String::from(text))
+                .is_some()
+            {
+                return Err(format!(
+                    "Line {}: The lazy variable {} is already defined",
+                    id + 1,
+                    head
+                ));
+            }
+
+            continue;
+        }
+
+        if kind == "range" {
+            let range: VariableRange = parse_range(text).ok_or_else(invalid_line)?;
+
+            if variable_ranges.insert(String::from(head), range).is_some() {
+                return Err(format!(
+                    "Line {}: The range of {} is already defined",
+                    id + 1,
+                    head
+                ));
+            }
+
+            continue;
+        }
+
+        let value: Value = parse_value(kind, text).ok_or_else(invalid_line)?;
+
+        if variables.insert(String::from(head), value).is_some() {
+            return Err(format!(
+                "Line {}: The variable {} is already defined",
+                id + 1,
+                head
+            ));
+        }
+    }
+
+    return Ok(CalculatorState {
+        variables,
+        functions,
+        references,
+        lazy_definitions,
+        constants,
+        variable_ranges,
+    });
+}
+
+/// Read a range written in a state script, as `[0, 10] step 0.5`
+fn parse_range(text: &str) -> Option<VariableRange> {
+    let (bounds, step) = match text.split_once(" step ") {
+        Some((bounds, step)) => (bounds, Some(step.parse::<f64>().ok()?)),
+        None => (text, None),
+    };
+
+    let (min, max) = bounds
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split_once(", ")?;
+
+    return Some(VariableRange {
+        min: min.parse::<f64>().ok()?,
+        max: max.parse::<f64>().ok()?,
+        step,
+    });
+}
+
+/// Read a value of given type written in a state script
+fn parse_value(kind: &str, text: &str) -> Option<Value> {
+    return match kind {
+        "number" => text.parse::<f64>().ok().map(Value::Number),
+        "bool" => text.parse::<bool>().ok().map(Value::Bool),
+        "integer" => text.parse::<i64>().ok().map(Value::Integer),
+        "vector" => {
+            let elements: &str = text.strip_prefix('[')?.strip_suffix(']')?;
+
+            if elements.is_empty() {
+                return Some(Value::Vector(Vec::new()));
+            }
+
+            elements
+                .split(", ")
+                .map(|element| element.parse::<f64>().ok())
+                .collect::<Option<Vec<f64>>>()
+                .map(Value::Vector)
+        }
+        #[cfg(feature = "decimal")]
+        "decimal" => text
+            .parse::<rust_decimal::Decimal>()
+            .ok()
+            .map(Value::Decimal),
+        #[cfg(feature = "complex")]
+        "complex" => {
+            let (re, im) = text.split_once(", ")?;
+
+            Some(Value::Complex(num_complex::Complex64::new(
+                re.parse::<f64>().ok()?,
+                im.parse::<f64>().ok()?,
+            )))
+        }
+        _ => None,
+    };
+}
+
+/// Encode bytes in URL-safe base64 without padding
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group: u32 = chunk.iter().enumerate().fold(0, |group, (id, byte)| {
+            group | (u32::from(*byte) << (16 - 8 * id))
+        });
+
+        // A chunk of n bytes gives n + 1 characters
+        for id in 0..=chunk.len() {
+            let index: usize = ((group >> (18 - 6 * id)) & 0x3F) as usize;
+            encoded.push(char::from(BASE64_ALPHABET[index]));
+        }
+    }
+
+    return encoded;
+}
+
+/// Decode URL-safe base64 without padding, none if text is not valid
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() * 3 / 4);
+
+    for chunk in text.as_bytes().chunks(4) {
+        let mut group: u32 = 0;
+
+        for (id, character) in chunk.iter().enumerate() {
+            let index: usize = BASE64_ALPHABET
+                .iter()
+                .position(|alphabet_character| alphabet_character == character)?;
+
+            group |= (index as u32) << (18 - 6 * id);
+        }
+
+        // A chunk of n characters gives n - 1 bytes
+        for id in 0..(chunk.len() - 1) {
+            bytes.push((group >> (16 - 8 * id)) as u8);
+        }
+    }
+
+    return Some(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_base64() {
+        let samples: Vec<(&[u8], &str)> = vec![
+            (b"", ""),
+            (b"f", "Zg"),
+            (b"fo", "Zm8"),
+            (b"foo", "Zm9v"),
+            (b"\xfb\xff", "-_8"),
+        ];
+
+        for (bytes, text) in samples {
+            assert_eq!(encode_base64(bytes), text);
+            assert_eq!(decode_base64(text), Some(bytes.to_vec()));
+        }
+
+        assert_eq!(decode_base64("Zm9vY"), None);
+        assert_eq!(decode_base64("Zm+v"), None);
+    }
+
+    #[test]
+    fn test_fragment_round_trip() {
+        let mut state: CalculatorState = CalculatorState::default();
+
+        for id in 0..50 {
+            state
+                .variables
+                .insert(format!("x{}", id), Value::Number(id as f64 / 7.0));
+
+            state.functions.insert(
+                format!("f{}", id),
+                (
+                    vec![String::from("a"), String::from("b")],
+                    format!("a * x{} + b == {}", id, id),
+                ),
+            );
+        }
+
+        state
+            .variables
+            .insert(String::from("n"), Value::Integer(-3));
+        state
+            .variables
+            .insert(String::from("flag"), Value::Bool(true));
+        state.variables.insert(
+            String::from("v"),
+            Value::Vector(vec![1.5, -2.0, f64::INFINITY]),
+        );
+        state
+            .variables
+            .insert(String::from("empty"), Value::Vector(Vec::new()));
+        state
+            .functions
+            .insert(String::from("one"), (Vec::new(), String::from("1")));
+        state
+            .references
+            .insert(String::from("r"), String::from("n"));
+        state
+            .lazy_definitions
+            .insert(String::from("x1"), String::from("1 / 7"));
+        state.constants.insert(String::from("x2"));
+        state.variable_ranges.insert(
+            String::from("x3"),
+            VariableRange {
+                min: -1.5,
+                max: 10.0,
+                step: Some(0.25),
+            },
+        );
+        state.variable_ranges.insert(
+            String::from("speed"),
+            VariableRange {
+                min: 0.0,
+                max: f64::INFINITY,
+                step: None,
+            },
+        );
+
+        let fragment: String = encode(&state).unwrap();
+
+        assert!(fragment.starts_with("v1."));
+        assert!(fragment.len() < state_script(&state).len());
+        assert!(fragment
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "-_.".contains(character)));
+
+        assert_eq!(decode(fragment.as_str()), Ok(state.clone()));
+        assert_eq!(decode(format!("#{}", fragment).as_str()), Ok(state));
+    }
+
+    #[test]
+    fn test_fragment_tampered() {
+        let mut state: CalculatorState = CalculatorState::default();
+        state
+            .variables
+            .insert(String::from("x"), Value::Number(2.0));
+
+        let fragment: String = encode(&state).unwrap();
+
+        // Change one character of encoded data, checksum of zlib detects it
+        let tampered: String = fragment
+            .char_indices()
+            .map(
+                |(id, character)| match (id == fragment.len() / 2, character) {
+                    (true, 'A') => 'B',
+                    (true, _) => 'A',
+                    (false, _) => character,
+                },
+            )
+            .collect();
+
+        assert!(decode(tampered.as_str()).is_err());
+
+        assert_eq!(
+            decode(fragment.replacen("v1.", "v2.", 1).as_str()),
+            Err(String::from("The fragment has an unknown version"))
+        );
+
+        assert_eq!(
+            decode("v1.not*base64"),
+            Err(String::from("The fragment is not valid URL-safe base64"))
+        );
+
+        let invalid_script: String = format!(
+            "v1.{}",
+            encode_base64(&compress_to_vec_zlib(b"number x = two\n", 6))
+        );
+
+        assert_eq!(
+            decode(invalid_script.as_str()),
+            Err(String::from("Line 1: The line of state is invalid"))
+        );
+
+        // Entries are checked as any imported state
+        let unsafe_script: String = format!(
+            "v1.{}",
+            encode_base64(&compress_to_vec_zlib(
+                "function f: x = x \u{202e}+ 1\n".as_bytes(),
+                6
+            ))
+        );
+
+        assert_eq!(
+            decode(unsafe_script.as_str()),
+            Err(String::from(
+                "The character U+202E in definition of f is not allowed"
+            ))
+        );
+
+        let invalid_name_script: String = format!(
+            "v1.{}",
+            encode_base64(&compress_to_vec_zlib(b"number 2x = 1\n", 6))
+        );
+
+        assert_eq!(
+            decode(invalid_name_script.as_str()),
+            Err(String::from("The variable name 2x is not valid"))
+        );
+    }
+
+    #[test]
+    fn test_fragment_duplicate_names() {
+        let duplicate_scripts: Vec<(&[u8], &str)> = vec![
+            (
+                b"number x = 1\nnumber x = 2\n",
+                "Line 2: The variable x is already defined",
+            ),
+            (
+                b"function f: a = a\nfunction f: a = 2\n",
+                "Line 2: The function f is already defined",
+            ),
+            (
+                b"number x = 1\nreference r = x\nreference r = x\n",
+                "Line 3: The reference r is already defined",
+            ),
+            (
+                b"number x = 1\nlazy x = 1\nlazy x = 2\n",
+                "Line 3: The lazy variable x is already defined",
+            ),
+            (
+                b"number x = 1\nconstant x\nconstant x\n",
+                "Line 3: The constant x is already defined",
+            ),
+            (
+                b"range x = [0, 1]\nrange x = [0, 2] step 1\n",
+                "Line 2: The range of x is already defined",
+            ),
+        ];
+
+        for (script, message) in duplicate_scripts {
+            let fragment: String =
+                format!("v1.{}", encode_base64(&compress_to_vec_zlib(script, 6)));
+
+            assert_eq!(decode(fragment.as_str()), Err(String::from(message)));
+        }
+    }
+
+    #[test]
+    fn test_fragment_size_limit() {
+        let too_long: String = format!("v1.{}", "A".repeat(MAX_FRAGMENT_LENGTH));
+
+        assert_eq!(
+            decode(too_long.as_str()),
+            Err(format!(
+                "The fragment has {} characters, more than the maximum of {}",
+                MAX_FRAGMENT_LENGTH + 3,
+                MAX_FRAGMENT_LENGTH
+            ))
+        );
+
+        // Repeated text compresses well, its fragment is short but its state is too large
+        let huge_script: String = "number x = 1\n".repeat(MAX_SCRIPT_LENGTH / 10);
+        let huge_fragment: String = format!(
+            "v1.{}",
+            encode_base64(&compress_to_vec_zlib(huge_script.as_bytes(), 10))
+        );
+
+        assert!(huge_fragment.len() <= MAX_FRAGMENT_LENGTH);
+
+        assert_eq!(
+            decode(huge_fragment.as_str()),
+            Err(format!(
+                "The fragment is corrupted or its state exceeds {} bytes",
+                MAX_SCRIPT_LENGTH
+            ))
+        );
+
+        // Encoding refuses a state whose fragment could not be decoded
+        let mut huge_state: CalculatorState = CalculatorState::default();
+
+        for id in 0..(MAX_SCRIPT_LENGTH / 10) {
+            huge_state
+                .variables
+                .insert(format!("x{}", id), Value::Number(id as f64));
+        }
+
+        assert!(encode(&huge_state)
+            .unwrap_err()
+            .starts_with("The state has "));
+
+        // Values of random bits do not compress, fragment is too long before script
+        let mut random_state: CalculatorState = CalculatorState::default();
+        let mut bits: u64 = 0x9e37_79b9_7f4a_7c15;
+
+        for id in 0..1000 {
+            bits ^= bits << 13;
+            bits ^= bits >> 7;
+            bits ^= bits << 17;
+
+            random_state
+                .variables
+                .insert(format!("r{}", id), Value::Integer(bits as i64));
+        }
+
+        assert!(encode(&random_state)
+            .unwrap_err()
+            .starts_with("The fragment has "));
+    }
+}
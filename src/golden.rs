@@ -0,0 +1,221 @@
+/// Expected outcome of a golden case
+#[derive(Debug, Clone, PartialEq)]
+pub enum GoldenOutcome {
+    Value(String, f64), // expansion given to evaluator and evaluated value
+    Error(String),      // message of error given by calculator
+}
+
+/// Case of golden tests exported by `Calculator::export_golden_tests`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenCase {
+    pub expression: String,     // raw expression processed by calculator
+    pub outcome: GoldenOutcome, // expected outcome of expression
+}
+
+/// Write a text as a TOML basic string, between quotes and with escaped quotes and backslashes
+pub(crate) fn quote(text: &str) -> String {
+    let mut quoted: String = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+
+    for character in text.chars() {
+        match character {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(character);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(character),
+        }
+    }
+
+    quoted.push('"');
+
+    return quoted;
+}
+
+/// Read a TOML basic string written by `quote`
+fn unquote(text: &str) -> Option<String> {
+    let content: &str = text.strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unquoted: String = String::with_capacity(content.len());
+    let mut characters = content.chars();
+
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unquoted.push(character);
+            continue;
+        }
+
+        match characters.next()? {
+            'n' => unquoted.push('\n'),
+            escaped @ ('"' | '\\') => unquoted.push(escaped),
+            _ => return None,
+        }
+    }
+
+    return Some(unquoted);
+}
+
+/// Parse golden tests exported by `Calculator::export_golden_tests`
+///
+/// Empty lines and comment lines starting with `#` are skipped. Each case starts with `[[case]]`
+/// and contains `expression`, then either `expansion` and `value` or `error`.
+/// If a line cannot be read or a case is incomplete, the error message indicates the line number.
+pub fn parse(golden: &str) -> Result<Vec<GoldenCase>, String> {
+    let mut cases: Vec<GoldenCase> = Vec::new();
+    let mut fields: Vec<(usize, String, String)> = Vec::new(); // (line number, key, value) of current case
+
+    let lines = golden
+        .lines()
+        .enumerate()
+        .map(|(id, line)| (id + 1, line.trim()))
+        .chain(std::iter::once((0, "[[case]]"))); // last case is built at end of text
+
+    for (number, line) in lines {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line == "[[case]]" {
+            if let Some(case) = build_case(&fields)? {
+                cases.push(case);
+            }
+
+            fields.clear();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(format!("Line {}: The line is not a key-value pair", number))?;
+
+        fields.push((number, String::from(key.trim()), String::from(value.trim())));
+    }
+
+    return Ok(cases);
+}
+
+/// Build a case from its fields, none if there is no field before first case
+fn build_case(fields: &[(usize, String, String)]) -> Result<Option<GoldenCase>, String> {
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    let field = |key: &str| -> Option<&str> {
+        return fields
+            .iter()
+            .find(|(_, name, _)| name == key)
+            .map(|(_, _, value)| value.as_str());
+    };
+
+    let string_field = |key: &str| -> Result<Option<String>, String> {
+        return match field(key) {
+            Some(text) => match unquote(text) {
+                Some(unquoted) => Ok(Some(unquoted)),
+                None => Err(format!(
+                    "Line {}: The value of {} is not a string",
+                    line_of(fields, key),
+                    key
+                )),
+            },
+            None => Ok(None),
+        };
+    };
+
+    let expression: String = string_field("expression")?
+        .ok_or(format!("Line {}: The case has no expression", fields[0].0))?;
+
+    if let Some(error) = string_field("error")? {
+        return Ok(Some(GoldenCase {
+            expression,
+            outcome: GoldenOutcome::Error(error),
+        }));
+    }
+
+    let expansion: Option<String> = string_field("expansion")?;
+
+    let value: Option<f64> = match field("value") {
+        Some(text) => Some(text.parse::<f64>().map_err(|_| {
+            format!(
+                "Line {}: The value {} is not a number",
+                line_of(fields, "value"),
+                text
+            )
+        })?),
+        None => None,
+    };
+
+    return match (expansion, value) {
+        (Some(expansion), Some(value)) => Ok(Some(GoldenCase {
+            expression,
+            outcome: GoldenOutcome::Value(expansion, value),
+        })),
+        _ => Err(format!(
+            "Line {}: The case of {} has neither expansion and value nor error",
+            line_of(fields, "expression"),
+            expression
+        )),
+    };
+}
+
+/// Get line number of a field of case
+fn line_of(fields: &[(usize, String, String)], key: &str) -> usize {
+    return fields
+        .iter()
+        .find(|(_, name, _)| name == key)
+        .map_or(0, |(number, _, _)| *number);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden_quote() {
+        let texts: Vec<&str> = vec!["f(1, x)", "say \"hi\"", "a \\ b", "two\nlines", ""];
+
+        for text in texts {
+            assert_eq!(unquote(quote(text).as_str()), Some(String::from(text)));
+        }
+
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(unquote("no quotes"), None);
+        assert_eq!(unquote("\"bad \\t escape\""), None);
+    }
+
+    #[test]
+    fn test_golden_parse() {
+        let golden: &str = "# golden tests\n\n[[case]]\nexpression = \"f(1)\"\nexpansion = \"(1 + 2)\"\nvalue = 3\n\n[[case]]\nexpression = \"g(1)\"\nerror = \"The function g is not defined\"\n";
+
+        assert_eq!(
+            parse(golden),
+            Ok(vec![
+                GoldenCase {
+                    expression: String::from("f(1)"),
+                    outcome: GoldenOutcome::Value(String::from("(1 + 2)"), 3.0),
+                },
+                GoldenCase {
+                    expression: String::from("g(1)"),
+                    outcome: GoldenOutcome::Error(String::from("The function g is not defined")),
+                },
+            ])
+        );
+
+        assert_eq!(
+            parse("[[case]]\nexpression = \"x\"\nvalue = three\n"),
+            Err(String::from("Line 3: The value three is not a number"))
+        );
+
+        assert_eq!(
+            parse("[[case]]\nexpression = \"x\"\n"),
+            Err(String::from(
+                "Line 2: The case of x has neither expansion and value nor error"
+            ))
+        );
+
+        assert_eq!(
+            parse("[[case]]\nexpression\n"),
+            Err(String::from("Line 2: The line is not a key-value pair"))
+        );
+    }
+}
@@ -3,17 +3,224 @@
 //! Tazor is Rust library implementing a calculator based on mathematical expression
 //!
 
+#![allow(clippy::needless_return)]
+#![cfg_attr(test, allow(clippy::assertions_on_constants))]
+
+pub mod binary;
+#[cfg(feature = "commands")]
+pub mod command;
+pub mod diff;
+pub mod error;
+#[cfg(feature = "builtin-eval")]
+pub mod evaluator;
 pub mod expression;
+pub mod format;
+#[cfg(feature = "url")]
+pub mod fragment;
+pub mod golden;
+#[cfg(feature = "limits")]
+pub mod limits;
+pub mod locale;
+pub mod notation;
+pub mod session;
+pub mod template;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod token;
+pub mod validation;
+pub mod value;
+pub mod view;
+
+pub use session::SessionManager;
+
+#[cfg(feature = "commands")]
+use command::{CommandContext, CommandHandler};
+use diff::StateDiff;
+use error::{Cause, Error, EvaluatorError, Failure};
+use expression::{
+    is_syntactically_empty, EmptyInputPolicy, ExpansionLimits, ExpansionStatistics, Expression,
+    InputKind, SubstitutionOrder,
+};
+use format::Format;
+#[cfg(feature = "limits")]
+use limits::Limits;
+use locale::NumberLocale;
+use notation::Notation;
+use token::IdentifierPredicate;
+use validation::UnsafeCharacterPolicy;
+use value::{BooleanSubstitution, EvaluateValue, Value};
+use view::StateView;
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "commands", feature = "observer"))]
+use std::sync::PoisonError;
+use std::sync::{Mutex, MutexGuard};
+
+/// Maximal number of cached expansions of calls of pure functions, cache is emptied when it is reached
+const MAX_CACHED_EXPANSIONS: usize = 1024;
+
+/// Default maximal number of inputs kept in history, see `Calculator::set_max_history`
+#[cfg(feature = "history")]
+pub const DEFAULT_MAX_HISTORY: usize = 1000;
+
+/// Structured result of a processed expression
+///
+/// Beside the string returned by `Calculator::process`, it contains the evaluated value
+/// and the expanded expression given to evaluator, i.e. with functions and variables replaced.
+/// The expanded expression is never truncated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessOutput {
+    pub result: String,            // string representing result of process
+    pub value: Option<Value>,      // evaluated value, none for function definition
+    pub expansion: Option<String>, // expanded expression given to evaluator, none for function definition
+    pub warnings: Vec<String>, // warnings about processed expression, as a number which cannot be stored exactly
+}
+
+/// Observer notified of each input processed successfully by calculator, see `Calculator::add_observer`
+///
+/// It receives the input and its structured output. As a command handler, it is `Send`
+/// and calculator keeps it behind a mutex, thus it does not need to be `Sync`.
+#[cfg(feature = "observer")]
+pub type ProcessObserver = Box<dyn FnMut(&str, &ProcessOutput) + Send>;
+
+/// Analysis of expression size and complexity after its expansion
+///
+/// The expansion is the expression given to evaluator, i.e. with functions and variables replaced.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Analysis {
+    pub expanded_length: usize,       // length in bytes of expanded expression
+    pub function_expansions: usize,   // number of replaced function calls
+    pub max_expansion_depth: usize,   // maximal depth of replaced function calls
+    pub substituted_variables: usize, // number of distinct replaced variables
+    pub operator_count: usize,        // number of arithmetic operators in expanded expression
+}
+
+/// Metrics of size and references of a function defined by user
+///
+/// Reverse references are the number of other functions whose definition calls this function.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FunctionMetrics {
+    pub body_length: usize,          // length in characters of definition
+    pub token_count: usize,          // number of tokens of definition
+    pub parameter_count: usize,      // number of variables of function
+    pub referenced_functions: usize, // number of distinct other functions called in definition
+    pub referenced_variables: usize, // number of distinct variables defined by user referenced in definition
+    pub reverse_references: usize,   // number of other functions calling this function
+}
+
+/// Statistics of values of a function of one variable sampled over a range, see `Calculator::function_stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FunctionStats {
+    pub min: f64,  // minimal sampled value
+    pub max: f64,  // maximal sampled value
+    pub mean: f64, // mean of sampled values
+}
+
+/// Range of a variable declared for frontends, as a slider between minimum and maximum
+///
+/// It is only metadata, a value outside range gives a warning but it is substituted as any value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableRange {
+    pub min: f64,          // minimal value of variable
+    pub max: f64,          // maximal value of variable
+    pub step: Option<f64>, // step between two values of variable, none for continuous values
+}
+
+/// Definition of a variable or a function given by a preview
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewDefinition {
+    Value(Value), // value of a variable, or of a raw expression stored in `last`
+    Function(Vec<String>, String), // variables and definition of a function
+}
+
+/// Effect that an input would have if it was processed, see `Calculator::preview`
+///
+/// A raw expression is previewed as a definition of variable `last`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+    pub kind: InputKind, // kind of input, i.e. raw expression, variable or function
+    pub name: String,    // name of defined variable or function, `last` for raw expression
+    pub definition: PreviewDefinition, // definition which would be stored
+    pub previous_definition: Option<PreviewDefinition>, // definition currently stored, none if name is not defined
+    pub affected: Vec<String>, // sorted names of lazy variables, references and functions depending on name
+}
+
+/// Input checked, expanded and evaluated without modifying calculator, see `Calculator::evaluate_input`
+struct InputEvaluation {
+    input: EvaluatedInput,  // expanded and evaluated input
+    expansion_count: usize, // number of function calls replaced in input
+    warnings: Vec<String>,  // warnings about precision of numbers and range of variable
+}
+
+/// Kind of evaluated input with its definition, see `InputEvaluation`
+enum EvaluatedInput {
+    Raw(String, Value), // expansion and value of raw expression
+    Variable(String, String, Value, Option<String>), // name, expansion, value and lazy definition of variable
+    Function(String, Vec<String>, String),           // name, variables and definition of function
+}
+
+/// State of calculator, i.e. variables, functions, references, lazy variables, constants and ranges of variables defined by user
+///
+/// A snapshot of calculator can be restored later or used to evaluate expressions
+/// as they would have been evaluated when snapshot was taken.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalculatorState {
+    pub variables: HashMap<String, Value>, // variables defined by user, key is name of variable
+    pub functions: HashMap<String, (Vec<String>, String)>, // functions defined by user, key is name of function and value is (variables, definition)
+    pub references: HashMap<String, String>, // references defined by user, key is name of reference and value is name of its target
+    pub lazy_definitions: HashMap<String, String>, // definitions of lazy variables with functions expanded, key is name of variable
+    pub constants: HashSet<String>, // variables which cannot be redefined by processed expressions
+    pub variable_ranges: HashMap<String, VariableRange>, // ranges of variables declared for frontends, key is name of variable
+}
+
+impl CalculatorState {
+    /// Compute differences from this state to other state given in argument, see `diff::StateDiff`
+    pub fn diff(&self, other: &CalculatorState) -> StateDiff {
+        return StateDiff::between(self, other);
+    }
 
-use expression::Expression;
+    /// Encode state in a compact URL-safe fragment, to share a session by a link, see `fragment`
+    ///
+    /// A state whose fragment would be rejected by `from_url_fragment` since it is too long gives an error.
+    #[cfg(feature = "url")]
+    pub fn to_url_fragment(&self) -> Result<String, String> {
+        return fragment::encode(self);
+    }
+
+    /// Decode state from a fragment given by `to_url_fragment`, with or without leading `#`
+    ///
+    /// A fragment longer than `fragment::MAX_FRAGMENT_LENGTH`, giving a state larger
+    /// than `fragment::MAX_SCRIPT_LENGTH` once decompressed, or corrupted gives an error,
+    /// as well as a state not accepted by `validation::check_state` with default identifiers.
+    #[cfg(feature = "url")]
+    pub fn from_url_fragment(fragment: &str) -> Result<CalculatorState, String> {
+        return fragment::decode(fragment);
+    }
+}
 
-use std::collections::HashMap;
+/// Calculator whose evaluator is a function returning its error as a string, the most common case
+///
+/// Its type can be named, as in a field of structure, whatever the evaluator function given to `new`.
+pub type StringCalculator = Calculator<fn(&str) -> Result<f64, String>>;
 
 /// Evaluate mathematical expression and store user-define variable and function to reuse it after.
 ///
 /// The calculator is based on Evaluator which is a function taking a string, representing a mathematical expression
 /// and return the value of evaluation on 64-bit float.
 ///
+/// The error of evaluator can be a string or any type implementing `Display`, as an enumeration,
+/// it is written in error message of `process`.
+///
+/// An evaluator distinguishing kinds of values, as booleans or integers, can be used
+/// by implementing `value::EvaluateValue` trait, the calculator stores then each value with its kind.
+///
+/// Results are identical across runs, although variables and functions are stored in hash maps:
+/// substitutions only look up names and every listing or export is sorted by name.
+///
 /// # Example with simple expression
 /// ```
 /// use tazor;
@@ -86,7 +293,7 @@ use std::collections::HashMap;
 /// let function: String = String::from("f: x, y = factor * (x * x + y * y)");
 /// assert!(calculator.process(function.as_str()).is_ok());
 ///
-/// let expression: String = String::from("f(1,75, 2.54) + 2.43");
+/// let expression: String = String::from("f(1.75, 2.54) + 2.43");
 ///
 /// match calculator.process(expression.as_str()) {
 ///     Ok(str_result) => println!("{}", str_result),
@@ -97,16 +304,65 @@ use std::collections::HashMap;
 ///
 pub struct Calculator<Evaluator>
 where
-    Evaluator: Fn(&str) -> Result<f64, String>,
+    Evaluator: EvaluateValue,
 {
-    evaluator: Evaluator,            // mathematical expression evaluator
-    variables: HashMap<String, f64>, // map to store custom variable defined by user, key is name of variable and value is its evaluation
+    evaluator: Evaluator,              // mathematical expression evaluator
+    variables: HashMap<String, Value>, // map to store custom variable defined by user, key is name of variable and value is its evaluation
     functions: HashMap<String, (Vec<String>, String)>, // map to store custom function defined by user, key is name of function and value is its expression (variables, definition)
+    echo_expansion: bool, // if true, the result string of raw expression contains its expansion
+    echo_expansion_length: usize, // maximal number of characters of expansion in result string
+    strict_mode: bool,    // if true, expression is validated before its process
+    number_locale: NumberLocale, // convention used to read numbers of expression and write results
+    boolean_substitution: BooleanSubstitution, // way to write boolean values replacing variables
+    last_expanded: Option<String>, // expansion of last processed raw or variable expression
+    #[cfg(feature = "limits")]
+    limits: Limits, // limits applied on processed expressions
+    notation: Notation,   // notation used to write numbers of results
+    disabled_functions: HashSet<String>, // functions whose calls are not expanded
+    #[cfg(feature = "commands")]
+    commands: HashMap<String, Mutex<CommandHandler>>, // commands registered by frontend, key is name of command without leading colon
+    #[cfg(feature = "history")]
+    history: Vec<String>, // inputs processed successfully, from oldest to newest
+    #[cfg(feature = "history")]
+    max_history: Option<usize>, // maximal number of inputs kept in history, none for no limit
+    #[cfg(feature = "observer")]
+    observers: Vec<Mutex<ProcessObserver>>, // observers notified of inputs processed successfully, in order of addition
+    #[cfg(feature = "commands")]
+    snapshots: HashMap<String, CalculatorState>, // states saved by command :save, key is name of snapshot
+    argument_separator: char, // separator between arguments of function calls and variables of function definitions
+    pretty_definitions: bool, // if true, definitions of functions are written with canonical spacing in results and exported scripts
+    evaluation_count: AtomicU64, // number of expressions given to evaluator, counted from construction or last reset
+    format: Format,              // options applied on result and error strings given by process
+    display_epsilon: Option<f64>, // if defined, numbers of results whose magnitude is below it are written 0, as well as negative zero
+    pure_functions: HashSet<String>, // functions declared pure, whose expansions are cached across process calls
+    expansion_cache: Mutex<HashMap<(String, String), (String, usize)>>, // expansions of calls of pure functions with their number of replaced calls, key is name of function and its arguments between parenthesis
+    references: HashMap<String, String>, // variables tracking another variable, key is name of reference and value is name of its target
+    results: Vec<Value>, // results of raw expressions kept for references as %3, from oldest to newest
+    evicted_results: usize, // number of oldest results of raw expressions which are no longer kept
+    max_results: Option<usize>, // maximal number of results of raw expressions kept for references, none for no limit
+    unsafe_character_policy: UnsafeCharacterPolicy, // way to handle control and bidirectional characters of inputs
+    identifier_predicate: IdentifierPredicate, // predicate giving characters which can be part of a variable name
+    strict_precision: bool, // if true, a number which cannot be stored exactly as 64-bit float gives an error
+    lazy_definitions: HashMap<String, String>, // definitions of lazy variables with functions expanded, key is name of variable
+    lazy_variables: bool, // if true, definitions of variables are stored to be recomputed later
+    default_undefined: Option<f64>, // value of undefined variables referenced in raw expression, none to keep them
+    expansion_count: usize, // number of function calls replaced in last processed expression
+    variable_ranges: HashMap<String, VariableRange>, // ranges of variables declared for frontends, key is name of variable
+    minimal_parentheses: bool, // if true, expanded definitions of functions are wrapped in parentheses only when needed
+    autoname: bool, // if true, result of each raw expression is also stored in variable _N
+    autoname_count: usize, // number of results stored in variables _N
+    constants: HashSet<String>, // variables which cannot be redefined by processed expressions, with feature constants
+    #[cfg(feature = "constants")]
+    env_lowercase: bool, // if true, names of variables imported from environment are lowercased
+    substitution_order: SubstitutionOrder, // order of replacement of functions and variables in processed expressions
+    strict_duplicates: bool, // if true, a variable or function defined twice in a loaded script gives an error
+    empty_input_policy: EmptyInputPolicy, // way to handle an empty or whitespace-only input
+    result_template: String, // template of result of raw expression and variable, with placeholders {name} and {value}
 }
 
 impl<Evaluator> Calculator<Evaluator>
 where
-    Evaluator: Fn(&str) -> Result<f64, String>,
+    Evaluator: EvaluateValue,
 {
     /// Construct a calculator given an evaluator in argument
     pub fn new(evaluator: Evaluator) -> Self {
@@ -114,566 +370,7788 @@ where
             evaluator,
             variables: HashMap::with_capacity(25),
             functions: HashMap::with_capacity(25),
+            echo_expansion: false,
+            echo_expansion_length: 80,
+            strict_mode: false,
+            number_locale: NumberLocale::canonical(),
+            boolean_substitution: BooleanSubstitution::Literal,
+            last_expanded: None,
+            #[cfg(feature = "limits")]
+            limits: Limits::default(),
+            notation: Notation::Auto,
+            disabled_functions: HashSet::new(),
+            #[cfg(feature = "commands")]
+            commands: HashMap::new(),
+            #[cfg(feature = "history")]
+            history: Vec::new(),
+            #[cfg(feature = "history")]
+            max_history: Some(DEFAULT_MAX_HISTORY),
+            #[cfg(feature = "observer")]
+            observers: Vec::new(),
+            #[cfg(feature = "commands")]
+            snapshots: HashMap::new(),
+            argument_separator: ',',
+            pretty_definitions: false,
+            evaluation_count: AtomicU64::new(0),
+            format: Format::default(),
+            display_epsilon: None,
+            pure_functions: HashSet::new(),
+            expansion_cache: Mutex::new(HashMap::new()),
+            references: HashMap::new(),
+            results: Vec::new(),
+            evicted_results: 0,
+            max_results: None,
+            unsafe_character_policy: UnsafeCharacterPolicy::Reject,
+            identifier_predicate: token::is_identifier_character,
+            strict_precision: false,
+            lazy_definitions: HashMap::new(),
+            lazy_variables: false,
+            default_undefined: None,
+            expansion_count: 0,
+            variable_ranges: HashMap::new(),
+            minimal_parentheses: false,
+            autoname: false,
+            autoname_count: 0,
+            constants: HashSet::new(),
+            #[cfg(feature = "constants")]
+            env_lowercase: true,
+            substitution_order: SubstitutionOrder::FunctionsFirst,
+            strict_duplicates: false,
+            empty_input_policy: EmptyInputPolicy::Reject,
+            result_template: String::from("{name} = {value}"),
         };
     }
 
-    /// Process an expression
+    /// Set initial value of variables `last` and `ans`, so that first expression can reference them
     ///
-    /// If error occurs during process, an error message is stored in string contained in Result output.
+    /// By default `last` is not defined until a raw expression is processed and `ans` is not defined,
+    /// giving none in argument keeps this behavior. Only `last` is then updated by raw expressions,
+    /// `ans` keeps its initial value unless it is redefined.
+    pub fn initial_last(mut self, value: Option<f64>) -> Self {
+        for name in ["last", "ans"] {
+            match value {
+                Some(last_value) => self
+                    .variables
+                    .insert(String::from(name), Value::Number(last_value)),
+                None => self.variables.remove(name),
+            };
+        }
+
+        return self;
+    }
+
+    /// Enable or disable echo of expansion in result string of raw expression
     ///
-    /// Otherwise, the Result output contains string which represent result according to kind of expression:
-    ///    - raw => `last = evaluated_expression`
-    ///    - variable => `variable_name = variable_value`
-    ///    - function => `function_name(function_variables) = function_definition`
+    /// When enabled, result string is `last = value    [expansion]` where expansion
+    /// is the expression given to evaluator, truncated with `…` if it is too long.
+    pub fn set_echo_expansion(&mut self, echo_expansion: bool) {
+        self.echo_expansion = echo_expansion;
+    }
+
+    /// Set maximal number of characters of expansion echoed in result string
+    pub fn set_echo_expansion_length(&mut self, echo_expansion_length: usize) {
+        self.echo_expansion_length = echo_expansion_length;
+    }
+
+    /// Enable or disable strict mode
     ///
-    pub fn process(&mut self, expression_str: &str) -> Result<String, String> {
-        let mut expression: Expression = Expression::new(expression_str);
+    /// In strict mode, expression is validated before its process (see `validation::validate`)
+    /// to report syntax errors, as mismatched brackets, before they reach evaluator.
+    /// Name of a definition must also be a valid identifier, thus `2 + 2 = 4` is rejected.
+    pub fn set_strict_mode(&mut self, strict_mode: bool) {
+        self.strict_mode = strict_mode;
+    }
+
+    /// Set order of replacement of functions and variables in processed expressions
+    ///
+    /// By default functions are replaced first, thus variables of function definitions which were
+    /// undefined when function was defined are replaced by their current value. With variables
+    /// replaced first, these ones are left in expansion, as `f(1)` giving `(1 + k)` with `f: t = t + k`.
+    /// In both orders, variables in arguments of a call are replaced, as `x` of `f(x)`.
+    pub fn set_substitution_order(&mut self, substitution_order: SubstitutionOrder) {
+        self.substitution_order = substitution_order;
+    }
 
-        expression.replace_functions(&self.functions)?;
-        expression.replace_variables(&self.variables);
+    /// Enable or disable minimal parentheses around expanded definitions of functions
+    ///
+    /// By default, each expanded call is wrapped in parentheses, as `f(5)` giving `(5)` with `f: x = x`.
+    /// With minimal parentheses, a definition which is a single number or already between parentheses
+    /// after substitution of arguments is not wrapped, thus `f(5)` gives `5` but `f(y)` gives `(y)`,
+    /// since `y` can be replaced by a negative value.
+    pub fn set_minimal_parentheses(&mut self, minimal_parentheses: bool) {
+        self.minimal_parentheses = minimal_parentheses;
+        self.cached_expansions().clear();
+    }
 
-        let result: String = match expression {
-            Expression::Raw(raw_expression) => {
-                let value: f64 = (self.evaluator)(&raw_expression.as_str())?;
+    /// Set convention used to read numbers of processed expressions and to write results
+    ///
+    /// Exported scripts are not affected, they always use canonical convention.
+    /// Decimal separator cannot be the argument separator, thus with `,` as decimal separator,
+    /// arguments must be separated by `;` (see `set_argument_separator`), which must be set first.
+    pub fn set_number_locale(&mut self, number_locale: NumberLocale) -> Result<(), String> {
+        Self::check_separators(self.argument_separator, number_locale)?;
+        self.number_locale = number_locale;
 
-                let raw_expression_result: String = format!("last = {}", value);
-                self.variables.insert(String::from("last"), value);
+        return Ok(());
+    }
 
-                raw_expression_result
-            }
-            Expression::Variable(name, definition) => {
-                let value: f64 = (self.evaluator)(&definition.as_str())?;
+    /// Set separator between arguments of function calls and variables of function definitions
+    ///
+    /// It is `,` by default or `;`, as in `f: x; y = x * y` and `f(1; 2)`. The other separator
+    /// is rejected in calls of functions and in variables of function definitions.
+    /// Exported scripts use this separator, thus they must be loaded with the same one.
+    /// It cannot be the decimal separator of number locale (see `set_number_locale`).
+    pub fn set_argument_separator(&mut self, argument_separator: char) -> Result<(), String> {
+        if argument_separator != ',' && argument_separator != ';' {
+            return Err(format!(
+                "The argument separator {} is not valid, it must be ',' or ';'",
+                argument_separator
+            ));
+        }
 
-                let variable_result: String = format!("{} = {}", name, value);
-                self.variables.insert(name, value);
+        Self::check_separators(argument_separator, self.number_locale)?;
+        self.argument_separator = argument_separator;
+        self.cached_expansions().clear();
 
-                variable_result
-            }
-            Expression::Function(name, variables, definition) => {
-                let function_result: String =
-                    format!("{}({}) = {}", name, variables.join(", "), definition);
+        return Ok(());
+    }
 
-                self.functions.insert(name, (variables, definition));
+    /// Check that argument separator is not decimal separator of number locale
+    fn check_separators(
+        argument_separator: char,
+        number_locale: NumberLocale,
+    ) -> Result<(), String> {
+        if argument_separator == number_locale.decimal_separator() {
+            return Err(format!(
+                "The argument separator '{}' cannot be the decimal separator of number locale",
+                argument_separator
+            ));
+        }
 
-                function_result
-            }
+        return Ok(());
+    }
+
+    /// Write definitions of functions with canonical spacing, as `x * x + y * y`, in results and exported scripts
+    ///
+    /// Definitions are stored as written by user, only their whitespaces are changed when they are written
+    /// (see `Expression::pretty`).
+    pub fn set_pretty_definitions(&mut self, pretty_definitions: bool) {
+        self.pretty_definitions = pretty_definitions;
+    }
+
+    /// Set notation used to write numbers of results
+    ///
+    /// It does not change values stored in calculator, nor numbers replacing variables in expressions.
+    pub fn set_notation(&mut self, notation: Notation) {
+        self.notation = notation;
+    }
+
+    /// Set number of significant figures of numbers written in results, none to write them in automatic notation
+    ///
+    /// It sets notation to `Notation::Significant`, thus it replaces a fixed number of decimals
+    /// and conversely, the last one set wins. Stored values of variables and `last` are kept unchanged.
+    pub fn set_significant_figures(&mut self, significant_figures: Option<usize>) {
+        self.notation = match significant_figures {
+            Some(figures) => Notation::Significant(figures),
+            None => Notation::Auto,
         };
+    }
 
-        return Ok(result);
+    /// Set how boolean values are written when they replace variables in an expression
+    ///
+    /// By default they are written `true` or `false`.
+    pub fn set_boolean_substitution(&mut self, boolean_substitution: BooleanSubstitution) {
+        self.boolean_substitution = boolean_substitution;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Enable or disable strict precision, where a number which cannot be stored exactly gives an error
+    ///
+    /// Otherwise, as by default, such a number is stored as its nearest 64-bit float
+    /// and a warning is given in `ProcessOutput`, as for `9007199254740993` stored as `9007199254740992`.
+    pub fn set_strict_precision(&mut self, strict_precision: bool) {
+        self.strict_precision = strict_precision;
+    }
 
-    // Define mock evaluator for units tests
-    fn evaluate(expression: &str) -> Result<f64, String> {
-        if expression.is_empty() {
-            return Err(String::from("Expression is empty"));
-        }
+    /// Set way to handle an empty or whitespace-only input, see `expression::EmptyInputPolicy`
+    ///
+    /// By default, such an input is rejected with an error whose cause is `Cause::EmptyInput`.
+    /// It never modifies calculator, in particular `last` and history are kept.
+    pub fn set_empty_input_policy(&mut self, empty_input_policy: EmptyInputPolicy) {
+        self.empty_input_policy = empty_input_policy;
+    }
 
-        return Ok(expression.len() as f64);
+    /// Enable or disable strict duplicates, where a script defining a variable or function twice gives an error
+    ///
+    /// Otherwise, as by default, `load_script` silently keeps the last definition.
+    pub fn set_strict_duplicates(&mut self, strict_duplicates: bool) {
+        self.strict_duplicates = strict_duplicates;
     }
 
-    #[test]
-    fn test_calculator_new() {
-        let calculator = Calculator::new(evaluate);
+    /// Set predicate giving characters which can be part of a variable name when variables are replaced
+    ///
+    /// By default they are letters, digits and underscore (see `token::is_identifier_character`).
+    /// An evaluator with other rules can extend them, as with `$` so that `$x` is replaced as a whole name.
+    /// Only whole names are replaced, thus a name containing a character accepted by predicate
+    /// is never replaced partially, as `x` in `$x`.
+    pub fn set_identifier_predicate(&mut self, identifier_predicate: IdentifierPredicate) {
+        self.identifier_predicate = identifier_predicate;
+    }
 
-        assert!(calculator.variables.capacity() > 0);
+    /// Set how unsafe characters of inputs are handled, as bidirectional control characters
+    ///
+    /// By default an input containing one is rejected, so that no definition, listing or exported script
+    /// contains them (see `validation::is_unsafe_character`). Imported states, as by `restore`
+    /// or `load_binary`, containing one are always rejected, whatever the policy.
+    pub fn set_unsafe_character_policy(&mut self, unsafe_character_policy: UnsafeCharacterPolicy) {
+        self.unsafe_character_policy = unsafe_character_policy;
+    }
 
-        let empty_expression: String = String::default();
-        assert!((calculator.evaluator)(empty_expression.as_str()).is_err());
+    /// Set limits applied on processed expressions
+    #[cfg(feature = "limits")]
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
 
-        let expression: String = String::from("taz");
-        let result: Result<f64, String> = (calculator.evaluator)(expression.as_str());
+    /// Set maximal number of characters of a processed expression, none for no limit
+    ///
+    /// Leading and trailing whitespaces are not counted, as well as comment lines skipped by `load_script`.
+    /// A longer expression is rejected before any process, including a command.
+    #[cfg(feature = "limits")]
+    pub fn set_max_expression_length(&mut self, max_expression_length: Option<usize>) {
+        self.limits.max_expression_length = max_expression_length;
+    }
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap() as usize, expression.len())
+    /// Set epsilon below which magnitude of a number is written 0 in results, none to write numbers as they are
+    ///
+    /// With an epsilon, negative zero is also written 0, thus `Some(0.0)` only normalizes negative zero.
+    /// Only written results are affected, stored values of variables and `last` are kept unchanged.
+    pub fn set_display_epsilon(&mut self, display_epsilon: Option<f64>) {
+        self.display_epsilon = display_epsilon;
     }
 
-    #[test]
-    fn test_calculator_process_raw_expression() {
-        let mut calculator = Calculator::new(evaluate);
+    /// Set options applied on result and error strings given by `process`, see `Format`
+    ///
+    /// They apply on echo of function definitions, results of commands and error messages,
+    /// also given by `process_line`, `process_iter` and `process_with_diagnostics`,
+    /// while `process_structured` and `try_process` always give complete result.
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
 
-        let expression: String = String::from("1 + 1");
+    /// Set template of result of raw expression and variable, `{name} = {value}` by default
+    ///
+    /// Placeholder `{name}` is replaced by name of variable, `last` for a raw expression,
+    /// and `{value}` by its value written according to notation and locale,
+    /// thus with template `{name} -> {value}` processing `x = 2` gives `x -> 2`.
+    pub fn set_result_template(&mut self, template: String) {
+        self.result_template = template;
+    }
 
-        match calculator.process(expression.as_str()) {
-            Ok(str_result) => {
-                let variable_name: String = String::from("last");
-                let variable_value: f64 = expression.len() as f64;
+    /// Check that expression respects limits, before any replacement in it
+    #[cfg(feature = "limits")]
+    fn check_limits(&self, expression_str: &str) -> Result<(), Failure> {
+        self.check_expression_length(expression_str)?;
 
-                let str_reference: String = format!("{} = {}", variable_name, variable_value);
-                assert_eq!(str_result, str_reference);
+        let max_token_length: usize = self.limits.max_token_length;
 
-                assert_eq!(calculator.variables.len(), 1);
-                assert!(calculator.variables.contains_key(&variable_name));
-                assert_eq!(calculator.variables[&variable_name], variable_value);
-            }
-            Err(_) => assert!(false),
+        if let Some(column) = validation::find_too_long_token(expression_str, max_token_length) {
+            return Err(Failure {
+                message: format!(
+                    "The token is too long at column {} (maximal length is {})",
+                    column, max_token_length
+                ),
+                cause: Some(Cause::TokenTooLong {
+                    column,
+                    limit: max_token_length,
+                }),
+                legacy_message: None,
+            });
         }
+
+        return Ok(());
     }
 
-    #[test]
-    fn test_calculator_process_several_raw_expression() {
-        let mut calculator = Calculator::new(evaluate);
+    /// Check that expression respects limits, without feature `limits` there is no limit
+    #[cfg(not(feature = "limits"))]
+    fn check_limits(&self, _expression_str: &str) -> Result<(), Failure> {
+        return Ok(());
+    }
 
-        // Evaluate first expression
-        let first_expression: String = String::from("1 + 1");
+    /// Check that expression, without leading and trailing whitespaces, is not longer than maximal length
+    ///
+    /// Characters are only counted when number of bytes exceeds maximal length, thus it is checked
+    /// before any other scan of an input.
+    fn check_expression_length(&self, expression_str: &str) -> Result<(), Failure> {
+        let max_expression_length: usize = match self.max_expression_length() {
+            Some(max_expression_length) => max_expression_length,
+            None => return Ok(()),
+        };
 
-        let variable_name: String = String::from("last");
+        let trimmed_expression: &str = expression_str.trim();
 
-        match calculator.process(first_expression.as_str()) {
-            Ok(str_result) => {
-                let variable_value: f64 = first_expression.len() as f64;
+        if trimmed_expression.len() <= max_expression_length {
+            return Ok(());
+        }
 
-                let str_reference: String = format!("{} = {}", variable_name, variable_value);
-                assert_eq!(str_result, str_reference);
+        let expression_length: usize = trimmed_expression.chars().count();
 
-                assert_eq!(calculator.variables.len(), 1);
-                assert!(calculator.variables.contains_key(&variable_name));
-                assert_eq!(calculator.variables[&variable_name], variable_value);
-            }
-            Err(_) => assert!(false),
+        if expression_length > max_expression_length {
+            return Err(Failure {
+                message: format!(
+                    "The expression is too long, its length is {} (maximal length is {})",
+                    expression_length, max_expression_length
+                ),
+                cause: Some(Cause::ExpressionTooLong {
+                    length: expression_length,
+                    limit: max_expression_length,
+                }),
+                legacy_message: None,
+            });
         }
 
-        // Evaluate second expresion
-        // The value of variable named 'last' must be replaced by value of second expression
-        let second_expression: String = String::from("1 + 1 + 3");
-
-        match calculator.process(second_expression.as_str()) {
-            Ok(str_result) => {
-                let variable_value: f64 = second_expression.len() as f64;
+        return Ok(());
+    }
 
-                let str_reference: String = format!("{} = {}", variable_name, variable_value);
-                assert_eq!(str_result, str_reference);
+    /// Check that expansion of an expression substituting its variables is not longer than maximal length
+    fn check_expansion_length(&self, expression: &Expression) -> Result<(), Failure> {
+        if let Some(max_length) = self.max_expression_length() {
+            let expansion: &str = expression.definition();
 
-                assert_eq!(calculator.variables.len(), 1);
-                assert!(calculator.variables.contains_key(&variable_name));
-                assert_eq!(calculator.variables[&variable_name], variable_value);
+            if expansion.len() > max_length && expansion.chars().count() > max_length {
+                return Err(Failure {
+                    message: format!(
+                        "The expansion is too long after substitution of variables (maximal length is {})",
+                        max_length
+                    ),
+                    cause: Some(Cause::ExpansionTooLong {
+                        function: None,
+                        limit: max_length,
+                    }),
+                    legacy_message: None,
+                });
             }
-            Err(_) => assert!(false),
         }
+
+        return Ok(());
     }
 
-    #[test]
-    fn test_calculator_process_variable_expression() {
-        let mut calculator = Calculator::new(evaluate);
+    /// Get maximal number of function calls replaced in a processed expression
+    fn max_function_expansions(&self) -> usize {
+        #[cfg(feature = "limits")]
+        return self.limits.max_function_expansions;
 
-        let variable_name: String = String::from("x");
-        let variable_definition: String = String::from("1 + 1");
+        #[cfg(not(feature = "limits"))]
+        return expression::MAX_FUNCTION_EXPANSIONS;
+    }
 
-        let expression: String = format!("{} = {}", variable_name, variable_definition);
+    /// Get maximal number of characters of an expression and its expansion, none for no limit
+    fn max_expression_length(&self) -> Option<usize> {
+        #[cfg(feature = "limits")]
+        return self.limits.max_expression_length;
 
-        match calculator.process(expression.as_str()) {
-            Ok(str_result) => {
-                let variable_value: f64 = variable_definition.len() as f64;
+        #[cfg(not(feature = "limits"))]
+        return None;
+    }
 
-                let str_reference: String = format!("{} = {}", variable_name, variable_value);
-                assert_eq!(str_result, str_reference);
+    /// Get limits of replacement of functions in a processed expression
+    fn expansion_limits(&self) -> ExpansionLimits {
+        return ExpansionLimits {
+            max_expansions: self.max_function_expansions(),
+            max_length: self.max_expression_length(),
+        };
+    }
 
-                assert_eq!(calculator.variables.len(), 1);
-                assert!(calculator.variables.contains_key(&variable_name));
-                assert_eq!(calculator.variables[&variable_name], variable_value);
+    /// Replace variables contained in expression by their value
+    fn replace_variables(&self, expression: &mut Expression, variables: &HashMap<String, Value>) {
+        let substitution = |name: &str| -> Option<String> {
+            if let Ok(Some(value)) = self.referenced_result(name) {
+                return Some(value.substitution(self.boolean_substitution));
             }
-            Err(_) => assert!(false),
-        }
+
+            let target: &str = self.resolve_reference(name).unwrap_or(name);
+
+            return variables
+                .get(target)
+                .map(|value| value.substitution(self.boolean_substitution));
+        };
+
+        expression.replace_variables_with_predicate(substitution, self.identifier_predicate);
     }
 
-    #[test]
-    fn test_calculator_process_several_variable_expression() {
-        let mut calculator = Calculator::new(evaluate);
+    /// Set value of undefined variables referenced in a raw expression, none to give them as is to evaluator
+    ///
+    /// With a default value, any identifier of a raw expression which is not a variable, a reference,
+    /// a result as `%2` or a name reserved by evaluator is replaced by this value, thus with `0`,
+    /// `x + 5` gives `0 + 5` when `x` is not defined. Names followed by `(` are left to evaluator
+    /// as function calls, and escaped names as `` `x `` are kept.
+    pub fn set_default_undefined(&mut self, default_undefined: Option<f64>) {
+        self.default_undefined = default_undefined;
+    }
 
-        let first_variable_name: String = String::from("x");
-        let first_variable_definition: String = String::from("1 + 1");
+    /// Replace undefined variables of a raw expression by default value, if any
+    fn replace_undefined_variables(&self, expression: &mut Expression) {
+        let default_undefined: f64 = match (self.default_undefined, &expression) {
+            (Some(default_undefined), Expression::Raw(_)) => default_undefined,
+            _ => return,
+        };
 
-        let first_expression: String =
-            format!("{} = {}", first_variable_name, first_variable_definition);
+        let definition: &str = expression.definition();
+        let substitution: String =
+            Value::Number(default_undefined).substitution(self.boolean_substitution);
 
-        match calculator.process(first_expression.as_str()) {
-            Ok(str_result) => {
-                let variable_value: f64 = first_variable_definition.len() as f64;
+        let mut replaced_definition: String = String::with_capacity(definition.len());
+        let mut last_position: usize = 0;
 
-                let str_reference: String = format!("{} = {}", first_variable_name, variable_value);
-                assert_eq!(str_result, str_reference);
+        for lexeme in token::lex_with_identifier_predicate(definition, self.identifier_predicate) {
+            let name: &str = &definition[lexeme.range.clone()];
 
-                assert_eq!(calculator.variables.len(), 1);
-                assert!(calculator.variables.contains_key(&first_variable_name));
-                assert_eq!(calculator.variables[&first_variable_name], variable_value);
+            let is_undefined: bool = lexeme.kind == token::LexemeKind::Identifier
+                && !definition[..lexeme.range.start].ends_with('`')
+                && !definition[lexeme.range.end..].trim_start().starts_with('(')
+                && !self.variables.contains_key(name)
+                && !self.references.contains_key(name)
+                && !matches!(self.referenced_result(name), Ok(Some(_)))
+                && !self.evaluator.reserved_names().contains(&name);
+
+            if is_undefined {
+                replaced_definition.push_str(&definition[last_position..lexeme.range.start]);
+                replaced_definition.push_str(substitution.as_str());
+                last_position = lexeme.range.end;
             }
-            Err(_) => assert!(false),
         }
 
-        let second_variable_name: String = String::from("y");
-        let second_variable_definition: String = String::from("9 + 1");
+        replaced_definition.push_str(&definition[last_position..]);
 
-        let second_expression: String =
-            format!("{} = {}", second_variable_name, second_variable_definition);
+        *expression = Expression::Raw(replaced_definition);
+    }
 
-        match calculator.process(second_expression.as_str()) {
-            Ok(str_result) => {
-                let variable_value: f64 = second_variable_definition.len() as f64;
+    /// Set if names of variables imported by `import_env` are lowercased, true by default
+    #[cfg(feature = "constants")]
+    pub fn set_env_lowercase(&mut self, env_lowercase: bool) {
+        self.env_lowercase = env_lowercase;
+    }
 
-                let str_reference: String =
-                    format!("{} = {}", second_variable_name, variable_value);
+    /// Import variables from environment variables whose name starts with given prefix
+    ///
+    /// Prefix is removed from name of environment variable, which is lowercased unless disabled
+    /// by `set_env_lowercase`, thus `TAZOR_RATE=0.2` with prefix `TAZOR_` defines `rate = 0.2`.
+    /// Imported variables are constants, they cannot be redefined by processed expressions.
+    /// An invalid name or value does not stop import, it returns number of imported variables
+    /// or the list of rejected environment variables with error message. An environment variable
+    /// whose name or value is not valid Unicode is rejected, its name being given lossily.
+    /// A variable already defined as constant is redefined by import.
+    #[cfg(feature = "constants")]
+    pub fn import_env(&mut self, prefix: &str) -> Result<usize, Vec<(String, String)>> {
+        let mut env_vars: Vec<(String, Option<String>)> = std::env::vars_os()
+            .filter_map(|(env_name, env_value)| {
+                let env_name_str: String = env_name.to_string_lossy().into_owned();
 
-                assert_eq!(str_result, str_reference);
+                if !env_name_str.starts_with(prefix) {
+                    return None;
+                }
 
-                assert_eq!(calculator.variables.len(), 2);
-                assert!(calculator.variables.contains_key(&second_variable_name));
-                assert_eq!(calculator.variables[&second_variable_name], variable_value);
-            }
-            Err(_) => assert!(false),
-        }
-    }
+                return Some((
+                    env_name_str,
+                    env_name.to_str().and(env_value.into_string().ok()),
+                ));
+            })
+            .collect();
 
-    #[test]
-    fn test_calculator_process_expression_with_variables() {
-        let mut calculator = Calculator::new(evaluate);
+        env_vars.sort_unstable();
 
-        let first_variable_name: String = String::from("x");
-        let first_variable_definition: String = String::from("1 + 1");
+        let mut imported_count: usize = 0;
+        let mut errors: Vec<(String, String)> = Vec::new();
 
-        let first_expression: String =
-            format!("{} = {}", first_variable_name, first_variable_definition);
+        for (env_name, env_value) in env_vars {
+            let env_value: String = match env_value {
+                Some(env_value) => env_value,
+                None => {
+                    errors.push((
+                        env_name,
+                        String::from("The environment variable is not valid Unicode"),
+                    ));
+                    continue;
+                }
+            };
 
-        let first_process_result = calculator.process(first_expression.as_str());
-        assert!(first_process_result.is_ok());
+            let name: String = match self.env_lowercase {
+                true => env_name[prefix.len()..].to_lowercase(),
+                false => String::from(&env_name[prefix.len()..]),
+            };
 
-        let second_variable_name: String = String::from("y");
-        let second_variable_definition: String = String::from("97 + 1");
+            if !validation::is_identifier(&name) {
+                errors.push((env_name, format!("The variable name {} is not valid", name)));
+                continue;
+            }
 
-        let second_expression: String =
-            format!("{} = {}", second_variable_name, second_variable_definition);
+            let value: f64 = match env_value.trim().parse::<f64>() {
+                Ok(value) => value,
+                Err(_) => {
+                    errors.push((
+                        env_name,
+                        format!(
+                            "The value {} of variable {} is not a number",
+                            env_value, name
+                        ),
+                    ));
+                    continue;
+                }
+            };
 
-        let second_process_result = calculator.process(second_expression.as_str());
+            self.references.remove(&name);
+            self.lazy_definitions.remove(&name);
+            self.variables.insert(name.clone(), Value::Number(value));
+            self.constants.insert(name);
+
+            imported_count += 1;
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        return Ok(imported_count);
+    }
+
+    /// Declare range of a variable for frontends, as a slider
+    ///
+    /// It is also declared by processing `slider x = 2.5 in [0, 10] step 0.5`, where step is optional.
+    /// A variable defined outside its range gives a warning, or an error in strict mode,
+    /// but range never changes its value. Variable does not need to be defined yet.
+    /// Identifier predicate is taken into account, see `set_identifier_predicate`.
+    pub fn set_range(
+        &mut self,
+        name: &str,
+        min: f64,
+        max: f64,
+        step: Option<f64>,
+    ) -> Result<(), String> {
+        let range: VariableRange = VariableRange { min, max, step };
+
+        validation::check_range(name, &range, self.identifier_predicate)?;
+
+        self.variable_ranges.insert(String::from(name), range);
+
+        return Ok(());
+    }
+
+    /// Remove range of a variable, return true if it was declared
+    pub fn remove_range(&mut self, name: &str) -> bool {
+        return self.variable_ranges.remove(name).is_some();
+    }
+
+    /// Get range of a variable, none if it is not declared
+    pub fn variable_range(&self, name: &str) -> Option<VariableRange> {
+        return self.variable_ranges.get(name).copied();
+    }
+
+    /// Process a slider declaration, as `x = 2.5 in [0, 10] step 0.5` after keyword `slider`
+    ///
+    /// Range is declared before definition of variable, it is restored if definition fails.
+    fn process_slider(&mut self, declaration: &str) -> Result<ProcessOutput, Failure> {
+        let (definition, range_str): (&str, &str) = declaration.rsplit_once(" in ").ok_or_else(|| {
+            String::from("The slider declaration must give a range, as `slider x = 1 in [0, 10] step 0.5`")
+        })?;
+
+        let name: String = match Expression::new(definition) {
+            Expression::Variable(name, _) => name,
+            _ => {
+                return Err(Failure::from(String::from(
+                    "The slider declaration must define a variable",
+                )))
+            }
+        };
+
+        let (bounds_str, step_str): (&str, Option<&str>) = match range_str.split_once("step") {
+            Some((bounds_str, step_str)) => (bounds_str, Some(step_str)),
+            None => (range_str, None),
+        };
+
+        let parse_number = |number_str: &str| -> Result<f64, String> {
+            return number_str
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("The range {} of slider is not valid", range_str.trim()));
+        };
+
+        let (min_str, max_str): (&str, &str) = bounds_str
+            .trim()
+            .strip_prefix('[')
+            .and_then(|bounds_str| bounds_str.strip_suffix(']'))
+            .and_then(|bounds_str| bounds_str.split_once(','))
+            .ok_or_else(|| format!("The range {} of slider is not valid", range_str.trim()))?;
+
+        let step: Option<f64> = step_str.map(parse_number).transpose()?;
+
+        let previous_range: Option<VariableRange> = self.variable_range(&name);
+        self.set_range(&name, parse_number(min_str)?, parse_number(max_str)?, step)?;
+
+        let output: Result<ProcessOutput, Failure> = self.process_canonical(definition);
+
+        if output.is_err() {
+            match previous_range {
+                Some(previous_range) => self.variable_ranges.insert(name, previous_range),
+                None => self.variable_ranges.remove(&name),
+            };
+        }
+
+        return output;
+    }
+
+    /// Process declaration of a lazy variable as `area = circle(r)`, following keyword `lazy`
+    fn process_lazy(&mut self, declaration: &str) -> Result<ProcessOutput, Failure> {
+        if !matches!(Expression::new(declaration), Expression::Variable(_, _)) {
+            return Err(Failure::from(String::from(
+                "The lazy declaration must define a variable",
+            )));
+        }
+
+        let lazy_variables: bool = self.lazy_variables;
+        self.lazy_variables = true;
+
+        let output: Result<ProcessOutput, Failure> = self.process_canonical(declaration);
+        self.lazy_variables = lazy_variables;
+
+        return output;
+    }
+
+    /// Process declaration of a constant as `rate = 0.2`, following keyword `const`
+    #[cfg(feature = "constants")]
+    fn process_constant(&mut self, declaration: &str) -> Result<ProcessOutput, Failure> {
+        let name: String = match Expression::new(declaration) {
+            Expression::Variable(name, _) => name,
+            _ => {
+                return Err(Failure::from(String::from(
+                    "The constant declaration must define a variable",
+                )))
+            }
+        };
+
+        let output: ProcessOutput = self.process_canonical(declaration)?;
+        self.constants.insert(name);
+
+        return Ok(output);
+    }
+
+    /// Process declaration of a reference as `b = a`, following keyword `reference`, see `set_reference`
+    fn process_reference(&mut self, declaration: &str) -> Result<ProcessOutput, Failure> {
+        let (name, target): (String, String) = match Expression::new(declaration) {
+            Expression::Variable(name, target) => (name, String::from(target.trim())),
+            _ => {
+                return Err(Failure::from(String::from(
+                    "The reference declaration must give a target, as `reference b = a`",
+                )))
+            }
+        };
+
+        self.set_reference(&name, &target)?;
+
+        let value: Option<Value> = self
+            .resolve_reference(&name)
+            .and_then(|target| self.variables.get(target))
+            .cloned();
+
+        return Ok(ProcessOutput {
+            result: format!("reference {} = {}", name, target),
+            value,
+            expansion: None,
+            warnings: Vec::new(),
+        });
+    }
+
+    /// Get warnings about value of a variable outside its range, in strict mode the first one gives an error
+    fn range_warnings(&self, name: &str, value: &Value) -> Result<Vec<String>, String> {
+        let range: VariableRange = match self.variable_ranges.get(name) {
+            Some(range) => *range,
+            None => return Ok(Vec::new()),
+        };
+
+        let number: f64 = value.to_f64();
+
+        if number >= range.min && number <= range.max {
+            return Ok(Vec::new());
+        }
+
+        let message: String = format!(
+            "The value {} of variable {} is outside its range [{}, {}]",
+            self.format_value(value),
+            name,
+            range.min,
+            range.max
+        );
+
+        if self.strict_mode {
+            return Err(message);
+        }
+
+        return Ok(vec![message]);
+    }
+
+    /// Set maximal number of results of raw expressions kept for references as `%3`, none for no limit
+    ///
+    /// When it is reached, the oldest result is no longer kept, but numbering of results is unchanged.
+    pub fn set_max_results(&mut self, max_results: Option<usize>) {
+        self.max_results = max_results;
+        self.evict_results();
+    }
+
+    /// Enable or disable storage of results of raw expressions under generated names `_1`, `_2`, ...
+    ///
+    /// Result of each raw expression processed while enabled is stored under next name,
+    /// in addition to `last`, and numbering goes on when it is enabled again.
+    pub fn set_autoname(&mut self, autoname: bool) {
+        self.autoname = autoname;
+    }
+
+    /// Get result stored under generated name `_n`, none if it is not defined
+    pub fn get_result(&self, n: usize) -> Option<&Value> {
+        return self.variables.get(&format!("_{}", n));
+    }
+
+    /// Get names of variables storing result of next raw expression, `last` and `_N` when autoname is enabled
+    fn result_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![String::from("last")];
+
+        if self.autoname {
+            names.push(format!("_{}", self.autoname_count + 1));
+        }
+
+        return names;
+    }
+
+    /// Get results of raw expressions kept for references, with their number, from oldest to newest
+    pub fn results(&self) -> Vec<(usize, &Value)> {
+        return self
+            .results
+            .iter()
+            .enumerate()
+            .map(|(id, value)| (self.evicted_results + id + 1, value))
+            .collect();
+    }
+
+    /// Get result referenced by a name as `%3`, i.e. result of 3rd processed raw expression,
+    /// none if name is not a reference to a result
+    fn referenced_result(&self, name: &str) -> Result<Option<&Value>, String> {
+        let number: usize = match name.strip_prefix('%').map(str::parse::<usize>) {
+            Some(Ok(number)) => number,
+            _ => return Ok(None),
+        };
+
+        if number == 0 || number > self.evicted_results + self.results.len() {
+            return Err(format!(
+                "The result %{} does not exist, {} result(s) have been computed",
+                number,
+                self.evicted_results + self.results.len()
+            ));
+        }
+
+        if number <= self.evicted_results {
+            return Err(format!(
+                "The result %{} is no longer kept, only the last {} results are kept",
+                number,
+                self.max_results.unwrap_or_default()
+            ));
+        }
+
+        return Ok(self.results.get(number - self.evicted_results - 1));
+    }
+
+    /// Remove oldest results of raw expressions exceeding maximal number of results
+    fn evict_results(&mut self) {
+        if let Some(max_results) = self.max_results {
+            let evicted_count: usize = self.results.len().saturating_sub(max_results);
+
+            self.results.drain(..evicted_count);
+            self.evicted_results += evicted_count;
+        }
+    }
+
+    /// Enable or disable lazy variables, whose definitions are stored to be recomputed later
+    ///
+    /// A lazy variable is evaluated at definition as any variable, but its definition is also stored
+    /// with functions expanded and variables kept, so that `recompute` evaluates it again
+    /// against current values of variables. Thus with `area = circle(r)`, a new value of `r`
+    /// is taken into account by `recompute("area")`. A variable defined while lazy variables
+    /// are disabled is not lazy. In a script, a lazy variable is declared as `lazy area = circle(r)`,
+    /// whatever this setting.
+    pub fn set_lazy_variables(&mut self, lazy_variables: bool) {
+        self.lazy_variables = lazy_variables;
+    }
+
+    /// Evaluate again a lazy variable against current values of variables, store and return its new value
+    ///
+    /// Variable `last` is not updated.
+    pub fn recompute(&mut self, name: &str) -> Result<Value, String> {
+        let definition: &String = self
+            .lazy_definitions
+            .get(name)
+            .ok_or_else(|| format!("The variable {} is not lazy", name))?;
+
+        let mut expression: Expression = Expression::Raw(definition.clone());
+        self.replace_variables(&mut expression, &self.variables);
+
+        let value: Value = self.evaluate_value(expression.definition())?;
+        self.variables.insert(String::from(name), value.clone());
+
+        return Ok(value);
+    }
+
+    /// Define a variable tracking another variable, i.e. whose value is the current value of its target
+    ///
+    /// A variable defined as `b = a` copies value of `a` at definition, whereas with `set_reference("b", "a")`
+    /// each use of `b` reads value of `a` at this time. A target can be a reference itself, it is resolved
+    /// transitively, but a cycle of references or a target which does not lead to a defined variable
+    /// gives an error. Defining a variable with the same name as a reference removes the reference,
+    /// and conversely. In a script, a reference is declared as `reference b = a`.
+    pub fn set_reference(&mut self, name: &str, target: &str) -> Result<(), String> {
+        for identifier in [name, target] {
+            if !validation::is_identifier(identifier) {
+                return Err(format!("The variable name {} is not valid", identifier));
+            }
+        }
+
+        #[cfg(feature = "constants")]
+        if self.constants.contains(name) {
+            return Err(format!(
+                "The variable {} is a constant, it cannot be redefined",
+                name
+            ));
+        }
+
+        let mut cycle: Vec<&str> = vec![name];
+        let mut current_target: &str = target;
+
+        loop {
+            cycle.push(current_target);
+
+            if current_target == name {
+                return Err(format!("The reference {} is cyclic", cycle.join(" -> ")));
+            }
+
+            match self.references.get(current_target) {
+                Some(next_target) => current_target = next_target.as_str(),
+                None => break,
+            }
+        }
+
+        if !self.variables.contains_key(current_target) {
+            return Err(format!("The variable {} is not defined", current_target));
+        }
+
+        self.variables.remove(name);
+        self.references
+            .insert(String::from(name), String::from(target));
+
+        return Ok(());
+    }
+
+    /// Get variable tracked by a reference, following references transitively, none if name is not a reference
+    fn resolve_reference(&self, name: &str) -> Option<&str> {
+        let mut target: &str = self.references.get(name)?.as_str();
+
+        for _ in 0..self.references.len() {
+            match self.references.get(target) {
+                Some(next_target) => target = next_target.as_str(),
+                None => break,
+            }
+        }
+
+        return Some(target);
+    }
+
+    /// Enable or disable a function defined by user
+    ///
+    /// Calls of a disabled function are not expanded, they are given as is to evaluator,
+    /// but its definition is kept so that it is expanded again once enabled.
+    pub fn set_function_enabled(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        if !self.functions.contains_key(name) {
+            return Err(format!("The function {} is not defined", name));
+        }
+
+        if enabled {
+            self.disabled_functions.remove(name);
+        } else {
+            self.disabled_functions.insert(String::from(name));
+        }
+
+        self.invalidate_expansions(name);
+
+        return Ok(());
+    }
+
+    /// Declare a function defined by user as pure or not
+    ///
+    /// Expansion of a call of a pure function is cached, keyed on function name and its arguments,
+    /// so that a following call with the same arguments is not expanded again, even in another process.
+    /// Cached expansions of a function are invalidated when it, a function it calls or a function
+    /// called in its arguments is redefined, and all of them when argument separator or minimal
+    /// parentheses change. Variables are replaced after expansion, thus a cached expansion uses
+    /// current values of variables. A cached expansion counts its replaced calls as if it was expanded
+    /// again, see `Limits::max_function_expansions`, and at most 1024 expansions are cached.
+    pub fn set_function_pure(&mut self, name: &str, pure: bool) -> Result<(), String> {
+        if !self.functions.contains_key(name) {
+            return Err(format!("The function {} is not defined", name));
+        }
+
+        if pure {
+            self.pure_functions.insert(String::from(name));
+        } else {
+            self.pure_functions.remove(name);
+            self.invalidate_expansions(name);
+        }
+
+        return Ok(());
+    }
+
+    /// Get cached expansions of calls of pure functions
+    fn cached_expansions(&self) -> MutexGuard<'_, HashMap<(String, String), (String, usize)>> {
+        return match self.expansion_cache.lock() {
+            Ok(cache) => cache,
+            Err(poisoned_cache) => poisoned_cache.into_inner(),
+        };
+    }
+
+    /// Remove cached expansions of a function and of functions calling it, directly or not,
+    /// as well as cached expansions of calls whose arguments call one of these functions
+    fn invalidate_expansions(&self, name: &str) {
+        let mut cache = self.cached_expansions();
+
+        if cache.is_empty() {
+            return;
+        }
+
+        let dependency_graph: HashMap<String, Vec<String>> = self.function_dependency_graph();
+        let mut stale_functions: HashSet<&str> = HashSet::from([name]);
+
+        loop {
+            let stale_count: usize = stale_functions.len();
+
+            for (function, dependencies) in dependency_graph.iter() {
+                if dependencies
+                    .iter()
+                    .any(|dependency| stale_functions.contains(dependency.as_str()))
+                {
+                    stale_functions.insert(function.as_str());
+                }
+            }
+
+            if stale_functions.len() == stale_count {
+                break;
+            }
+        }
+
+        cache.retain(|(function, arguments), _| {
+            return !stale_functions.contains(function.as_str())
+                && !token::lex(arguments).into_iter().any(|lexeme| {
+                    return lexeme.kind == token::LexemeKind::Identifier
+                        && stale_functions.contains(&arguments[lexeme.range]);
+                });
+        });
+    }
+
+    /// Replace calls of pure functions by their cached expansions, expansions missing in cache are added
+    ///
+    /// It returns number of function calls replaced to expand calls missing in cache.
+    fn replace_pure_function_calls(
+        &self,
+        expression: &mut Expression,
+        functions: &HashMap<String, (Vec<String>, String)>,
+    ) -> Result<usize, Failure> {
+        let pure_functions: HashMap<String, (Vec<String>, String)> = functions
+            .iter()
+            .filter(|(name, _)| self.pure_functions.contains(*name))
+            .map(|(name, function)| (name.clone(), function.clone()))
+            .collect();
+
+        if pure_functions.is_empty() {
+            return Ok(0);
+        }
+
+        let mut expansion_count: usize = 0;
+
+        // Failure of an expansion is kept to give its cause, replacement only gives its message
+        let mut expansion_failure: Option<Failure> = None;
+
+        let replacement: Result<(), String> =
+            expression.replace_calls_with(&pure_functions, |name, arguments| {
+                let key: (String, String) = (String::from(name), String::from(arguments));
+
+                if let Some((expansion, call_count)) = self.cached_expansions().get(&key) {
+                    let max_expansions: usize = self.max_function_expansions();
+
+                    if expansion_count + call_count > max_expansions {
+                        let message: String = format!(
+                            "The maximal number of function expansions ({}) is reached during expansion of function {}",
+                            max_expansions, name
+                        );
+
+                        expansion_failure = Some(Failure {
+                            message: message.clone(),
+                            cause: Some(Cause::TooManyExpansions {
+                                function: String::from(name),
+                                performed: expansion_count,
+                                limit: max_expansions,
+                            }),
+                            legacy_message: None,
+                        });
+
+                        return Err(message);
+                    }
+
+                    expansion_count += call_count;
+
+                    return Ok(expansion.clone());
+                }
+
+                let previous_expansion_count: usize = expansion_count;
+                let mut call: Expression = Expression::Raw(format!("{}{}", name, arguments));
+
+                match call.replace_functions_with_limit(
+                    functions,
+                    self.argument_separator,
+                    self.minimal_parentheses,
+                    expansion_count,
+                    self.expansion_limits(),
+                    self.identifier_predicate,
+                ) {
+                    Ok(statistics) => expansion_count = statistics.expansion_count,
+                    Err(failure) => {
+                        let message: String = failure.message.clone();
+                        expansion_failure = Some(failure);
+                        return Err(message);
+                    }
+                }
+
+                let expansion: String = String::from(call.definition());
+                let mut cache = self.cached_expansions();
+
+                if cache.len() >= MAX_CACHED_EXPANSIONS {
+                    cache.clear();
+                }
+
+                cache.insert(
+                    key,
+                    (expansion.clone(), expansion_count - previous_expansion_count),
+                );
+
+                return Ok(expansion);
+            });
+
+        return match (replacement, expansion_failure) {
+            (Ok(()), _) => Ok(expansion_count),
+            (Err(_), Some(failure)) => Err(failure),
+            (Err(message), None) => Err(Failure::from(message)),
+        };
+    }
+
+    /// Rename a function defined by user, it keeps its variables, its definition and its enabled state
+    ///
+    /// If `update_references` is true, calls of function in definitions of other functions
+    /// are renamed too, else they refer to old name which is no longer defined.
+    /// New name cannot be the name of a function, a variable, a constant or a reference already defined,
+    /// and identifier predicate is taken into account, see `set_identifier_predicate`.
+    pub fn rename_function(
+        &mut self,
+        from: &str,
+        to: &str,
+        update_references: bool,
+    ) -> Result<(), String> {
+        if !self.functions.contains_key(from) {
+            return Err(format!("The function {} is not defined", from));
+        }
+
+        if !validation::is_identifier_with_predicate(to, self.identifier_predicate) {
+            return Err(format!("The function name {} is not valid", to));
+        }
+
+        if from == to {
+            return Ok(());
+        }
+
+        if self.functions.contains_key(to) {
+            return Err(format!("The function {} is already defined", to));
+        }
+
+        if self.constants.contains(to) {
+            return Err(format!("The name {} is already defined as a constant", to));
+        }
+
+        if self.variables.contains_key(to) {
+            return Err(format!("The name {} is already defined as a variable", to));
+        }
+
+        if self.references.contains_key(to) {
+            return Err(format!("The name {} is already defined as a reference", to));
+        }
+
+        if let Some(function) = self.functions.remove(from) {
+            self.functions.insert(String::from(to), function);
+        }
+
+        if self.disabled_functions.remove(from) {
+            self.disabled_functions.insert(String::from(to));
+        }
+
+        if self.pure_functions.remove(from) {
+            self.pure_functions.insert(String::from(to));
+        }
+
+        self.cached_expansions().clear();
+
+        if update_references {
+            for (_, definition) in self.functions.values_mut() {
+                let mut expression: Expression = Expression::Raw(definition.clone());
+
+                if expression.rename_function_calls(from, to) > 0 {
+                    *definition = String::from(expression.definition());
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Get functions which are not disabled, they are the ones expanded in expressions
+    fn enabled_functions(&self) -> Cow<'_, HashMap<String, (Vec<String>, String)>> {
+        if self.disabled_functions.is_empty() {
+            return Cow::Borrowed(&self.functions);
+        }
+
+        return Cow::Owned(
+            self.functions
+                .iter()
+                .filter(|(name, _)| !self.disabled_functions.contains(*name))
+                .map(|(name, function)| (name.clone(), function.clone()))
+                .collect(),
+        );
+    }
+
+    /// Replace functions and variables of expression in substitution order of calculator
+    ///
+    /// If `replace_undefined` is true, undefined variables of a raw expression are replaced
+    /// by default value, see `set_default_undefined`.
+    fn substitute(
+        &self,
+        expression: &mut Expression,
+        replace_undefined: bool,
+    ) -> Result<ExpansionStatistics, Failure> {
+        let statistics: ExpansionStatistics = match self.substitution_order {
+            SubstitutionOrder::FunctionsFirst => {
+                let statistics: ExpansionStatistics = self.replace_functions(expression)?;
+
+                if replace_undefined {
+                    self.replace_undefined_variables(expression);
+                }
+
+                self.replace_variables(expression, &self.variables);
+                self.check_expansion_length(expression)?;
+
+                statistics
+            }
+            SubstitutionOrder::VariablesFirst => {
+                self.replace_variables(expression, &self.variables);
+                self.check_expansion_length(expression)?;
+
+                let statistics: ExpansionStatistics = self.replace_functions(expression)?;
+
+                if replace_undefined {
+                    self.replace_undefined_variables(expression);
+                    self.check_expansion_length(expression)?;
+                }
+
+                statistics
+            }
+        };
+
+        return Ok(statistics);
+    }
+
+    /// Replace functions called in expression according to its kind
+    ///
+    /// Functions called in a function definition are replaced when it is called,
+    /// unless the definition is frozen as `g!: x = f(x) * 2`
+    ///
+    /// Number of replaced function calls, including the ones replaced to expand pure functions,
+    /// is bounded by `Limits::max_function_expansions`.
+    fn replace_functions(
+        &self,
+        expression: &mut Expression,
+    ) -> Result<ExpansionStatistics, Failure> {
+        let is_frozen: bool = expression.remove_freeze_marker();
+
+        if is_frozen || !matches!(expression, Expression::Function(..)) {
+            let functions = self.enabled_functions();
+
+            let pure_expansion_count: usize =
+                self.replace_pure_function_calls(expression, &functions)?;
+
+            return expression.replace_functions_with_limit(
+                &functions,
+                self.argument_separator,
+                self.minimal_parentheses,
+                pure_expansion_count,
+                self.expansion_limits(),
+                self.identifier_predicate,
+            );
+        }
+
+        return Ok(ExpansionStatistics::default());
+    }
+
+    /// Process an expression
+    ///
+    /// If error occurs during process, an error message is stored in string contained in Result output.
+    ///
+    /// Otherwise, the Result output contains string which represent result according to kind of expression:
+    ///    - raw => `last = evaluated_expression`
+    ///    - variable => `variable_name = variable_value`
+    ///    - function => `function_name(function_variables) = function_definition`
+    ///    - command => `setting = new_value`
+    ///
+    /// Result of n-th processed raw expression can be referenced as `%n`, as in `%1 * 2`,
+    /// see `set_max_results` to bound number of kept results.
+    ///
+    /// A command starts with `:`, available commands are:
+    ///    - `:sci` to write results in scientific notation
+    ///    - `:eng` to write results in engineering notation
+    ///    - `:fix n` to write results with `n` decimals, at most 17
+    ///    - `:auto` to write results in shortest decimal form
+    ///    - `:save name` to save a snapshot of variables and functions
+    ///    - `:snapshots` to list saved snapshots
+    ///    - `:at name expression` to evaluate expression against a saved snapshot
+    ///    - `:results` to list numbered results of raw expressions
+    ///    - `:metrics name` to show size and references of a function
+    ///    - `:preview expression` to show effect of an expression without processing it
+    ///    - `:help topic` to show help on variables, functions, commands or operators of evaluator,
+    ///      `:help` alone lists these topics
+    ///
+    /// Commands registered with `register_command` are dispatched before these ones.
+    ///
+    pub fn process(&mut self, expression_str: &str) -> Result<String, String> {
+        return self
+            .process_structured(expression_str)
+            .map(|output| self.format.truncate(output.result))
+            .map_err(|error| self.format.truncate(error));
+    }
+
+    /// Process expressions given by an iterator lazily, each one when its result is consumed
+    ///
+    /// Expressions are processed in order as with `process`, thus an expression can use definitions
+    /// of previous ones. Process continues after an error, its message is given in place of result.
+    pub fn process_iter<'a, I>(
+        &'a mut self,
+        expressions: I,
+    ) -> impl Iterator<Item = Result<String, String>> + 'a
+    where
+        I: Iterator<Item = &'a str> + 'a,
+    {
+        return expressions.map(move |expression_str| self.process(expression_str));
+    }
+
+    /// Process a line containing several expressions separated by semicolons, as `x = 2; x * 3`
+    ///
+    /// Expressions are processed in order as with `process`, thus `last` is updated after each
+    /// raw expression and can be used by following ones, as in `2 + 2; last * 2`.
+    /// Empty expressions are skipped and a semicolon between double quotes is not a separator.
+    /// If error occurs, the process stops and the error message indicates the expression number.
+    ///
+    /// When `;` is the argument separator, a semicolon between parenthesis is not a separator either,
+    /// and a function definition with several variables must be processed alone with `process`.
+    pub fn process_line(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let mut statements: Vec<&str> = Vec::new();
+        let mut is_quoted: bool = false;
+        let mut parenthesis_counter: usize = 0;
+        let mut start: usize = 0;
+
+        for (position, c) in line.char_indices() {
+            match c {
+                '"' => is_quoted = !is_quoted,
+                '(' if self.argument_separator == ';' => parenthesis_counter += 1,
+                ')' if self.argument_separator == ';' => {
+                    parenthesis_counter = parenthesis_counter.saturating_sub(1)
+                }
+                ';' if !is_quoted && parenthesis_counter == 0 => {
+                    statements.push(&line[start..position]);
+                    start = position + 1;
+                }
+                _ => {}
+            }
+        }
+
+        statements.push(&line[start..]);
+
+        let mut results: Vec<String> = Vec::with_capacity(statements.len());
+
+        for (id, statement) in statements.into_iter().enumerate() {
+            if statement.trim().is_empty() {
+                continue;
+            }
+
+            let output: ProcessOutput = self.process_structured(statement).map_err(|error| {
+                self.format
+                    .truncate(format!("Expression {}: {}", id + 1, error))
+            })?;
+
+            results.push(self.format.truncate(output.result));
+        }
+
+        return Ok(results);
+    }
+
+    /// Process an expression and return structured output
+    ///
+    /// It behaves as `process` but the Result output contains a `ProcessOutput`
+    pub fn process_structured(&mut self, expression_str: &str) -> Result<ProcessOutput, String> {
+        return self
+            .try_process(expression_str)
+            .map_err(|error| error.legacy_message());
+    }
+
+    /// Process an expression and return its result with identifiers left in its expansion
+    ///
+    /// It behaves as `process`, and the expression is evaluated even if identifiers remain
+    /// once functions and variables are replaced, as undefined variables given to evaluator.
+    /// Leftover identifiers are sorted and distinct, names followed by `(` are calls passed through
+    /// to evaluator and names reserved by evaluator are not counted. A function definition,
+    /// whose expansion is done when it is called, gives no leftover identifiers.
+    pub fn process_with_diagnostics(
+        &mut self,
+        expression_str: &str,
+    ) -> Result<(String, Vec<String>), String> {
+        let output: ProcessOutput = self
+            .process_structured(expression_str)
+            .map_err(|error| self.format.truncate(error))?;
+
+        let expansion: &str = match &output.expansion {
+            Some(expansion) => expansion.as_str(),
+            None => return Ok((self.format.truncate(output.result), Vec::new())),
+        };
+
+        let mut leftover_identifiers: Vec<String> =
+            token::lex_with_identifier_predicate(expansion, self.identifier_predicate)
+                .into_iter()
+                .filter(|lexeme| lexeme.kind == token::LexemeKind::Identifier)
+                .filter(|lexeme| !expansion[lexeme.range.end..].trim_start().starts_with('('))
+                .map(|lexeme| &expansion[lexeme.range])
+                .filter(|name| !self.evaluator.reserved_names().contains(name))
+                .map(String::from)
+                .collect();
+
+        leftover_identifiers.sort_unstable();
+        leftover_identifiers.dedup();
+
+        return Ok((self.format.truncate(output.result), leftover_identifiers));
+    }
+
+    /// Define a variable from an expression and return its value, as processing `name = expression`
+    ///
+    /// The value is converted to 64-bit float, a boolean gives 1 or 0, see `Value::try_to_f64`.
+    /// A value which is not a real number, as a vector, is stored but gives an error.
+    /// A name which is not a valid identifier gives an error, since `name = expression`
+    /// would not define it, as for `a + b`. Identifier predicate is taken into account,
+    /// see `set_identifier_predicate`, thus with `$` accepted in names, `$rate` can be defined.
+    pub fn define(&mut self, name: &str, expression_str: &str) -> Result<f64, String> {
+        if !validation::is_identifier_with_predicate(name, self.identifier_predicate) {
+            return Err(format!("The variable name {} is not valid", name));
+        }
+
+        let output: ProcessOutput =
+            self.process_structured(format!("{} = {}", name, expression_str).as_str())?;
+
+        return output
+            .value
+            .ok_or_else(|| format!("The variable {} has no value", name))?
+            .try_to_f64();
+    }
+
+    /// Process an expression and return structured output, or typed error
+    ///
+    /// The error carries kind of expression detected and name of defined variable or function,
+    /// its display contains a hint when this name is not a valid identifier (see `error::Error`).
+    pub fn try_process(&mut self, expression_str: &str) -> Result<ProcessOutput, Error> {
+        self.check_expression_length(expression_str)
+            .map_err(|failure| Error::from_failure(failure, expression_str))?;
+
+        if expression::input_kind(expression_str) == InputKind::Empty {
+            return match self.empty_input_policy {
+                EmptyInputPolicy::Reject => Err(Error::from_failure(
+                    Failure {
+                        message: String::from("The expression is empty"),
+                        cause: Some(Cause::EmptyInput),
+                        legacy_message: None,
+                    },
+                    expression_str,
+                )),
+                EmptyInputPolicy::Ignore => Ok(ProcessOutput {
+                    result: String::new(),
+                    value: None,
+                    expansion: None,
+                    warnings: Vec::new(),
+                }),
+            };
+        }
+
+        let output: ProcessOutput = self
+            .process_input(expression_str)
+            .map_err(|failure| Error::from_failure(failure, expression_str))?;
+
+        #[cfg(feature = "history")]
+        {
+            self.history.push(String::from(expression_str));
+            self.evict_history();
+        }
+
+        #[cfg(feature = "observer")]
+        for observer in self.observers.iter_mut() {
+            observer.get_mut().unwrap_or_else(PoisonError::into_inner)(expression_str, &output);
+        }
+
+        return Ok(output);
+    }
+
+    /// Set maximal number of inputs kept in history, none for no limit
+    ///
+    /// When it is reached, the oldest input is no longer kept. By default, at most
+    /// `DEFAULT_MAX_HISTORY` inputs are kept.
+    #[cfg(feature = "history")]
+    pub fn set_max_history(&mut self, max_history: Option<usize>) {
+        self.max_history = max_history;
+        self.evict_history();
+    }
+
+    /// Remove oldest inputs of history beyond maximal number of kept inputs
+    #[cfg(feature = "history")]
+    fn evict_history(&mut self) {
+        if let Some(max_history) = self.max_history {
+            let evicted_count: usize = self.history.len().saturating_sub(max_history);
+
+            self.history.drain(..evicted_count);
+        }
+    }
+
+    /// Get inputs processed successfully, from oldest to newest
+    ///
+    /// Without feature `history`, inputs are not kept and history is always empty.
+    pub fn history(&self) -> &[String] {
+        #[cfg(feature = "history")]
+        return self.history.as_slice();
+
+        #[cfg(not(feature = "history"))]
+        return &[];
+    }
+
+    /// Get a read-only view of variables, functions and history, see `view::StateView`
+    ///
+    /// It only needs a shared reference, thus with calculator behind a `RwLock`,
+    /// several threads can read it while inputs are processed through write guard.
+    pub fn read_view(&self) -> StateView<'_> {
+        return StateView::new(
+            &self.variables,
+            &self.functions,
+            &self.references,
+            &self.lazy_definitions,
+            &self.constants,
+            &self.variable_ranges,
+            self.history(),
+        );
+    }
+
+    /// Add an observer notified of each input processed successfully, after it is added to history
+    ///
+    /// Observers are notified in order of addition, an input giving an error is not notified.
+    #[cfg(feature = "observer")]
+    pub fn add_observer(&mut self, observer: ProcessObserver) {
+        self.observers.push(Mutex::new(observer));
+    }
+
+    /// Register a command which can be processed as `:name arguments`
+    ///
+    /// Arguments are separated by whitespaces, except inside double quotes.
+    /// A registered command is dispatched before a built-in command with the same name,
+    /// and registering a command again replaces its handler.
+    #[cfg(feature = "commands")]
+    pub fn register_command(&mut self, name: &str, handler: CommandHandler) {
+        self.commands.insert(
+            String::from(name.trim_start_matches(':')),
+            Mutex::new(handler),
+        );
+    }
+
+    /// Process an input, either a command or an expression
+    fn process_input(&mut self, expression_str: &str) -> Result<ProcessOutput, Failure> {
+        let sanitized_expression: Cow<str> = self.sanitize(expression_str)?;
+        let expression_str: &str = sanitized_expression.as_ref();
+
+        #[cfg(feature = "commands")]
+        if expression::input_kind(expression_str) == InputKind::Command {
+            self.check_limits(expression_str)?;
+
+            let (name, arguments): (String, Vec<String>) = command::split_command(expression_str);
+            let arguments: Vec<&str> = arguments.iter().map(|argument| argument.as_str()).collect();
+
+            return self
+                .process_command(name.as_str(), arguments.as_slice())
+                .map(|command_result| ProcessOutput {
+                    result: command_result,
+                    value: None,
+                    expansion: None,
+                    warnings: Vec::new(),
+                })
+                .map_err(Failure::from);
+        }
+
+        #[cfg(not(feature = "commands"))]
+        if expression::input_kind(expression_str) == InputKind::Command {
+            return Err(Failure::from(format!(
+                "The command {} is not available without feature commands",
+                expression_str.trim()
+            )));
+        }
+
+        let canonical_expression: String = self.number_locale.delocalize_expression(expression_str);
+
+        return self.process_canonical(canonical_expression.as_str());
+    }
+
+    /// Process a command, i.e. an input starting with `:`, given its name and its arguments
+    #[cfg(feature = "commands")]
+    fn process_command(&mut self, name: &str, arguments: &[&str]) -> Result<String, String> {
+        // Handler and context borrow distinct fields, thus handler stays registered while it runs
+        if let Some(handler) = self.commands.get_mut(name) {
+            #[cfg(feature = "history")]
+            let history: &[String] = self.history.as_slice();
+            #[cfg(not(feature = "history"))]
+            let history: &[String] = &[];
+
+            let mut context: CommandContext = CommandContext::new(
+                &mut self.variables,
+                &mut self.functions,
+                history,
+                self.notation,
+                self.number_locale,
+                self.strict_mode,
+            );
+
+            let command_result: Result<String, String> =
+                handler.get_mut().unwrap_or_else(PoisonError::into_inner)(&mut context, arguments);
+
+            // Functions may have been modified by command
+            self.cached_expansions().clear();
+
+            return command_result;
+        }
+
+        match (name, arguments) {
+            ("save", [snapshot_name]) => {
+                self.snapshots
+                    .insert(String::from(*snapshot_name), self.snapshot());
+
+                return Ok(format!("snapshot {} saved", snapshot_name));
+            }
+            ("snapshots", []) => {
+                let mut snapshot_names: Vec<&str> =
+                    self.snapshots.keys().map(|name| name.as_str()).collect();
+
+                snapshot_names.sort_unstable();
+
+                return Ok(format!("snapshots = {}", snapshot_names.join(", ")));
+            }
+            ("at", [snapshot_name, expression_words @ ..]) if !expression_words.is_empty() => {
+                let snapshot: &CalculatorState = self
+                    .snapshots
+                    .get(*snapshot_name)
+                    .ok_or_else(|| format!("The snapshot {} is not saved", snapshot_name))?;
+
+                let expression_str: String = self
+                    .number_locale
+                    .delocalize_expression(expression_words.join(" ").as_str());
+
+                let value: f64 = self.evaluate_at(snapshot, expression_str.as_str())?;
+
+                return Ok(format!(
+                    "{} at {} = {}",
+                    expression_words.join(" "),
+                    snapshot_name,
+                    self.format_value(&Value::Number(value))
+                ));
+            }
+            _ => {}
+        }
+
+        let notation: Notation = match (name, arguments) {
+            ("sci", []) => Notation::Scientific,
+            ("eng", []) => Notation::Engineering,
+            ("auto", []) => Notation::Auto,
+            ("fix", [decimals]) => match decimals.parse::<usize>() {
+                Ok(decimals) if decimals <= command::MAX_FIXED_DECIMALS => {
+                    Notation::Fixed(decimals)
+                }
+                Ok(_) => {
+                    return Err(format!(
+                        "The number of decimals {} is too large (maximal number is {})",
+                        decimals,
+                        command::MAX_FIXED_DECIMALS
+                    ))
+                }
+                Err(_) => return Err(format!("The number of decimals {} is not valid", decimals)),
+            },
+            ("help", []) => {
+                return Ok(format!("help topics = {}", command::HELP_TOPICS.join(", ")));
+            }
+            ("help", [topic]) => {
+                return match self.help_entries(topic) {
+                    Some(entries) => Ok(command::write_help(topic, entries.as_slice())),
+                    None => Err(format!(
+                        "The help topic {} is unknown, available topics are {}",
+                        topic,
+                        command::HELP_TOPICS.join(", ")
+                    )),
+                };
+            }
+            ("results", []) => {
+                let mut results_str: String = String::from("results:");
+
+                for (number, value) in self.results() {
+                    results_str.push_str(
+                        format!("\n  %{} = {}", number, self.format_value(value)).as_str(),
+                    );
+                }
+
+                return Ok(results_str);
+            }
+            ("preview", expression_words) if !expression_words.is_empty() => {
+                let preview: Preview = self.preview(expression_words.join(" ").as_str())?;
+
+                let mut preview_str: String = format!(
+                    "preview of {}:\n  new {}",
+                    preview.name,
+                    self.write_preview_definition(&preview.name, &preview.definition)
+                );
+
+                if let Some(previous_definition) = &preview.previous_definition {
+                    preview_str.push_str(
+                        format!(
+                            "\n  previous {}",
+                            self.write_preview_definition(&preview.name, previous_definition)
+                        )
+                        .as_str(),
+                    );
+                }
+
+                if !preview.affected.is_empty() {
+                    preview_str.push_str(
+                        format!("\n  affected = {}", preview.affected.join(", ")).as_str(),
+                    );
+                }
+
+                return Ok(preview_str);
+            }
+            ("metrics", [function_name]) => {
+                let metrics: FunctionMetrics = self
+                    .function_metrics(function_name)
+                    .ok_or_else(|| format!("The function {} is not defined", function_name))?;
+
+                return Ok(format!(
+                    "metrics of {}:\n  body length = {} characters, {} tokens\n  parameters = {}\n  referenced functions = {}\n  referenced variables = {}\n  referencing functions = {}",
+                    function_name,
+                    metrics.body_length,
+                    metrics.token_count,
+                    metrics.parameter_count,
+                    metrics.referenced_functions,
+                    metrics.referenced_variables,
+                    metrics.reverse_references
+                ));
+            }
+            (
+                "sci" | "eng" | "auto" | "fix" | "save" | "snapshots" | "at" | "help" | "results"
+                | "metrics" | "preview",
+                _,
+            ) => {
+                return Err(format!(
+                    "The number of arguments of command :{} is not valid",
+                    name
+                ))
+            }
+            _ => {
+                let mut names: Vec<&str> = command::BUILTIN_COMMANDS.to_vec();
+                names.extend(self.commands.keys().map(|name| name.as_str()));
+                names.sort_unstable();
+                names.dedup();
+
+                return Err(format!(
+                    "The command :{} is unknown, available commands are :{}",
+                    name,
+                    names.join(", :")
+                ));
+            }
+        };
+
+        self.set_notation(notation);
+
+        let notation_str: String = match notation {
+            Notation::Auto => String::from("auto"),
+            Notation::Scientific => String::from("scientific"),
+            Notation::Engineering => String::from("engineering"),
+            Notation::Fixed(decimals) => format!("fixed {}", decimals),
+            Notation::Significant(figures) => format!("significant {}", figures),
+        };
+
+        return Ok(format!("notation = {}", notation_str));
+    }
+
+    /// Write a definition given by a preview as result of its process would be written
+    #[cfg(feature = "commands")]
+    fn write_preview_definition(&self, name: &str, definition: &PreviewDefinition) -> String {
+        return match definition {
+            PreviewDefinition::Value(value) => format!("{} = {}", name, self.format_value(value)),
+            PreviewDefinition::Function(variables, definition) => format!(
+                "{}({}) = {}",
+                name,
+                variables.join(self.variable_separator().as_str()),
+                self.number_locale
+                    .localize_expression(self.written_definition(definition).as_ref())
+            ),
+        };
+    }
+
+    /// Get entries of a help topic, each entry is a syntax and its description, none if topic is unknown
+    ///
+    /// Entries are built from configuration of calculator and evaluator, so that help is always accurate,
+    /// they end with variables, functions or commands currently defined.
+    #[cfg(feature = "commands")]
+    fn help_entries(&self, topic: &str) -> Option<Vec<(String, String)>> {
+        let entry = |syntax: String, description: &str| -> (String, String) {
+            return (syntax, String::from(description));
+        };
+
+        let mut entries: Vec<(String, String)> = Vec::new();
+
+        match topic {
+            "variables" => {
+                entries.push(entry(
+                    String::from("name = expression"),
+                    "define a variable with value of expression",
+                ));
+                entries.push(entry(
+                    String::from("last"),
+                    "value of last processed raw expression",
+                ));
+                entries.push(entry(
+                    String::from("`name"),
+                    "keep name in expression instead of its value",
+                ));
+
+                let mut names: Vec<&String> = self.variables.keys().collect();
+                names.sort_unstable();
+
+                for name in names {
+                    entries.push((name.clone(), self.format_value(&self.variables[name])));
+                }
+            }
+            "functions" => {
+                entries.push(entry(
+                    format!("name: x{}y = expression", self.variable_separator()),
+                    "define a function of variables x and y",
+                ));
+                entries.push(entry(
+                    String::from("name: 2 = $1 * $2"),
+                    "define a function of positional variables",
+                ));
+                entries.push(entry(
+                    format!("name{}: x = expression", expression::FREEZE_MARKER),
+                    "define a function whose calls are expanded at definition",
+                ));
+                entries.push(entry(
+                    format!("name(1{}2)", self.variable_separator()),
+                    "call a function",
+                ));
+
+                let mut names: Vec<&String> = self.functions.keys().collect();
+                names.sort_unstable();
+
+                for name in names {
+                    let (variables, definition) = &self.functions[name];
+
+                    entries.push((
+                        format!(
+                            "{}({})",
+                            name,
+                            variables.join(self.variable_separator().as_str())
+                        ),
+                        self.written_definition(definition).into_owned(),
+                    ));
+                }
+            }
+            "commands" => {
+                for (usage, description) in command::BUILTIN_COMMAND_USAGES {
+                    entries.push(entry(String::from(usage), description));
+                }
+
+                let mut names: Vec<&String> = self.commands.keys().collect();
+                names.sort_unstable();
+
+                for name in names {
+                    entries.push(entry(
+                        format!(":{}", name),
+                        "command registered by frontend",
+                    ));
+                }
+            }
+            "operators" => {
+                entries = self.evaluator.syntax_help();
+
+                if entries.is_empty() {
+                    entries.push(entry(
+                        String::from("..."),
+                        "operators and functions are provided by evaluator, see its documentation",
+                    ));
+                }
+            }
+            _ => return None,
+        }
+
+        return Some(entries);
+    }
+
+    /// Evaluate an expanded expression with evaluator, its error is written in error message
+    ///
+    /// A syntactically empty expression is rejected without calling evaluator.
+    fn evaluate_value(&self, expression_str: &str) -> Result<Value, Failure> {
+        return self.evaluate_value_with_count(expression_str, true);
+    }
+
+    /// Evaluate an expanded expression with evaluator as `evaluate_value`,
+    /// counting evaluation only if `count` is true
+    fn evaluate_value_with_count(
+        &self,
+        expression_str: &str,
+        count: bool,
+    ) -> Result<Value, Failure> {
+        if is_syntactically_empty(expression_str) {
+            return Err(self.empty_expansion_failure(expression_str));
+        }
+
+        if count {
+            self.evaluation_count.fetch_add(1, Ordering::Relaxed);
+        }
+
+        return self
+            .evaluator
+            .evaluate(expression_str)
+            .map_err(|error| Failure::from(EvaluatorError::new(error)));
+    }
+
+    /// Write result of a variable according to result template
+    fn format_result(&self, name: &str, value: &Value) -> String {
+        return self
+            .result_template
+            .replace("{name}", name)
+            .replace("{value}", self.format_value(value).as_str());
+    }
+
+    /// Write value of a result according to notation and locale
+    fn format_value(&self, value: &Value) -> String {
+        return match value {
+            Value::Number(number) => self.format_number(*number),
+            Value::Vector(elements) => {
+                value::format_vector(elements, |element| self.format_number(*element))
+            }
+            #[cfg(feature = "complex")]
+            Value::Complex(complex) => value::format_complex(
+                &num_complex::Complex64::new(
+                    self.normalize_number(complex.re),
+                    self.normalize_number(complex.im),
+                ),
+                |part| self.format_number(part),
+            ),
+            _ => self.number_locale.format_value(value),
+        };
+    }
+
+    /// Write a number of a result according to display epsilon, notation and locale
+    fn format_number(&self, number: f64) -> String {
+        return self
+            .number_locale
+            .localize_number(self.notation.format(self.normalize_number(number)).as_str());
+    }
+
+    /// Replace a number by zero if it is negative zero or its magnitude is below display epsilon
+    fn normalize_number(&self, number: f64) -> f64 {
+        return match self.display_epsilon {
+            Some(display_epsilon) if number.abs() < display_epsilon || number == 0.0 => 0.0,
+            _ => number,
+        };
+    }
+
+    /// Get number of times evaluator has been called since construction or last reset
+    ///
+    /// Each raw or variable expression processed, each call of `evaluate` or `call_function`
+    /// counts one evaluation, function definitions are not evaluated thus not counted.
+    pub fn evaluation_count(&self) -> u64 {
+        return self.evaluation_count.load(Ordering::Relaxed);
+    }
+
+    /// Reset number of times evaluator has been called
+    pub fn reset_evaluation_count(&mut self) {
+        self.evaluation_count = AtomicU64::new(0);
+    }
+
+    /// Get number of function calls replaced in last processed expression
+    ///
+    /// It is reset by each processed expression, see `Limits::max_function_expansions` bounding it.
+    pub fn expansion_count(&self) -> usize {
+        return self.expansion_count;
+    }
+
+    /// Get expansion of last processed raw or variable expression
+    ///
+    /// It is the expression given to evaluator, after replacement of functions and variables,
+    /// none if no raw or variable expression has been processed.
+    pub fn last_expanded(&self) -> Option<&str> {
+        return self.last_expanded.as_deref();
+    }
+
+    /// Process an expression whose numbers are written with canonical convention
+    fn process_canonical(&mut self, expression_str: &str) -> Result<ProcessOutput, Failure> {
+        if let Some(declaration) = expression::keyword_declaration(expression_str, "slider") {
+            return self.process_slider(declaration);
+        }
+
+        if let Some(declaration) = expression::keyword_declaration(expression_str, "reference") {
+            return self.process_reference(declaration);
+        }
+
+        if let Some(declaration) = expression::keyword_declaration(expression_str, "lazy") {
+            return self.process_lazy(declaration);
+        }
+
+        #[cfg(feature = "constants")]
+        if let Some(declaration) = expression::keyword_declaration(expression_str, "const") {
+            return self.process_constant(declaration);
+        }
+
+        self.expansion_count = 0;
+
+        let evaluation: InputEvaluation = self.evaluate_input(expression_str, true)?;
+        let warnings: Vec<String> = evaluation.warnings;
+        self.expansion_count = evaluation.expansion_count;
+
+        let output: ProcessOutput = match evaluation.input {
+            EvaluatedInput::Raw(raw_expression, value) => {
+                let mut raw_expression_result: String = self.format_result("last", &value);
+
+                if self.echo_expansion {
+                    raw_expression_result.push_str(
+                        format!("    [{}]", self.truncate_expansion(&raw_expression)).as_str(),
+                    );
+                }
+
+                // A reference or a lazy variable of the same name is replaced, as by a variable definition
+                for name in self.result_names() {
+                    self.references.remove(&name);
+                    self.lazy_definitions.remove(&name);
+                    self.variables.insert(name, value.clone());
+                }
+
+                self.results.push(value.clone());
+
+                if self.autoname {
+                    self.autoname_count += 1;
+                }
+                self.evict_results();
+
+                ProcessOutput {
+                    result: raw_expression_result,
+                    value: Some(value),
+                    expansion: Some(raw_expression),
+                    warnings,
+                }
+            }
+            EvaluatedInput::Variable(name, definition, value, lazy_definition) => {
+                let variable_result: String = self.format_result(name.as_str(), &value);
+                self.references.remove(&name);
+
+                match lazy_definition {
+                    Some(lazy_definition) => {
+                        self.lazy_definitions.insert(name.clone(), lazy_definition)
+                    }
+                    None => self.lazy_definitions.remove(&name),
+                };
+
+                self.variables.insert(name, value.clone());
+
+                ProcessOutput {
+                    result: variable_result,
+                    value: Some(value),
+                    expansion: Some(definition),
+                    warnings,
+                }
+            }
+            EvaluatedInput::Function(name, variables, definition) => {
+                let function_result: String = format!(
+                    "{}({}) = {}",
+                    name,
+                    variables.join(self.variable_separator().as_str()),
+                    self.number_locale
+                        .localize_expression(self.written_definition(&definition).as_ref())
+                );
+
+                self.functions.insert(name.clone(), (variables, definition));
+                self.invalidate_expansions(name.as_str());
+
+                ProcessOutput {
+                    result: function_result,
+                    value: None,
+                    expansion: None,
+                    warnings,
+                }
+            }
+        };
+
+        if let Some(expansion) = &output.expansion {
+            self.last_expanded = Some(expansion.clone());
+        }
+
+        return Ok(output);
+    }
+
+    /// Check, expand and evaluate an expression whose numbers are written with canonical convention,
+    /// as processed by `process` but without modifying calculator, with warnings about it
+    ///
+    /// It is shared by `process` and `preview`, the latter not counting evaluation if `count` is false.
+    fn evaluate_input(
+        &self,
+        expression_str: &str,
+        count: bool,
+    ) -> Result<InputEvaluation, Failure> {
+        self.check_limits(expression_str)?;
+        validation::check_argument_separator(expression_str, self.argument_separator)?;
+
+        if self.strict_mode {
+            validation::validate(expression_str)?;
+        }
+
+        let precision_warnings: Vec<String> = self.precision_warnings(expression_str)?;
+
+        let mut expression: Expression = Expression::with_identifier_predicate(
+            expression_str,
+            self.argument_separator,
+            self.identifier_predicate,
+        );
+
+        validation::check_reserved_names(&expression, self.evaluator.reserved_names())?;
+
+        // Variables of a function are validated before substitution, where they shadow other variables
+        if let Expression::Function(name, variables, definition) = &expression {
+            let name: &str = name
+                .strip_suffix(expression::FREEZE_MARKER)
+                .map_or(name.as_str(), str::trim_end);
+
+            validation::check_function_variables_with_predicate(
+                name,
+                variables,
+                definition,
+                self.identifier_predicate,
+            )?;
+        }
+
+        for lexeme in token::lex(expression_str) {
+            self.referenced_result(&expression_str[lexeme.range])?;
+        }
+
+        if self.strict_mode {
+            validation::check_definition_name_with_predicate(
+                &expression,
+                self.identifier_predicate,
+            )?;
+
+            if let Some(name) = expression.uncalled_functions(&self.functions).first() {
+                return Err(Failure::from(format!(
+                    "The function {} is referenced without call, its arguments between parenthesis are missing",
+                    name
+                )));
+            }
+        }
+
+        let lazy_definition: Option<String> = match &expression {
+            Expression::Variable(name, definition) if self.lazy_variables => {
+                let mut lazy_expression: Expression =
+                    Expression::Variable(name.clone(), definition.clone());
+                self.replace_functions(&mut lazy_expression)?;
+
+                Some(String::from(lazy_expression.definition()))
+            }
+            _ => None,
+        };
+
+        let statistics: ExpansionStatistics = self.substitute(&mut expression, true)?;
+
+        if let Expression::Raw(expansion) | Expression::Variable(_, expansion) = &expression {
+            if is_syntactically_empty(expansion) {
+                return Err(self.empty_expansion_failure(expression_str));
+            }
+        }
+
+        let (input, warnings): (EvaluatedInput, Vec<String>) = match expression {
+            Expression::Raw(raw_expression) => {
+                #[cfg(feature = "constants")]
+                if let Some(name) = self
+                    .result_names()
+                    .into_iter()
+                    .find(|name| self.constants.contains(name))
+                {
+                    return Err(Failure::from(format!(
+                        "The variable {} is a constant, it cannot be redefined",
+                        name
+                    )));
+                }
+
+                let value: Value =
+                    self.evaluate_value_with_count(raw_expression.as_str(), count)?;
+
+                (EvaluatedInput::Raw(raw_expression, value), Vec::new())
+            }
+            Expression::Variable(name, definition) => {
+                #[cfg(feature = "constants")]
+                if self.constants.contains(&name) {
+                    return Err(Failure::from(format!(
+                        "The variable {} is a constant, it cannot be redefined",
+                        name
+                    )));
+                }
+
+                let value: Value = self.evaluate_value_with_count(definition.as_str(), count)?;
+                let range_warnings: Vec<String> = self.range_warnings(&name, &value)?;
+
+                (
+                    EvaluatedInput::Variable(name, definition, value, lazy_definition),
+                    range_warnings,
+                )
+            }
+            Expression::Function(name, variables, definition) => (
+                EvaluatedInput::Function(name, variables, definition),
+                Vec::new(),
+            ),
+        };
+
+        return Ok(InputEvaluation {
+            input,
+            expansion_count: statistics.expansion_count,
+            warnings: [precision_warnings, warnings].concat(),
+        });
+    }
+
+    /// Get warnings about numbers of expression which cannot be stored exactly as 64-bit float,
+    /// in strict precision the first one gives an error
+    fn precision_warnings(&self, expression_str: &str) -> Result<Vec<String>, String> {
+        let imprecise_numbers: Vec<(&str, f64)> =
+            validation::find_imprecise_numbers(expression_str);
+
+        if self.strict_precision {
+            if let Some((number_str, value)) = imprecise_numbers.first() {
+                return Err(format!(
+                    "The number {} cannot be stored exactly, it would be stored as {}",
+                    number_str, value
+                ));
+            }
+        }
+
+        return Ok(imprecise_numbers
+            .into_iter()
+            .map(|(number_str, value)| format!("The number {} is stored as {}", number_str, value))
+            .collect());
+    }
+
+    /// Build failure of an expression whose expansion is syntactically empty
+    ///
+    /// The first function whose definition is syntactically empty, called by expression or by
+    /// definitions of functions it calls, is identified as cause, else input itself is empty.
+    /// String-based API gives the fixed message of an empty expression, evaluator is never called.
+    fn empty_expansion_failure(&self, expression_str: &str) -> Failure {
+        let empty_definition_name: Option<String> =
+            self.empty_definition_name(expression_str, &mut HashSet::new());
+
+        let message: String = match &empty_definition_name {
+            Some(name) => format!(
+                "The expression is empty after substitution of function {}",
+                name
+            ),
+            None => String::from("The expression is empty"),
+        };
+
+        return Failure {
+            message,
+            legacy_message: empty_definition_name
+                .as_ref()
+                .map(|_| String::from("The expression is empty")),
+            cause: Some(Cause::EmptyExpansion(empty_definition_name)),
+        };
+    }
+
+    /// Get name of the first function whose definition is syntactically empty, called by text
+    /// or by definitions of functions it calls, functions of visited names being skipped
+    fn empty_definition_name(&self, text: &str, visited: &mut HashSet<String>) -> Option<String> {
+        for lexeme in token::lex(text) {
+            let name: &str = &text[lexeme.range];
+
+            let definition: &str = match self.functions.get(name) {
+                Some((_, definition)) if visited.insert(String::from(name)) => definition,
+                _ => continue,
+            };
+
+            if is_syntactically_empty(definition) {
+                return Some(String::from(name));
+            }
+
+            if let Some(empty_name) = self.empty_definition_name(definition, visited) {
+                return Some(empty_name);
+            }
+        }
+
+        return None;
+    }
+
+    /// Export variables, references and functions of calculator in a script
+    ///
+    /// Each line of script is a variable definition `name = value`, a constant declaration
+    /// `const name = value`, a lazy variable declaration
+    /// `lazy name = definition`, a reference declaration `reference name = target` or a function
+    /// definition `name: variables = definition`. Variables are written first and sorted by name,
+    /// then lazy variables and references after the ones they depend on.
+    /// Functions are written after the functions they call, so that script replays cleanly,
+    /// and sorted by name otherwise. Functions depending on a cycle of calls, as `f` calling `g`
+    /// calling `f`, are written last, after a comment line listing them.
+    /// Numbers are always written with canonical convention, whatever the number locale of calculator,
+    /// but variables of functions are separated by argument separator of calculator.
+    pub fn export_script(&self) -> String {
+        let mut variable_names: Vec<&String> = self
+            .variables
+            .keys()
+            .filter(|name| !self.lazy_definitions.contains_key(*name))
+            .collect();
+        variable_names.sort_unstable();
+
+        let (function_names, cyclic_function_names): (Vec<String>, Vec<String>) =
+            self.function_export_order();
+
+        let mut script: String = String::new();
+
+        for name in variable_names {
+            let variable_line: String = match self.variable_ranges.get(name) {
+                Some(range) => format!(
+                    "slider {} = {} in [{}, {}]{}\n",
+                    name,
+                    self.variables[name],
+                    range.min,
+                    range.max,
+                    range
+                        .step
+                        .map_or(String::new(), |step| format!(" step {}", step))
+                ),
+                None if cfg!(feature = "constants") && self.constants.contains(name) => {
+                    format!("const {} = {}\n", name, self.variables[name])
+                }
+                None => format!("{} = {}\n", name, self.variables[name]),
+            };
+
+            script.push_str(variable_line.as_str());
+        }
+
+        for name in self.dependent_export_order() {
+            let dependent_line: String = match self.references.get(name) {
+                Some(target) => format!("reference {} = {}\n", name, target),
+                None => format!("lazy {} = {}\n", name, self.lazy_definitions[name]),
+            };
+
+            script.push_str(dependent_line.as_str());
+        }
+
+        for name in function_names.iter() {
+            script.push_str(self.exported_function(name).as_str());
+        }
+
+        if !cyclic_function_names.is_empty() {
+            script.push_str(
+                format!(
+                    "# functions depending on a cycle of calls: {}\n",
+                    cyclic_function_names.join(", ")
+                )
+                .as_str(),
+            );
+
+            for name in cyclic_function_names.iter() {
+                script.push_str(self.exported_function(name).as_str());
+            }
+        }
+
+        return script;
+    }
+
+    /// Get line of exported script defining a function
+    fn exported_function(&self, name: &str) -> String {
+        let (variables, definition) = &self.functions[name];
+
+        return format!(
+            "{}: {} = {}\n",
+            name,
+            variables.join(self.variable_separator().as_str()),
+            self.written_definition(definition)
+        );
+    }
+
+    /// Get order of lazy variables and references in exported script, each one after the lazy variables
+    /// and references it depends on and by name otherwise, then the ones depending on a cycle
+    fn dependent_export_order(&self) -> Vec<&String> {
+        let mut remaining_names: Vec<&String> = self
+            .references
+            .keys()
+            .chain(self.lazy_definitions.keys())
+            .collect();
+        remaining_names.sort_unstable();
+
+        let dependencies = |name: &str| -> Vec<&str> {
+            if let Some(target) = self.references.get(name) {
+                return vec![target.as_str()];
+            }
+
+            let definition: &str = self.lazy_definitions[name].as_str();
+
+            return token::lex(definition)
+                .into_iter()
+                .filter(|lexeme| lexeme.kind == token::LexemeKind::Identifier)
+                .map(|lexeme| &definition[lexeme.range])
+                .filter(|dependency| *dependency != name)
+                .collect();
+        };
+
+        let mut ordered_names: Vec<&String> = Vec::new();
+
+        while !remaining_names.is_empty() {
+            let (ready_names, waiting_names): (Vec<&String>, Vec<&String>) =
+                remaining_names.iter().partition(|name| {
+                    return dependencies(name.as_str()).into_iter().all(|dependency| {
+                        return !(self.references.contains_key(dependency)
+                            || self.lazy_definitions.contains_key(dependency))
+                            || ordered_names
+                                .iter()
+                                .any(|ordered_name| *ordered_name == dependency);
+                    });
+                });
+
+            if ready_names.is_empty() {
+                ordered_names.extend(waiting_names);
+                break;
+            }
+
+            ordered_names.extend(ready_names);
+            remaining_names = waiting_names;
+        }
+
+        return ordered_names;
+    }
+
+    /// Get order of functions in exported script, each function after the functions it calls
+    /// and by name otherwise, then functions depending on a cycle of calls sorted by name
+    fn function_export_order(&self) -> (Vec<String>, Vec<String>) {
+        let mut dependency_graph: HashMap<String, Vec<String>> = self.function_dependency_graph();
+        let mut ordered_names: Vec<String> = Vec::with_capacity(dependency_graph.len());
+
+        loop {
+            // Functions whose called functions are all written, they are written by name
+            let mut ready_names: Vec<String> = dependency_graph
+                .iter()
+                .filter(|(_, dependencies)| {
+                    return dependencies
+                        .iter()
+                        .all(|dependency| !dependency_graph.contains_key(dependency));
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+
+            if ready_names.is_empty() {
+                break;
+            }
+
+            ready_names.sort_unstable();
+
+            for name in ready_names.iter() {
+                dependency_graph.remove(name);
+            }
+
+            ordered_names.extend(ready_names);
+        }
+
+        let mut cyclic_names: Vec<String> = dependency_graph.into_keys().collect();
+        cyclic_names.sort_unstable();
+
+        return (ordered_names, cyclic_names);
+    }
+
+    /// Export golden tests recording expansion and value of raw expressions
+    ///
+    /// Each expression gives a `[[case]]` table of a small TOML file, with its `expansion`
+    /// and `value`, or its `error` if expression cannot be evaluated. The calculator is not modified.
+    /// Goldens are checked against a calculator loaded from definitions with `testing::run_golden`,
+    /// thus editing definitions changing result of a case is caught.
+    pub fn export_golden_tests(&self, cases: &[&str]) -> String {
+        let mut golden: String = String::from("# golden tests of calculator\n");
+
+        for case in cases.iter() {
+            golden.push_str(format!("\n[[case]]\nexpression = {}\n", golden::quote(case)).as_str());
+
+            let outcome: Result<(String, f64), String> = self
+                .expand(case)
+                .and_then(|expansion| Ok((expansion, self.evaluate(case)?)));
+
+            let recorded_outcome: String = match outcome {
+                Ok((expansion, value)) => format!(
+                    "expansion = {}\nvalue = {}\n",
+                    golden::quote(expansion.as_str()),
+                    value
+                ),
+                Err(error) => format!("error = {}\n", golden::quote(error.as_str())),
+            };
+
+            golden.push_str(recorded_outcome.as_str());
+        }
+
+        return golden;
+    }
+
+    /// Remove all variables and functions, including `last` and disabled functions
+    ///
+    /// Configuration of calculator, history of processed inputs and snapshots are kept.
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+        self.disabled_functions.clear();
+        self.pure_functions.clear();
+        self.references.clear();
+        self.lazy_definitions.clear();
+        self.variable_ranges.clear();
+        self.constants.clear();
+        self.autoname_count = 0;
+        self.cached_expansions().clear();
+        self.last_expanded = None;
+    }
+
+    /// Clear variables and functions, then load a script defining default ones
+    ///
+    /// It behaves as `clear` followed by `load_script`, thus if error occurs in script,
+    /// only definitions of lines before the error remain.
+    pub fn reset_to_defaults(&mut self, script: &str) -> Result<(), String> {
+        self.clear();
+
+        return self.load_script(script);
+    }
+
+    /// Load a script, as one given by `export_script`, processing each line
+    ///
+    /// Empty lines and comment lines starting with `#` are skipped.
+    /// Numbers are read with canonical convention, whatever the number locale of calculator.
+    /// If error occurs, the process stops and the error message indicates the line number.
+    /// With strict duplicates, see `set_strict_duplicates`, a variable or function defined
+    /// twice gives an error indicating both lines, before the second definition is processed.
+    pub fn load_script(&mut self, script: &str) -> Result<(), String> {
+        let mut definition_lines: HashMap<(String, bool), usize> = HashMap::new(); // line of definitions, key is (name, is function)
+
+        for (id, line) in script.lines().enumerate() {
+            if matches!(
+                expression::input_kind(line),
+                InputKind::Empty | InputKind::Comment
+            ) {
+                continue;
+            }
+
+            self.check_expression_length(line)
+                .map_err(|failure| format!("Line {}: {}", id + 1, failure))?;
+
+            if self.strict_duplicates {
+                if let Some((name, is_function)) = self.defined_name(line) {
+                    if let Some(first_line) = definition_lines.get(&(name.clone(), is_function)) {
+                        return Err(format!(
+                            "Line {}: The {} {} is already defined at line {}",
+                            id + 1,
+                            if is_function { "function" } else { "variable" },
+                            name,
+                            first_line
+                        ));
+                    }
+
+                    definition_lines.insert((name, is_function), id + 1);
+                }
+            }
+
+            self.sanitize(line)
+                .and_then(|sanitized_line| self.process_canonical(sanitized_line.as_ref()))
+                .map_err(|error| format!("Line {}: {}", id + 1, error))?;
+        }
+
+        return Ok(());
+    }
+
+    /// Get name of variable or function defined by a line of script and if it is a function
+    fn defined_name(&self, line: &str) -> Option<(String, bool)> {
+        let definition: &str = match expression::keyword_declaration(line, "slider") {
+            Some(declaration) => declaration
+                .rsplit_once(" in ")
+                .map_or(declaration, |(definition, _)| definition),
+            None => expression::keyword_declaration(line, "reference")
+                .or_else(|| expression::keyword_declaration(line, "lazy"))
+                .or_else(|| expression::keyword_declaration(line, "const"))
+                .unwrap_or(line),
+        };
+
+        let mut expression: Expression = Expression::with_identifier_predicate(
+            definition,
+            self.argument_separator,
+            self.identifier_predicate,
+        );
+        expression.remove_freeze_marker();
+
+        return match expression {
+            Expression::Variable(name, _) => Some((name, false)),
+            Expression::Function(name, _, _) => Some((name, true)),
+            Expression::Raw(_) => None,
+        };
+    }
+
+    /// Reject or strip unsafe characters of an input according to policy of calculator
+    fn sanitize<'a>(&self, input: &'a str) -> Result<Cow<'a, str>, Failure> {
+        let (character, column): (char, usize) = match validation::find_unsafe_character(input) {
+            Some(unsafe_character) => unsafe_character,
+            None => return Ok(Cow::Borrowed(input)),
+        };
+
+        return match self.unsafe_character_policy {
+            UnsafeCharacterPolicy::Reject => Err(Failure {
+                message: format!(
+                    "The character U+{:04X} at column {} is not allowed",
+                    u32::from(character),
+                    column
+                ),
+                cause: Some(Cause::UnsafeCharacter(character, column)),
+                legacy_message: None,
+            }),
+            UnsafeCharacterPolicy::Strip => Ok(Cow::Owned(
+                input
+                    .chars()
+                    .filter(|c| !validation::is_unsafe_character(*c))
+                    .collect(),
+            )),
+        };
+    }
+
+    /// Get definition of a function as it is written in results and exported scripts
+    fn written_definition<'a>(&self, definition: &'a str) -> Cow<'a, str> {
+        if self.pretty_definitions {
+            return Cow::Owned(Expression::Raw(String::from(definition)).pretty());
+        }
+
+        return Cow::Borrowed(definition);
+    }
+
+    /// Get separator written between variables of a function, as `, ` or `; `
+    fn variable_separator(&self) -> String {
+        return format!("{} ", self.argument_separator);
+    }
+
+    /// Truncate expansion to echo it in result string
+    fn truncate_expansion(&self, expansion: &str) -> String {
+        if expansion.chars().count() <= self.echo_expansion_length {
+            return String::from(expansion);
+        }
+
+        let mut truncated_expansion: String =
+            expansion.chars().take(self.echo_expansion_length).collect();
+
+        truncated_expansion.push('…');
+
+        return truncated_expansion;
+    }
+
+    /// Evaluate an expression given variables and functions in argument
+    fn evaluate_with_definitions(
+        &self,
+        expression_str: &str,
+        variables: &HashMap<String, Value>,
+        functions: &HashMap<String, (Vec<String>, String)>,
+    ) -> Result<f64, String> {
+        self.check_limits(expression_str)?;
+        validation::check_argument_separator(expression_str, self.argument_separator)?;
+
+        let mut expression: Expression = Expression::with_identifier_predicate(
+            expression_str,
+            self.argument_separator,
+            self.identifier_predicate,
+        );
+
+        if let Expression::Function(name, _, _) = expression {
+            return Err(format!(
+                "The definition of function {} cannot be evaluated",
+                name
+            ));
+        }
+
+        expression.replace_functions_with_limit(
+            functions,
+            self.argument_separator,
+            self.minimal_parentheses,
+            0,
+            self.expansion_limits(),
+            self.identifier_predicate,
+        )?;
+        self.replace_variables(&mut expression, variables);
+
+        return self.evaluate_value(expression.definition())?.try_to_f64();
+    }
+
+    /// Evaluate an expression without modifying the calculator
+    ///
+    /// The expression is expanded as in `process` and evaluated, but nothing is stored,
+    /// in particular the variable `last` is not updated. For a variable definition,
+    /// its value is returned but the variable is not defined.
+    /// The value is converted to 64-bit float, a boolean gives 1 or 0 and a vector gives an error.
+    pub fn evaluate(&self, expression_str: &str) -> Result<f64, String> {
+        return self.evaluate_with_definitions(
+            expression_str,
+            &self.variables,
+            &self.enabled_functions(),
+        );
+    }
+
+    /// Evaluate an expression as `evaluate` and check if its value is finite
+    ///
+    /// The Result output contains the value and true if it is neither infinite nor NaN.
+    pub fn evaluate_checked(&self, expression_str: &str) -> Result<(f64, bool), String> {
+        let value: f64 = self.evaluate(expression_str)?;
+
+        return Ok((value, value.is_finite()));
+    }
+
+    /// Evaluate an expression with temporary variables without modifying the calculator
+    ///
+    /// The overrides are given in argument through HashMap where pair (key, value)
+    /// correspond respectively to name and value of variable. They take precedence
+    /// over variables of calculator only during this evaluation.
+    pub fn evaluate_with(
+        &self,
+        expression_str: &str,
+        overrides: &HashMap<String, f64>,
+    ) -> Result<f64, String> {
+        return self
+            .evaluate_batch_with(&[expression_str], overrides)
+            .remove(0);
+    }
+
+    /// Evaluate several expressions with the same temporary variables without modifying the calculator
+    ///
+    /// It behaves as `evaluate_with` for each expression, the result of each evaluation
+    /// is given in the same order as expressions.
+    pub fn evaluate_batch_with(
+        &self,
+        expressions: &[&str],
+        overrides: &HashMap<String, f64>,
+    ) -> Vec<Result<f64, String>> {
+        let mut variables: HashMap<String, Value> = self.variables.clone();
+
+        overrides.iter().for_each(|(name, value)| {
+            variables.insert(name.clone(), Value::Number(*value));
+        });
+
+        let functions = self.enabled_functions();
+
+        return expressions
+            .iter()
+            .map(|expression_str| {
+                return self.evaluate_with_definitions(expression_str, &variables, &functions);
+            })
+            .collect();
+    }
+
+    /// Take a snapshot of calculator state, i.e. its variables, functions, references, lazy variables and constants
+    pub fn snapshot(&self) -> CalculatorState {
+        return CalculatorState {
+            variables: self.variables.clone(),
+            functions: self.functions.clone(),
+            references: self.references.clone(),
+            lazy_definitions: self.lazy_definitions.clone(),
+            constants: self.constants.clone(),
+            variable_ranges: self.variable_ranges.clone(),
+        };
+    }
+
+    /// Compute differences from a snapshot to current state of calculator, as changes made by a replayed script
+    pub fn diff_since(&self, snapshot: &CalculatorState) -> StateDiff {
+        return snapshot.diff(&self.snapshot());
+    }
+
+    /// Restore a state of calculator, replacing its variables, functions, references, lazy variables,
+    /// constants and ranges of variables
+    ///
+    /// State is checked first, see `validation::check_state`, thus a state with a name
+    /// which is not valid, with an unsafe character or with a dangling reference gives an error
+    /// and calculator is unchanged.
+    pub fn restore(&mut self, state: CalculatorState) -> Result<(), String> {
+        validation::check_state(&state, self.identifier_predicate)?;
+
+        self.variables = state.variables;
+        self.functions = state.functions;
+        self.references = state.references;
+        self.lazy_definitions = state.lazy_definitions;
+        self.constants = state.constants;
+        self.variable_ranges = state.variable_ranges;
+        self.cached_expansions().clear();
+
+        return Ok(());
+    }
+
+    /// Save variables and functions of calculator in compact binary format, see `binary`
+    pub fn save_binary<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        return binary::write(&self.snapshot(), w);
+    }
+
+    /// Load variables and functions saved by `save_binary`, replacing current ones as `restore`
+    ///
+    /// Whole state is read and checked before being restored, so calculator is unchanged if data is invalid.
+    pub fn load_binary<R: std::io::Read>(&mut self, r: R) -> std::io::Result<()> {
+        return self
+            .restore(binary::read(r)?)
+            .map_err(|message| std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+    }
+
+    /// Construct a new calculator with evaluator given in argument and a copy of definitions and settings of this one
+    ///
+    /// Definitions, history, results and all settings are copied, as argument separator, number locale,
+    /// notation or limits. Registered commands and observers are not copied, since they cannot be cloned,
+    /// and count of evaluations and cache of expansions of pure functions start empty.
+    pub fn with_new_evaluator<NewEvaluator>(
+        &self,
+        evaluator: NewEvaluator,
+    ) -> Calculator<NewEvaluator>
+    where
+        NewEvaluator: EvaluateValue,
+    {
+        return Calculator {
+            evaluator,
+            variables: self.variables.clone(),
+            functions: self.functions.clone(),
+            echo_expansion: self.echo_expansion,
+            echo_expansion_length: self.echo_expansion_length,
+            strict_mode: self.strict_mode,
+            number_locale: self.number_locale,
+            boolean_substitution: self.boolean_substitution,
+            last_expanded: self.last_expanded.clone(),
+            #[cfg(feature = "limits")]
+            limits: self.limits,
+            notation: self.notation,
+            disabled_functions: self.disabled_functions.clone(),
+            #[cfg(feature = "commands")]
+            commands: HashMap::new(),
+            #[cfg(feature = "history")]
+            history: self.history.clone(),
+            #[cfg(feature = "history")]
+            max_history: self.max_history,
+            #[cfg(feature = "observer")]
+            observers: Vec::new(),
+            #[cfg(feature = "commands")]
+            snapshots: self.snapshots.clone(),
+            argument_separator: self.argument_separator,
+            pretty_definitions: self.pretty_definitions,
+            evaluation_count: AtomicU64::new(0),
+            format: self.format,
+            display_epsilon: self.display_epsilon,
+            pure_functions: self.pure_functions.clone(),
+            expansion_cache: Mutex::new(HashMap::new()),
+            references: self.references.clone(),
+            results: self.results.clone(),
+            evicted_results: self.evicted_results,
+            max_results: self.max_results,
+            unsafe_character_policy: self.unsafe_character_policy,
+            identifier_predicate: self.identifier_predicate,
+            strict_precision: self.strict_precision,
+            lazy_definitions: self.lazy_definitions.clone(),
+            lazy_variables: self.lazy_variables,
+            default_undefined: self.default_undefined,
+            expansion_count: self.expansion_count,
+            variable_ranges: self.variable_ranges.clone(),
+            minimal_parentheses: self.minimal_parentheses,
+            autoname: self.autoname,
+            autoname_count: self.autoname_count,
+            constants: self.constants.clone(),
+            #[cfg(feature = "constants")]
+            env_lowercase: self.env_lowercase,
+            substitution_order: self.substitution_order,
+            strict_duplicates: self.strict_duplicates,
+            empty_input_policy: self.empty_input_policy,
+            result_template: self.result_template.clone(),
+        };
+    }
+
+    /// Evaluate an expression against a snapshot without modifying the calculator
+    ///
+    /// The expression is expanded with variables and functions of snapshot,
+    /// then evaluated with evaluator of calculator as in `evaluate`.
+    pub fn evaluate_at(
+        &self,
+        snapshot: &CalculatorState,
+        expression_str: &str,
+    ) -> Result<f64, String> {
+        return self.evaluate_with_definitions(
+            expression_str,
+            &snapshot.variables,
+            &snapshot.functions,
+        );
+    }
+
+    /// Expand an expression without evaluating it
+    ///
+    /// Functions and variables are replaced as in `process`, the Result output contains
+    /// the expression which would be given to evaluator. The calculator is not modified.
+    pub fn expand(&self, expression_str: &str) -> Result<String, String> {
+        self.check_limits(expression_str)?;
+        validation::check_argument_separator(expression_str, self.argument_separator)?;
+
+        let mut expression: Expression = Expression::with_identifier_predicate(
+            expression_str,
+            self.argument_separator,
+            self.identifier_predicate,
+        );
+
+        self.substitute(&mut expression, false)?;
+
+        return Ok(String::from(expression.definition()));
+    }
+
+    /// Substitute only some variables in an expression, without replacing functions nor evaluating it
+    ///
+    /// Variables whose names are given in argument are replaced by their value as in `process`,
+    /// other ones are left as is, thus with `x` and `y` defined, substituting `x` in `x + y` gives `2 + y`.
+    /// A given name which is not a defined variable gives an error.
+    pub fn substitute_some(&self, expression_str: &str, names: &[&str]) -> Result<String, String> {
+        self.check_limits(expression_str)?;
+
+        if let Some(name) = names
+            .iter()
+            .find(|name| !self.variables.contains_key(**name))
+        {
+            return Err(format!("The variable {} is not defined", name));
+        }
+
+        let mut expression: Expression = Expression::with_identifier_predicate(
+            expression_str,
+            self.argument_separator,
+            self.identifier_predicate,
+        );
+
+        expression.replace_variables_with(|name| {
+            if !names.contains(&name) {
+                return None;
+            }
+
+            return self
+                .variables
+                .get(name)
+                .map(|value| value.substitution(self.boolean_substitution));
+        });
+
+        return Ok(String::from(expression.definition()));
+    }
+
+    /// Analyze size and complexity of an expression after its expansion
+    ///
+    /// The expression is expanded as in `process` but it is not evaluated
+    /// and the calculator is not modified.
+    pub fn analyze(&self, expression_str: &str) -> Result<Analysis, String> {
+        self.check_limits(expression_str)?;
+        validation::check_argument_separator(expression_str, self.argument_separator)?;
+
+        let mut expression: Expression = Expression::with_identifier_predicate(
+            expression_str,
+            self.argument_separator,
+            self.identifier_predicate,
+        );
+
+        let statistics: ExpansionStatistics = self.replace_functions(&mut expression)?;
+        let substituted_variables: usize = expression.referenced_variables(&self.variables).len();
+
+        self.replace_variables(&mut expression, &self.variables);
+
+        let expansion: &str = expression.definition();
+
+        let operator_count: usize = token::lex(expansion)
+            .into_iter()
+            .filter(|lexeme| {
+                matches!(
+                    &expansion[lexeme.range.clone()],
+                    "+" | "-" | "*" | "/" | "^" | "%"
+                )
+            })
+            .count();
+
+        return Ok(Analysis {
+            expanded_length: expansion.len(),
+            function_expansions: statistics.expansion_count,
+            max_expansion_depth: statistics.max_depth,
+            substituted_variables,
+            operator_count,
+        });
+    }
+
+    /// Preview effect of an input without processing it
+    ///
+    /// The input is expanded and evaluated as in `process`, but nothing is stored,
+    /// thus variables, functions and results of calculator are not modified.
+    /// Affected definitions are the ones which would give another value after processing input:
+    /// lazy variables to recompute and references using a defined variable,
+    /// as well as functions using it as free variable or calling a defined function,
+    /// directly or through other functions.
+    pub fn preview(&self, input: &str) -> Result<Preview, String> {
+        let sanitized_input: Cow<str> = self.sanitize(input).map_err(|failure| failure.message)?;
+
+        let kind: InputKind = expression::input_kind(sanitized_input.as_ref());
+
+        if !matches!(
+            kind,
+            InputKind::Raw | InputKind::Variable | InputKind::Function
+        ) {
+            return Err(String::from(
+                "The input cannot be previewed, it is not an expression",
+            ));
+        }
+
+        let expression_str: String = self
+            .number_locale
+            .delocalize_expression(sanitized_input.as_ref());
+
+        let evaluation: InputEvaluation = self
+            .evaluate_input(expression_str.as_str(), false)
+            .map_err(|failure| failure.message)?;
+
+        return Ok(match evaluation.input {
+            EvaluatedInput::Raw(_, value) => Preview {
+                kind: InputKind::Raw,
+                name: String::from("last"),
+                definition: PreviewDefinition::Value(value),
+                previous_definition: self
+                    .variables
+                    .get("last")
+                    .cloned()
+                    .map(PreviewDefinition::Value),
+                affected: self.affected_definitions("last", false),
+            },
+            EvaluatedInput::Variable(name, _, value, _) => Preview {
+                kind: InputKind::Variable,
+                definition: PreviewDefinition::Value(value),
+                previous_definition: self
+                    .variables
+                    .get(&name)
+                    .cloned()
+                    .map(PreviewDefinition::Value),
+                affected: self.affected_definitions(&name, false),
+                name,
+            },
+            EvaluatedInput::Function(name, variables, definition) => Preview {
+                kind: InputKind::Function,
+                definition: PreviewDefinition::Function(variables, definition),
+                previous_definition: self.functions.get(&name).map(|(variables, definition)| {
+                    PreviewDefinition::Function(variables.clone(), definition.clone())
+                }),
+                affected: self.affected_definitions(&name, true),
+                name,
+            },
+        });
+    }
+
+    /// Get sorted names of stored definitions depending on a variable or a function, see `preview`
+    fn affected_definitions(&self, name: &str, is_function: bool) -> Vec<String> {
+        let mut affected: HashSet<String> = HashSet::new();
+        let mut affected_functions: Vec<String> = Vec::new();
+
+        if is_function {
+            affected_functions.push(String::from(name));
+        } else {
+            let is_referencing = |definition: &str| -> bool {
+                return token::lex(definition).into_iter().any(|lexeme| {
+                    lexeme.kind == token::LexemeKind::Identifier
+                        && &definition[lexeme.range] == name
+                });
+            };
+
+            for (lazy_name, lazy_definition) in self.lazy_definitions.iter() {
+                if lazy_name != name && is_referencing(lazy_definition) {
+                    affected.insert(lazy_name.clone());
+                }
+            }
+
+            for reference_name in self.references.keys() {
+                let mut target: &str = reference_name.as_str();
+
+                for _ in 0..self.references.len() {
+                    match self.references.get(target) {
+                        Some(next_target) if next_target == name => {
+                            affected.insert(reference_name.clone());
+                            break;
+                        }
+                        Some(next_target) => target = next_target.as_str(),
+                        None => break,
+                    }
+                }
+            }
+
+            let variable: HashMap<String, ()> = HashMap::from([(String::from(name), ())]);
+
+            for (function_name, (variables, definition)) in self.functions.iter() {
+                let function: Expression = Expression::Function(
+                    function_name.clone(),
+                    variables.clone(),
+                    definition.clone(),
+                );
+
+                if !function.referenced_variables(&variable).is_empty() {
+                    affected.insert(function_name.clone());
+                    affected_functions.push(function_name.clone());
+                }
+            }
+        }
+
+        let dependency_graph: HashMap<String, Vec<String>> = self.function_dependency_graph();
+
+        while let Some(function_name) = affected_functions.pop() {
+            for (caller, dependencies) in dependency_graph.iter() {
+                if dependencies.contains(&function_name)
+                    && caller != name
+                    && affected.insert(caller.clone())
+                {
+                    affected_functions.push(caller.clone());
+                }
+            }
+        }
+
+        let mut affected: Vec<String> = affected.into_iter().collect();
+        affected.sort_unstable();
+
+        return affected;
+    }
+
+    /// Get names of variables starting with given prefix, sorted in alphabetical order
+    pub fn variables_with_prefix(&self, prefix: &str) -> Vec<&str> {
+        return self.read_view().variables_with_prefix(prefix);
+    }
+
+    /// Get names of functions starting with given prefix, sorted in alphabetical order
+    pub fn functions_with_prefix(&self, prefix: &str) -> Vec<&str> {
+        return self.read_view().functions_with_prefix(prefix);
+    }
+
+    /// Count how many times each defined function is called in an expression
+    ///
+    /// Only call sites written in expression are counted, calls contained
+    /// in definition of called functions are not. The calculator is not modified.
+    pub fn call_counts(&self, expression_str: &str) -> HashMap<String, usize> {
+        return Expression::new(expression_str).call_counts(&self.functions);
+    }
+
+    /// Get dependency graph of functions defined by user
+    ///
+    /// Each function name is mapped to sorted names of defined functions called in its definition.
+    /// A function calling no other function is mapped to an empty list.
+    pub fn function_dependency_graph(&self) -> HashMap<String, Vec<String>> {
+        return self
+            .functions
+            .iter()
+            .map(|(name, (variables, definition))| {
+                let function: Expression =
+                    Expression::Function(name.clone(), variables.clone(), definition.clone());
+
+                let mut dependencies: Vec<String> =
+                    function.call_counts(&self.functions).into_keys().collect();
+
+                dependencies.sort_unstable();
+
+                return (name.clone(), dependencies);
+            })
+            .collect();
+    }
+
+    /// Get metrics of size and references of a function defined by user, none if function is not defined
+    ///
+    /// Referenced and referencing functions are the ones of `function_dependency_graph`,
+    /// without the function itself when it calls itself.
+    pub fn function_metrics(&self, name: &str) -> Option<FunctionMetrics> {
+        let (variables, definition) = self.functions.get(name)?;
+
+        let function: Expression =
+            Expression::Function(String::from(name), variables.clone(), definition.clone());
+
+        let dependency_graph: HashMap<String, Vec<String>> = self.function_dependency_graph();
+
+        let referenced_functions: usize = dependency_graph[name]
+            .iter()
+            .filter(|dependency| dependency.as_str() != name)
+            .count();
+
+        let reverse_references: usize = dependency_graph
+            .iter()
+            .filter(|(caller, dependencies)| {
+                caller.as_str() != name && dependencies.iter().any(|dependency| dependency == name)
+            })
+            .count();
+
+        return Some(FunctionMetrics {
+            body_length: definition.chars().count(),
+            token_count: token::lex(definition).len(),
+            parameter_count: variables.len(),
+            referenced_functions,
+            referenced_variables: function.referenced_variables(&self.variables).len(),
+            reverse_references,
+        });
+    }
+
+    /// Call a function defined by user with values of its variables given in argument
+    ///
+    /// The function call is expanded as in a processed expression, then evaluated.
+    /// The calculator is not modified, in particular the variable `last` is not updated.
+    /// The value is converted to 64-bit float, a boolean gives 1 or 0 and a vector gives an error.
+    pub fn call_function(&self, name: &str, arguments: &[f64]) -> Result<f64, String> {
+        if !self.functions.contains_key(name) {
+            return Err(format!("The function {} is not defined", name));
+        }
+
+        let arguments_str: Vec<String> = arguments
+            .iter()
+            .map(|argument| format!("{}", argument))
+            .collect();
+
+        let mut expression: Expression = Expression::new(
+            format!(
+                "{}({})",
+                name,
+                arguments_str.join(self.variable_separator().as_str())
+            )
+            .as_str(),
+        );
+
+        expression.replace_functions_with_limit(
+            &self.enabled_functions(),
+            self.argument_separator,
+            self.minimal_parentheses,
+            0,
+            self.expansion_limits(),
+            self.identifier_predicate,
+        )?;
+        self.replace_variables(&mut expression, &self.variables);
+
+        return match expression {
+            Expression::Raw(raw_expression) => {
+                self.evaluate_value(raw_expression.as_str())?.try_to_f64()
+            }
+            _ => Err(format!("The function name {} is not valid", name)),
+        };
+    }
+
+    /// Substitute some variables of a function defined by user, the other ones are left symbolic
+    ///
+    /// Each argument gives value of corresponding variable, none leaves variable as is,
+    /// thus with `f: x, y = x + y`, arguments `3` and none give `3 + y`. A compound argument
+    /// is put between parentheses as in expansion of a call. Functions called in definition
+    /// are not expanded and the calculator is not modified.
+    pub fn partial_apply(&self, name: &str, arguments: &[Option<&str>]) -> Result<String, String> {
+        let (variables, definition) = self
+            .functions
+            .get(name)
+            .ok_or_else(|| format!("The function {} is not defined", name))?;
+
+        if variables.len() != arguments.len() {
+            return Err(format!(
+                "The function {} has {} variable(s) but {} argument(s) are given",
+                name,
+                variables.len(),
+                arguments.len()
+            ));
+        }
+
+        return Ok(Expression::substitute_arguments(
+            definition,
+            variables,
+            arguments,
+            self.identifier_predicate,
+        ));
+    }
+
+    /// Sample a function of one variable defined by user at evenly spaced points of a range
+    ///
+    /// The range from `start` to `end` is split in `steps` intervals, thus `steps + 1` points
+    /// including both bounds are evaluated. The Result output contains pairs `(x, f(x))`
+    /// in order of points. Bounds must be finite. The function is called at each point
+    /// as with `call_function`, thus the calculator is not modified.
+    pub fn sample(
+        &self,
+        name: &str,
+        start: f64,
+        end: f64,
+        steps: usize,
+    ) -> Result<Vec<(f64, f64)>, String> {
+        match self.function_arity(name) {
+            Some(1) => {}
+            Some(_) => {
+                return Err(format!(
+                    "The function {} must have a single variable to be sampled",
+                    name
+                ))
+            }
+            None => return Err(format!("The function {} is not defined", name)),
+        }
+
+        if steps == 0 {
+            return Err(String::from("The number of steps must be positive"));
+        }
+
+        if !start.is_finite() || !end.is_finite() {
+            return Err(format!(
+                "The range [{}, {}] of sampling must have finite bounds",
+                start, end
+            ));
+        }
+
+        return (0..=steps)
+            .map(|id| {
+                let x: f64 = start + (end - start) * (id as f64) / (steps as f64);
+
+                return self.call_function(name, &[x]).map(|value| (x, value));
+            })
+            .collect();
+    }
+
+    /// Compute minimum, maximum and mean of values of a function sampled as by `sample`
+    ///
+    /// It is handy to scale axis of a plot of function.
+    pub fn function_stats(
+        &self,
+        name: &str,
+        start: f64,
+        end: f64,
+        steps: usize,
+    ) -> Result<FunctionStats, String> {
+        let samples: Vec<(f64, f64)> = self.sample(name, start, end, steps)?;
+
+        let values = samples.iter().map(|(_, value)| *value);
+
+        return Ok(FunctionStats {
+            min: values.clone().fold(f64::INFINITY, f64::min),
+            max: values.clone().fold(f64::NEG_INFINITY, f64::max),
+            mean: values.sum::<f64>() / (samples.len() as f64),
+        });
+    }
+
+    /// Get number of variables of a function defined by user, none if function is not defined
+    pub fn function_arity(&self, name: &str) -> Option<usize> {
+        return self
+            .functions
+            .get(name)
+            .map(|(variables, _)| variables.len());
+    }
+
+    /// Compute derivative of a function with a single variable at given point
+    ///
+    /// The derivative is approximated by central difference `(f(at + h) - f(at - h)) / 2h`,
+    /// step `h` must be a finite nonzero number.
+    pub fn derivative(&self, name: &str, at: f64, h: f64) -> Result<f64, String> {
+        match self.functions.get(name) {
+            Some((variables, _)) if variables.len() == 1 => {}
+            Some(_) => {
+                return Err(format!(
+                    "The function {} must have a single variable to be derived",
+                    name
+                ))
+            }
+            None => return Err(format!("The function {} is not defined", name)),
+        }
+
+        if h == 0.0 || !h.is_finite() {
+            return Err(format!(
+                "The step {} of derivation must be a finite nonzero number",
+                h
+            ));
+        }
+
+        let forward_value: f64 = self.call_function(name, &[at + h])?;
+        let backward_value: f64 = self.call_function(name, &[at - h])?;
+
+        return Ok((forward_value - backward_value) / (2.0 * h));
+    }
+
+    /// Compute definite integral of a function with a single variable between two bounds
+    ///
+    /// The integral is approximated by trapezoidal rule over given number of steps of equal width,
+    /// the function being called at each step as with `call_function`. If lower bound is greater
+    /// than upper bound, the integral is negated, as in mathematics.
+    pub fn integrate(&self, name: &str, from: f64, to: f64, steps: usize) -> Result<f64, String> {
+        match self.function_arity(name) {
+            Some(1) => {}
+            Some(_) => {
+                return Err(format!(
+                    "The function {} must have a single variable to be integrated",
+                    name
+                ))
+            }
+            None => return Err(format!("The function {} is not defined", name)),
+        }
+
+        if steps == 0 {
+            return Err(String::from(
+                "The number of steps of integration must be positive",
+            ));
+        }
+
+        if from > to {
+            return self
+                .integrate(name, to, from, steps)
+                .map(|integral| -integral);
+        }
+
+        let step: f64 = (to - from) / steps as f64;
+        let mut sum: f64 =
+            (self.call_function(name, &[from])? + self.call_function(name, &[to])?) / 2.0;
+
+        for id in 1..steps {
+            sum += self.call_function(name, &[from + step * id as f64])?;
+        }
+
+        return Ok(sum * step);
+    }
+
+    /// Evaluate an expression and approximate its value by a reduced fraction `(numerator, denominator)`
+    ///
+    /// The approximation is computed with continued fractions, it is the closest fraction
+    /// whose denominator is not greater than maximal denominator given in argument.
+    /// The expression is evaluated as with `evaluate`, the calculator is not modified.
+    pub fn evaluate_as_fraction(
+        &self,
+        expression_str: &str,
+        max_denominator: u64,
+    ) -> Result<(i64, i64), String> {
+        if max_denominator == 0 {
+            return Err(String::from("The maximal denominator must be positive"));
+        }
+
+        let value: f64 = self.evaluate(expression_str)?;
+
+        if !value.is_finite() || value.abs() >= i64::MAX as f64 {
+            return Err(format!(
+                "The value {} cannot be approximated by a fraction",
+                value
+            ));
+        }
+
+        let max_denominator: i128 = i128::from(max_denominator);
+
+        // Convergents h/k are computed from previous ones with h = a * h1 + h2 and k = a * k1 + k2
+        let (mut h1, mut h2): (i128, i128) = (1, 0);
+        let (mut k1, mut k2): (i128, i128) = (0, 1);
+        let mut remainder: f64 = value.abs();
+
+        for _ in 0..64 {
+            let a: i128 = remainder.floor() as i128;
+
+            let h: i128 = a * h1 + h2;
+            let k: i128 = a * k1 + k2;
+
+            if k > max_denominator {
+                // Best semiconvergent with bounded denominator may be closer than last convergent
+                let m: i128 = (max_denominator - k2) / k1;
+                let (semi_h, semi_k): (i128, i128) = (m * h1 + h2, m * k1 + k2);
+
+                let distance =
+                    |h: i128, k: i128| -> f64 { (value.abs() - h as f64 / k as f64).abs() };
+
+                if m > 0 && distance(semi_h, semi_k) < distance(h1, k1) {
+                    (h1, k1) = (semi_h, semi_k);
+                }
+
+                break;
+            }
+
+            (h2, h1) = (h1, h);
+            (k2, k1) = (k1, k);
+
+            let fractional_part: f64 = remainder - remainder.floor();
+
+            if fractional_part < 1e-12 {
+                break;
+            }
+
+            remainder = 1.0 / fractional_part;
+        }
+
+        let numerator: i64 = i64::try_from(h1)
+            .map_err(|_| format!("The value {} cannot be approximated by a fraction", value))?;
+
+        return Ok((if value < 0.0 { -numerator } else { numerator }, k1 as i64));
+    }
+}
+
+#[cfg(feature = "builtin-eval")]
+impl Calculator<evaluator::BuiltinEvaluator> {
+    /// Set maximal difference of two numbers considered equal by comparisons `==` and `!=` of built-in evaluator
+    ///
+    /// By default it is 0, i.e. equality is exact. Ordering comparisons, as `<`, are not affected.
+    /// A negative, infinite or NaN epsilon gives an error and epsilon is unchanged.
+    pub fn set_comparison_epsilon(&mut self, comparison_epsilon: f64) -> Result<(), String> {
+        if !comparison_epsilon.is_finite() || comparison_epsilon < 0.0 {
+            return Err(format!(
+                "The comparison epsilon {} is not valid, it must be a finite number not lower than 0",
+                comparison_epsilon
+            ));
+        }
+
+        self.evaluator.set_comparison_epsilon(comparison_epsilon);
+
+        return Ok(());
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl Calculator<evaluator::DecimalEvaluator> {
+    /// Construct a calculator computing on exact decimal numbers, available with feature `decimal`
+    ///
+    /// Values are stored as decimal numbers and written exactly in results, thus `0.1 + 0.2` gives `0.3`.
+    /// Notation does not apply on them. Rounding of results can be configured on a `DecimalEvaluator`
+    /// given to `new`.
+    pub fn with_decimal_evaluator() -> Self {
+        return Self::new(evaluator::DecimalEvaluator::new());
+    }
+}
+
+#[cfg(feature = "complex")]
+impl Calculator<evaluator::ComplexEvaluator> {
+    /// Construct a calculator computing on complex numbers, available with feature `complex`
+    ///
+    /// Imaginary unit is written `i` or `j`, thus `z = 3 + 4i` defines a complex variable.
+    /// Results are written `a + bi` where each part follows notation and locale.
+    pub fn with_complex_evaluator() -> Self {
+        return Self::new(evaluator::ComplexEvaluator::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Define mock evaluator for units tests
+    fn evaluate(expression: &str) -> Result<f64, String> {
+        if expression.is_empty() {
+            return Err(String::from("Expression is empty"));
+        }
+
+        return Ok(expression.len() as f64);
+    }
+
+    // Define arithmetic evaluator for units tests needing real values
+    // It handles numbers, parenthesis, unary minus and operators +, -, *, /
+    fn evaluate_arithmetic(expression: &str) -> Result<f64, String> {
+        fn parse_sum(chars: &[char], position: &mut usize) -> Result<f64, String> {
+            let mut value: f64 = parse_product(chars, position)?;
+
+            while *position < chars.len() {
+                match chars[*position] {
+                    '+' => {
+                        *position += 1;
+                        value += parse_product(chars, position)?;
+                    }
+                    '-' => {
+                        *position += 1;
+                        value -= parse_product(chars, position)?;
+                    }
+                    _ => break,
+                }
+            }
+
+            return Ok(value);
+        }
+
+        fn parse_product(chars: &[char], position: &mut usize) -> Result<f64, String> {
+            let mut value: f64 = parse_factor(chars, position)?;
+
+            while *position < chars.len() {
+                match chars[*position] {
+                    '*' => {
+                        *position += 1;
+                        value *= parse_factor(chars, position)?;
+                    }
+                    '/' => {
+                        *position += 1;
+                        value /= parse_factor(chars, position)?;
+                    }
+                    _ => break,
+                }
+            }
+
+            return Ok(value);
+        }
+
+        fn parse_factor(chars: &[char], position: &mut usize) -> Result<f64, String> {
+            match chars.get(*position) {
+                Some('-') => {
+                    *position += 1;
+                    return Ok(-parse_factor(chars, position)?);
+                }
+                Some('(') => {
+                    *position += 1;
+                    let value: f64 = parse_sum(chars, position)?;
+
+                    if chars.get(*position) != Some(&')') {
+                        return Err(String::from("Missing closing parenthesis"));
+                    }
+
+                    *position += 1;
+                    return Ok(value);
+                }
+                _ => {
+                    let start: usize = *position;
+
+                    while *position < chars.len()
+                        && (chars[*position].is_ascii_digit() || chars[*position] == '.')
+                    {
+                        *position += 1;
+                    }
+
+                    return chars[start..*position]
+                        .iter()
+                        .collect::<String>()
+                        .parse::<f64>()
+                        .map_err(|_| String::from("Invalid number"));
+                }
+            }
+        }
+
+        let chars: Vec<char> = expression.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut position: usize = 0;
+
+        let value: f64 = parse_sum(&chars, &mut position)?;
+
+        if position != chars.len() {
+            return Err(String::from("Unexpected character"));
+        }
+
+        return Ok(value);
+    }
+
+    // Define evaluator giving typed values for units tests
+    // It compares two operands with ==, reads boolean and integer literals
+    // and evaluates arithmetic expression otherwise
+    struct TypedEvaluator;
+
+    // Define error of evaluator for units tests
+    #[derive(Debug, PartialEq)]
+    enum CustomError {
+        DivisionByZero,
+        UnknownToken(String),
+    }
+
+    impl std::fmt::Display for CustomError {
+        fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            return match self {
+                Self::DivisionByZero => write!(formatter, "Division by zero"),
+                Self::UnknownToken(token) => write!(formatter, "Unknown token {}", token),
+            };
+        }
+    }
+
+    // Define evaluator with custom error for units tests
+    // It evaluates a division of two numbers, parenthesis are ignored
+    fn evaluate_division(expression: &str) -> Result<f64, CustomError> {
+        let expression: String = expression.replace(['(', ')'], "");
+        let operands: Vec<&str> = expression
+            .split('/')
+            .map(|operand| operand.trim())
+            .collect();
+
+        let mut values: Vec<f64> = Vec::with_capacity(operands.len());
+
+        for operand in operands {
+            match operand.parse::<f64>() {
+                Ok(value) => values.push(value),
+                Err(_) => return Err(CustomError::UnknownToken(String::from(operand))),
+            }
+        }
+
+        return match values.as_slice() {
+            [value] => Ok(*value),
+            [_, divisor] if *divisor == 0.0 => Err(CustomError::DivisionByZero),
+            [dividend, divisor] => Ok(dividend / divisor),
+            _ => Err(CustomError::UnknownToken(String::from("/"))),
+        };
+    }
+
+    impl EvaluateValue for TypedEvaluator {
+        type Error = String;
+
+        fn evaluate(&self, expression: &str) -> Result<Value, String> {
+            if let Some((left, right)) = expression.split_once("==") {
+                return Ok(Value::Bool(left.trim() == right.trim()));
+            }
+
+            if let Ok(boolean) = expression.trim().parse::<bool>() {
+                return Ok(Value::Bool(boolean));
+            }
+
+            if let Ok(integer) = expression.trim().parse::<i64>() {
+                return Ok(Value::Integer(integer));
+            }
+
+            return evaluate_arithmetic(expression).map(Value::Number);
+        }
+    }
+
+    #[test]
+    fn test_calculator_new() {
+        let calculator = Calculator::new(evaluate);
+
+        assert!(calculator.variables.capacity() > 0);
+
+        let empty_expression: String = String::default();
+        assert!((calculator.evaluator)(empty_expression.as_str()).is_err());
+
+        let expression: String = String::from("taz");
+        let result: Result<f64, String> = (calculator.evaluator)(expression.as_str());
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap() as usize, expression.len())
+    }
+
+    #[test]
+    fn test_calculator_process_raw_expression() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let expression: String = String::from("1 + 1");
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => {
+                let variable_name: String = String::from("last");
+                let variable_value: f64 = expression.len() as f64;
+
+                let str_reference: String = format!("{} = {}", variable_name, variable_value);
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.variables.len(), 1);
+                assert!(calculator.variables.contains_key(&variable_name));
+                assert_eq!(calculator.variables[&variable_name], variable_value);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_several_raw_expression() {
+        let mut calculator = Calculator::new(evaluate);
+
+        // Evaluate first expression
+        let first_expression: String = String::from("1 + 1");
+
+        let variable_name: String = String::from("last");
+
+        match calculator.process(first_expression.as_str()) {
+            Ok(str_result) => {
+                let variable_value: f64 = first_expression.len() as f64;
+
+                let str_reference: String = format!("{} = {}", variable_name, variable_value);
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.variables.len(), 1);
+                assert!(calculator.variables.contains_key(&variable_name));
+                assert_eq!(calculator.variables[&variable_name], variable_value);
+            }
+            Err(_) => assert!(false),
+        }
+
+        // Evaluate second expresion
+        // The value of variable named 'last' must be replaced by value of second expression
+        let second_expression: String = String::from("1 + 1 + 3");
+
+        match calculator.process(second_expression.as_str()) {
+            Ok(str_result) => {
+                let variable_value: f64 = second_expression.len() as f64;
+
+                let str_reference: String = format!("{} = {}", variable_name, variable_value);
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.variables.len(), 1);
+                assert!(calculator.variables.contains_key(&variable_name));
+                assert_eq!(calculator.variables[&variable_name], variable_value);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_variable_expression() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let variable_name: String = String::from("x");
+        let variable_definition: String = String::from("1 + 1");
+
+        let expression: String = format!("{} = {}", variable_name, variable_definition);
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => {
+                let variable_value: f64 = variable_definition.len() as f64;
+
+                let str_reference: String = format!("{} = {}", variable_name, variable_value);
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.variables.len(), 1);
+                assert!(calculator.variables.contains_key(&variable_name));
+                assert_eq!(calculator.variables[&variable_name], variable_value);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_several_variable_expression() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let first_variable_name: String = String::from("x");
+        let first_variable_definition: String = String::from("1 + 1");
+
+        let first_expression: String =
+            format!("{} = {}", first_variable_name, first_variable_definition);
+
+        match calculator.process(first_expression.as_str()) {
+            Ok(str_result) => {
+                let variable_value: f64 = first_variable_definition.len() as f64;
+
+                let str_reference: String = format!("{} = {}", first_variable_name, variable_value);
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.variables.len(), 1);
+                assert!(calculator.variables.contains_key(&first_variable_name));
+                assert_eq!(calculator.variables[&first_variable_name], variable_value);
+            }
+            Err(_) => assert!(false),
+        }
+
+        let second_variable_name: String = String::from("y");
+        let second_variable_definition: String = String::from("9 + 1");
+
+        let second_expression: String =
+            format!("{} = {}", second_variable_name, second_variable_definition);
+
+        match calculator.process(second_expression.as_str()) {
+            Ok(str_result) => {
+                let variable_value: f64 = second_variable_definition.len() as f64;
+
+                let str_reference: String =
+                    format!("{} = {}", second_variable_name, variable_value);
+
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.variables.len(), 2);
+                assert!(calculator.variables.contains_key(&second_variable_name));
+                assert_eq!(calculator.variables[&second_variable_name], variable_value);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_expression_with_variables() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let first_variable_name: String = String::from("x");
+        let first_variable_definition: String = String::from("1 + 1");
+
+        let first_expression: String =
+            format!("{} = {}", first_variable_name, first_variable_definition);
+
+        let first_process_result = calculator.process(first_expression.as_str());
+        assert!(first_process_result.is_ok());
+
+        let second_variable_name: String = String::from("y");
+        let second_variable_definition: String = String::from("97 + 1");
+
+        let second_expression: String =
+            format!("{} = {}", second_variable_name, second_variable_definition);
+
+        let second_process_result = calculator.process(second_expression.as_str());
         assert!(second_process_result.is_ok());
 
-        let variable_name: String = String::from("distance");
-        let variable_definition: String = String::from("x + y");
+        let variable_name: String = String::from("distance");
+        let variable_definition: String = String::from("x + y");
+
+        let expression: String = format!("{} = {}", variable_name, variable_definition);
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => {
+                let variable_value: f64 = (first_variable_definition.len().to_string().len()
+                    + second_variable_definition.len().to_string().len()
+                    + 3) as f64;
+
+                let str_reference: String = format!("{} = {}", variable_name, variable_value);
+                assert_eq!(str_result, str_reference);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_function_expression() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let function_name: String = String::from("distance");
+        let function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
+        let function_definition: String = format!(
+            "{} * {} + {} * {}",
+            function_variables[0],
+            function_variables[0],
+            function_variables[1],
+            function_variables[1]
+        );
+
+        let expression: String = format!(
+            "{}: {}, {} = {}",
+            function_name, function_variables[0], function_variables[1], function_definition
+        );
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => {
+                let str_reference: String = format!(
+                    "{}({}) = {}",
+                    function_name,
+                    function_variables.join(", "),
+                    function_definition
+                );
+
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.functions.len(), 1);
+                assert!(calculator.functions.contains_key(&function_name));
+                assert_eq!(
+                    calculator.functions[&function_name],
+                    (function_variables, function_definition)
+                );
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_several_function_expression() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let first_function_name: String = String::from("distance");
+        let first_function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
+        let first_function_definition: String = format!(
+            "{} * {} + {} * {}",
+            first_function_variables[0],
+            first_function_variables[0],
+            first_function_variables[1],
+            first_function_variables[1]
+        );
+
+        let first_expression: String = format!(
+            "{}: {}, {} = {}",
+            first_function_name,
+            first_function_variables[0],
+            first_function_variables[1],
+            first_function_definition
+        );
+
+        match calculator.process(first_expression.as_str()) {
+            Ok(str_result) => {
+                let str_reference: String = format!(
+                    "{}({}) = {}",
+                    first_function_name,
+                    first_function_variables.join(", "),
+                    first_function_definition
+                );
+
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.functions.len(), 1);
+                assert!(calculator.functions.contains_key(&first_function_name));
+                assert_eq!(
+                    calculator.functions[&first_function_name],
+                    (first_function_variables, first_function_definition)
+                );
+            }
+            Err(_) => assert!(false),
+        }
+
+        let second_function_name: String = String::from("velocity");
+        let second_function_variables: Vec<String> =
+            vec![String::from("distance"), String::from("time")];
+
+        let second_function_definition: String = format!(
+            "{} / {}",
+            second_function_variables[0], second_function_variables[1]
+        );
+
+        let second_expression: String = format!(
+            "{}: {}, {} = {}",
+            second_function_name,
+            second_function_variables[0],
+            second_function_variables[1],
+            second_function_definition
+        );
+
+        match calculator.process(second_expression.as_str()) {
+            Ok(str_result) => {
+                let str_reference: String = format!(
+                    "{}({}) = {}",
+                    second_function_name,
+                    second_function_variables.join(", "),
+                    second_function_definition
+                );
+
+                assert_eq!(str_result, str_reference);
+
+                assert_eq!(calculator.functions.len(), 2);
+                assert!(calculator.functions.contains_key(&second_function_name));
+                assert_eq!(
+                    calculator.functions[&second_function_name],
+                    (second_function_variables, second_function_definition)
+                );
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_expression_with_functions() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let first_function_name: String = String::from("distance");
+        let first_function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
+        let first_function_definition: String = format!(
+            "{} * {} + {} * {}",
+            first_function_variables[0],
+            first_function_variables[0],
+            first_function_variables[1],
+            first_function_variables[1]
+        );
+
+        let first_function_expression: String = format!(
+            "{}: {}, {} = {}",
+            first_function_name,
+            first_function_variables[0],
+            first_function_variables[1],
+            first_function_definition
+        );
+
+        assert!(calculator
+            .process(first_function_expression.as_str())
+            .is_ok());
+
+        let second_function_name: String = String::from("velocity");
+        let second_function_variables: Vec<String> =
+            vec![String::from("distance"), String::from("time")];
+
+        let second_function_definition: String = format!(
+            "{} / {}",
+            second_function_variables[0], second_function_variables[1]
+        );
+
+        let second_function_expression: String = format!(
+            "{}: {}, {} = {}",
+            second_function_name,
+            second_function_variables[0],
+            second_function_variables[1],
+            second_function_definition
+        );
+
+        assert!(calculator
+            .process(second_function_expression.as_str())
+            .is_ok());
+
+        let expression: String = format!(
+            "3.14 * {}(6.89, 5.43) - {}(2.4, 4.3) + (2 * 3 - 7)",
+            second_function_name, first_function_name
+        );
+
+        let replaced_expression: String =
+            String::from("3.14 * (6.89 / 5.43) - (2.4 * 2.4 + 4.3 * 4.3) + (2 * 3 - 7)");
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => {
+                let str_reference: String = format!("last = {}", replaced_expression.len());
+                assert_eq!(str_result, str_reference);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_expression_with_variables_and_functions() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let first_function_name: String = String::from("distance");
+        let first_function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
+        let first_function_definition: String = format!(
+            "{} * {} + {} * {}",
+            first_function_variables[0],
+            first_function_variables[0],
+            first_function_variables[1],
+            first_function_variables[1]
+        );
+
+        let first_function_expression: String = format!(
+            "{}: {}, {} = {}",
+            first_function_name,
+            first_function_variables[0],
+            first_function_variables[1],
+            first_function_definition
+        );
+
+        assert!(calculator
+            .process(first_function_expression.as_str())
+            .is_ok());
+
+        let second_function_name: String = String::from("velocity");
+        let second_function_variables: Vec<String> =
+            vec![String::from("distance"), String::from("time")];
+
+        let second_function_definition: String = format!(
+            "{} / {}",
+            second_function_variables[0], second_function_variables[1]
+        );
+
+        let second_function_expression: String = format!(
+            "{}: {}, {} = {}",
+            second_function_name,
+            second_function_variables[0],
+            second_function_variables[1],
+            second_function_definition
+        );
+
+        assert!(calculator
+            .process(second_function_expression.as_str())
+            .is_ok());
+
+        let first_variable_name: String = String::from("x");
+        let first_variable_definition: String = String::from("1 + 1");
+
+        let first_expression: String =
+            format!("{} = {}", first_variable_name, first_variable_definition);
+
+        assert!(calculator.process(first_expression.as_str()).is_ok());
+
+        let second_variable_name: String = String::from("y");
+        let second_variable_definition: String = String::from("97 + 1");
+
+        let second_expression: String =
+            format!("{} = {}", second_variable_name, second_variable_definition);
+
+        assert!(calculator.process(second_expression.as_str()).is_ok());
+
+        let expression: String = format!(
+            "3.14 * {}({}, {}) - {}(2.4, 4.3) + (2 * 3 - 7)",
+            second_function_name, first_variable_name, second_variable_name, first_function_name
+        );
+
+        let replaced_expression: String = format!(
+            "3.14 * ({} / {}) - (2.4 * 2.4 + 4.3 * 4.3) + (2 * 3 - 7)",
+            first_variable_definition.len(),
+            second_variable_definition.len()
+        );
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => {
+                let str_reference: String = format!("last = {}", replaced_expression.len());
+                assert_eq!(str_result, str_reference);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_expression_with_variables_and_function_variables_equal_to_variables()
+    {
+        let mut calculator = Calculator::new(evaluate);
+
+        let first_variable_name: String = String::from("x");
+        let first_variable_definition: String = String::from("1 + 1");
+
+        let first_expression: String =
+            format!("{} = {}", first_variable_name, first_variable_definition);
+
+        assert!(calculator.process(first_expression.as_str()).is_ok());
+
+        let second_variable_name: String = String::from("y");
+        let second_variables_definition: String = String::from("2 * 5");
+
+        let second_expression: String =
+            format!("{} = {}", second_variable_name, second_variables_definition);
+
+        assert!(calculator.process(second_expression.as_str()).is_ok());
+
+        let function_name: String = String::from("distance");
+        let function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
+        let function_definition: String = format!(
+            "{} * {} + {} * {}",
+            function_variables[0],
+            function_variables[0],
+            function_variables[1],
+            function_variables[1]
+        );
+
+        let function_expression: String = format!(
+            "{}: {}, {} = {}",
+            function_name, function_variables[0], function_variables[1], function_definition
+        );
+
+        assert!(calculator.process(function_expression.as_str()).is_ok());
+        assert!(function_definition == calculator.functions[&function_name].1);
+    }
+
+    #[test]
+    fn test_calculator_call_function() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x, y = x * x + y").is_ok());
+
+        match calculator.call_function("f", &[3.0, -1.0]) {
+            Ok(value) => assert_eq!(value, 8.0),
+            Err(_) => assert!(false),
+        }
+
+        assert!(calculator.call_function("f", &[3.0]).is_err());
+        assert!(calculator.call_function("g", &[3.0]).is_err());
+    }
+
+    #[test]
+    fn test_calculator_process_precision_warnings() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        match calculator.process_structured("x = 9007199254740993") {
+            Ok(output) => {
+                assert_eq!(output.result, "x = 9007199254740992");
+                assert_eq!(
+                    output.warnings,
+                    vec![String::from(
+                        "The number 9007199254740993 is stored as 9007199254740992"
+                    )]
+                );
+            }
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process_structured("y = 9007199254740992 + 0.25") {
+            Ok(output) => assert!(output.warnings.is_empty()),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_strict_precision(true);
+
+        assert_eq!(
+            calculator.process("x = 9007199254740993"),
+            Err(String::from(
+                "The number 9007199254740993 cannot be stored exactly, it would be stored as 9007199254740992"
+            ))
+        );
+        assert_eq!(
+            calculator.process("x"),
+            Ok(String::from("last = 9007199254740992"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_identifier_predicate() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("$x = 5").is_ok());
+
+        // By default `$` is not part of a name, only `x` is replaced
+        assert_eq!(calculator.expand("$x + 1"), Ok(String::from("$2 + 1")));
+
+        calculator.set_identifier_predicate(|c| token::is_identifier_character(c) || c == '$');
+
+        assert_eq!(calculator.process("$x + x"), Ok(String::from("last = 7")));
+        assert_eq!(calculator.last_expanded(), Some("5 + 2"));
+
+        // Variables of functions and their calls follow the same predicate
+        assert!(calculator.process("f: $y = $y * x").is_ok());
+        assert_eq!(calculator.process("f(3)"), Ok(String::from("last = 6")));
+        assert_eq!(calculator.last_expanded(), Some("(3 * 2)"));
+
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_identifier_predicate(|c| token::is_identifier_character(c) || c == '.');
+        calculator.set_strict_mode(true);
+
+        assert!(calculator.process("f: a.b = a.b * 2").is_ok());
+        assert_eq!(
+            calculator
+                .functions
+                .get("f")
+                .map(|(variables, _)| variables.clone()),
+            Some(vec![String::from("a.b")])
+        );
+        assert_eq!(calculator.process("f(4)"), Ok(String::from("last = 8")));
+    }
+
+    #[test]
+    fn test_calculator_process_unsafe_characters() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(calculator.process("x\t= 2"), Ok(String::from("x = 2")));
+
+        match calculator.try_process("y = 1 + \u{202e}2") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(
+                    error.message(),
+                    "The character U+202E at column 9 is not allowed"
+                );
+                assert_eq!(error.cause(), Some(&Cause::UnsafeCharacter('\u{202e}', 9)));
+            }
+        }
+
+        assert!(calculator.load_script("z = \u{1b}3").is_err());
+        assert!(calculator.process(":sci\u{2066}").is_err());
+
+        calculator.set_unsafe_character_policy(UnsafeCharacterPolicy::Strip);
+
+        assert_eq!(
+            calculator.process("f\u{202e}: t = t + 1\u{7}"),
+            Ok(String::from("f(t) = t + 1"))
+        );
+        assert!(calculator.load_script("z = \u{1b}3").is_ok());
+        assert_eq!(calculator.export_script(), "x = 2\nz = 3\nf: t = t + 1\n");
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_process_result_references() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_max_results(Some(2));
+
+        assert!(calculator.process("f: x = x * 10").is_ok());
+        assert!(calculator.process("2 + 2").is_ok());
+        assert!(calculator.process("x = 3").is_ok());
+        assert!(calculator.process("%1 + 1").is_ok());
+
+        assert_eq!(
+            calculator.process("f(%1) + %2"),
+            Ok(String::from("last = 45"))
+        );
+        assert_eq!(calculator.last_expanded(), Some("(4 * 10) + 5"));
+
+        assert_eq!(
+            calculator.process("%1"),
+            Err(String::from(
+                "The result %1 is no longer kept, only the last 2 results are kept"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("%4 + 1"),
+            Err(String::from(
+                "The result %4 does not exist, 3 result(s) have been computed"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process(":results"),
+            Ok(String::from("results:\n  %2 = 5\n  %3 = 45"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_lazy_variables() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_lazy_variables(true);
+
+        assert!(calculator.process("circle: x = 3 * x * x").is_ok());
+        assert!(calculator.process("r = 1").is_ok());
+        assert_eq!(
+            calculator.process("area = circle(r)"),
+            Ok(String::from("area = 3"))
+        );
+
+        assert!(calculator.process("r = 2").is_ok());
+        assert_eq!(calculator.process("area"), Ok(String::from("last = 3")));
+
+        assert_eq!(calculator.recompute("area"), Ok(Value::Number(12.0)));
+        assert_eq!(calculator.process("area"), Ok(String::from("last = 12")));
+
+        // Lazy variables are kept by snapshots and scripts, after the ones they depend on
+        assert!(calculator.process("double = 2 * area").is_ok());
+
+        let snapshot: CalculatorState = calculator.snapshot();
+        assert_eq!(snapshot.lazy_definitions["area"], "(3 * r * r)");
+
+        let script: String = calculator.export_script();
+        assert!(script.starts_with(
+            "last = 12\nlazy r = 2\nlazy area = (3 * r * r)\nlazy double = 2 * area\n"
+        ));
+
+        let mut replayed = Calculator::new(evaluate_arithmetic);
+        assert!(replayed.load_script(script.as_str()).is_ok());
+        assert_eq!(replayed.snapshot(), snapshot);
+
+        assert!(replayed.process("r = 1").is_ok());
+        assert_eq!(replayed.recompute("area"), Ok(Value::Number(3.0)));
+        assert_eq!(
+            replayed.process("lazy r + 1"),
+            Err(String::from("The lazy declaration must define a variable"))
+        );
+
+        // Restore replaces lazy variables
+        assert_eq!(replayed.restore(CalculatorState::default()), Ok(()));
+        assert!(replayed.process("area = 1").is_ok());
+        assert_eq!(
+            replayed.recompute("area"),
+            Err(String::from("The variable area is not lazy"))
+        );
+
+        calculator.set_lazy_variables(false);
+        assert!(calculator.process("area = circle(r)").is_ok());
+
+        assert_eq!(
+            calculator.recompute("area"),
+            Err(String::from("The variable area is not lazy"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_reference_variable() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("a = 2").is_ok());
+        assert!(calculator.process("copy = a").is_ok());
+        assert!(calculator.set_reference("reference", "a").is_ok());
+        assert!(calculator.set_reference("chained", "reference").is_ok());
+
+        assert!(calculator.process("a = 5").is_ok());
+
+        assert_eq!(calculator.process("copy"), Ok(String::from("last = 2")));
+        assert_eq!(
+            calculator.process("reference"),
+            Ok(String::from("last = 5"))
+        );
+        assert_eq!(
+            calculator.process("chained + 1"),
+            Ok(String::from("last = 6"))
+        );
+
+        assert_eq!(
+            calculator.set_reference("a", "chained"),
+            Err(String::from(
+                "The reference a -> chained -> reference -> a is cyclic"
+            ))
+        );
+
+        assert_eq!(
+            calculator.set_reference("a", "a"),
+            Err(String::from("The reference a -> a is cyclic"))
+        );
+
+        assert_eq!(
+            calculator.set_reference("dangling", "undefined"),
+            Err(String::from("The variable undefined is not defined"))
+        );
+
+        // References are kept by snapshots and scripts, after their targets
+        let snapshot: CalculatorState = calculator.snapshot();
+        assert_eq!(snapshot.references["chained"], "reference");
+
+        let script: String = calculator.export_script();
+        assert!(script.ends_with("reference reference = a\nreference chained = reference\n"));
+
+        let mut replayed = Calculator::new(evaluate_arithmetic);
+        assert!(replayed.load_script(script.as_str()).is_ok());
+        assert_eq!(replayed.snapshot(), snapshot);
+        assert_eq!(
+            replayed.process("reference b = a"),
+            Ok(String::from("reference b = a"))
+        );
+        assert_eq!(
+            format!("{}", snapshot.diff(&replayed.snapshot())),
+            "+reference b = a"
+        );
+
+        // Restore replaces references
+        assert_eq!(replayed.restore(CalculatorState::default()), Ok(()));
+        assert!(replayed.process("chained").is_err());
+
+        let mut dangling_snapshot: CalculatorState = snapshot.clone();
+        dangling_snapshot.variables.remove("a");
+        assert_eq!(
+            replayed.restore(dangling_snapshot),
+            Err(String::from(
+                "The reference chained does not lead to a defined variable"
+            ))
+        );
+
+        assert!(calculator.process("reference = 1").is_ok());
+        assert!(calculator.process("a = 7").is_ok());
+        assert_eq!(
+            calculator.process("reference"),
+            Ok(String::from("last = 1"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_function_arity() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x, y = x * x + y").is_ok());
+
+        assert_eq!(calculator.function_arity("f"), Some(2));
+        assert_eq!(calculator.function_arity("g"), None);
+    }
+
+    #[test]
+    fn test_calculator_process_with_format() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_format(Format::default().max_output_len(20));
+
+        assert_eq!(calculator.process("2 + 3"), Ok(String::from("last = 5")));
+
+        assert_eq!(
+            calculator.process("f: é = é * é + é * é + é"),
+            Ok(String::from("f(é) = … (+17 chars)"))
+        );
+
+        assert_eq!(
+            calculator.process("f(1, 2)"),
+            Err(String::from("The num… (+34 chars)"))
+        );
+
+        assert_eq!(
+            calculator.process_line("1 + 1; f(1, 2)"),
+            Err(String::from("Express… (+48 chars)"))
+        );
+
+        match calculator.process_structured("h: à = à * à + à * à + à") {
+            Ok(output) => assert_eq!(output.result, "h(à) = à * à + à * à + à"),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_format(Format::default());
+        assert_eq!(
+            calculator.process("f: é = é * é + é * é + é"),
+            Ok(String::from("f(é) = é * é + é * é + é"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_pure_function_expansion_cache() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        let mut fresh_calculator = Calculator::new(evaluate_arithmetic);
+
+        for definition in ["g: x = x + 1", "f: x, y = g(x) * y", "h: x = x - 1"] {
+            assert!(calculator.process(definition).is_ok());
+            assert!(fresh_calculator.process(definition).is_ok());
+        }
+
+        assert!(calculator.set_function_pure("f", true).is_ok());
+        assert!(calculator.set_function_pure("h", true).is_ok());
+        assert!(calculator.set_function_pure("k", true).is_err());
+
+        let expression: &str = "f(2, h(3)) + f(2, h(3)) * h(1)";
+
+        assert_eq!(
+            calculator.expand(expression),
+            fresh_calculator.expand(expression)
+        );
+        assert_eq!(calculator.cached_expansions().len(), 2);
+
+        assert_eq!(
+            calculator.process(expression),
+            fresh_calculator.process(expression)
+        );
+        assert_eq!(calculator.cached_expansions().len(), 2);
+
+        // Redefinition of a function called by f invalidates cached expansions of f
+        assert!(calculator.process("g: x = x + 2").is_ok());
+        assert!(fresh_calculator.process("g: x = x + 2").is_ok());
+
+        assert_eq!(calculator.cached_expansions().len(), 1);
+        assert_eq!(
+            calculator.expand(expression),
+            fresh_calculator.expand(expression)
+        );
+
+        // Redefinition of f invalidates its cached expansions
+        assert!(calculator.process("f: x, y = x * y").is_ok());
+        assert!(!calculator
+            .cached_expansions()
+            .keys()
+            .any(|(name, _)| name == "f"));
+
+        assert_eq!(calculator.expand("f(2, 3)"), Ok(String::from("(2 * 3)")));
+
+        // Redefinition of a function called in arguments invalidates cached expansions
+        assert_eq!(
+            calculator.expand("h(g(1))"),
+            Ok(String::from("((1 + 2) - 1)"))
+        );
+        assert!(calculator.process("g: x = x * 10").is_ok());
+        assert_eq!(
+            calculator.expand("h(g(1))"),
+            Ok(String::from("((1 * 10) - 1)"))
+        );
+
+        // Change of minimal parentheses invalidates all cached expansions
+        assert!(calculator.process("identity: x = x").is_ok());
+        assert!(calculator.set_function_pure("identity", true).is_ok());
+        assert_eq!(calculator.expand("identity(5)"), Ok(String::from("(5)")));
+
+        calculator.set_minimal_parentheses(true);
+        assert!(calculator.cached_expansions().is_empty());
+        assert_eq!(calculator.expand("identity(5)"), Ok(String::from("5")));
+        calculator.set_minimal_parentheses(false);
+
+        // Cached expansions count their replaced calls
+        assert!(calculator.process("f(h(1), 2) + f(h(1), 2)").is_ok());
+        assert!(fresh_calculator.process("f: x, y = x * y").is_ok());
+        assert!(fresh_calculator.process("f(h(1), 2) + f(h(1), 2)").is_ok());
+        assert_eq!(
+            calculator.expansion_count(),
+            fresh_calculator.expansion_count()
+        );
+
+        #[cfg(feature = "limits")]
+        {
+            calculator.set_limits(Limits::default().with_max_function_expansions(1));
+            fresh_calculator.set_limits(Limits::default().with_max_function_expansions(1));
+
+            assert!(fresh_calculator.process("f(h(1), 2)").is_err());
+            assert!(calculator.process("f(h(1), 2)").is_err());
+
+            calculator.set_limits(Limits::default());
+        }
+
+        // Number of cached expansions is bounded
+        for id in 0..(MAX_CACHED_EXPANSIONS + 10) {
+            assert!(calculator.expand(format!("h({})", id).as_str()).is_ok());
+        }
+
+        assert!(calculator.cached_expansions().len() <= MAX_CACHED_EXPANSIONS);
+    }
+
+    #[test]
+    fn test_calculator_process_with_empty_expansion() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        match calculator.try_process("( )") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(error.message(), "The expression is empty");
+                assert_eq!(error.input(), "( )");
+                assert_eq!(error.cause(), Some(&Cause::EmptyExpansion(None)));
+            }
+        }
+
+        // Function whose definition is empty, as one defined by a command
+        calculator.functions.insert(
+            String::from("blank"),
+            (vec![String::from("x")], String::from(" ")),
+        );
+
+        match calculator.try_process("y = blank(2)") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(
+                    error.message(),
+                    "The expression is empty after substitution of function blank"
+                );
+                assert_eq!(error.input(), "y = blank(2)");
+                assert_eq!(
+                    error.cause(),
+                    Some(&Cause::EmptyExpansion(Some(String::from("blank"))))
+                );
+            }
+        }
+
+        assert_eq!(
+            calculator.process("y = blank(2)"),
+            Err(String::from("The expression is empty"))
+        );
+
+        // Function calling a function whose definition is empty
+        assert!(calculator.process("outer: x = blank(x)").is_ok());
+
+        match calculator.try_process("outer(1)") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.cause(),
+                Some(&Cause::EmptyExpansion(Some(String::from("blank"))))
+            ),
+        }
+
+        assert!(!calculator.variables.contains_key("y"));
+        assert_eq!(calculator.evaluation_count(), 0);
+    }
+
+    #[cfg(feature = "builtin-eval")]
+    #[test]
+    fn test_calculator_process_with_display_epsilon() {
+        let mut calculator = Calculator::new(evaluator::BuiltinEvaluator::new());
+        calculator.set_notation(Notation::Scientific);
+
+        assert_eq!(
+            calculator.process("-0 * 2"),
+            Ok(String::from("last = -0e0"))
+        );
+        assert_eq!(
+            calculator.process("x = 1e-300 * 1"),
+            Ok(String::from("x = 1e-300"))
+        );
+
+        calculator.set_display_epsilon(Some(0.0));
+        assert_eq!(calculator.process("-0 * 2"), Ok(String::from("last = 0e0")));
+
+        calculator.set_display_epsilon(Some(1e-12));
+        assert_eq!(
+            calculator.process("x = 1e-300 * 1"),
+            Ok(String::from("x = 0e0"))
+        );
+        assert_eq!(
+            calculator.process("y = -1e-3 * 1"),
+            Ok(String::from("y = -1e-3"))
+        );
+
+        assert_eq!(calculator.variables["x"], 1e-300);
+    }
+
+    #[test]
+    fn test_calculator_process_iter() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        let expressions: Vec<&str> = vec!["x = 2 * 3", "x + 1", "y +", "z = 1"];
+        let mut results = calculator.process_iter(expressions.into_iter());
+
+        assert_eq!(results.next(), Some(Ok(String::from("x = 6"))));
+        assert_eq!(results.next(), Some(Ok(String::from("last = 7"))));
+        assert!(matches!(results.next(), Some(Err(_))));
+
+        drop(results);
+
+        // Last expression is not processed since its result has not been consumed
+        assert!(!calculator.variables.contains_key("z"));
+    }
+
+    #[test]
+    fn test_calculator_evaluation_count() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(calculator.evaluation_count(), 0);
+
+        assert!(calculator.process("2 + 3").is_ok());
+        assert_eq!(calculator.evaluation_count(), 1);
+
+        assert!(calculator.process("x = 4 * 2").is_ok());
+        assert_eq!(calculator.evaluation_count(), 2);
+
+        assert!(calculator.process("f: y = y * x").is_ok());
+        assert_eq!(calculator.evaluation_count(), 2);
+
+        assert!(calculator.evaluate("f(2) + x").is_ok());
+        assert!(calculator.call_function("f", &[3.0]).is_ok());
+        assert_eq!(calculator.evaluation_count(), 4);
+
+        calculator.reset_evaluation_count();
+        assert_eq!(calculator.evaluation_count(), 0);
+    }
+
+    #[test]
+    fn test_calculator_derivative() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x * x").is_ok());
+        assert!(calculator.process("g: x, y = x * y").is_ok());
+
+        match calculator.derivative("f", 3.0, 1e-3) {
+            Ok(value) => assert!((value - 6.0).abs() < 1e-6),
+            Err(_) => assert!(false),
+        }
+
+        assert!(calculator.derivative("g", 3.0, 1e-3).is_err());
+        assert!(calculator.derivative("h", 3.0, 1e-3).is_err());
+
+        for h in [0.0, -0.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                calculator.derivative("f", 3.0, h),
+                Err(format!(
+                    "The step {} of derivation must be a finite nonzero number",
+                    h
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_calculator_integrate() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x").is_ok());
+        assert!(calculator.process("g: x, y = x * y").is_ok());
+
+        match calculator.integrate("f", 0.0, 2.0, 100) {
+            Ok(value) => assert!((value - 2.0).abs() < 1e-9),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.integrate("f", 2.0, 0.0, 100) {
+            Ok(value) => assert!((value + 2.0).abs() < 1e-9),
+            Err(_) => assert!(false),
+        }
+
+        assert!(calculator.integrate("f", 0.0, 2.0, 0).is_err());
+        assert!(calculator.integrate("g", 0.0, 2.0, 100).is_err());
+        assert!(calculator.integrate("h", 0.0, 2.0, 100).is_err());
+    }
+
+    #[test]
+    fn test_calculator_process_raw_expression_with_echo_expansion() {
+        let mut calculator = Calculator::new(evaluate);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+
+        let expression: String = String::from("f(2) * 3");
+        let expansion: String = String::from("(2 + 1) * 3");
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => assert_eq!(str_result, format!("last = {}", expansion.len())),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_echo_expansion(true);
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => assert_eq!(
+                str_result,
+                format!("last = {}    [{}]", expansion.len(), expansion)
+            ),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_echo_expansion(false);
+
+        match calculator.process(expression.as_str()) {
+            Ok(str_result) => assert_eq!(str_result, format!("last = {}", expansion.len())),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_raw_expression_with_truncated_echo_expansion() {
+        let mut calculator = Calculator::new(evaluate);
+
+        calculator.set_echo_expansion(true);
+        calculator.set_echo_expansion_length(5);
+
+        let short_expression: String = String::from("1 + 2");
+
+        match calculator.process(short_expression.as_str()) {
+            Ok(str_result) => assert_eq!(
+                str_result,
+                format!(
+                    "last = {}    [{}]",
+                    short_expression.len(),
+                    short_expression
+                )
+            ),
+            Err(_) => assert!(false),
+        }
+
+        let long_expression: String = String::from("1 + 2 + 3");
+
+        match calculator.process(long_expression.as_str()) {
+            Ok(str_result) => assert_eq!(
+                str_result,
+                format!("last = {}    [1 + 2…]", long_expression.len())
+            ),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_structured_contains_full_expansion() {
+        let mut calculator = Calculator::new(evaluate);
+
+        calculator.set_echo_expansion(true);
+        calculator.set_echo_expansion_length(5);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+
+        let expansion: String = String::from("(2 + 1) * 3");
+
+        match calculator.process_structured("f(2) * 3") {
+            Ok(output) => {
+                assert_eq!(
+                    output.result,
+                    format!("last = {}    [(2 + …]", expansion.len())
+                );
+                assert_eq!(output.value, Some(Value::Number(expansion.len() as f64)));
+                assert_eq!(output.expansion, Some(expansion));
+            }
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process_structured("f: x = x - 1") {
+            Ok(output) => {
+                assert_eq!(output.value, None);
+                assert_eq!(output.expansion, None);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_initial_last() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert!(calculator.process("last + 1").is_err());
+
+        let mut calculator = Calculator::new(evaluate_arithmetic).initial_last(Some(0.0));
+
+        match calculator.process("last + 1") {
+            Ok(str_result) => assert_eq!(str_result, "last = 1"),
+            Err(_) => assert!(false),
+        }
+
+        let mut calculator = Calculator::new(evaluate_arithmetic).initial_last(Some(2.0));
+        assert_eq!(calculator.process("ans + 1"), Ok(String::from("last = 3")));
+
+        let calculator = Calculator::new(evaluate_arithmetic)
+            .initial_last(Some(0.0))
+            .initial_last(None);
+
+        assert!(calculator.variables.is_empty());
+    }
+
+    #[test]
+    fn test_calculator_variables_and_functions_with_prefix() {
+        let mut calculator = Calculator::new(evaluate);
+
+        assert!(calculator.process("radius = 2").is_ok());
+        assert!(calculator.process("radians = 3").is_ok());
+        assert!(calculator.process("rho = 4").is_ok());
+        assert!(calculator.process("radial: r = r * 2").is_ok());
+        assert!(calculator.process("rotate: r = r + 1").is_ok());
+
+        assert_eq!(
+            calculator.variables_with_prefix("rad"),
+            vec!["radians", "radius"]
+        );
+        assert_eq!(
+            calculator.variables_with_prefix("r"),
+            vec!["radians", "radius", "rho"]
+        );
+        assert!(calculator.variables_with_prefix("Rad").is_empty());
+
+        assert_eq!(calculator.functions_with_prefix("rad"), vec!["radial"]);
+        assert!(calculator.functions_with_prefix("x").is_empty());
+    }
+
+    #[test]
+    fn test_calculator_process_variable_defined_by_function_call() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("distance: x, y = x * x + y * y").is_ok());
+
+        match calculator.process_structured("d = distance(3, 4)") {
+            Ok(output) => {
+                assert_eq!(output.result, "d = 25");
+                assert_eq!(output.expansion, Some(String::from("(3 * 3 + 4 * 4)")));
+            }
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.variables["d"], 25.0);
+        assert_eq!(
+            calculator.variables["d"],
+            evaluate_arithmetic("(3 * 3 + 4 * 4)").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_calculator_process_function_using_redefined_function() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert!(calculator.process("g: x = f(x) * 2").is_ok());
+        assert!(calculator.process("f: x = x - 1").is_ok());
+
+        // By default, g uses the latest definition of f
+        assert_eq!(calculator.functions["g"].1, "f(x) * 2");
+
+        match calculator.process("g(3)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 4"),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_frozen_function_using_redefined_function() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+
+        match calculator.process("g!: x = f(x) * 2") {
+            Ok(str_result) => assert_eq!(str_result, "g(x) = (x + 1) * 2"),
+            Err(_) => assert!(false),
+        }
+
+        assert!(calculator.process("f: x = x - 1").is_ok());
+
+        // Frozen g keeps the definition of f given when g was defined
+        match calculator.process("g(3)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 8"),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_in_strict_mode() {
+        let mut calculator = Calculator::new(evaluate);
+
+        assert!(calculator.process("(a]").is_ok());
+
+        calculator.set_strict_mode(true);
+
+        assert!(calculator.process("(a]").is_err());
+        assert!(calculator.process("x = [a)").is_err());
+        assert!(calculator.process("([a])").is_ok());
+
+        assert_eq!(
+            calculator.process("x = 2 + 2 oops"),
+            Err(String::from("The token 'oops' at column 11 is unexpected"))
+        );
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+
+        assert_eq!(
+            calculator.process("f + 2"),
+            Err(String::from(
+                "The function f is referenced without call, its arguments between parenthesis are missing"
+            ))
+        );
+
+        assert!(calculator.process("f(1) + 2").is_ok());
+        assert!(calculator.process("g: f = f + 2").is_ok());
+
+        assert_eq!(
+            calculator.process("y = 2 * / 3"),
+            Err(String::from(
+                "The operator '/' at column 9 is missing its left operand"
+            ))
+        );
+
+        assert!(calculator.process("y = 2 * -3").is_ok());
+
+        // Trailing tokens and uncalled functions are accepted without strict mode
+        calculator.set_strict_mode(false);
+        assert!(calculator.process("x = 2 + 2 oops").is_ok());
+        assert!(calculator.process("f + 2").is_ok());
+    }
+
+    #[test]
+    fn test_calculator_process_definition_with_invalid_name_in_strict_mode() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_strict_mode(true);
+
+        match calculator.try_process("2 + 2 = 4") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(error.message(), "The variable name 2 + 2 is not valid");
+                assert_eq!(error.kind(), InputKind::Variable);
+            }
+        }
+
+        assert_eq!(
+            calculator.process("f-g: x = x"),
+            Err(String::from("The function name f-g is not valid (hint: this was interpreted as a function definition for 'f-g')"))
+        );
+
+        assert!(calculator.variables.is_empty());
+        assert!(calculator.functions.is_empty());
+
+        assert!(calculator.process("x = 2 + 2").is_ok());
+        assert!(calculator.process("g!: y = y * x").is_ok());
+    }
+
+    #[test]
+    fn test_calculator_analyze_expression_with_nested_functions() {
+        let mut calculator = Calculator::new(evaluate);
+
+        assert!(calculator.process("a = 2").is_ok());
+        assert!(calculator.process("b = 3").is_ok());
+        assert!(calculator.process("f: x = x + a").is_ok());
+        assert!(calculator.process("g: x = f(x) * f(b)").is_ok());
+
+        let expression: String = String::from("g(1) - a");
+        let expansion: String = String::from("((1 + 2) * (3 + 2)) - 2");
+
+        match calculator.analyze(expression.as_str()) {
+            Ok(analysis) => {
+                assert_eq!(analysis.expanded_length, expansion.len());
+                assert_eq!(analysis.function_expansions, 3);
+                assert_eq!(analysis.max_expansion_depth, 2);
+                // Variables of function definitions are replaced when functions are defined
+                assert_eq!(analysis.substituted_variables, 1);
+                assert_eq!(analysis.operator_count, 4);
+            }
+            Err(_) => assert!(false),
+        }
+
+        // Calculator is not modified
+        assert!(!calculator.variables.contains_key("last"));
+    }
+
+    #[test]
+    fn test_calculator_analyze_literal_expression() {
+        let calculator = Calculator::new(evaluate);
+
+        match calculator.analyze("2 + 3") {
+            Ok(analysis) => {
+                assert_eq!(analysis.expanded_length, 5);
+                assert_eq!(analysis.function_expansions, 0);
+                assert_eq!(analysis.max_expansion_depth, 0);
+                assert_eq!(analysis.substituted_variables, 0);
+                assert_eq!(analysis.operator_count, 1);
+            }
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_evaluate() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("f: y = y * x").is_ok());
+
+        assert_eq!(calculator.evaluate("f(3) + x"), Ok(8.0));
+        assert_eq!(calculator.evaluate("z = x + 1"), Ok(3.0));
+        assert!(calculator.evaluate("g: y = y + 1").is_err());
+
+        assert!(!calculator.variables.contains_key("last"));
+        assert!(!calculator.variables.contains_key("z"));
+        assert!(!calculator.functions.contains_key("g"));
+    }
+
+    #[test]
+    fn test_calculator_evaluate_with_overrides() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("y = 3").is_ok());
+
+        let mut overrides: HashMap<String, f64> = HashMap::new();
+        overrides.insert(String::from("x"), 10.0);
+        overrides.insert(String::from("t"), 0.5);
+
+        assert_eq!(calculator.evaluate_with("x * y + t", &overrides), Ok(30.5));
+
+        assert_eq!(
+            calculator.evaluate_batch_with(&["x + y", "x * t", "t + u"], &overrides),
+            vec![Ok(13.0), Ok(5.0), Err(String::from("Invalid number"))]
+        );
+
+        // Stored state is not modified by overrides
+        assert_eq!(calculator.variables.len(), 2);
+        assert_eq!(calculator.variables["x"], 2.0);
+        assert_eq!(calculator.evaluate("x + y"), Ok(5.0));
+    }
+
+    #[test]
+    fn test_calculator_process_with_number_locale() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        // Decimal separator cannot be the argument separator
+        assert_eq!(
+            calculator.set_number_locale(NumberLocale::new(',', Some(' ')).unwrap()),
+            Err(String::from(
+                "The argument separator ',' cannot be the decimal separator of number locale"
+            ))
+        );
+        assert!(calculator.number_locale.is_canonical());
+
+        assert!(calculator.set_argument_separator(';').is_ok());
+        assert!(calculator
+            .set_number_locale(NumberLocale::new(',', Some(' ')).unwrap())
+            .is_ok());
+
+        assert_eq!(
+            calculator.set_argument_separator(','),
+            Err(String::from(
+                "The argument separator ',' cannot be the decimal separator of number locale"
+            ))
+        );
+
+        match calculator.process("x = 3,5") {
+            Ok(str_result) => assert_eq!(str_result, "x = 3,5"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("y = x * 1000") {
+            Ok(str_result) => assert_eq!(str_result, "y = 3 500"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("f: a; b = a * 1,5 + b") {
+            Ok(str_result) => assert_eq!(str_result, "f(a; b) = a * 1,5 + b"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("f(2; 0,5)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 3,5"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.variables["x"], 3.5);
+    }
+
+    #[test]
+    fn test_calculator_export_script_in_dependency_order() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("a: x = b(x) + 1").is_ok());
+        assert!(calculator.process("b: x = c(x) * 2").is_ok());
+        assert!(calculator.process("c: x = x + 1").is_ok());
+        assert!(calculator.process("d: x = x - 1").is_ok());
+
+        let script: String = calculator.export_script();
+        assert_eq!(
+            script,
+            "c: x = x + 1\nd: x = x - 1\nb: x = c(x) * 2\na: x = b(x) + 1\n"
+        );
+
+        let mut imported_calculator = Calculator::new(evaluate_arithmetic);
+        imported_calculator.set_strict_mode(true);
+
+        assert!(imported_calculator.load_script(script.as_str()).is_ok());
+        assert_eq!(imported_calculator.functions, calculator.functions);
+        assert_eq!(imported_calculator.evaluate("a(1)"), Ok(5.0));
+    }
+
+    #[test]
+    fn test_calculator_export_script_with_cyclic_functions() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("p: x = q(x) + 1").is_ok());
+        assert!(calculator.process("q: x = p(x) - 1").is_ok());
+        assert!(calculator.process("r: x = p(x) * 2").is_ok());
+        assert!(calculator.process("s: x = x * 2").is_ok());
+
+        let script: String = calculator.export_script();
+        assert_eq!(
+            script,
+            "s: x = x * 2\n# functions depending on a cycle of calls: p, q, r\np: x = q(x) + 1\nq: x = p(x) - 1\nr: x = p(x) * 2\n"
+        );
+
+        let mut imported_calculator = Calculator::new(evaluate_arithmetic);
+        assert!(imported_calculator.load_script(script.as_str()).is_ok());
+        assert_eq!(imported_calculator.functions, calculator.functions);
+    }
+
+    #[test]
+    fn test_calculator_export_script_with_number_locale() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert!(calculator.set_argument_separator(';').is_ok());
+        assert!(calculator
+            .set_number_locale(NumberLocale::new(',', Some(' ')).unwrap())
+            .is_ok());
+
+        assert!(calculator.process("x = 3,5").is_ok());
+        assert!(calculator.process("y = x * 1000").is_ok());
+        assert!(calculator.process("f: a = a * 1,5").is_ok());
+
+        let script: String = calculator.export_script();
+        assert_eq!(script, "x = 3.5\ny = 3500\nf: a = a * 1.5\n");
+
+        // Script is read with canonical convention whatever the number locale
+        let mut imported_calculator = Calculator::new(evaluate_arithmetic);
+        assert!(imported_calculator.set_argument_separator(';').is_ok());
+        assert!(imported_calculator
+            .set_number_locale(NumberLocale::new(',', Some(' ')).unwrap())
+            .is_ok());
+
+        assert!(imported_calculator.load_script(script.as_str()).is_ok());
+        assert_eq!(imported_calculator.variables, calculator.variables);
+        assert_eq!(imported_calculator.functions, calculator.functions);
+        assert_eq!(imported_calculator.export_script(), script);
+    }
+
+    #[test]
+    fn test_calculator_process_with_semicolon_argument_separator() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(calculator.set_argument_separator(';'), Ok(()));
+        assert!(calculator
+            .set_number_locale(NumberLocale::new(',', None).unwrap())
+            .is_ok());
+
+        match calculator.process("f: a; b = a * 1,5 + b") {
+            Ok(str_result) => assert_eq!(str_result, "f(a; b) = a * 1,5 + b"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("f(2; f(1,5; 0))") {
+            Ok(str_result) => assert_eq!(str_result, "last = 5,25"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process_line("x = f(1; 1); x * 2") {
+            Ok(results) => assert_eq!(results, vec!["x = 2,5", "last = 5"]),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.call_function("f", &[2.0, 1.0]), Ok(4.0));
+
+        assert_eq!(
+            calculator.set_argument_separator('|'),
+            Err(String::from(
+                "The argument separator | is not valid, it must be ',' or ';'"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_calculator_process_with_comma_in_semicolon_mode() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert!(calculator.set_argument_separator(';').is_ok());
+
+        assert!(calculator.process("f: a; b = a + b").is_ok());
+
+        assert_eq!(
+            calculator.process("f(1; 2, 3)"),
+            Err(String::from(
+                "The argument separator ',' at column 7 is not valid, arguments are separated by ';'"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("g: a, b = a * b"),
+            Err(String::from(
+                "The argument separator ',' at column 5 is not valid, arguments are separated by ';'"
+            ))
+        );
+
+        assert!(!calculator.functions.contains_key("g"));
+    }
+
+    #[test]
+    fn test_calculator_export_script_with_semicolon_argument_separator() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert!(calculator.set_argument_separator(';').is_ok());
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("f: a; b = a * b").is_ok());
+        assert!(calculator.process("g: a; b = f(a; b) + x").is_ok());
+
+        let script: String = calculator.export_script();
+        assert_eq!(script, "x = 2\nf: a; b = a * b\ng: a; b = f(a; b) + 2\n");
+
+        let mut imported_calculator = Calculator::new(evaluate_arithmetic);
+        assert!(imported_calculator.set_argument_separator(';').is_ok());
+
+        assert!(imported_calculator.load_script(script.as_str()).is_ok());
+        assert_eq!(imported_calculator.variables, calculator.variables);
+        assert_eq!(imported_calculator.functions, calculator.functions);
+        assert_eq!(imported_calculator.export_script(), script);
+        assert_eq!(imported_calculator.evaluate("g(3; 4)"), Ok(14.0));
+    }
+
+    #[test]
+    fn test_calculator_process_with_custom_evaluator_error() {
+        let mut calculator = Calculator::new(evaluate_division);
+
+        assert_eq!(evaluate_division("1 / 0"), Err(CustomError::DivisionByZero));
+
+        match calculator.process("f: x = 6 / x") {
+            Ok(str_result) => assert_eq!(str_result, "f(x) = 6 / x"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("f(4)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 1.5"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(
+            calculator.process("f(0)"),
+            Err(String::from("Division by zero"))
+        );
+
+        assert_eq!(
+            calculator.process("y = 2 / z"),
+            Err(String::from("Unknown token z"))
+        );
+
+        assert_eq!(
+            calculator.evaluate("f(0)"),
+            Err(String::from("Division by zero"))
+        );
+        match calculator.try_process("y = 2 / z") {
+            Ok(_) => assert!(false),
+            Err(error) => match error.cause() {
+                Some(Cause::Evaluator(evaluator_error)) => {
+                    assert_eq!(evaluator_error.message(), "Unknown token z");
+                    assert_eq!(
+                        evaluator_error.downcast_ref::<CustomError>(),
+                        Some(&CustomError::UnknownToken(String::from("z")))
+                    );
+                    assert_eq!(evaluator_error.downcast_ref::<String>(), None);
+                }
+                _ => assert!(false),
+            },
+        }
+    }
+
+    #[test]
+    fn test_string_calculator() {
+        let mut calculators: Vec<StringCalculator> = vec![
+            StringCalculator::new(evaluate),
+            StringCalculator::new(evaluate_arithmetic),
+        ];
+
+        for calculator in calculators.iter_mut() {
+            assert!(calculator.process("x = 1 + 1").is_ok());
+        }
+
+        assert_eq!(calculators[0].variables["x"], 5.0);
+        assert_eq!(calculators[1].variables["x"], 2.0);
+    }
+
+    #[test]
+    fn test_calculator_pretty_definitions() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        match calculator.process("f: x, y = x*x+ y *y") {
+            Ok(str_result) => assert_eq!(str_result, "f(x, y) = x*x+ y *y"),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_pretty_definitions(true);
+
+        match calculator.process("g: x = -( x+1 )*2") {
+            Ok(str_result) => assert_eq!(str_result, "g(x) = -(x + 1) * 2"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(
+            calculator.export_script(),
+            "f: x, y = x * x + y * y\ng: x = -(x + 1) * 2\n"
+        );
+
+        // Stored definitions are not modified
+        assert_eq!(calculator.functions["f"].1, "x*x+ y *y");
+    }
+
+    #[test]
+    fn test_calculator_load_script_with_error() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        let script: &str = "# constants\nx = 2\n\ny = x +\nz = 3\n";
+
+        assert_eq!(
+            calculator.load_script(script),
+            Err(String::from("Line 4: Invalid number"))
+        );
+
+        assert!(calculator.variables.contains_key("x"));
+        assert!(!calculator.variables.contains_key("z"));
+    }
+
+    #[test]
+    fn test_calculator_reset_to_defaults() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        let defaults: &str = "# defaults\nratio = 2\nscale: x = x * ratio\n";
+
+        assert!(calculator.load_script(defaults).is_ok());
+        assert!(calculator.process("ratio = 5").is_ok());
+        assert!(calculator.process("y = 3").is_ok());
+        assert!(calculator.process("g: x = x + 1").is_ok());
+        assert!(calculator.process("7").is_ok());
+
+        assert!(calculator.reset_to_defaults(defaults).is_ok());
+
+        assert_eq!(calculator.variables.len(), 1);
+        assert_eq!(calculator.variables["ratio"], 2.0);
+
+        assert_eq!(calculator.functions.len(), 1);
+        assert!(calculator.functions.contains_key("scale"));
+    }
+
+    #[test]
+    fn test_calculator_process_with_typed_values() {
+        let mut calculator = Calculator::new(TypedEvaluator);
+
+        match calculator.process("b = 2 == 2") {
+            Ok(str_result) => assert_eq!(str_result, "b = true"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("n = 42") {
+            Ok(str_result) => assert_eq!(str_result, "n = 42"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.process("x = 1.5 * 2") {
+            Ok(str_result) => assert_eq!(str_result, "x = 3"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.variables["b"], Value::Bool(true));
+        assert_eq!(calculator.variables["n"], Value::Integer(42));
+        assert_eq!(calculator.variables["x"], Value::Number(3.0));
+
+        assert_eq!(calculator.evaluate("b"), Ok(1.0));
+        assert_eq!(calculator.export_script(), "b = true\nn = 42\nx = 3\n");
+    }
+
+    #[test]
+    fn test_calculator_process_with_boolean_substitution() {
+        let mut calculator = Calculator::new(TypedEvaluator);
+
+        assert!(calculator.process("b = 1 == 2").is_ok());
+
+        match calculator.process("b") {
+            Ok(str_result) => assert_eq!(str_result, "last = false"),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_boolean_substitution(BooleanSubstitution::Numeric);
+
+        match calculator.process("b") {
+            Ok(str_result) => assert_eq!(str_result, "last = 0"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.variables["last"], Value::Integer(0));
+    }
+
+    #[test]
+    fn test_calculator_last_expanded() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(calculator.last_expanded(), None);
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert_eq!(calculator.last_expanded(), None);
+
+        match calculator.process("f(2)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 3"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.last_expanded(), Some("(2 + 1)"));
+
+        assert!(calculator.process("y = f(last) * 2").is_ok());
+        assert_eq!(calculator.last_expanded(), Some("(3 + 1) * 2"));
+
+        // Function definition keeps last expansion
+        assert!(calculator.process("g: x = x * 2").is_ok());
+        assert_eq!(calculator.last_expanded(), Some("(3 + 1) * 2"));
+    }
+
+    #[test]
+    fn test_calculator_process_function_with_parameter_shadowing_variable() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 10").is_ok());
+        assert!(calculator.process("y = 2").is_ok());
+
+        // Parameter x stays symbolic while variable y is replaced by its value
+        match calculator.process("f: x = x + y") {
+            Ok(str_result) => assert_eq!(str_result, "f(x) = x + 2"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.functions["f"].1, "x + 2");
+
+        match calculator.process("f(3)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 5"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.last_expanded(), Some("(3 + 2)"));
+
+        // Variable x is used outside of function body
+        match calculator.process("f(x) + x") {
+            Ok(str_result) => assert_eq!(str_result, "last = 22"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.last_expanded(), Some("(10 + 2) + 10"));
+        assert_eq!(calculator.variables["x"], 10.0);
+    }
+
+    #[cfg(feature = "limits")]
+    #[test]
+    fn test_calculator_process_expression_with_too_long_token() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        let blob: String = "QUJD".repeat(512 * 1024);
+
+        assert_eq!(
+            calculator.process(format!("x = 1 + {}", blob).as_str()),
+            Err(String::from(
+                "The token is too long at column 9 (maximal length is 4096)"
+            ))
+        );
+
+        match calculator.try_process(format!("x = 1 + {}", blob).as_str()) {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.cause(),
+                Some(&Cause::TokenTooLong {
+                    column: 9,
+                    limit: 4096,
+                })
+            ),
+        }
+
+        assert!(calculator
+            .evaluate(format!("2 * {}", "9".repeat(5000)).as_str())
+            .is_err());
+
+        assert!(!calculator.variables.contains_key("x"));
+
+        calculator.set_limits(Limits::default().with_max_token_length(8));
+
+        assert!(calculator.process("velocity = 2").is_ok());
+        assert!(calculator.process("acceleration = 2").is_err());
+    }
+
+    #[cfg(feature = "limits")]
+    #[test]
+    fn test_calculator_max_function_expansions() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("a: x = x + 1").is_ok());
+
+        for (name, called_name) in [("b", "a"), ("c", "b"), ("d", "c"), ("e", "d"), ("f", "e")] {
+            let definition: String =
+                format!("{}: x = {}(x) + {}(x)", name, called_name, called_name);
+            assert!(calculator.process(definition.as_str()).is_ok());
+        }
+
+        assert_eq!(calculator.process("f(1)"), Ok(String::from("last = 64")));
+        assert_eq!(calculator.expansion_count(), 63);
+
+        match calculator.analyze("f(1)") {
+            Ok(analysis) => assert_eq!(analysis.function_expansions, 63),
+            Err(_) => assert!(false),
+        }
+
+        calculator.set_limits(Limits::default().with_max_function_expansions(40));
+
+        match calculator.try_process("2 * f(1)") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(
+                    error.message(),
+                    "The maximal number of function expansions (40) is reached during expansion of function f"
+                );
+                assert_eq!(
+                    error.cause(),
+                    Some(&Cause::TooManyExpansions {
+                        function: String::from("f"),
+                        performed: 40,
+                        limit: 40,
+                    })
+                );
+            }
+        }
+
+        assert_eq!(
+            calculator.process("c(1) + a(1)"),
+            Ok(String::from("last = 10"))
+        );
+        assert_eq!(calculator.expansion_count(), 8);
+
+        // Limit also applies without processing
+        let limit_message: String = String::from(
+            "The maximal number of function expansions (40) is reached during expansion of function f",
+        );
+
+        assert_eq!(calculator.evaluate("f(1)"), Err(limit_message.clone()));
+        assert_eq!(calculator.call_function("f", &[1.0]), Err(limit_message));
+        assert_eq!(calculator.call_function("e", &[1.0]), Ok(32.0));
+
+        calculator.set_limits(Limits::default().with_max_function_expansions(100));
+        assert_eq!(calculator.evaluate("f(1)"), Ok(64.0));
+    }
+
+    #[cfg(feature = "limits")]
+    #[test]
+    fn test_calculator_max_expression_length() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_max_expression_length(Some(10));
+
+        assert!(calculator.process("  x = 1 + 2  ").is_ok());
+
+        assert_eq!(
+            calculator.process("y = 1 + 2 + 3"),
+            Err(String::from(
+                "The expression is too long, its length is 13 (maximal length is 10)"
+            ))
+        );
+
+        assert!(calculator.process(":fix 12345678").is_err());
+        assert!(calculator.evaluate("x + x + x + x").is_err());
+        assert!(calculator
+            .load_script("# a long comment line\nz = x * 2")
+            .is_ok());
+        assert!(!calculator.variables.contains_key("y"));
+
+        match calculator.try_process("y = 1 + 2 + 3") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.cause(),
+                Some(&Cause::ExpressionTooLong {
+                    length: 13,
+                    limit: 10,
+                })
+            ),
+        }
+
+        // Length is checked before any other check of input, as unsafe characters
+        match calculator.try_process("y = 1 + \u{202e}2 + 3") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.cause(),
+                Some(&Cause::ExpressionTooLong {
+                    length: 14,
+                    limit: 10,
+                })
+            ),
+        }
+
+        // Substitution stops as soon as expansion exceeds limit
+        calculator.set_max_expression_length(Some(20));
+
+        assert!(calculator.process("g: y = y * y * y * y").is_ok());
+        assert_eq!(calculator.process("g(2)"), Ok(String::from("last = 16")));
+
+        match calculator.try_process("g(g(g(g(2))))") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(
+                    error.message(),
+                    "The expansion is too long during expansion of function g (maximal length is 20)"
+                );
+                assert_eq!(
+                    error.cause(),
+                    Some(&Cause::ExpansionTooLong {
+                        function: Some(String::from("g")),
+                        limit: 20,
+                    })
+                );
+            }
+        }
+
+        assert!(calculator.process("w = 1234567.5").is_ok());
+
+        match calculator.try_process("w + w + w") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.cause(),
+                Some(&Cause::ExpansionTooLong {
+                    function: None,
+                    limit: 20,
+                })
+            ),
+        }
+
+        assert!(calculator.expand("w + w").is_err());
+
+        calculator.set_max_expression_length(None);
+
+        assert!(calculator.process("y = 1 + 2 + 3").is_ok());
+        assert!(calculator.process("w + w + w").is_ok());
+    }
+
+    #[test]
+    fn test_calculator_legacy_error_messages() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x, y = x + y").is_ok());
+
+        // Messages of String-based API are pinned, code matching them must keep working
+        let inputs_and_messages: [(&str, &str); 3] = [
+            ("f(1)", "The number of variables is not consistent"),
+            ("f(1, 2", "The closing parenthesis of function f is missing"),
+            (
+                "z = f(1, 2, 3) * 2",
+                "The number of variables is not consistent",
+            ),
+        ];
+
+        for (input, message) in inputs_and_messages {
+            assert_eq!(calculator.process(input), Err(String::from(message)));
+
+            match calculator.try_process(input) {
+                Ok(_) => assert!(false),
+                Err(error) => {
+                    assert_eq!(error.legacy_message(), message);
+                    assert_eq!(error.message(), message);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_calculator_substitute_some() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("y = 3").is_ok());
+        assert!(calculator.process("xy = 4").is_ok());
+
+        assert_eq!(
+            calculator.substitute_some("x + y", &["x"]),
+            Ok(String::from("2 + y"))
+        );
+        assert_eq!(
+            calculator.substitute_some("x * xy + `x", &["x", "xy"]),
+            Ok(String::from("2 * 4 + x"))
+        );
+        assert_eq!(
+            calculator.substitute_some("x + y", &["z"]),
+            Err(String::from("The variable z is not defined"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_call_counts() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert!(calculator.process("g: x = f(x) * 2").is_ok());
+
+        let counts: HashMap<String, usize> = calculator.call_counts("f(1) + f(2) + g(3)");
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["f"], 2);
+        assert_eq!(counts["g"], 1);
+
+        assert!(calculator.call_counts("1 + x").is_empty());
+    }
+
+    #[test]
+    fn test_calculator_process_with_notation() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        calculator.set_notation(Notation::Engineering);
+
+        match calculator.process("x = 0.0000001234") {
+            Ok(str_result) => assert_eq!(str_result, "x = 123.4e-9"),
+            Err(_) => assert!(false),
+        }
+
+        // Substitution uses stored value whatever the notation
+        assert!(calculator.process("y = x * 2").is_ok());
+        assert_eq!(calculator.last_expanded(), Some("0.0000001234 * 2"));
+
+        assert!(calculator.set_argument_separator(';').is_ok());
+        assert!(calculator
+            .set_number_locale(NumberLocale::new(',', None).unwrap())
+            .is_ok());
+        calculator.set_notation(Notation::Scientific);
+
+        match calculator.process("x * 4") {
+            Ok(str_result) => assert_eq!(str_result, "last = 4,936e-7"),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_with_significant_figures() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        calculator.set_notation(Notation::Fixed(4));
+        calculator.set_significant_figures(Some(2));
+
+        assert_eq!(
+            calculator.process("x = 123.456"),
+            Ok(String::from("x = 120"))
+        );
+        assert_eq!(
+            calculator.process("y = 0.004567"),
+            Ok(String::from("y = 0.0046"))
+        );
+
+        // Stored value is kept unchanged
+        assert!(calculator.process("x + 0").is_ok());
+        assert_eq!(calculator.last_expanded(), Some("123.456 + 0"));
+
+        calculator.set_notation(Notation::Fixed(1));
+        assert_eq!(calculator.process("x"), Ok(String::from("last = 123.5")));
+
+        calculator.set_significant_figures(None);
+        assert_eq!(calculator.process("y"), Ok(String::from("last = 0.004567")));
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_process_notation_commands() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 1234.5678").is_ok());
+
+        let commands: Vec<(&str, &str, &str)> = vec![
+            (":sci", "notation = scientific", "last = 1.2345678e3"),
+            (":eng", "notation = engineering", "last = 1.2345678e3"),
+            (":fix 2", "notation = fixed 2", "last = 1234.57"),
+            (" :auto", "notation = auto", "last = 1234.5678"),
+        ];
+
+        for (command, command_result, str_result) in commands {
+            assert_eq!(
+                calculator.process(command),
+                Ok(String::from(command_result))
+            );
+            assert_eq!(calculator.process("x"), Ok(String::from(str_result)));
+        }
+
+        assert_eq!(
+            calculator.process(":fix"),
+            Err(String::from(
+                "The number of arguments of command :fix is not valid"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process(":fix two"),
+            Err(String::from("The number of decimals two is not valid"))
+        );
+
+        assert!(calculator.process(":fix 17").is_ok());
+        assert!(calculator.process(":auto").is_ok());
+
+        assert_eq!(
+            calculator.process(":fix 18"),
+            Err(String::from(
+                "The number of decimals 18 is too large (maximal number is 17)"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process(":plot"),
+            Err(String::from(
+                "The command :plot is unknown, available commands are :at, :auto, :eng, :fix, :help, :metrics, :preview, :results, :save, :sci, :snapshots"
+            ))
+        );
+
+        assert_eq!(calculator.notation, Notation::Auto);
+    }
+
+    #[test]
+    fn test_calculator_process_error_with_hint() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(
+            calculator.process("y - 2 = x"),
+            Err(String::from(
+                "Invalid number (hint: this was interpreted as a variable definition for 'y - 2'; if you meant a comparison, use '==')"
+            ))
+        );
+
+        match calculator.try_process("y - 2 = x") {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(error.message(), "Invalid number");
+                assert_eq!(error.kind(), InputKind::Variable);
+                assert_eq!(error.name(), Some("y - 2"));
+            }
+        }
+
+        // Ordinary errors have no hint
+        assert_eq!(
+            calculator.process("y = x"),
+            Err(String::from("Invalid number"))
+        );
+
+        assert_eq!(
+            calculator.process("1 +"),
+            Err(String::from("Invalid number"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_evaluate_as_fraction() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 3").is_ok());
+
+        assert_eq!(calculator.evaluate_as_fraction("1 / 2", 100), Ok((1, 2)));
+        assert_eq!(calculator.evaluate_as_fraction("1 / x", 10), Ok((1, 3)));
+        assert_eq!(calculator.evaluate_as_fraction("-6 / 4", 10), Ok((-3, 2)));
+        assert_eq!(calculator.evaluate_as_fraction("x", 10), Ok((3, 1)));
+
+        // Approximations of pi
+        assert_eq!(
+            calculator.evaluate_as_fraction("3.14159265358979", 10),
+            Ok((22, 7))
+        );
+
+        assert_eq!(
+            calculator.evaluate_as_fraction("3.14159265358979", 1000),
+            Ok((355, 113))
+        );
+
+        assert!(calculator.evaluate_as_fraction("1 / 0", 10).is_err());
+        assert!(calculator.evaluate_as_fraction("1 / 2", 0).is_err());
+    }
+
+    #[test]
+    fn test_calculator_disable_function() {
+        let mut calculator = Calculator::new(evaluate);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert!(calculator.set_function_enabled("f", false).is_ok());
+
+        match calculator.process("f(2)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 4"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.last_expanded(), Some("f(2)"));
+        assert!(calculator.functions.contains_key("f"));
+
+        assert!(calculator.set_function_enabled("f", true).is_ok());
+
+        match calculator.process("f(2)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 7"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.last_expanded(), Some("(2 + 1)"));
+
+        assert_eq!(
+            calculator.set_function_enabled("g", false),
+            Err(String::from("The function g is not defined"))
+        );
+    }
+
+    #[cfg(feature = "builtin-eval")]
+    #[test]
+    fn test_calculator_process_with_parse_error() {
+        let mut calculator = Calculator::new(evaluator::BuiltinEvaluator::new());
+
+        assert!(calculator.process("f: x = x *").is_ok());
+
+        match calculator.try_process("f(2) + 1") {
+            Ok(_) => assert!(false),
+            Err(error) => match error.cause() {
+                Some(Cause::Evaluator(evaluator_error)) => {
+                    let parse_error: &evaluator::ParseError =
+                        evaluator_error.downcast_ref().unwrap();
+
+                    assert_eq!(parse_error.expression(), "(2 *) + 1");
+                    assert_eq!(parse_error.span(), 4..5);
+                    assert_eq!(parse_error.render().lines().nth(2), Some("    ^"));
+                }
+                _ => assert!(false),
+            },
+        }
+    }
+
+    #[cfg(feature = "builtin-eval")]
+    #[test]
+    fn test_calculator_comparison_epsilon() {
+        let mut calculator = Calculator::new(evaluator::BuiltinEvaluator::new());
+
+        assert_eq!(
+            calculator.process("0.1 + 0.2 == 0.3"),
+            Ok(String::from("last = 0"))
+        );
+
+        assert!(calculator.set_comparison_epsilon(1e-12).is_ok());
+
+        assert_eq!(
+            calculator.process("0.1 + 0.2 == 0.3"),
+            Ok(String::from("last = 1"))
+        );
+        assert_eq!(
+            calculator.process("0.3 < 0.1 + 0.2"),
+            Ok(String::from("last = 1"))
+        );
+
+        assert_eq!(
+            calculator.set_comparison_epsilon(-1e-12),
+            Err(String::from(
+                "The comparison epsilon -0.000000000001 is not valid, it must be a finite number not lower than 0"
+            ))
+        );
+        assert!(calculator.set_comparison_epsilon(f64::NAN).is_err());
+        assert!(calculator.set_comparison_epsilon(f64::INFINITY).is_err());
+
+        assert_eq!(
+            calculator.process("0.1 + 0.2 == 0.3"),
+            Ok(String::from("last = 1"))
+        );
+    }
+
+    #[cfg(feature = "builtin-eval")]
+    #[test]
+    fn test_calculator_process_with_builtin_evaluator() {
+        let mut calculator = Calculator::new(evaluator::BuiltinEvaluator::new());
+
+        assert!(calculator.process("f: x, y = x * x + y * y").is_ok());
+
+        match calculator.process("f(3, 4) - 5") {
+            Ok(str_result) => assert_eq!(str_result, "last = 20"),
+            Err(_) => assert!(false),
+        }
+
+        match calculator.try_process("x = 2 *") {
+            Ok(_) => assert!(false),
+            Err(error) => assert_eq!(
+                error.message(),
+                "Expected a number, an identifier or '(' after operator '*' at column 4, found end of expression"
+            ),
+        }
+    }
+
+    #[cfg(feature = "builtin-eval")]
+    #[test]
+    fn test_calculator_process_vectors_with_builtin_evaluator() {
+        let mut calculator = Calculator::new(evaluator::BuiltinEvaluator::new());
+
+        let results: Vec<String> = [
+            "v = [1, 2, 3]",
+            "w = [4, -5, 6]",
+            "scale: u, k = u * k",
+            "scale(v, 2)",
+            "dot(v, w)",
+            "len(w) + v[0]",
+            "scale([1, 2], v[2])",
+        ]
+        .iter()
+        .map(|line| calculator.process(line).unwrap_or_else(|error| error))
+        .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                "v = [1, 2, 3]",
+                "w = [4, -5, 6]",
+                "scale(u, k) = u * k",
+                "last = [2, 4, 6]",
+                "last = 12",
+                "last = 4",
+                "last = [3, 6]",
+            ]
+        );
+
+        assert_eq!(
+            calculator.variables["v"],
+            Value::Vector(vec![1.0, 2.0, 3.0])
+        );
+        assert_eq!(calculator.expand("v[1]"), Ok(String::from("[1, 2, 3][1]")));
+
+        assert_eq!(
+            calculator.process("v[3]"),
+            Err(String::from(
+                "The index 3 is out of range for a vector of length 3"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("v + [1, 2]"),
+            Err(String::from(
+                "The vectors of lengths 3 and 2 cannot be combined by operator '+'"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("dot(v, [1, 2])"),
+            Err(String::from(
+                "The dot product of vectors of lengths 3 and 2 is not defined"
+            ))
+        );
+
+        // Numeric API does not convert a vector to NaN
+        let vector_error: Result<f64, String> = Err(String::from(
+            "The value [2, 4, 6] cannot be converted to a real number",
+        ));
+
+        assert_eq!(calculator.evaluate("scale(v, 2)"), vector_error);
+        assert_eq!(calculator.call_function("scale", &[2.0, 1.0]), Ok(2.0));
+        assert_eq!(calculator.define("u", "v * 2"), vector_error);
+        assert_eq!(calculator.evaluate("dot(v, v)"), Ok(14.0));
+
+        // Elements of a vector are separated by `,` whatever the argument separator
+        assert!(calculator.set_argument_separator(';').is_ok());
+        assert_eq!(
+            calculator.process("scale([1, 2]; 3)"),
+            Ok(String::from("last = [3, 6]"))
+        );
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn test_calculator_process_with_complex_evaluator() {
+        let mut calculator = Calculator::with_complex_evaluator();
+
+        // Impedance of a resistor in parallel with a capacitor and in series with an inductor
+        let results: Vec<String> = [
+            "r = 3 + 4i",
+            "c = 1 - 2i",
+            "parallel: a, b = a * b / (a + b)",
+            "parallel(r, c) + 0.5j",
+        ]
+        .iter()
+        .map(|line| calculator.process(line).unwrap_or_else(|error| error))
+        .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                "r = 3 + 4i",
+                "c = 1 - 2i",
+                "parallel(a, b) = a * b / (a + b)",
+                "last = 2 - 1i",
+            ]
+        );
+
+        assert_eq!(
+            calculator.expand("parallel(r, c)"),
+            Ok(String::from(
+                "((3 + 4i) * (1 - 2i) / ((3 + 4i) + (1 - 2i)))"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("abs(3+4i)"),
+            Ok(String::from("last = 5"))
+        );
+
+        calculator.set_notation(Notation::Fixed(2));
+        assert_eq!(
+            calculator.process("r / 3"),
+            Ok(String::from("last = 1.00 + 1.33i"))
+        );
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn test_calculator_process_reserved_name_with_complex_evaluator() {
+        let mut calculator = Calculator::with_complex_evaluator();
+
+        assert_eq!(
+            calculator.process("i = 2"),
+            Err(String::from(
+                "The variable name i is reserved by evaluator and cannot be defined"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("j: x = x + 1"),
+            Err(String::from(
+                "The function name j is reserved by evaluator and cannot be defined"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process("f: x, i = x + i"),
+            Err(String::from(
+                "The variable name i is reserved by evaluator and cannot be defined"
+            ))
+        );
+
+        assert!(calculator.variables.is_empty());
+        assert!(calculator.functions.is_empty());
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_calculator_process_with_decimal_evaluator() {
+        let mut calculator = Calculator::with_decimal_evaluator();
+
+        match calculator.process("0.1 + 0.2") {
+            Ok(str_result) => assert_eq!(str_result, "last = 0.3"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(
+            calculator.variables["last"],
+            Value::Decimal(rust_decimal::Decimal::new(3, 1))
+        );
+
+        // Substitution of a decimal variable keeps its exact value
+        assert!(calculator.process("price = 19.99").is_ok());
+
+        match calculator.process("total = price * 3 - 0.97") {
+            Ok(str_result) => assert_eq!(str_result, "total = 59"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(calculator.last_expanded(), Some("19.99 * 3 - 0.97"));
+        assert_eq!(
+            calculator.variables["total"],
+            Value::Decimal(rust_decimal::Decimal::new(59, 0))
+        );
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_calculator_process_with_decimal_rounding() {
+        let mut evaluator = evaluator::DecimalEvaluator::new();
+        evaluator.set_decimals(Some(2));
+
+        let mut calculator = Calculator::new(evaluator);
+
+        let inputs_and_results: [(&str, &str); 3] = [
+            ("10 / 3", "last = 3.33"),
+            ("0.125 / 1", "last = 0.12"),
+            ("0.135 / 1", "last = 0.14"),
+        ];
+
+        for (input, result) in inputs_and_results {
+            match calculator.process(input) {
+                Ok(str_result) => assert_eq!(str_result, result),
+                Err(_) => assert!(false),
+            }
+        }
+
+        evaluator.set_rounding_strategy(rust_decimal::RoundingStrategy::MidpointAwayFromZero);
+        calculator = Calculator::new(evaluator);
+
+        match calculator.process("0.125 / 1") {
+            Ok(str_result) => assert_eq!(str_result, "last = 0.13"),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(
+            calculator.process("1 / (2 - 2)"),
+            Err(String::from("Division by zero"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_is_independent_of_definition_order() {
+        let definitions: Vec<&str> = vec![
+            "zeta = 3",
+            "alpha = 2",
+            "mid = 5.5",
+            "f: x = x * 2",
+            "area: x, y = x * y",
+            "g: x = f(x) + 3",
+        ];
+
+        // Definitions are independent, since variables are replaced in function body when it is defined
+
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        let mut reversed_calculator = Calculator::new(evaluate_arithmetic);
+
+        for definition in definitions.iter() {
+            assert!(calculator.process(definition).is_ok());
+        }
+
+        for definition in definitions.iter().rev() {
+            assert!(reversed_calculator.process(definition).is_ok());
+        }
+
+        let script: String = calculator.export_script();
+
+        assert_eq!(
+            script,
+            "alpha = 2\nmid = 5.5\nzeta = 3\narea: x, y = x * y\nf: x = x * 2\ng: x = f(x) + 3\n"
+        );
+
+        assert_eq!(reversed_calculator.export_script(), script);
+
+        assert_eq!(
+            calculator.variables_with_prefix(""),
+            reversed_calculator.variables_with_prefix("")
+        );
+
+        assert_eq!(
+            calculator.functions_with_prefix(""),
+            reversed_calculator.functions_with_prefix("")
+        );
+
+        assert_eq!(
+            calculator.process("g(mid) + area(alpha, zeta)"),
+            reversed_calculator.process("g(mid) + area(alpha, zeta)")
+        );
+    }
+
+    #[cfg(all(feature = "commands", feature = "history"))]
+    #[test]
+    fn test_calculator_register_command() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        calculator.register_command(
+            "sumvars",
+            Box::new(|context: &mut CommandContext, _: &[&str]| {
+                let sum: f64 = context.variables().values().map(Value::to_f64).sum();
+                context.set_variable("sum", Value::Number(sum))?;
+
+                return Ok(format!("sum = {}", sum));
+            }),
+        );
+
+        calculator.register_command(
+            ":define",
+            Box::new(|context: &mut CommandContext, arguments: &[&str]| {
+                return match arguments {
+                    [name, variable, definition] => context
+                        .set_function(name, &[variable], definition)
+                        .map(|_| {
+                            format!("{} defined after {} inputs", name, context.history().len())
+                        }),
+                    _ => Err(String::from("The command :define needs three arguments")),
+                };
+            }),
+        );
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("y = 3.5").is_ok());
+
+        assert_eq!(
+            calculator.process(":sumvars"),
+            Ok(String::from("sum = 5.5"))
+        );
+        assert_eq!(calculator.variables["sum"], Value::Number(5.5));
+
+        assert_eq!(
+            calculator.process(":define double x \"x * 2\""),
+            Ok(String::from("double defined after 3 inputs"))
+        );
+
+        assert_eq!(
+            calculator.process("double(sum)"),
+            Ok(String::from("last = 11"))
+        );
+
+        assert_eq!(
+            calculator.process(":define 2x x \"x * 2\""),
+            Err(String::from("The function name 2x is not valid"))
+        );
+
+        assert_eq!(
+            calculator.process(":plot"),
+            Err(String::from(
+                "The command :plot is unknown, available commands are :at, :auto, :define, :eng, :fix, :help, :metrics, :preview, :results, :save, :sci, :snapshots, :sumvars"
+            ))
+        );
+
+        assert_eq!(calculator.history().len(), 5);
+        assert_eq!(calculator.history()[4], "double(sum)");
+    }
+
+    // Define mock evaluator modifying its state during evaluation, as a random number generator
+    struct CountingEvaluator {
+        count: std::cell::Cell<u32>, // number of evaluated expressions
+    }
+
+    impl EvaluateValue for CountingEvaluator {
+        type Error = String;
+
+        fn evaluate(&self, expression: &str) -> Result<Value, String> {
+            self.count.set(self.count.get() + 1);
+
+            return match expression.trim() {
+                "count" => Ok(Value::Integer(i64::from(self.count.get()))),
+                _ => evaluate_arithmetic(expression).map(Value::Number),
+            };
+        }
+    }
+
+    #[test]
+    fn test_calculator_process_with_stateful_evaluator() {
+        let mut calculator = Calculator::new(CountingEvaluator {
+            count: std::cell::Cell::new(0),
+        });
+
+        assert_eq!(calculator.process("1 + 1"), Ok(String::from("last = 2")));
+        assert_eq!(calculator.process("count"), Ok(String::from("last = 2")));
+        assert_eq!(calculator.evaluate("count"), Ok(3.0));
+    }
+
+    // Define mock evaluator providing a native function shown in help
+    #[cfg(all(feature = "commands", feature = "builtin-eval"))]
+    struct NativeEvaluator;
+
+    #[cfg(all(feature = "commands", feature = "builtin-eval"))]
+    impl EvaluateValue for NativeEvaluator {
+        type Error = String;
+
+        fn evaluate(&self, expression: &str) -> Result<Value, String> {
+            return evaluate_arithmetic(expression).map(Value::Number);
+        }
+
+        fn syntax_help(&self) -> Vec<(String, String)> {
+            return vec![
+                (String::from("x + y"), String::from("addition")),
+                (
+                    String::from("hypot(x, y)"),
+                    String::from("native hypotenuse"),
+                ),
+            ];
+        }
+    }
+
+    #[cfg(all(feature = "commands", feature = "builtin-eval"))]
+    #[test]
+    fn test_calculator_process_help_command() {
+        let mut calculator = Calculator::new(NativeEvaluator);
+
+        assert_eq!(
+            calculator.process(":help"),
+            Ok(String::from(
+                "help topics = commands, functions, operators, variables"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process(":help operators"),
+            Ok(String::from(
+                "help on operators:\n  x + y        addition\n  hypot(x, y)  native hypotenuse"
+            ))
+        );
+
+        assert!(calculator.process("f: x = x * 2").is_ok());
+
+        assert_eq!(
+            calculator.process(":help functions"),
+            Ok(String::from(
+                "help on functions:\n  \
+                name: x, y = expression  define a function of variables x and y\n  \
+                name: 2 = $1 * $2        define a function of positional variables\n  \
+                name!: x = expression    define a function whose calls are expanded at definition\n  \
+                name(1, 2)               call a function\n  \
+                f(x)                     x * 2"
+            ))
+        );
+
+        match calculator.process(":help commands") {
+            Ok(help) => assert!(help.contains(":help [topic]")),
+            Err(_) => assert!(false),
+        }
+
+        assert_eq!(
+            calculator.process(":help units"),
+            Err(String::from(
+                "The help topic units is unknown, available topics are commands, functions, operators, variables"
+            ))
+        );
+
+        let mut calculator = Calculator::new(evaluator::BuiltinEvaluator::new());
+
+        match calculator.process(":help operators") {
+            Ok(help) => assert!(help.contains("dot(u, v)")),
+            Err(_) => assert!(false),
+        }
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_register_command_not_sync() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        // Handler state which can be moved to another thread but not shared between threads
+        let count: std::cell::Cell<usize> = std::cell::Cell::new(0);
+
+        calculator.register_command(
+            "count",
+            Box::new(move |_: &mut CommandContext, _: &[&str]| {
+                count.set(count.get() + 1);
+                return Ok(format!("count = {}", count.get()));
+            }),
+        );
+
+        assert_eq!(calculator.process(":count"), Ok(String::from("count = 1")));
+        assert_eq!(calculator.process(":count"), Ok(String::from("count = 2")));
+
+        // Calculator can still be shared between threads behind a lock
+        fn assert_sync<T: Sync>(_: &T) {}
+        assert_sync(&calculator);
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_register_command_shadowing_builtin_command() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        calculator.register_command(
+            "sci",
+            Box::new(|context: &mut CommandContext, arguments: &[&str]| {
+                return Ok(format!(
+                    "custom sci with {} arguments in {:?} notation",
+                    arguments.len(),
+                    context.notation()
+                ));
+            }),
+        );
+
+        assert_eq!(
+            calculator.process(":sci 1 \"2 3\""),
+            Ok(String::from("custom sci with 2 arguments in Auto notation"))
+        );
+
+        assert_eq!(calculator.notation, Notation::Auto);
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_register_command_panicking() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        calculator.register_command(
+            "check",
+            Box::new(|_: &mut CommandContext, arguments: &[&str]| {
+                if arguments.is_empty() {
+                    panic!("missing argument");
+                }
+
+                return Ok(format!("checked {}", arguments[0]));
+            }),
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            return calculator.process(":check");
+        }));
+        assert!(result.is_err());
+
+        // Handler is still registered after its panic
+        assert_eq!(
+            calculator.process(":check x"),
+            Ok(String::from("checked x"))
+        );
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_calculator_max_history() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        for id in 0..(DEFAULT_MAX_HISTORY + 5) {
+            assert!(calculator.process(format!("x = {}", id).as_str()).is_ok());
+        }
+
+        assert_eq!(calculator.history().len(), DEFAULT_MAX_HISTORY);
+        assert_eq!(calculator.history()[0], "x = 5");
+
+        calculator.set_max_history(Some(2));
+        assert_eq!(calculator.history(), &["x = 1003", "x = 1004"]);
+
+        assert!(calculator.process("y = 1").is_ok());
+        assert_eq!(calculator.history(), &["x = 1004", "y = 1"]);
+
+        calculator.set_max_history(None);
+        assert!(calculator.process("z = 2").is_ok());
+        assert_eq!(calculator.history().len(), 3);
+    }
+
+    #[cfg(feature = "observer")]
+    #[test]
+    fn test_calculator_add_observer() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        let observed: std::sync::Arc<Mutex<Vec<String>>> =
+            std::sync::Arc::new(Mutex::new(Vec::new()));
+        let observed_by_observer: std::sync::Arc<Mutex<Vec<String>>> = observed.clone();
+
+        calculator.add_observer(Box::new(move |input: &str, output: &ProcessOutput| {
+            observed_by_observer
+                .lock()
+                .unwrap()
+                .push(format!("{} -> {}", input, output.result));
+        }));
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("x +").is_err());
+        assert!(calculator.process("x * 3").is_ok());
+
+        assert_eq!(
+            *observed.lock().unwrap(),
+            vec![
+                String::from("x = 2 -> x = 2"),
+                String::from("x * 3 -> last = 6")
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_calculator_state_serde() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("f: y = y * x").is_ok());
+
+        let state: CalculatorState = calculator.snapshot();
+        let json: String = serde_json::to_string(&state).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<CalculatorState>(json.as_str()).unwrap(),
+            state
+        );
+    }
+
+    #[test]
+    fn test_calculator_process_line_chaining_last() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(
+            calculator.process_line("2+2; last*2"),
+            Ok(vec![String::from("last = 4"), String::from("last = 8")])
+        );
+
+        assert_eq!(
+            calculator.process_line("x = last + 1;; f: y = y * x ; f(last);"),
+            Ok(vec![
+                String::from("x = 9"),
+                String::from("f(y) = y * 9"),
+                String::from("last = 72")
+            ])
+        );
+
+        assert_eq!(
+            calculator.process_line("1 + 1; 2 *; last"),
+            Err(String::from("Expression 2: Invalid number"))
+        );
 
-        let expression: String = format!("{} = {}", variable_name, variable_definition);
+        // Expressions before error are processed
+        assert_eq!(calculator.variables["last"], 2.0);
+    }
 
-        match calculator.process(expression.as_str()) {
-            Ok(str_result) => {
-                let variable_value: f64 = (first_variable_definition.len().to_string().len()
-                    + second_variable_definition.len().to_string().len()
-                    + 3) as f64;
+    #[test]
+    fn test_calculator_function_dependency_graph() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-                let str_reference: String = format!("{} = {}", variable_name, variable_value);
-                assert_eq!(str_result, str_reference);
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert!(calculator.process("g: x = f(x)").is_ok());
+        assert!(calculator.process("h: x = g(x) + f(f(x)) + k(x)").is_ok());
+
+        let graph: HashMap<String, Vec<String>> = calculator.function_dependency_graph();
+
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph["f"], Vec::<String>::new());
+        assert_eq!(graph["g"], vec![String::from("f")]);
+        assert_eq!(graph["h"], vec![String::from("f"), String::from("g")]);
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_function_metrics() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert!(calculator.process("g: x, y = f(x) * y").is_ok());
+        assert!(calculator.process("h: x = g(x, f(x)) + f(k)").is_ok());
+        assert!(calculator.process("k = 2").is_ok());
+
+        assert_eq!(
+            calculator.function_metrics("f"),
+            Some(FunctionMetrics {
+                body_length: 5,
+                token_count: 3,
+                parameter_count: 1,
+                referenced_functions: 0,
+                referenced_variables: 0,
+                reverse_references: 2,
+            })
+        );
+
+        assert_eq!(
+            calculator.function_metrics("g"),
+            Some(FunctionMetrics {
+                body_length: 8,
+                token_count: 6,
+                parameter_count: 2,
+                referenced_functions: 1,
+                referenced_variables: 0,
+                reverse_references: 1,
+            })
+        );
+
+        assert_eq!(
+            calculator.function_metrics("h"),
+            Some(FunctionMetrics {
+                body_length: 17,
+                token_count: 14,
+                parameter_count: 1,
+                referenced_functions: 2,
+                referenced_variables: 1,
+                reverse_references: 0,
+            })
+        );
+
+        assert_eq!(calculator.function_metrics("k"), None);
+
+        assert_eq!(
+            calculator.process(":metrics g"),
+            Ok(String::from(
+                "metrics of g:\n  body length = 8 characters, 6 tokens\n  parameters = 2\n  referenced functions = 1\n  referenced variables = 0\n  referencing functions = 1"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process(":metrics k"),
+            Err(String::from("The function k is not defined"))
+        );
+    }
+
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_preview() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_lazy_variables(true);
+
+        assert!(calculator.process("g = 10").is_ok());
+        assert!(calculator.process("weight = 2 * g").is_ok());
+        assert!(calculator.process("f: m = m * h").is_ok());
+        assert!(calculator.process("k: m = f(m) + 1").is_ok());
+        assert!(calculator.process("h = 1").is_ok());
+        assert!(calculator.set_reference("gravity", "g").is_ok());
+
+        let state: CalculatorState = calculator.snapshot();
+
+        assert_eq!(
+            calculator.preview("g = 9.5"),
+            Ok(Preview {
+                kind: InputKind::Variable,
+                name: String::from("g"),
+                definition: PreviewDefinition::Value(Value::Number(9.5)),
+                previous_definition: Some(PreviewDefinition::Value(Value::Number(10.0))),
+                affected: vec![String::from("gravity"), String::from("weight")],
+            })
+        );
+
+        assert_eq!(
+            calculator.process(":preview h = 3"),
+            Ok(String::from(
+                "preview of h:\n  new h = 3\n  previous h = 1\n  affected = f, k"
+            ))
+        );
+
+        assert_eq!(
+            calculator.process(":preview f: m = m + 1"),
+            Ok(String::from(
+                "preview of f:\n  new f(m) = m + 1\n  previous f(m) = m * h\n  affected = k"
+            ))
+        );
+
+        assert_eq!(
+            calculator.preview("g * 2"),
+            Ok(Preview {
+                kind: InputKind::Raw,
+                name: String::from("last"),
+                definition: PreviewDefinition::Value(Value::Number(20.0)),
+                previous_definition: None,
+                affected: Vec::new(),
+            })
+        );
+
+        assert!(calculator.preview(":sci").is_err());
+
+        assert_eq!(calculator.snapshot(), state);
+        assert!(!calculator.variables.contains_key("last"));
+        assert!(calculator.results().is_empty());
+
+        // Preview checks input as process, without counting evaluations
+        let evaluation_count: u64 = calculator.evaluation_count();
+
+        calculator.set_strict_precision(true);
+        assert_eq!(
+            calculator.preview("g = 9007199254740993"),
+            Err(String::from(
+                "The number 9007199254740993 cannot be stored exactly, it would be stored as 9007199254740992"
+            ))
+        );
+        calculator.set_strict_precision(false);
+
+        #[cfg(feature = "constants")]
+        {
+            assert!(calculator.process("const rate = 1").is_ok());
+            assert_eq!(
+                calculator.preview("rate = 2"),
+                Err(String::from(
+                    "The variable rate is a constant, it cannot be redefined"
+                ))
+            );
+        }
+
+        assert!(calculator.preview("g + 1").is_ok());
+        assert_eq!(
+            calculator.evaluation_count(),
+            evaluation_count + u64::from(cfg!(feature = "constants"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_default_undefined() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("x + 5").is_err());
+
+        calculator.set_default_undefined(Some(0.0));
+
+        match calculator.process_structured("x + 5") {
+            Ok(output) => {
+                assert_eq!(output.result, "last = 5");
+                assert_eq!(output.expansion, Some(String::from("0 + 5")));
             }
             Err(_) => assert!(false),
         }
+
+        assert!(calculator.process("y = 2").is_ok());
+        assert!(calculator.process("f: t = t * z").is_ok());
+        assert_eq!(
+            calculator.expand("f(y) + w"),
+            Ok(String::from("(2 * z) + w"))
+        );
+
+        match calculator.process_structured("f(y) + w") {
+            Ok(output) => assert_eq!(output.expansion, Some(String::from("(2 * 0) + 0"))),
+            Err(_) => assert!(false),
+        }
+
+        assert!(calculator.process("z = x").is_err());
     }
 
     #[test]
-    fn test_calculator_process_function_expression() {
-        let mut calculator = Calculator::new(evaluate);
+    fn test_calculator_with_new_evaluator() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let function_name: String = String::from("distance");
-        let function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
-        let function_definition: String = format!(
-            "{} * {} + {} * {}",
-            function_variables[0],
-            function_variables[0],
-            function_variables[1],
-            function_variables[1]
+        assert!(calculator.process("x = 3").is_ok());
+        assert!(calculator.process("f: t = t * x + 1").is_ok());
+
+        let evaluate_doubled = |expression: &str| -> Result<f64, String> {
+            return evaluate_arithmetic(expression).map(|value| 2.0 * value);
+        };
+
+        let mut derived_calculator = calculator.with_new_evaluator(evaluate_doubled);
+
+        assert_eq!(derived_calculator.snapshot(), calculator.snapshot());
+        assert_eq!(derived_calculator.evaluate("f(2)"), Ok(14.0));
+        assert_eq!(calculator.evaluate("f(2)"), Ok(7.0));
+
+        assert!(derived_calculator.process("x = 5").is_ok());
+        assert_eq!(calculator.process("x"), Ok(String::from("last = 3")));
+
+        // Settings are copied, as argument separator and notation
+        assert_eq!(calculator.set_argument_separator(';'), Ok(()));
+        calculator.set_notation(Notation::Fixed(2));
+        assert!(calculator.process("g: a; b = a * b").is_ok());
+
+        let mut derived_calculator = calculator.with_new_evaluator(evaluate_arithmetic);
+
+        assert_eq!(
+            derived_calculator.process("g(2; 3)"),
+            Ok(String::from("last = 6.00"))
         );
+        assert!(derived_calculator.process("g(2, 3)").is_err());
+    }
 
-        let expression: String = format!(
-            "{}: {}, {} = {}",
-            function_name, function_variables[0], function_variables[1], function_definition
+    #[test]
+    fn test_calculator_variable_range() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(
+            calculator.process("slider x = 2.5 in [0, 10] step 0.5"),
+            Ok(String::from("x = 2.5"))
+        );
+        assert_eq!(
+            calculator.variable_range("x"),
+            Some(VariableRange {
+                min: 0.0,
+                max: 10.0,
+                step: Some(0.5),
+            })
         );
 
-        match calculator.process(expression.as_str()) {
-            Ok(str_result) => {
-                let str_reference: String = format!(
-                    "{}({}) = {}",
-                    function_name,
-                    function_variables.join(", "),
-                    function_definition
-                );
+        match calculator.process_structured("x = 12") {
+            Ok(output) => assert_eq!(
+                output.warnings,
+                vec![String::from(
+                    "The value 12 of variable x is outside its range [0, 10]"
+                )]
+            ),
+            Err(_) => assert!(false),
+        }
+
+        // Range never changes substituted value
+        assert_eq!(calculator.process("x + 1"), Ok(String::from("last = 13")));
+
+        assert!(calculator.process("slider y = 1 in [10, 0]").is_err());
+        assert!(calculator.process("slider y = 1 in 0, 10").is_err());
+        assert!(!calculator.variables.contains_key("y"));
+
+        assert!(calculator.set_range("y", -1.0, 1.0, None).is_ok());
+        assert!(calculator.process("y = 0.5").is_ok());
+
+        let script: String = calculator.export_script();
+        assert_eq!(
+            script,
+            "last = 13\nslider x = 12 in [0, 10] step 0.5\nslider y = 0.5 in [-1, 1]\n"
+        );
+
+        calculator.set_strict_mode(true);
+        assert_eq!(
+            calculator.process("x = 11"),
+            Err(String::from(
+                "The value 11 of variable x is outside its range [0, 10]"
+            ))
+        );
+
+        assert!(calculator.remove_range("x"));
+        assert!(!calculator.remove_range("x"));
+        assert_eq!(calculator.variable_range("x"), None);
+        assert!(calculator.process("x = 11").is_ok());
+
+        let mut imported_calculator = Calculator::new(evaluate_arithmetic);
+        assert!(imported_calculator
+            .load_script("slider y = 0.5 in [-1, 1]")
+            .is_ok());
+        assert_eq!(
+            imported_calculator.variable_range("y"),
+            calculator.variable_range("y")
+        );
+
+        // Keyword slider is not reserved as a name
+        assert_eq!(
+            calculator.process("slider = 3"),
+            Ok(String::from("slider = 3"))
+        );
+        assert_eq!(
+            calculator.process("slider + 1"),
+            Ok(String::from("last = 4"))
+        );
+        assert_eq!(
+            calculator.process("slider=2"),
+            Ok(String::from("slider = 2"))
+        );
+        assert!(calculator.process("sliders: x = x * slider").is_ok());
+        assert_eq!(
+            calculator.process("sliders(3)"),
+            Ok(String::from("last = 6"))
+        );
+        assert_eq!(calculator.variable_range("slider"), None);
+
+        // Ranges are kept in snapshots
+        let snapshot: CalculatorState = calculator.snapshot();
+        assert!(calculator.remove_range("y"));
+        assert!(calculator.restore(snapshot).is_ok());
+        assert_eq!(
+            calculator.variable_range("y"),
+            imported_calculator.variable_range("y")
+        );
+
+        // Identifier predicate is taken into account
+        assert_eq!(
+            calculator.set_range("$rate", 0.0, 1.0, None),
+            Err(String::from("The variable name $rate is not valid"))
+        );
+        calculator.set_identifier_predicate(|c| token::is_identifier_character(c) || c == '$');
+        assert!(calculator.set_range("$rate", 0.0, 1.0, None).is_ok());
+    }
+
+    #[test]
+    fn test_calculator_minimal_parentheses() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x = x").is_ok());
+        assert!(calculator.process("g: x = (x + 1)").is_ok());
+        assert!(calculator.process("h: x = (x) * (2)").is_ok());
+
+        assert_eq!(calculator.expand("f(5)"), Ok(String::from("(5)")));
+
+        calculator.set_minimal_parentheses(true);
+
+        assert_eq!(calculator.expand("f(5)"), Ok(String::from("5")));
+        assert_eq!(
+            calculator.expand("2 * g(f(5))"),
+            Ok(String::from("2 * (5 + 1)"))
+        );
+        assert_eq!(
+            calculator.expand("h(3) * 2"),
+            Ok(String::from("((3) * (2)) * 2"))
+        );
+        assert_eq!(calculator.expand("f(-5)"), Ok(String::from("(-5)")));
+        assert_eq!(calculator.process("2 * g(3)"), Ok(String::from("last = 8")));
+
+        // A variable argument is wrapped, since its value may be negative
+        assert!(calculator.process("y = -3").is_ok());
+        assert_eq!(calculator.expand("f(y) ^ 2"), Ok(String::from("(-3) ^ 2")));
+
+        #[cfg(feature = "builtin-eval")]
+        {
+            let mut builtin_calculator =
+                calculator.with_new_evaluator(evaluator::BuiltinEvaluator::new());
+            assert_eq!(
+                builtin_calculator.process("f(y) ^ 2"),
+                Ok(String::from("last = 9"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_calculator_autoname() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("1 + 1").is_ok());
+        assert_eq!(calculator.get_result(1), None);
+
+        calculator.set_autoname(true);
+
+        assert_eq!(calculator.process("2 * 3"), Ok(String::from("last = 6")));
+        assert!(calculator.process("x = 5").is_ok());
+        assert_eq!(calculator.process("_1 + x"), Ok(String::from("last = 11")));
+
+        assert_eq!(calculator.get_result(1), Some(&Value::Number(6.0)));
+        assert_eq!(calculator.get_result(2), Some(&Value::Number(11.0)));
+        assert_eq!(calculator.get_result(3), None);
+        assert_eq!(calculator.variables["last"], Value::Number(11.0));
+
+        // A reference named as a result is replaced by the result
+        assert!(calculator.set_reference("_3", "x").is_ok());
+        assert_eq!(calculator.process("x + 1"), Ok(String::from("last = 6")));
+        assert!(calculator.set_reference("last", "x").is_ok());
+        assert_eq!(calculator.process("x * 2"), Ok(String::from("last = 10")));
+
+        assert!(calculator.references.is_empty());
+        assert_eq!(calculator.get_result(3), Some(&Value::Number(6.0)));
+        assert_eq!(calculator.process("last"), Ok(String::from("last = 10")));
+
+        // A constant named as a result cannot be overwritten
+        #[cfg(feature = "constants")]
+        {
+            assert!(calculator.process("const _6 = 1").is_ok());
+            assert_eq!(
+                calculator.process("x"),
+                Err(String::from(
+                    "The variable _6 is a constant, it cannot be redefined"
+                ))
+            );
+            assert_eq!(calculator.get_result(6), Some(&Value::Number(1.0)));
+            assert_eq!(calculator.variables["last"], Value::Number(10.0));
+        }
+    }
+
+    #[cfg(feature = "constants")]
+    #[test]
+    fn test_calculator_import_env() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        std::env::set_var("TAZOR_TEST_IMPORT_RATE", "0.25");
+        std::env::set_var("TAZOR_TEST_IMPORT_Count", " 3 ");
+
+        assert_eq!(calculator.import_env("TAZOR_TEST_IMPORT_"), Ok(2));
+        assert_eq!(
+            calculator.process("rate * count"),
+            Ok(String::from("last = 0.75"))
+        );
+
+        assert_eq!(
+            calculator.process("rate = 1"),
+            Err(String::from(
+                "The variable rate is a constant, it cannot be redefined"
+            ))
+        );
+
+        std::env::set_var("TAZOR_TEST_IMPORT_BAD", "ten");
+        std::env::set_var("TAZOR_TEST_IMPORT_2X", "1");
+        std::env::set_var("TAZOR_TEST_OTHER_WIDTH", "4");
+
+        calculator.set_env_lowercase(false);
+
+        assert_eq!(
+            calculator.import_env("TAZOR_TEST_IMPORT_"),
+            Err(vec![
+                (
+                    String::from("TAZOR_TEST_IMPORT_2X"),
+                    String::from("The variable name 2X is not valid")
+                ),
+                (
+                    String::from("TAZOR_TEST_IMPORT_BAD"),
+                    String::from("The value ten of variable BAD is not a number")
+                ),
+            ])
+        );
+
+        // Valid variables are imported despite errors, other prefixes are ignored
+        assert_eq!(calculator.variables["RATE"], Value::Number(0.25));
+        assert_eq!(calculator.variables["Count"], Value::Number(3.0));
+        assert!(!calculator.variables.contains_key("BAD"));
+        assert!(!calculator.variables.contains_key("WIDTH"));
+
+        for name in ["RATE", "Count", "BAD", "2X"] {
+            std::env::remove_var(format!("TAZOR_TEST_IMPORT_{}", name));
+        }
+
+        std::env::remove_var("TAZOR_TEST_OTHER_WIDTH");
+
+        // An environment variable which is not valid Unicode is reported
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            std::env::set_var("TAZOR_TEST_IMPORT_WIDE", OsStr::from_bytes(b"\xff"));
+
+            assert_eq!(
+                calculator.import_env("TAZOR_TEST_IMPORT_"),
+                Err(vec![(
+                    String::from("TAZOR_TEST_IMPORT_WIDE"),
+                    String::from("The environment variable is not valid Unicode")
+                )])
+            );
+
+            std::env::remove_var("TAZOR_TEST_IMPORT_WIDE");
+        }
+
+        // Constants cannot be replaced by a reference, and are kept by snapshots and scripts
+        assert_eq!(
+            calculator.set_reference("RATE", "Count"),
+            Err(String::from(
+                "The variable RATE is a constant, it cannot be redefined"
+            ))
+        );
+
+        let snapshot: CalculatorState = calculator.snapshot();
+        assert!(snapshot.constants.contains("RATE"));
+
+        let script: String = calculator.export_script();
+        assert!(script.contains("const RATE = 0.25\n"));
+
+        let mut replayed = Calculator::new(evaluate_arithmetic);
+        assert!(replayed.load_script(script.as_str()).is_ok());
+        assert_eq!(replayed.snapshot(), snapshot);
+        assert!(replayed.process("RATE = 1").is_err());
+
+        // Restore replaces constants
+        assert_eq!(replayed.restore(CalculatorState::default()), Ok(()));
+        assert!(replayed.process("RATE = 1").is_ok());
+    }
+
+    #[test]
+    fn test_calculator_process_with_diagnostics() {
+        let mut calculator = Calculator::new(|_: &str| -> Result<f64, String> { Ok(1.0) });
+
+        assert!(calculator.process("y = 2").is_ok());
+        assert!(calculator.process("f: t = t * y + z").is_ok());
+
+        assert_eq!(
+            calculator.process_with_diagnostics("x + foo(1)"),
+            Ok((String::from("last = 1"), vec![String::from("x")]))
+        );
+
+        // Undefined function foo is passed through to evaluator
+        assert_eq!(calculator.last_expanded(), Some("x + foo(1)"));
+
+        assert_eq!(
+            calculator.process_with_diagnostics("w = f(x) + x + y"),
+            Ok((
+                String::from("w = 1"),
+                vec![String::from("x"), String::from("z")]
+            ))
+        );
+
+        assert_eq!(
+            calculator.process_with_diagnostics("g: t = t + k"),
+            Ok((String::from("g(t) = t + k"), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_calculator_substitution_order() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: t = t * 2 + k").is_ok());
+        assert!(calculator.process("k = 1").is_ok());
+        assert!(calculator.process("x = 3").is_ok());
+
+        // Variable in argument of a call is replaced in both orders
+        assert_eq!(calculator.expand("f(x)"), Ok(String::from("(3 * 2 + 1)")));
+        assert_eq!(calculator.process("f(x)"), Ok(String::from("last = 7")));
+
+        calculator.set_substitution_order(SubstitutionOrder::VariablesFirst);
+
+        assert_eq!(calculator.expand("f(x)"), Ok(String::from("(3 * 2 + k)")));
+        assert!(calculator.process("f(x)").is_err());
+
+        calculator.set_substitution_order(SubstitutionOrder::FunctionsFirst);
+        assert_eq!(calculator.process("f(x)"), Ok(String::from("last = 7")));
+    }
+
+    #[test]
+    fn test_calculator_partial_apply() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert!(calculator.process("f: x, y = x + y * g(x)").is_ok());
+
+        assert_eq!(
+            calculator.partial_apply("f", &[Some("3"), None]),
+            Ok(String::from("3 + y * g(3)"))
+        );
+        assert_eq!(
+            calculator.partial_apply("f", &[None, Some("1 - t")]),
+            Ok(String::from("x + (1 - t) * g(x)"))
+        );
+        assert_eq!(
+            calculator.partial_apply("f", &[Some("3"), Some("4")]),
+            Ok(String::from("3 + 4 * g(3)"))
+        );
+        assert_eq!(
+            calculator.partial_apply("f", &[None, None]),
+            Ok(String::from("x + y * g(x)"))
+        );
+
+        assert_eq!(
+            calculator.partial_apply("f", &[Some("3")]),
+            Err(String::from(
+                "The function f has 2 variable(s) but 1 argument(s) are given"
+            ))
+        );
+        assert_eq!(
+            calculator.partial_apply("h", &[]),
+            Err(String::from("The function h is not defined"))
+        );
+    }
+
+    #[test]
+    fn test_calculator_load_script_keeps_function_variables_symbolic() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        // Script edited by hand, with extra spaces around variables of functions, global y is replaced in g
+        let script: &str = "x = 5\ny = 7\nf:  x ,  y = x * y + 1\ng:\tx\t= x - y\n";
+        assert!(calculator.load_script(script).is_ok());
+
+        assert_eq!(
+            calculator.functions["f"],
+            (
+                vec![String::from("x"), String::from("y")],
+                String::from("x * y + 1")
+            )
+        );
+        assert_eq!(
+            calculator.functions["g"],
+            (vec![String::from("x")], String::from("x - 7"))
+        );
+
+        // Reprocessing exported script keeps variables symbolic
+        let exported_script: String = calculator.export_script();
+        calculator.clear();
+        assert!(calculator.load_script(exported_script.as_str()).is_ok());
+
+        assert_eq!(calculator.functions["f"].1, "x * y + 1");
+        assert_eq!(calculator.process("f(2, 3)"), Ok(String::from("last = 7")));
+        assert_eq!(calculator.process("g(10)"), Ok(String::from("last = 3")));
+    }
+
+    #[test]
+    fn test_calculator_load_script_with_duplicates() {
+        let script: &str = "x = 1\nf: a = a + x\n# redefine x\nx = 2\nf: b = b * x\n";
+
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert!(calculator.load_script(script).is_ok());
+        assert_eq!(
+            calculator.read_view().variable("x"),
+            Some(&Value::from(2.0))
+        );
+
+        let mut strict_calculator = Calculator::new(evaluate_arithmetic);
+        strict_calculator.set_strict_duplicates(true);
+
+        assert_eq!(
+            strict_calculator.load_script(script),
+            Err(String::from(
+                "Line 4: The variable x is already defined at line 1"
+            ))
+        );
+
+        assert_eq!(
+            strict_calculator
+                .load_script("f: a = a\ng!: a = 2 * a\nslider y = 1 in [0, 2]\nf: b = b"),
+            Err(String::from(
+                "Line 4: The function f is already defined at line 1"
+            ))
+        );
+
+        assert_eq!(
+            strict_calculator.load_script("slider y = 1 in [0, 2]\ny = 2"),
+            Err(String::from(
+                "Line 2: The variable y is already defined at line 1"
+            ))
+        );
+
+        // A variable and a function can have the same name, and each script is checked alone
+        assert!(strict_calculator
+            .load_script("g = 1\ng: a = a\nx = 3")
+            .is_ok());
+        assert!(strict_calculator.load_script("x = 4").is_ok());
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_calculator_process_empty_input() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert_eq!(calculator.process("1 + 2"), Ok(String::from("last = 3")));
+
+        for input in ["", "   ", " \t "] {
+            match calculator.try_process(input) {
+                Ok(_) => assert!(false),
+                Err(error) => {
+                    assert_eq!(error.message(), "The expression is empty");
+                    assert_eq!(error.input(), input);
+                    assert_eq!(error.kind(), InputKind::Empty);
+                    assert_eq!(error.cause(), Some(&Cause::EmptyInput));
+                }
+            }
+
+            // String-based API gives a fixed message, without calling evaluator
+            assert_eq!(
+                calculator.process(input),
+                Err(String::from("The expression is empty"))
+            );
+        }
+
+        calculator.set_empty_input_policy(EmptyInputPolicy::Ignore);
+
+        for input in ["", "   ", " \t "] {
+            assert_eq!(calculator.process(input), Ok(String::new()));
+            assert_eq!(
+                calculator.process_structured(input),
+                Ok(ProcessOutput {
+                    result: String::new(),
+                    value: None,
+                    expansion: None,
+                    warnings: Vec::new(),
+                })
+            );
+        }
+
+        // Empty inputs never modify calculator
+        assert_eq!(calculator.variables.get("last"), Some(&Value::from(3.0)));
+        assert_eq!(calculator.history(), &[String::from("1 + 2")]);
+        assert_eq!(calculator.evaluation_count(), 1);
+    }
+
+    #[test]
+    fn test_calculator_function_stats() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        assert!(calculator.process("f: x = x * x").is_ok());
+        assert!(calculator.process("g: x, y = x + y").is_ok());
+
+        assert_eq!(
+            calculator.sample("f", -2.0, 2.0, 4),
+            Ok(vec![
+                (-2.0, 4.0),
+                (-1.0, 1.0),
+                (0.0, 0.0),
+                (1.0, 1.0),
+                (2.0, 4.0)
+            ])
+        );
+
+        for steps in [4, 8, 40] {
+            let stats: FunctionStats = calculator.function_stats("f", -2.0, 2.0, steps).unwrap();
+
+            assert_eq!(stats.min, 0.0);
+            assert_eq!(stats.max, 4.0);
+            assert!(stats.mean > 1.0 && stats.mean <= 2.0);
+        }
+
+        assert_eq!(
+            calculator.function_stats("f", -2.0, 2.0, 4),
+            Ok(FunctionStats {
+                min: 0.0,
+                max: 4.0,
+                mean: 2.0
+            })
+        );
+
+        assert_eq!(
+            calculator.function_stats("f", -2.0, 2.0, 0),
+            Err(String::from("The number of steps must be positive"))
+        );
+        assert_eq!(
+            calculator.function_stats("g", -2.0, 2.0, 4),
+            Err(String::from(
+                "The function g must have a single variable to be sampled"
+            ))
+        );
+        assert_eq!(
+            calculator.sample("f", f64::NEG_INFINITY, 2.0, 4),
+            Err(String::from(
+                "The range [-inf, 2] of sampling must have finite bounds"
+            ))
+        );
+        assert!(calculator.sample("f", 0.0, f64::NAN, 4).is_err());
+        assert_eq!(
+            calculator.function_stats("h", -2.0, 2.0, 4),
+            Err(String::from("The function h is not defined"))
+        );
+
+        assert!(!calculator.variables.contains_key("last"));
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_calculator_define() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        assert_eq!(calculator.define("x", "2 + 3"), Ok(5.0));
+        assert_eq!(calculator.variables.get("x"), Some(&Value::from(5.0)));
+
+        assert_eq!(calculator.define("y", "x * 2"), Ok(10.0));
+        assert_eq!(calculator.history(), &["x = 2 + 3", "y = x * 2"]);
 
-                assert_eq!(str_result, str_reference);
+        assert_eq!(
+            calculator.define("a + b", "1"),
+            Err(String::from("The variable name a + b is not valid"))
+        );
+        assert_eq!(
+            calculator.define("z", ""),
+            Err(String::from("The expression is empty"))
+        );
 
-                assert_eq!(calculator.functions.len(), 1);
-                assert!(calculator.functions.contains_key(&function_name));
-                assert_eq!(
-                    calculator.functions[&function_name],
-                    (function_variables, function_definition)
-                );
-            }
-            Err(_) => assert!(false),
-        }
+        assert!(!calculator.variables.contains_key("z"));
+        assert!(!calculator.variables.contains_key("last"));
+
+        assert_eq!(
+            calculator.define("$rate", "2"),
+            Err(String::from("The variable name $rate is not valid"))
+        );
+
+        calculator.set_identifier_predicate(|c| token::is_identifier_character(c) || c == '$');
+
+        assert_eq!(calculator.define("$rate", "x / 10"), Ok(0.5));
+        assert_eq!(
+            calculator.process("$rate * 4"),
+            Ok(String::from("last = 2"))
+        );
+        assert!(calculator.define("$1", "2").is_err());
+        assert!(calculator.define("1$", "2").is_err());
     }
 
     #[test]
-    fn test_calculator_process_several_function_expression() {
-        let mut calculator = Calculator::new(evaluate);
+    fn test_calculator_save_load_binary() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let first_function_name: String = String::from("distance");
-        let first_function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
-        let first_function_definition: String = format!(
-            "{} * {} + {} * {}",
-            first_function_variables[0],
-            first_function_variables[0],
-            first_function_variables[1],
-            first_function_variables[1]
-        );
+        assert!(calculator.process("x = 2 * 3").is_ok());
+        assert!(calculator.process("rate = 0.5").is_ok());
+        assert!(calculator.process("f: a, b = a * rate + b").is_ok());
+        assert!(calculator.process("g: a = f(a, x) - 1").is_ok());
+        assert!(calculator.set_range("rate", 0.0, 1.0, Some(0.1)).is_ok());
 
-        let first_expression: String = format!(
-            "{}: {}, {} = {}",
-            first_function_name,
-            first_function_variables[0],
-            first_function_variables[1],
-            first_function_definition
+        let mut buffer: Vec<u8> = Vec::new();
+        assert!(calculator.save_binary(&mut buffer).is_ok());
+
+        let mut loaded_calculator = Calculator::new(evaluate_arithmetic);
+        assert!(loaded_calculator.process("y = 1").is_ok());
+        assert!(loaded_calculator.load_binary(buffer.as_slice()).is_ok());
+
+        assert_eq!(loaded_calculator.snapshot(), calculator.snapshot());
+        assert_eq!(
+            loaded_calculator.variable_range("rate"),
+            calculator.variable_range("rate")
         );
+        assert_eq!(loaded_calculator.evaluate("g(4)"), Ok(7.0));
+        assert!(loaded_calculator.read_view().variable("y").is_none());
 
-        match calculator.process(first_expression.as_str()) {
-            Ok(str_result) => {
-                let str_reference: String = format!(
-                    "{}({}) = {}",
-                    first_function_name,
-                    first_function_variables.join(", "),
-                    first_function_definition
-                );
+        // Invalid data leaves calculator unchanged
+        assert!(loaded_calculator
+            .load_binary(&buffer[..buffer.len() - 1])
+            .is_err());
+        assert_eq!(loaded_calculator.snapshot(), calculator.snapshot());
 
-                assert_eq!(str_result, str_reference);
+        // Definitions are checked before being restored
+        let mut unsafe_state: CalculatorState = calculator.snapshot();
+        unsafe_state.functions.insert(
+            String::from("h"),
+            (vec![String::from("a")], String::from("a \u{2066}+ 1")),
+        );
 
-                assert_eq!(calculator.functions.len(), 1);
-                assert!(calculator.functions.contains_key(&first_function_name));
+        let mut unsafe_buffer: Vec<u8> = Vec::new();
+        assert!(binary::write(&unsafe_state, &mut unsafe_buffer).is_ok());
+
+        match loaded_calculator.load_binary(unsafe_buffer.as_slice()) {
+            Ok(_) => assert!(false),
+            Err(error) => {
+                assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
                 assert_eq!(
-                    calculator.functions[&first_function_name],
-                    (first_function_variables, first_function_definition)
+                    error.to_string(),
+                    "The character U+2066 in definition of h is not allowed"
                 );
             }
-            Err(_) => assert!(false),
         }
+        assert_eq!(loaded_calculator.snapshot(), calculator.snapshot());
 
-        let second_function_name: String = String::from("velocity");
-        let second_function_variables: Vec<String> =
-            vec![String::from("distance"), String::from("time")];
+        let mut invalid_states: Vec<(CalculatorState, &str)> = Vec::new();
 
-        let second_function_definition: String = format!(
-            "{} / {}",
-            second_function_variables[0], second_function_variables[1]
-        );
+        let mut invalid_state: CalculatorState = calculator.snapshot();
+        invalid_state
+            .variables
+            .insert(String::from("a + b"), Value::Number(1.0));
+        invalid_states.push((invalid_state, "The variable name a + b is not valid"));
 
-        let second_expression: String = format!(
-            "{}: {}, {} = {}",
-            second_function_name,
-            second_function_variables[0],
-            second_function_variables[1],
-            second_function_definition
+        let mut invalid_state: CalculatorState = calculator.snapshot();
+        invalid_state.functions.insert(
+            String::from("h"),
+            (vec![String::from("a")], String::from("a + $1")),
         );
+        invalid_states.push((
+            invalid_state,
+            "The function h mixes named and positional variables",
+        ));
 
-        match calculator.process(second_expression.as_str()) {
-            Ok(str_result) => {
-                let str_reference: String = format!(
-                    "{}({}) = {}",
-                    second_function_name,
-                    second_function_variables.join(", "),
-                    second_function_definition
-                );
-
-                assert_eq!(str_result, str_reference);
+        for (invalid_state, message) in invalid_states {
+            let mut invalid_buffer: Vec<u8> = Vec::new();
+            assert!(binary::write(&invalid_state, &mut invalid_buffer).is_ok());
 
-                assert_eq!(calculator.functions.len(), 2);
-                assert!(calculator.functions.contains_key(&second_function_name));
-                assert_eq!(
-                    calculator.functions[&second_function_name],
-                    (second_function_variables, second_function_definition)
-                );
-            }
-            Err(_) => assert!(false),
+            assert_eq!(
+                loaded_calculator
+                    .load_binary(invalid_buffer.as_slice())
+                    .map_err(|error| error.to_string()),
+                Err(String::from(message))
+            );
         }
+
+        assert_eq!(loaded_calculator.snapshot(), calculator.snapshot());
     }
 
     #[test]
-    fn test_calculator_process_expression_with_functions() {
-        let mut calculator = Calculator::new(evaluate);
+    fn test_calculator_result_template() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+        calculator.set_result_template(String::from("{name} -> {value}"));
 
-        let first_function_name: String = String::from("distance");
-        let first_function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
-        let first_function_definition: String = format!(
-            "{} * {} + {} * {}",
-            first_function_variables[0],
-            first_function_variables[0],
-            first_function_variables[1],
-            first_function_variables[1]
+        assert_eq!(calculator.process("x = 2 * 3"), Ok(String::from("x -> 6")));
+        assert_eq!(calculator.process("x + 1"), Ok(String::from("last -> 7")));
+        assert_eq!(
+            calculator.process("f: a = a * x"),
+            Ok(String::from("f(a) = a * 6"))
         );
 
-        let first_function_expression: String = format!(
-            "{}: {}, {} = {}",
-            first_function_name,
-            first_function_variables[0],
-            first_function_variables[1],
-            first_function_definition
-        );
+        calculator.set_result_template(String::from("{value}"));
+        assert_eq!(calculator.process("f(2)"), Ok(String::from("12")));
 
-        assert!(calculator
-            .process(first_function_expression.as_str())
-            .is_ok());
+        calculator.set_result_template(String::from("{name} = {value}"));
+        assert_eq!(calculator.process("y = -1"), Ok(String::from("y = -1")));
+    }
 
-        let second_function_name: String = String::from("velocity");
-        let second_function_variables: Vec<String> =
-            vec![String::from("distance"), String::from("time")];
+    #[test]
+    fn test_calculator_rename_function() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let second_function_definition: String = format!(
-            "{} / {}",
-            second_function_variables[0], second_function_variables[1]
+        assert!(calculator.process("f: x = x + 1").is_ok());
+        assert!(calculator.process("h: x = f(x)").is_ok());
+        assert!(calculator.process("k: x = x * 2").is_ok());
+
+        assert_eq!(calculator.rename_function("f", "g", true), Ok(()));
+
+        assert!(!calculator.functions.contains_key("f"));
+        assert_eq!(
+            calculator.functions["g"],
+            (vec![String::from("x")], String::from("x + 1"))
         );
+        assert_eq!(
+            calculator.functions["h"],
+            (vec![String::from("x")], String::from("g(x)"))
+        );
+        assert_eq!(calculator.evaluate("h(2)"), Ok(3.0));
 
-        let second_function_expression: String = format!(
-            "{}: {}, {} = {}",
-            second_function_name,
-            second_function_variables[0],
-            second_function_variables[1],
-            second_function_definition
+        // Without update, references keep old name
+        assert_eq!(calculator.rename_function("k", "double", false), Ok(()));
+        assert_eq!(calculator.evaluate("double(2)"), Ok(4.0));
+
+        assert_eq!(
+            calculator.rename_function("f", "g", true),
+            Err(String::from("The function f is not defined"))
+        );
+        assert_eq!(
+            calculator.rename_function("g", "h", true),
+            Err(String::from("The function h is already defined"))
+        );
+        assert_eq!(
+            calculator.rename_function("g", "2g", true),
+            Err(String::from("The function name 2g is not valid"))
         );
 
-        assert!(calculator
-            .process(second_function_expression.as_str())
-            .is_ok());
+        assert!(calculator.process("rate = 2").is_ok());
+        assert_eq!(
+            calculator.rename_function("g", "rate", true),
+            Err(String::from(
+                "The name rate is already defined as a variable"
+            ))
+        );
+        assert!(calculator.functions.contains_key("g"));
 
-        let expression: String = format!(
-            "3.14 * {}(6.89, 5.43) - {}(2.4, 4.3) + (2 * 3 - 7)",
-            second_function_name, first_function_name
+        calculator.constants.insert(String::from("g0"));
+        assert_eq!(
+            calculator.rename_function("g", "g0", true),
+            Err(String::from("The name g0 is already defined as a constant"))
         );
 
-        let replaced_expression: String =
-            String::from("3.14 * (6.89 / 5.43) - (2.4 * 2.4 + 4.3 * 4.3) + (2 * 3 - 7)");
+        assert!(calculator.set_reference("current", "rate").is_ok());
+        assert_eq!(
+            calculator.rename_function("g", "current", true),
+            Err(String::from(
+                "The name current is already defined as a reference"
+            ))
+        );
 
-        match calculator.process(expression.as_str()) {
-            Ok(str_result) => {
-                let str_reference: String = format!("last = {}", replaced_expression.len());
-                assert_eq!(str_result, str_reference);
-            }
-            Err(_) => assert!(false),
-        }
+        // Identifier predicate accepts `$` in new name
+        assert_eq!(
+            calculator.rename_function("g", "$g", true),
+            Err(String::from("The function name $g is not valid"))
+        );
+        calculator.set_identifier_predicate(|c| token::is_identifier_character(c) || c == '$');
+        assert_eq!(calculator.rename_function("g", "$g", true), Ok(()));
+        assert_eq!(calculator.evaluate("$g(2)"), Ok(3.0));
     }
 
     #[test]
-    fn test_calculator_process_expression_with_variables_and_functions() {
-        let mut calculator = Calculator::new(evaluate);
+    fn test_calculator_diff_since_snapshot() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let first_function_name: String = String::from("distance");
-        let first_function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
-        let first_function_definition: String = format!(
-            "{} * {} + {} * {}",
-            first_function_variables[0],
-            first_function_variables[0],
-            first_function_variables[1],
-            first_function_variables[1]
-        );
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("f: t = t + x").is_ok());
 
-        let first_function_expression: String = format!(
-            "{}: {}, {} = {}",
-            first_function_name,
-            first_function_variables[0],
-            first_function_variables[1],
-            first_function_definition
+        let snapshot: CalculatorState = calculator.snapshot();
+        assert!(calculator.diff_since(&snapshot).is_empty());
+
+        assert!(calculator.load_script("x = 3\ny = 1\nf: t = t * x").is_ok());
+
+        assert_eq!(
+            format!("{}", calculator.diff_since(&snapshot)),
+            "-x = 2\n+x = 3\n+y = 1\n-f: t = t + 2\n+f: t = t * 3"
         );
+    }
 
-        assert!(calculator
-            .process(first_function_expression.as_str())
-            .is_ok());
+    #[test]
+    fn test_calculator_evaluate_at_snapshot() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let second_function_name: String = String::from("velocity");
-        let second_function_variables: Vec<String> =
-            vec![String::from("distance"), String::from("time")];
+        assert!(calculator.process("x = 2").is_ok());
+        assert!(calculator.process("g: y = y * x").is_ok());
 
-        let second_function_definition: String = format!(
-            "{} / {}",
-            second_function_variables[0], second_function_variables[1]
-        );
+        let snapshot: CalculatorState = calculator.snapshot();
 
-        let second_function_expression: String = format!(
-            "{}: {}, {} = {}",
-            second_function_name,
-            second_function_variables[0],
-            second_function_variables[1],
-            second_function_definition
+        assert!(calculator.process("g: y = y + 10").is_ok());
+        assert!(calculator.process("x = 5").is_ok());
+
+        assert_eq!(calculator.evaluate("g(3) + x"), Ok(18.0));
+        assert_eq!(calculator.evaluate_at(&snapshot, "g(3) + x"), Ok(8.0));
+
+        // Current state is unchanged
+        assert_eq!(calculator.functions["g"].1, "y + 10");
+        assert_eq!(calculator.variables["x"], 5.0);
+
+        assert_eq!(calculator.restore(snapshot.clone()), Ok(()));
+        assert_eq!(calculator.snapshot(), snapshot);
+
+        // A state with an unsafe character is rejected
+        let mut invalid_snapshot: CalculatorState = snapshot.clone();
+        invalid_snapshot
+            .variables
+            .insert(String::from("x\u{202e}"), Value::Number(1.0));
+
+        assert_eq!(
+            calculator.restore(invalid_snapshot),
+            Err(String::from(
+                "The character U+202E in definition of x\u{202e} is not allowed"
+            ))
         );
+        assert_eq!(calculator.snapshot(), snapshot);
+    }
 
-        assert!(calculator
-            .process(second_function_expression.as_str())
-            .is_ok());
+    #[cfg(feature = "commands")]
+    #[test]
+    fn test_calculator_snapshot_commands() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let first_variable_name: String = String::from("x");
-        let first_variable_definition: String = String::from("1 + 1");
+        assert!(calculator.process("g: y = y * 2").is_ok());
 
-        let first_expression: String =
-            format!("{} = {}", first_variable_name, first_variable_definition);
+        assert_eq!(
+            calculator.process(":save old"),
+            Ok(String::from("snapshot old saved"))
+        );
 
-        assert!(calculator.process(first_expression.as_str()).is_ok());
+        assert!(calculator.process("g: y = y * 3").is_ok());
+        assert!(calculator.process(":save new").is_ok());
 
-        let second_variable_name: String = String::from("y");
-        let second_variable_definition: String = String::from("97 + 1");
+        assert_eq!(
+            calculator.process(":snapshots"),
+            Ok(String::from("snapshots = new, old"))
+        );
 
-        let second_expression: String =
-            format!("{} = {}", second_variable_name, second_variable_definition);
+        assert_eq!(
+            calculator.process(":at old g(4) + 1"),
+            Ok(String::from("g(4) + 1 at old = 9"))
+        );
 
-        assert!(calculator.process(second_expression.as_str()).is_ok());
+        assert_eq!(
+            calculator.process("g(4) + 1"),
+            Ok(String::from("last = 13"))
+        );
 
-        let expression: String = format!(
-            "3.14 * {}({}, {}) - {}(2.4, 4.3) + (2 * 3 - 7)",
-            second_function_name, first_variable_name, second_variable_name, first_function_name
+        assert_eq!(
+            calculator.process(":at older g(4)"),
+            Err(String::from("The snapshot older is not saved"))
         );
 
-        let replaced_expression: String = format!(
-            "3.14 * ({} / {}) - (2.4 * 2.4 + 4.3 * 4.3) + (2 * 3 - 7)",
-            first_variable_definition.len(),
-            second_variable_definition.len()
+        assert_eq!(
+            calculator.process(":at old"),
+            Err(String::from(
+                "The number of arguments of command :at is not valid"
+            ))
         );
+    }
 
-        match calculator.process(expression.as_str()) {
-            Ok(str_result) => {
-                let str_reference: String = format!("last = {}", replaced_expression.len());
-                assert_eq!(str_result, str_reference);
-            }
+    #[test]
+    fn test_calculator_process_function_with_positional_variables() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
+
+        match calculator.process("f: 2 = $1 * $1 + $2") {
+            Ok(str_result) => assert_eq!(str_result, "f($1, $2) = $1 * $1 + $2"),
             Err(_) => assert!(false),
         }
-    }
 
-    #[test]
-    fn test_calculator_process_expression_with_variables_and_function_variables_equal_to_variables()
-    {
-        let mut calculator = Calculator::new(evaluate);
+        match calculator.process("f(3, 4)") {
+            Ok(str_result) => assert_eq!(str_result, "last = 13"),
+            Err(_) => assert!(false),
+        }
 
-        let first_variable_name: String = String::from("x");
-        let first_variable_definition: String = String::from("1 + 1");
+        assert_eq!(
+            calculator.process("f(3)"),
+            Err(String::from("The number of variables is not consistent"))
+        );
 
-        let first_expression: String =
-            format!("{} = {}", first_variable_name, first_variable_definition);
+        assert_eq!(
+            calculator.process("g: x = x + $1"),
+            Err(String::from(
+                "The function g mixes named and positional variables"
+            ))
+        );
 
-        assert!(calculator.process(first_expression.as_str()).is_ok());
+        assert!(!calculator.functions.contains_key("g"));
 
-        let second_variable_name: String = String::from("y");
-        let second_variables_definition: String = String::from("2 * 5");
+        // Exported script can be loaded again
+        let mut imported_calculator = Calculator::new(evaluate_arithmetic);
 
-        let second_expression: String =
-            format!("{} = {}", second_variable_name, second_variables_definition);
+        assert!(imported_calculator
+            .load_script(calculator.export_script().as_str())
+            .is_ok());
 
-        assert!(calculator.process(second_expression.as_str()).is_ok());
+        assert_eq!(imported_calculator.functions, calculator.functions);
+    }
 
-        let function_name: String = String::from("distance");
-        let function_variables: Vec<String> = vec![String::from("x"), String::from("y")];
-        let function_definition: String = format!(
-            "{} * {} + {} * {}",
-            function_variables[0],
-            function_variables[0],
-            function_variables[1],
-            function_variables[1]
-        );
+    #[test]
+    fn test_calculator_evaluate_checked() {
+        let mut calculator = Calculator::new(evaluate_arithmetic);
 
-        let function_expression: String = format!(
-            "{}: {}, {} = {}",
-            function_name, function_variables[0], function_variables[1], function_definition
-        );
+        assert!(calculator.process("x = 4").is_ok());
 
-        assert!(calculator.process(function_expression.as_str()).is_ok());
-        assert!(function_definition == calculator.functions[&function_name].1);
+        assert_eq!(calculator.evaluate_checked("x / 2"), Ok((2.0, true)));
+        assert_eq!(
+            calculator.evaluate_checked("x / 0"),
+            Ok((f64::INFINITY, false))
+        );
+        assert!(calculator.evaluate_checked("x /").is_err());
     }
 }
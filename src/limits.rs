@@ -0,0 +1,49 @@
+use crate::expression;
+
+/// Limits applied by calculator on processed expressions
+///
+/// They protect calculator against pathological inputs, as a huge blob pasted by mistake,
+/// which would be otherwise processed during a long time before giving an error.
+///
+/// New limits may be added in a future release, thus limits are built from default ones
+/// with `with_*` methods, as `Limits::default().with_max_function_expansions(100)`.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Limits {
+    pub max_token_length: usize, // maximal number of bytes of a single token (number, identifier)
+    pub max_expression_length: Option<usize>, // maximal number of characters of an expression and its expansion, none for no limit
+    pub max_function_expansions: usize, // maximal number of function calls replaced in a processed expression
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        return Self {
+            max_token_length: 4096,
+            max_expression_length: None,
+            max_function_expansions: expression::MAX_FUNCTION_EXPANSIONS,
+        };
+    }
+}
+
+impl Limits {
+    /// Set maximal number of bytes of a single token (number, identifier)
+    pub fn with_max_token_length(mut self, max_token_length: usize) -> Self {
+        self.max_token_length = max_token_length;
+        return self;
+    }
+
+    /// Set maximal number of characters of an expression, none for no limit
+    ///
+    /// Substitution of functions and variables also stops as soon as expansion exceeds it.
+    pub fn with_max_expression_length(mut self, max_expression_length: Option<usize>) -> Self {
+        self.max_expression_length = max_expression_length;
+        return self;
+    }
+
+    /// Set maximal number of function calls replaced in a processed expression
+    pub fn with_max_function_expansions(mut self, max_function_expansions: usize) -> Self {
+        self.max_function_expansions = max_function_expansions;
+        return self;
+    }
+}
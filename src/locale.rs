@@ -0,0 +1,280 @@
+use crate::token::{self, LexemeKind};
+use crate::value::Value;
+
+/// Convention used to write numbers, it is defined by a decimal separator and an optional thousands separator
+///
+/// Evaluator and exported scripts always use the canonical convention, i.e. `.` as decimal separator
+/// without thousands separator. A locale allows to read numbers written by user and to write results
+/// with another convention, as `3,5` in French.
+///
+/// Formatting and parsing of a number are inverse operations.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberLocale {
+    decimal_separator: char, // separator between integer and decimal part
+    thousands_separator: Option<char>, // separator between groups of three digits in integer part
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        return Self::canonical();
+    }
+}
+
+impl NumberLocale {
+    /// Construct a locale given its decimal separator and its optional thousands separator
+    ///
+    /// Separators must be different and cannot be a digit or a sign.
+    pub fn new(decimal_separator: char, thousands_separator: Option<char>) -> Result<Self, String> {
+        let is_valid_separator = |c: char| -> bool {
+            return !c.is_ascii_digit() && c != '-' && c != '+';
+        };
+
+        if !is_valid_separator(decimal_separator)
+            || !thousands_separator.is_none_or(is_valid_separator)
+        {
+            return Err(String::from(
+                "A number separator cannot be a digit or a sign",
+            ));
+        }
+
+        if thousands_separator == Some(decimal_separator) {
+            return Err(String::from(
+                "The decimal and thousands separators must be different",
+            ));
+        }
+
+        return Ok(Self {
+            decimal_separator,
+            thousands_separator,
+        });
+    }
+
+    /// Construct the canonical locale, with `.` as decimal separator and without thousands separator
+    pub fn canonical() -> Self {
+        return Self {
+            decimal_separator: '.',
+            thousands_separator: None,
+        };
+    }
+
+    /// Get separator between integer and decimal part
+    pub fn decimal_separator(&self) -> char {
+        return self.decimal_separator;
+    }
+
+    /// Check if locale is the canonical one
+    pub fn is_canonical(&self) -> bool {
+        return *self == Self::canonical();
+    }
+
+    /// Format a number according to locale
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return format!("{}", value);
+        }
+
+        return self.localize_number(format!("{}", value).as_str());
+    }
+
+    /// Format a value according to locale, a boolean is written `true` or `false`
+    pub fn format_value(&self, value: &Value) -> String {
+        return match value {
+            Value::Number(number) => self.format(*number),
+            Value::Bool(boolean) => format!("{}", boolean),
+            Value::Integer(integer) => self.localize_number(format!("{}", integer).as_str()),
+            Value::Vector(elements) => {
+                crate::value::format_vector(elements, |element| self.format(*element))
+            }
+            #[cfg(feature = "decimal")]
+            Value::Decimal(_) => self.localize_number(format!("{}", value).as_str()),
+            #[cfg(feature = "complex")]
+            Value::Complex(complex) => {
+                crate::value::format_complex(complex, |part| self.format(part))
+            }
+        };
+    }
+
+    /// Write a number written with canonical convention according to locale
+    ///
+    /// An exponent, as in `1.5e-7`, is kept after decimal part.
+    pub fn localize_number(&self, canonical_value: &str) -> String {
+        if self.is_canonical() {
+            return String::from(canonical_value);
+        }
+
+        let (sign, unsigned_value): (&str, &str) = match canonical_value.strip_prefix('-') {
+            Some(unsigned_value) => ("-", unsigned_value),
+            None => ("", canonical_value),
+        };
+
+        let (integer_part, decimal_part): (&str, Option<&str>) =
+            match unsigned_value.split_once('.') {
+                Some((integer_part, decimal_part)) => (integer_part, Some(decimal_part)),
+                None => (unsigned_value, None),
+            };
+
+        let mut formatted_value: String = String::from(sign);
+
+        for (id, digit) in integer_part.chars().enumerate() {
+            if id > 0 && (integer_part.len() - id) % 3 == 0 {
+                if let Some(thousands_separator) = self.thousands_separator {
+                    formatted_value.push(thousands_separator);
+                }
+            }
+
+            formatted_value.push(digit);
+        }
+
+        if let Some(decimal_part) = decimal_part {
+            formatted_value.push(self.decimal_separator);
+            formatted_value.push_str(decimal_part);
+        }
+
+        return formatted_value;
+    }
+
+    /// Parse a number written according to locale
+    pub fn parse(&self, value_str: &str) -> Result<f64, String> {
+        let canonical_value: String = value_str
+            .trim()
+            .chars()
+            .filter(|c| Some(*c) != self.thousands_separator)
+            .map(|c| if c == self.decimal_separator { '.' } else { c })
+            .collect();
+
+        return canonical_value
+            .parse::<f64>()
+            .map_err(|_| format!("The number {} is not valid", value_str));
+    }
+
+    /// Write numbers of a canonical expression according to locale
+    ///
+    /// Only decimal separator is changed, thousands separator is never inserted in an expression.
+    pub fn localize_expression(&self, expression: &str) -> String {
+        if self.decimal_separator == '.' {
+            return String::from(expression);
+        }
+
+        let decimal_separator: String = self.decimal_separator.to_string();
+        let mut localized_expression: String = String::with_capacity(expression.len());
+        let mut position: usize = 0;
+
+        for lexeme in token::lex(expression) {
+            localized_expression.push_str(&expression[position..lexeme.range.start]);
+
+            let lexeme_str: &str = &expression[lexeme.range.clone()];
+
+            if lexeme.kind == LexemeKind::Number {
+                localized_expression.push_str(&lexeme_str.replacen('.', &decimal_separator, 1));
+            } else {
+                localized_expression.push_str(lexeme_str);
+            }
+
+            position = lexeme.range.end;
+        }
+
+        localized_expression.push_str(&expression[position..]);
+
+        return localized_expression;
+    }
+
+    /// Write numbers of an expression written according to locale in canonical form
+    ///
+    /// A decimal separator is recognized only between two digits, thus with `,` as decimal separator,
+    /// `f(1,5; 2)` is a call with arguments `1.5` and `2` when arguments are separated by `;`.
+    pub fn delocalize_expression(&self, expression: &str) -> String {
+        if self.decimal_separator == '.' {
+            return String::from(expression);
+        }
+
+        let chars: Vec<char> = expression.chars().collect();
+
+        return chars
+            .iter()
+            .enumerate()
+            .map(|(id, c)| {
+                let is_decimal_separator: bool = *c == self.decimal_separator
+                    && id > 0
+                    && chars[id - 1].is_ascii_digit()
+                    && chars.get(id + 1).is_some_and(|c| c.is_ascii_digit());
+
+                return if is_decimal_separator { '.' } else { *c };
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_locale_new() {
+        assert!(NumberLocale::new(',', Some(' ')).is_ok());
+        assert!(NumberLocale::new(',', Some(',')).is_err());
+        assert!(NumberLocale::new('1', None).is_err());
+        assert!(NumberLocale::new('.', Some('-')).is_err());
+    }
+
+    #[test]
+    fn test_number_locale_format_and_parse() {
+        let locale: NumberLocale = NumberLocale::new(',', Some(' ')).unwrap();
+
+        let values: Vec<(f64, &str)> = vec![
+            (3.5, "3,5"),
+            (-1234567.25, "-1 234 567,25"),
+            (123.0, "123"),
+            (1000.0, "1 000"),
+            (0.001, "0,001"),
+        ];
+
+        for (value, value_str) in values {
+            assert_eq!(locale.format(value), value_str);
+            assert_eq!(locale.parse(value_str), Ok(value));
+        }
+
+        assert!(locale.parse("3,5,2").is_err());
+
+        assert_eq!(locale.format_value(&Value::Integer(-1234567)), "-1 234 567");
+        assert_eq!(locale.format_value(&Value::Bool(false)), "false");
+        assert_eq!(NumberLocale::canonical().format(-1234.5), "-1234.5");
+        assert_eq!(locale.localize_number("-1234.5e-7"), "-1 234,5e-7");
+    }
+
+    #[test]
+    fn test_number_locale_localize_and_delocalize_expression() {
+        let locale: NumberLocale = NumberLocale::new(',', None).unwrap();
+
+        let canonical_expression: &str = "f(1.5, 2) * 3.25e2 + x2";
+        let localized_expression: &str = "f(1,5, 2) * 3,25e2 + x2";
+
+        assert_eq!(
+            locale.localize_expression(canonical_expression),
+            localized_expression
+        );
+
+        assert_eq!(
+            locale.delocalize_expression(localized_expression),
+            canonical_expression
+        );
+    }
+
+    #[test]
+    fn test_number_locale_localize_expression_with_multi_byte_separator() {
+        let locale: NumberLocale = NumberLocale::new('·', None).unwrap();
+
+        let canonical_expression: &str = "f(1.5, 2.25) * 3.5 + x2";
+        let localized_expression: &str = "f(1·5, 2·25) * 3·5 + x2";
+
+        assert_eq!(
+            locale.localize_expression(canonical_expression),
+            localized_expression
+        );
+
+        assert_eq!(
+            locale.delocalize_expression(localized_expression),
+            canonical_expression
+        );
+    }
+}
@@ -0,0 +1,136 @@
+/// Notation used to write numbers of results
+///
+/// Auto writes numbers in shortest decimal form as `0.0000001234`, Scientific writes them
+/// with one digit before decimal point as `1.234e-7`, Engineering constrains exponent
+/// to multiples of 3 as `123.4e-9` and Fixed writes them with a given number of decimals.
+/// Significant writes them in decimal form rounded to a given number of significant figures,
+/// as `0.0046` for `0.004567` with 2 figures, trailing zeros being significant.
+///
+/// Numbers are always written with canonical convention, a locale can be applied afterwards.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Notation {
+    #[default]
+    Auto,
+    Scientific,
+    Engineering,
+    Fixed(usize),
+    Significant(usize),
+}
+
+impl Notation {
+    /// Write number given in argument according to notation
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return format!("{}", value);
+        }
+
+        return match self {
+            Self::Auto => format!("{}", value),
+            Self::Scientific => format!("{:e}", value),
+            Self::Engineering => Notation::format_engineering(value),
+            Self::Fixed(decimals) => format!("{:.*}", decimals, value),
+            Self::Significant(figures) => Notation::format_significant(value, *figures),
+        };
+    }
+
+    /// Write number in decimal form rounded to a number of significant figures, at least one
+    ///
+    /// Number is rounded by scientific notation, so that rounding changing its magnitude,
+    /// as `9.96` to `10` with 2 figures, gives the right number of decimals.
+    fn format_significant(value: f64, figures: usize) -> String {
+        if value == 0.0 {
+            return format!("{:.*}", figures.saturating_sub(1), 0.0);
+        }
+
+        let figures: usize = figures.max(1);
+        let scientific_value: String = format!("{:.*e}", figures - 1, value);
+
+        let exponent_str: &str = match scientific_value.split_once('e') {
+            Some((_, exponent_str)) => exponent_str,
+            None => return scientific_value,
+        };
+
+        let exponent: i64 = exponent_str.parse::<i64>().unwrap_or(0);
+        let rounded_value: f64 = scientific_value.parse::<f64>().unwrap_or(value);
+        let decimals: usize = (figures as i64 - 1 - exponent).max(0) as usize;
+
+        return format!("{:.*}", decimals, rounded_value);
+    }
+
+    /// Write number in engineering notation
+    ///
+    /// Digits are taken from scientific notation and decimal point is shifted,
+    /// so that no rounding error is introduced by a division.
+    fn format_engineering(value: f64) -> String {
+        let scientific_value: String = format!("{:e}", value);
+
+        let (mantissa, exponent_str): (&str, &str) = match scientific_value.split_once('e') {
+            Some(parts) => parts,
+            None => return scientific_value,
+        };
+
+        let exponent: i32 = exponent_str.parse::<i32>().unwrap_or(0);
+        let shift: usize = exponent.rem_euclid(3) as usize;
+
+        let (sign, unsigned_mantissa): (&str, &str) = match mantissa.strip_prefix('-') {
+            Some(unsigned_mantissa) => ("-", unsigned_mantissa),
+            None => ("", mantissa),
+        };
+
+        let mut digits: String = unsigned_mantissa.replace('.', "");
+
+        while digits.len() < shift + 1 {
+            digits.push('0');
+        }
+
+        let (integer_part, decimal_part): (&str, &str) = digits.split_at(shift + 1);
+
+        let mut engineering_value: String = format!("{}{}", sign, integer_part);
+
+        if !decimal_part.is_empty() {
+            engineering_value.push('.');
+            engineering_value.push_str(decimal_part);
+        }
+
+        return format!("{}e{}", engineering_value, exponent - shift as i32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notation_format_small_value() {
+        assert_eq!(Notation::Auto.format(0.0000001234), "0.0000001234");
+        assert_eq!(Notation::Scientific.format(0.0000001234), "1.234e-7");
+        assert_eq!(Notation::Engineering.format(0.0000001234), "123.4e-9");
+        assert_eq!(Notation::Fixed(3).format(0.0000001234), "0.000");
+        assert_eq!(Notation::Significant(2).format(0.004567), "0.0046");
+    }
+
+    #[test]
+    fn test_notation_format_large_value() {
+        assert_eq!(Notation::Auto.format(-45000000.0), "-45000000");
+        assert_eq!(Notation::Scientific.format(-45000000.0), "-4.5e7");
+        assert_eq!(Notation::Engineering.format(-45000000.0), "-45e6");
+        assert_eq!(Notation::Fixed(1).format(-45000000.0), "-45000000.0");
+        assert_eq!(Notation::Significant(3).format(-45678901.0), "-45700000");
+    }
+
+    #[test]
+    fn test_notation_format_mid_range_value() {
+        assert_eq!(Notation::Auto.format(5.4321), "5.4321");
+        assert_eq!(Notation::Scientific.format(5.4321), "5.4321e0");
+        assert_eq!(Notation::Engineering.format(271.828), "271.828e0");
+        assert_eq!(Notation::Engineering.format(1000.0), "1e3");
+        assert_eq!(Notation::Engineering.format(0.0), "0e0");
+        assert_eq!(Notation::Fixed(2).format(5.4321), "5.43");
+        assert_eq!(Notation::Significant(2).format(123.456), "120");
+        assert_eq!(Notation::Significant(2).format(9.96), "10");
+        assert_eq!(Notation::Significant(3).format(1.0), "1.00");
+        assert_eq!(Notation::Significant(2).format(0.0), "0.0");
+        assert_eq!(Notation::Scientific.format(f64::INFINITY), "inf");
+    }
+}
@@ -0,0 +1,432 @@
+#[cfg(feature = "limits")]
+use crate::limits::Limits;
+use crate::value::EvaluateValue;
+use crate::Calculator;
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Factory creating calculator of a new session
+pub type SessionFactory<Evaluator> = Box<dyn Fn() -> Calculator<Evaluator> + Send>;
+
+/// Session of a user, i.e. its calculator and its last use
+struct Session<Evaluator>
+where
+    Evaluator: EvaluateValue,
+{
+    calculator: Calculator<Evaluator>, // calculator isolating variables and functions of user
+    last_used: Instant,                // instant of last use, to evict idle session
+    last_use: u64, // rank of last use among all sessions, to evict least recently used session
+}
+
+/// Manager of isolated sessions, each one with its own calculator, keyed by user
+///
+/// A session is created lazily by factory on first use of its key. Sessions idle for longer than
+/// maximal idle duration are evicted, as well as least recently used sessions when maximal number
+/// of sessions is reached.
+///
+/// Behind a mutex, lock does not need to be held during evaluation: `checkout` takes calculator
+/// of a session out of manager, it is processed without lock, then given back by `checkin`.
+/// Meanwhile, the session cannot be processed nor checked out again.
+///
+pub struct SessionManager<Key, Evaluator>
+where
+    Key: Hash + Eq + Clone,
+    Evaluator: EvaluateValue,
+{
+    factory: SessionFactory<Evaluator>, // factory creating calculator of a new session
+    sessions: HashMap<Key, Session<Evaluator>>, // sessions of users, key identifies user
+    checked_out: HashSet<Key>,          // keys of sessions whose calculator is taken by `checkout`
+    max_sessions: Option<usize>,        // maximal number of sessions, none for no limit
+    max_idle: Option<Duration>, // maximal duration since last use of a session, none for no limit
+    #[cfg(feature = "limits")]
+    session_limits: Option<Limits>, // limits applied on calculator of each new session, none to keep the ones of factory
+    use_counter: u64, // number of uses of sessions, giving rank of last use
+}
+
+impl<Key, Evaluator> SessionManager<Key, Evaluator>
+where
+    Key: Hash + Eq + Clone,
+    Evaluator: EvaluateValue,
+{
+    /// Construct a manager whose sessions are created by factory given in argument
+    pub fn new<Factory>(factory: Factory) -> Self
+    where
+        Factory: Fn() -> Calculator<Evaluator> + Send + 'static,
+    {
+        return Self {
+            factory: Box::new(factory),
+            sessions: HashMap::new(),
+            checked_out: HashSet::new(),
+            max_sessions: None,
+            max_idle: None,
+            #[cfg(feature = "limits")]
+            session_limits: None,
+            use_counter: 0,
+        };
+    }
+
+    /// Construct a manager whose sessions are calculators with a clone of evaluator given in argument
+    pub fn from_evaluator(evaluator: Evaluator) -> Self
+    where
+        Evaluator: Clone + Send + 'static,
+    {
+        return Self::new(move || Calculator::new(evaluator.clone()));
+    }
+
+    /// Set maximal number of sessions, none for no limit
+    ///
+    /// When a new session would exceed it, least recently used session is evicted.
+    /// With zero, no session is kept: each input is processed by a new calculator,
+    /// and a calculator given back by `checkin` is dropped.
+    pub fn set_max_sessions(&mut self, max_sessions: Option<usize>) {
+        self.max_sessions = max_sessions;
+        self.evict_least_recently_used(0);
+    }
+
+    /// Set maximal duration since last use of a session, none for no limit
+    ///
+    /// Idle sessions are evicted when manager is used, or explicitly by `evict_idle_sessions`.
+    pub fn set_max_idle(&mut self, max_idle: Option<Duration>) {
+        self.max_idle = max_idle;
+    }
+
+    /// Set limits applied on calculator of each new session, none to keep the ones set by factory
+    #[cfg(feature = "limits")]
+    pub fn set_session_limits(&mut self, session_limits: Option<Limits>) {
+        self.session_limits = session_limits;
+    }
+
+    /// Process input of user identified by key, creating its session if needed
+    ///
+    /// Input is processed as with `Calculator::process`.
+    /// It fails if the session is checked out.
+    pub fn process(&mut self, key: &Key, input: &str) -> Result<String, String> {
+        self.check_not_checked_out(key)?;
+        self.evict_idle_sessions();
+
+        if self.max_sessions == Some(0) {
+            return self.create_calculator().process(input);
+        }
+
+        return self.session(key).process(input);
+    }
+
+    /// Take calculator of session identified by key out of manager, creating it if needed
+    ///
+    /// Session is not counted until its calculator is given back with `checkin`,
+    /// thus it can be processed without holding lock of a mutex around manager.
+    /// It fails if the session is already checked out.
+    pub fn checkout(&mut self, key: &Key) -> Result<Calculator<Evaluator>, String> {
+        self.check_not_checked_out(key)?;
+        self.evict_idle_sessions();
+
+        let calculator: Calculator<Evaluator> = match self.sessions.remove(key) {
+            Some(session) => session.calculator,
+            None => self.create_calculator(),
+        };
+
+        self.checked_out.insert(key.clone());
+
+        return Ok(calculator);
+    }
+
+    /// Give back calculator of session identified by key, taken by `checkout`
+    ///
+    /// If the session is not checked out, as when it has been removed by `remove_session`
+    /// meanwhile, calculator is dropped and an error is returned.
+    pub fn checkin(&mut self, key: Key, calculator: Calculator<Evaluator>) -> Result<(), String> {
+        if !self.checked_out.remove(&key) {
+            return Err(String::from(
+                "The session is not checked out, its calculator is dropped",
+            ));
+        }
+
+        self.insert_session(key, calculator);
+
+        return Ok(());
+    }
+
+    /// Remove session identified by key, return true if it existed
+    ///
+    /// A checked out session is removed as well, its calculator is then dropped by `checkin`.
+    pub fn remove_session(&mut self, key: &Key) -> bool {
+        let was_checked_out: bool = self.checked_out.remove(key);
+
+        return self.sessions.remove(key).is_some() || was_checked_out;
+    }
+
+    /// Check if session identified by key exists
+    pub fn contains_session(&self, key: &Key) -> bool {
+        return self.sessions.contains_key(key);
+    }
+
+    /// Get number of sessions, sessions taken by `checkout` are not counted
+    pub fn session_count(&self) -> usize {
+        return self.sessions.len();
+    }
+
+    /// Check if session identified by key is taken by `checkout`
+    pub fn is_checked_out(&self, key: &Key) -> bool {
+        return self.checked_out.contains(key);
+    }
+
+    /// Remove sessions idle for longer than maximal idle duration, return number of removed sessions
+    pub fn evict_idle_sessions(&mut self) -> usize {
+        let max_idle: Duration = match self.max_idle {
+            Some(max_idle) => max_idle,
+            None => return 0,
+        };
+
+        let session_count: usize = self.sessions.len();
+
+        self.sessions
+            .retain(|_, session| session.last_used.elapsed() <= max_idle);
+
+        return session_count - self.sessions.len();
+    }
+
+    /// Check that session identified by key is not taken by `checkout`
+    fn check_not_checked_out(&self, key: &Key) -> Result<(), String> {
+        if self.checked_out.contains(key) {
+            return Err(String::from(
+                "The session is checked out, it cannot be used",
+            ));
+        }
+
+        return Ok(());
+    }
+
+    /// Get calculator of session identified by key, creating it if needed, and mark it as used
+    fn session(&mut self, key: &Key) -> &mut Calculator<Evaluator> {
+        if !self.sessions.contains_key(key) {
+            let calculator: Calculator<Evaluator> = self.create_calculator();
+            self.insert_session(key.clone(), calculator);
+        }
+
+        let last_use: u64 = self.next_use();
+
+        let session: &mut Session<Evaluator> = self
+            .sessions
+            .get_mut(key)
+            .expect("The session has just been created");
+
+        session.last_used = Instant::now();
+        session.last_use = last_use;
+
+        return &mut session.calculator;
+    }
+
+    /// Create calculator of a new session, with limits of sessions if any
+    fn create_calculator(&self) -> Calculator<Evaluator> {
+        #[cfg_attr(not(feature = "limits"), allow(unused_mut))]
+        let mut calculator: Calculator<Evaluator> = (self.factory)();
+
+        #[cfg(feature = "limits")]
+        if let Some(session_limits) = self.session_limits {
+            calculator.set_limits(session_limits);
+        }
+
+        return calculator;
+    }
+
+    /// Insert session identified by key with calculator given in argument, replacing session of the same key
+    ///
+    /// Least recently used sessions are evicted to keep maximal number of sessions,
+    /// calculator is dropped if this number is zero.
+    fn insert_session(&mut self, key: Key, calculator: Calculator<Evaluator>) {
+        if !self.sessions.contains_key(&key) {
+            self.evict_least_recently_used(1);
+        }
+
+        if self.max_sessions == Some(0) {
+            return;
+        }
+
+        let last_use: u64 = self.next_use();
+
+        self.sessions.insert(
+            key,
+            Session {
+                calculator,
+                last_used: Instant::now(),
+                last_use,
+            },
+        );
+    }
+
+    /// Remove least recently used sessions so that given number of new sessions can be added
+    fn evict_least_recently_used(&mut self, new_sessions: usize) {
+        let max_sessions: usize = match self.max_sessions {
+            Some(max_sessions) => max_sessions,
+            None => return,
+        };
+
+        while !self.sessions.is_empty() && self.sessions.len() + new_sessions > max_sessions {
+            let least_recently_used: Key = self
+                .sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_use)
+                .map(|(key, _)| key.clone())
+                .expect("The sessions are not empty");
+
+            self.sessions.remove(&least_recently_used);
+        }
+    }
+
+    /// Get rank of a new use of a session
+    fn next_use(&mut self) -> u64 {
+        self.use_counter += 1;
+        return self.use_counter;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(expression: &str) -> Result<f64, String> {
+        return match expression.trim().parse::<f64>() {
+            Ok(number) => Ok(number),
+            Err(_) => Err(String::from("Invalid number")),
+        };
+    }
+
+    type StringEvaluator = fn(&str) -> Result<f64, String>;
+
+    fn session_manager() -> SessionManager<String, StringEvaluator> {
+        return SessionManager::from_evaluator(evaluate as StringEvaluator);
+    }
+
+    #[test]
+    fn test_session_isolation() {
+        let mut manager = session_manager();
+        let alice: String = String::from("alice");
+        let bob: String = String::from("bob");
+
+        assert_eq!(manager.process(&alice, "x = 2"), Ok(String::from("x = 2")));
+        assert_eq!(manager.process(&bob, "x = 5"), Ok(String::from("x = 5")));
+
+        assert_eq!(manager.process(&alice, "x"), Ok(String::from("last = 2")));
+        assert_eq!(manager.process(&bob, "x"), Ok(String::from("last = 5")));
+
+        assert!(manager.remove_session(&bob));
+        assert!(!manager.remove_session(&bob));
+        assert!(manager.process(&bob, "x").is_err());
+    }
+
+    #[test]
+    fn test_session_lazy_creation() {
+        let mut manager = session_manager();
+        let alice: String = String::from("alice");
+
+        assert_eq!(manager.session_count(), 0);
+        assert!(!manager.contains_session(&alice));
+
+        assert_eq!(manager.process(&alice, "1"), Ok(String::from("last = 1")));
+
+        assert_eq!(manager.session_count(), 1);
+        assert!(manager.contains_session(&alice));
+
+        let mut calculator = manager.checkout(&alice).unwrap();
+        assert_eq!(manager.session_count(), 0);
+        assert_eq!(calculator.process("last"), Ok(String::from("last = 1")));
+
+        assert!(manager.checkin(alice.clone(), calculator).is_ok());
+        assert_eq!(manager.session_count(), 1);
+    }
+
+    #[test]
+    fn test_session_checkout() {
+        let mut manager = session_manager();
+        let alice: String = String::from("alice");
+
+        assert_eq!(manager.process(&alice, "x = 2"), Ok(String::from("x = 2")));
+
+        let mut calculator = manager.checkout(&alice).unwrap();
+        assert!(manager.is_checked_out(&alice));
+
+        let error: Result<String, String> = Err(String::from(
+            "The session is checked out, it cannot be used",
+        ));
+        assert_eq!(manager.process(&alice, "x"), error);
+        assert!(manager.checkout(&alice).is_err());
+        assert!(!manager.contains_session(&alice));
+
+        assert_eq!(calculator.process("x = 3"), Ok(String::from("x = 3")));
+        assert!(manager.checkin(alice.clone(), calculator).is_ok());
+        assert!(!manager.is_checked_out(&alice));
+
+        assert_eq!(manager.process(&alice, "x"), Ok(String::from("last = 3")));
+        assert!(manager.checkout(&alice).is_ok());
+    }
+
+    #[test]
+    fn test_session_remove_checked_out() {
+        let mut manager = session_manager();
+        let alice: String = String::from("alice");
+
+        assert_eq!(manager.process(&alice, "x = 2"), Ok(String::from("x = 2")));
+
+        let calculator = manager.checkout(&alice).unwrap();
+        assert!(manager.remove_session(&alice));
+        assert!(!manager.is_checked_out(&alice));
+        assert!(!manager.remove_session(&alice));
+
+        // Calculator of removed session is dropped instead of being restored
+        assert_eq!(
+            manager.checkin(alice.clone(), calculator),
+            Err(String::from(
+                "The session is not checked out, its calculator is dropped"
+            ))
+        );
+        assert!(!manager.contains_session(&alice));
+        assert!(manager.process(&alice, "x").is_err());
+    }
+
+    #[test]
+    fn test_session_lru_eviction() {
+        let mut manager = session_manager();
+        manager.set_max_sessions(Some(2));
+
+        let keys: Vec<String> = vec![
+            String::from("alice"),
+            String::from("bob"),
+            String::from("carol"),
+        ];
+
+        assert!(manager.process(&keys[0], "x = 1").is_ok());
+        assert!(manager.process(&keys[1], "x = 2").is_ok());
+        assert!(manager.process(&keys[0], "x").is_ok());
+        assert!(manager.process(&keys[2], "x = 3").is_ok());
+
+        assert_eq!(manager.session_count(), 2);
+        assert!(manager.contains_session(&keys[0]));
+        assert!(!manager.contains_session(&keys[1]));
+        assert!(manager.contains_session(&keys[2]));
+
+        manager.set_max_idle(Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(manager.evict_idle_sessions(), 2);
+        assert_eq!(manager.session_count(), 0);
+    }
+
+    #[test]
+    fn test_session_zero_max_sessions() {
+        let mut manager = session_manager();
+        manager.set_max_sessions(Some(0));
+
+        let alice: String = String::from("alice");
+
+        assert_eq!(manager.process(&alice, "x = 2"), Ok(String::from("x = 2")));
+        assert!(manager.process(&alice, "x").is_err());
+        assert_eq!(manager.session_count(), 0);
+
+        let calculator = manager.checkout(&alice).unwrap();
+        assert!(manager.checkin(alice.clone(), calculator).is_ok());
+        assert_eq!(manager.session_count(), 0);
+        assert!(!manager.is_checked_out(&alice));
+    }
+}
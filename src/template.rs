@@ -0,0 +1,151 @@
+use crate::validation;
+use crate::value::{BooleanSubstitution, Value};
+
+use std::collections::HashMap;
+
+/// Render a template by replacing its placeholders `{name}` by values given in argument
+///
+/// Values are written as when they replace variables in an expression and a negative value
+/// is surrounded by parenthesis, thus with price -10 and vat 0.2, `{price} * (1 + {vat})`
+/// gives `(-10) * (1 + 0.2)` which can be processed by calculator.
+/// Braces are written by doubling them, as `{{` and `}}`.
+///
+/// A placeholder without value, a placeholder not closed, an invalid name or a stray brace gives an error
+/// indicating its column, so that rendered string never contains a placeholder.
+pub fn render(template: &str, values: &HashMap<&str, f64>) -> Result<String, String> {
+    let mut rendered_template: String = String::with_capacity(template.len());
+    let mut chars = template.chars().enumerate().peekable();
+
+    while let Some((id, c)) = chars.next() {
+        let column: usize = id + 1;
+
+        match c {
+            '{' if chars.next_if(|(_, c)| *c == '{').is_some() => rendered_template.push('{'),
+            '}' if chars.next_if(|(_, c)| *c == '}').is_some() => rendered_template.push('}'),
+            '{' => {
+                let mut name: String = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, '{')) | None => {
+                            return Err(format!(
+                                "The placeholder at column {} is not closed",
+                                column
+                            ));
+                        }
+                        Some((_, c)) => name.push(c),
+                    }
+                }
+
+                if !validation::is_identifier(name.as_str()) {
+                    return Err(format!(
+                        "The placeholder {{{}}} at column {} is not valid",
+                        name, column
+                    ));
+                }
+
+                let value: f64 = match values.get(name.as_str()) {
+                    Some(value) => *value,
+                    None => {
+                        return Err(format!(
+                            "The placeholder {{{}}} at column {} has no value",
+                            name, column
+                        ));
+                    }
+                };
+
+                if !value.is_finite() {
+                    return Err(format!(
+                        "The value {} of placeholder {{{}}} is not finite",
+                        value, name
+                    ));
+                }
+
+                let substitution: String =
+                    Value::Number(value).substitution(BooleanSubstitution::Literal);
+
+                if substitution.starts_with('-') {
+                    rendered_template.push_str(format!("({})", substitution).as_str());
+                } else {
+                    rendered_template.push_str(substitution.as_str());
+                }
+            }
+            '}' => {
+                return Err(format!("The brace '}}' at column {} is not opened", column));
+            }
+            c => rendered_template.push(c),
+        }
+    }
+
+    return Ok(rendered_template);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let values: HashMap<&str, f64> = HashMap::from([("price", 12.5), ("vat", 0.2)]);
+
+        assert_eq!(
+            render("{price} * (1 + {vat})", &values),
+            Ok(String::from("12.5 * (1 + 0.2)"))
+        );
+
+        assert_eq!(
+            render("{price}-{price}", &values),
+            Ok(String::from("12.5-12.5"))
+        );
+    }
+
+    #[test]
+    fn test_render_with_missing_value() {
+        let values: HashMap<&str, f64> = HashMap::from([("price", 12.5)]);
+
+        assert_eq!(
+            render("{price} * (1 + {vat})", &values),
+            Err(String::from(
+                "The placeholder {vat} at column 16 has no value"
+            ))
+        );
+
+        assert_eq!(
+            render("{price} * (1 + {vat", &values),
+            Err(String::from("The placeholder at column 16 is not closed"))
+        );
+
+        assert_eq!(
+            render("{ price }", &values),
+            Err(String::from(
+                "The placeholder { price } at column 1 is not valid"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_render_with_escaped_braces() {
+        let values: HashMap<&str, f64> = HashMap::from([("x", 2.0)]);
+
+        assert_eq!(
+            render("{{x}} + {x}}}", &values),
+            Ok(String::from("{x} + 2}"))
+        );
+
+        assert_eq!(
+            render("{x} }", &values),
+            Err(String::from("The brace '}' at column 5 is not opened"))
+        );
+    }
+
+    #[test]
+    fn test_render_with_negative_value() {
+        let values: HashMap<&str, f64> = HashMap::from([("x", -2.0), ("y", -0.5)]);
+
+        assert_eq!(
+            render("{x}^2 - {y}", &values),
+            Ok(String::from("(-2)^2 - (-0.5)"))
+        );
+    }
+}
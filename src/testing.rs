@@ -0,0 +1,278 @@
+//! Assertion helpers for crates testing sessions of calculator
+//!
+//! They are available with feature `testing`, each macro panics with a readable message
+//! showing expected and actual values when assertion fails.
+//!
+//! ```
+//! # #[cfg(feature = "testing")]
+//! # {
+//! use tazor::{assert_evaluates_to, assert_expands_to, session};
+//!
+//! fn evaluate(expression: &str) -> Result<f64, String> {
+//!     return Ok(expression.len() as f64);
+//! }
+//!
+//! let mut calculator = tazor::Calculator::new(evaluate);
+//!
+//! session!(calculator, ["x = 1 + 1", "f: a, b = a + b"]);
+//! assert_expands_to!(calculator, "f(1, x)", "(1 + 5)");
+//! assert_evaluates_to!(calculator, "f(1, x)", 7.0, 1e-12);
+//! # }
+//! ```
+
+use crate::golden::{self, GoldenCase, GoldenOutcome};
+use crate::value::EvaluateValue;
+use crate::Calculator;
+
+pub use crate::{assert_evaluates_to, assert_expands_to, session};
+
+/// Check that expression expands to expected string, see `assert_expands_to!`
+#[track_caller]
+pub fn check_expansion<Evaluator>(
+    calculator: &Calculator<Evaluator>,
+    expression: &str,
+    expected_expansion: &str,
+) where
+    Evaluator: EvaluateValue,
+{
+    match calculator.expand(expression) {
+        Ok(expansion) if expansion == expected_expansion => {}
+        Ok(expansion) => panic!(
+            "expansion of `{}` differs\n  expected: {}\n  actual:   {}",
+            expression, expected_expansion, expansion
+        ),
+        Err(error) => panic!(
+            "expansion of `{}` failed\n  expected: {}\n  error:    {}",
+            expression, expected_expansion, error
+        ),
+    }
+}
+
+/// Check that expression evaluates to expected value up to epsilon, see `assert_evaluates_to!`
+#[track_caller]
+pub fn check_evaluation<Evaluator>(
+    calculator: &Calculator<Evaluator>,
+    expression: &str,
+    expected_value: f64,
+    epsilon: f64,
+) where
+    Evaluator: EvaluateValue,
+{
+    match calculator.evaluate(expression) {
+        Ok(value) if (value - expected_value).abs() <= epsilon => {}
+        Ok(value) => panic!(
+            "value of `{}` differs\n  expected: {} ± {}\n  actual:   {}",
+            expression, expected_value, epsilon, value
+        ),
+        Err(error) => panic!(
+            "evaluation of `{}` failed\n  expected: {} ± {}\n  error:    {}",
+            expression, expected_value, epsilon, error
+        ),
+    }
+}
+
+/// Process lines in order and get their results, see `session!`
+#[track_caller]
+pub fn run_session<Evaluator>(calculator: &mut Calculator<Evaluator>, lines: &[&str]) -> Vec<String>
+where
+    Evaluator: EvaluateValue,
+{
+    return lines
+        .iter()
+        .enumerate()
+        .map(|(id, line)| match calculator.process(line) {
+            Ok(result) => result,
+            Err(error) => panic!(
+                "session failed at line {}\n  line:  {}\n  error: {}",
+                id + 1,
+                line,
+                error
+            ),
+        })
+        .collect();
+}
+
+/// Check golden tests of a file exported by `Calculator::export_golden_tests`
+///
+/// Each case is processed by calculator, loaded from definitions to be checked, and compared
+/// with its recorded outcome. It panics listing every failing case with expected and actual outcomes.
+#[track_caller]
+pub fn run_golden<Evaluator>(calculator: &Calculator<Evaluator>, path: &str)
+where
+    Evaluator: EvaluateValue,
+{
+    let golden: String = match std::fs::read_to_string(path) {
+        Ok(golden) => golden,
+        Err(error) => panic!("golden file {} cannot be read\n  error: {}", path, error),
+    };
+
+    let cases: Vec<GoldenCase> = match golden::parse(golden.as_str()) {
+        Ok(cases) => cases,
+        Err(error) => panic!("golden file {} is invalid\n  error: {}", path, error),
+    };
+
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| {
+            let actual_outcome: GoldenOutcome = match calculator
+                .expand(case.expression.as_str())
+                .and_then(|expansion| Ok((expansion, calculator.evaluate(&case.expression)?)))
+            {
+                Ok((expansion, value)) => GoldenOutcome::Value(expansion, value),
+                Err(error) => GoldenOutcome::Error(error),
+            };
+
+            if actual_outcome == case.outcome {
+                return None;
+            }
+
+            return Some(format!(
+                "golden case `{}` differs\n  expected: {}\n  actual:   {}",
+                case.expression,
+                written_outcome(&case.outcome),
+                written_outcome(&actual_outcome)
+            ));
+        })
+        .collect();
+
+    if !failures.is_empty() {
+        panic!("{}", failures.join("\n"));
+    }
+}
+
+/// Write outcome of a golden case as `expansion = value` or `error: message`
+fn written_outcome(outcome: &GoldenOutcome) -> String {
+    return match outcome {
+        GoldenOutcome::Value(expansion, value) => format!("{} = {}", expansion, value),
+        GoldenOutcome::Error(error) => format!("error: {}", error),
+    };
+}
+
+/// Assert that an expression expands to expected string, without evaluating it
+///
+/// `assert_expands_to!(calculator, "f(1, 2)", "(1 + 2)")`
+#[macro_export]
+macro_rules! assert_expands_to {
+    ($calculator:expr, $expression:expr, $expansion:expr $(,)?) => {
+        $crate::testing::check_expansion(&$calculator, $expression, $expansion)
+    };
+}
+
+/// Assert that an expression evaluates to expected value up to epsilon
+///
+/// `assert_evaluates_to!(calculator, "2 + 2", 4.0, 1e-12)`
+#[macro_export]
+macro_rules! assert_evaluates_to {
+    ($calculator:expr, $expression:expr, $value:expr, $epsilon:expr $(,)?) => {
+        $crate::testing::check_evaluation(&$calculator, $expression, $value, $epsilon)
+    };
+}
+
+/// Process a list of lines and get their results, it panics with failing line and its error
+///
+/// `session!(calculator, ["x = 2", "f: y = y * x"])`
+#[macro_export]
+macro_rules! session {
+    ($calculator:expr, [$($line:expr),* $(,)?]) => {
+        $crate::testing::run_session(&mut $calculator, &[$($line),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Define mock evaluator for units tests
+    fn evaluate(expression: &str) -> Result<f64, String> {
+        if expression.trim().is_empty() {
+            return Err(String::from("Expression is empty"));
+        }
+
+        return Ok(expression.len() as f64);
+    }
+
+    // Run function which must panic and get its message
+    fn panic_message<F: FnOnce()>(function: F) -> String {
+        return match panic::catch_unwind(AssertUnwindSafe(function)) {
+            Ok(_) => String::new(),
+            Err(payload) => match payload.downcast::<String>() {
+                Ok(message) => *message,
+                Err(_) => String::from("unknown panic"),
+            },
+        };
+    }
+
+    #[test]
+    fn test_testing_helpers_succeed() {
+        let mut calculator = Calculator::new(evaluate);
+
+        let results: Vec<String> = session!(calculator, ["x = 1 + 1", "f: a, b = a + b",]);
+
+        assert_eq!(results, vec!["x = 5", "f(a, b) = a + b"]);
+
+        assert_expands_to!(calculator, "f(1, x)", "(1 + 5)");
+        assert_evaluates_to!(calculator, "f(1, x)", 7.0, 1e-12);
+    }
+
+    #[test]
+    fn test_testing_helpers_failure_messages() {
+        let mut calculator = Calculator::new(evaluate);
+
+        assert_eq!(
+            panic_message(|| {
+                session!(calculator, ["x = 2", "y = ", "z = 3"]);
+            }),
+            "session failed at line 2\n  line:  y = \n  error: The expression is empty"
+        );
+
+        assert_eq!(
+            panic_message(|| assert_expands_to!(calculator, "x + 1", "2 + 1")),
+            "expansion of `x + 1` differs\n  expected: 2 + 1\n  actual:   1 + 1"
+        );
+
+        assert_eq!(
+            panic_message(|| assert_evaluates_to!(calculator, "x + 1", 3.0, 0.5)),
+            "value of `x + 1` differs\n  expected: 3 ± 0.5\n  actual:   5"
+        );
+    }
+
+    #[test]
+    fn test_testing_run_golden() {
+        let mut calculator = Calculator::new(evaluate);
+        session!(calculator, ["x = 2", "f: a = a + x", "g: a = f(a) * a"]);
+
+        let golden: String = calculator.export_golden_tests(&["f(1)", "g(10)", "()"]);
+
+        assert_eq!(
+            golden,
+            "# golden tests of calculator\n\n[[case]]\nexpression = \"f(1)\"\nexpansion = \"(1 + 1)\"\nvalue = 7\n\n[[case]]\nexpression = \"g(10)\"\nexpansion = \"((10 + 1) * 10)\"\nvalue = 15\n\n[[case]]\nexpression = \"()\"\nerror = \"The expression is empty\"\n"
+        );
+
+        let path: std::path::PathBuf =
+            std::env::temp_dir().join(format!("tazor_golden_{}.toml", std::process::id()));
+        std::fs::write(&path, golden).unwrap();
+        let path_str: &str = path.to_str().unwrap();
+
+        // Goldens are checked against a calculator loaded from the same definitions
+        let mut loaded_calculator = Calculator::new(evaluate);
+        loaded_calculator
+            .load_script(calculator.export_script().as_str())
+            .unwrap();
+
+        assert_eq!(
+            panic_message(|| run_golden(&loaded_calculator, path_str)),
+            ""
+        );
+
+        // Editing a function is caught by the case using it
+        session!(loaded_calculator, ["g: a = f(a) - a"]);
+
+        assert_eq!(
+            panic_message(|| run_golden(&loaded_calculator, path_str)),
+            "golden case `g(10)` differs\n  expected: ((10 + 1) * 10) = 15\n  actual:   ((10 + 1) - 10) = 15"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+use std::ops::Range;
+
+/// Kind of lexeme that we can find in an expression
+///
+/// Number is a sequence of digits with optional decimal part and optional exponent
+/// as `2`, `1.5`, `.5`, `1.5e2`, `2E-3` or `7e+10`.
+///
+/// Identifier starts with a letter or an underscore followed by letters, digits or underscores
+/// as `x`, `velocity` or `e2`. These characters can be changed by an identifier predicate,
+/// an identifier then starts with any character accepted by predicate except a digit. A positional variable of function, as `$1`, is also an identifier,
+/// as well as a reference to a previous result, as `%3`, when it is at the place of an operand.
+/// Elsewhere, as in `10 %3`, `%` is a symbol.
+///
+/// Symbol is any other character which is not a whitespace as `+`, `(` or `,`.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LexemeKind {
+    Number,
+    Identifier,
+    Symbol,
+}
+
+/// Token of an expression, as given by `tokenize`
+///
+/// Operator is any symbol which is not a parenthesis, a bracket or a comma, as `+`, `^` or `=`.
+/// Brackets surround elements of a vector, as `[1, 2]`, or an index, as `v[0]`.
+/// A positional variable of function, as `$1`, and a reference to a previous result, as `%3`, are identifiers.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Number(f64),
+    Identifier(String),
+    Operator(char),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+/// Predicate giving characters which can be part of an identifier, as `is_identifier_character`
+pub type IdentifierPredicate = fn(char) -> bool;
+
+/// Default identifier predicate, accepting letters, digits and underscore
+pub fn is_identifier_character(c: char) -> bool {
+    return c.is_alphanumeric() || c == '_';
+}
+
+/// Lexeme of an expression defined by its kind and its byte range in expression
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Lexeme {
+    pub kind: LexemeKind,
+    pub range: Range<usize>,
+}
+
+/// Iterator over lexemes of an expression, whitespaces are skipped
+///
+/// A number is always a single lexeme, in particular the sign of its exponent
+/// is part of the number and a letter in the exponent is never an identifier.
+pub(crate) struct Lexer<'a> {
+    expression: &'a str,                          // expression to split into lexemes
+    position: usize,           // byte position of next lexeme search in expression
+    is_operand_expected: bool, // if true, next lexeme is at the place of an operand, as after an operator
+    is_identifier_character: IdentifierPredicate, // predicate giving characters which can be part of an identifier
+}
+
+impl<'a> Lexer<'a> {
+    /// Construct a lexer over expression given in argument
+    pub fn new(expression: &'a str) -> Self {
+        return Self::with_identifier_predicate(expression, is_identifier_character);
+    }
+
+    /// Construct a lexer over expression given in argument, with an identifier predicate
+    pub fn with_identifier_predicate(
+        expression: &'a str,
+        is_identifier_character: IdentifierPredicate,
+    ) -> Self {
+        return Self {
+            expression,
+            position: 0,
+            is_operand_expected: true,
+            is_identifier_character,
+        };
+    }
+
+    /// Get byte length of number at start of string given in argument
+    fn number_length(remaining: &str) -> usize {
+        let bytes: &[u8] = remaining.as_bytes();
+        let is_digit = |id: usize| -> bool { bytes.get(id).is_some_and(u8::is_ascii_digit) };
+
+        // Integer and decimal part
+        let mut length: usize = 0;
+
+        while is_digit(length) {
+            length += 1;
+        }
+
+        if bytes.get(length) == Some(&b'.') {
+            length += 1;
+
+            while is_digit(length) {
+                length += 1;
+            }
+        }
+
+        // Exponent part is taken only if it contains at least one digit
+        if matches!(bytes.get(length), Some(b'e') | Some(b'E')) {
+            let sign_length: usize = match bytes.get(length + 1) {
+                Some(b'+') | Some(b'-') => 1,
+                _ => 0,
+            };
+
+            if is_digit(length + 1 + sign_length) {
+                length += 1 + sign_length;
+
+                while is_digit(length) {
+                    length += 1;
+                }
+            }
+        }
+
+        return length;
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Lexeme;
+
+    fn next(&mut self) -> Option<Lexeme> {
+        let remaining: &str = &self.expression[self.position..];
+        let trimmed_remaining: &str = remaining.trim_start();
+
+        let start: usize = self.position + remaining.len() - trimmed_remaining.len();
+
+        let mut chars = trimmed_remaining.chars();
+        let c: char = chars.next()?;
+
+        let (kind, length): (LexemeKind, usize) = if c.is_ascii_digit()
+            || (c == '.' && chars.next().is_some_and(|c| c.is_ascii_digit()))
+        {
+            (LexemeKind::Number, Lexer::number_length(trimmed_remaining))
+        } else if (c == '$' || (c == '%' && self.is_operand_expected))
+            && chars.next().is_some_and(|c| c.is_ascii_digit())
+        {
+            (
+                LexemeKind::Identifier,
+                1 + trimmed_remaining[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(trimmed_remaining.len() - 1),
+            )
+        } else if (self.is_identifier_character)(c) && !c.is_ascii_digit() {
+            (
+                LexemeKind::Identifier,
+                trimmed_remaining
+                    .find(|c: char| !(self.is_identifier_character)(c))
+                    .unwrap_or(trimmed_remaining.len()),
+            )
+        } else {
+            (LexemeKind::Symbol, c.len_utf8())
+        };
+
+        self.position = start + length;
+        self.is_operand_expected = kind == LexemeKind::Symbol
+            && !matches!(&self.expression[start..self.position], ")" | "]" | "}");
+
+        return Some(Lexeme {
+            kind,
+            range: start..self.position,
+        });
+    }
+}
+
+/// Split an expression into lexemes, whitespaces are skipped
+pub(crate) fn lex(expression: &str) -> Vec<Lexeme> {
+    return Lexer::new(expression).collect();
+}
+
+/// Split an expression into lexemes with an identifier predicate, whitespaces are skipped
+pub(crate) fn lex_with_identifier_predicate(
+    expression: &str,
+    is_identifier_character: IdentifierPredicate,
+) -> Vec<Lexeme> {
+    return Lexer::with_identifier_predicate(expression, is_identifier_character).collect();
+}
+
+/// Split an expression into tokens, whitespaces are skipped
+///
+/// `f(x, 2) + 3.5` gives identifier `f`, `(`, identifier `x`, `,`, number `2`, `)`,
+/// operator `+` and number `3.5`.
+pub fn tokenize(expression: &str) -> Vec<Token> {
+    return tokenize_with_identifier_predicate(expression, is_identifier_character);
+}
+
+/// Split an expression into tokens, whose identifiers are made of characters accepted by predicate
+///
+/// With a predicate accepting `$` and `.`, `$rate.year + 1` gives identifier `$rate.year`, operator `+`
+/// and number `1`.
+pub fn tokenize_with_identifier_predicate(
+    expression: &str,
+    is_identifier_character: IdentifierPredicate,
+) -> Vec<Token> {
+    return Lexer::with_identifier_predicate(expression, is_identifier_character)
+        .map(|lexeme| {
+            let text: &str = &expression[lexeme.range];
+
+            return match lexeme.kind {
+                LexemeKind::Number => Token::Number(text.parse::<f64>().unwrap_or(f64::NAN)),
+                LexemeKind::Identifier => Token::Identifier(String::from(text)),
+                LexemeKind::Symbol => match text {
+                    "(" => Token::LParen,
+                    ")" => Token::RParen,
+                    "[" => Token::LBracket,
+                    "]" => Token::RBracket,
+                    "," => Token::Comma,
+                    _ => Token::Operator(text.chars().next().unwrap_or_default()),
+                },
+            };
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Get kind and text of each lexeme of expression
+    fn lex_str(expression: &str) -> Vec<(LexemeKind, &str)> {
+        return lex(expression)
+            .into_iter()
+            .map(|lexeme| (lexeme.kind, &expression[lexeme.range]))
+            .collect();
+    }
+
+    #[test]
+    fn test_lex_expression() {
+        assert_eq!(
+            lex_str("f(x, 2) + 3.5"),
+            vec![
+                (LexemeKind::Identifier, "f"),
+                (LexemeKind::Symbol, "("),
+                (LexemeKind::Identifier, "x"),
+                (LexemeKind::Symbol, ","),
+                (LexemeKind::Number, "2"),
+                (LexemeKind::Symbol, ")"),
+                (LexemeKind::Symbol, "+"),
+                (LexemeKind::Number, "3.5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_expression() {
+        assert_eq!(
+            tokenize("f(x, 2) + 3.5"),
+            vec![
+                Token::Identifier(String::from("f")),
+                Token::LParen,
+                Token::Identifier(String::from("x")),
+                Token::Comma,
+                Token::Number(2.0),
+                Token::RParen,
+                Token::Operator('+'),
+                Token::Number(3.5),
+            ]
+        );
+
+        assert_eq!(
+            tokenize("$1^.5e1"),
+            vec![
+                Token::Identifier(String::from("$1")),
+                Token::Operator('^'),
+                Token::Number(5.0),
+            ]
+        );
+
+        assert_eq!(
+            tokenize("[1, 2][0]"),
+            vec![
+                Token::LBracket,
+                Token::Number(1.0),
+                Token::Comma,
+                Token::Number(2.0),
+                Token::RBracket,
+                Token::LBracket,
+                Token::Number(0.0),
+                Token::RBracket,
+            ]
+        );
+
+        assert!(tokenize("  ").is_empty());
+
+        assert_eq!(
+            tokenize_with_identifier_predicate("$rate.year + 1", |c| {
+                return is_identifier_character(c) || c == '$' || c == '.';
+            }),
+            vec![
+                Token::Identifier(String::from("$rate.year")),
+                Token::Operator('+'),
+                Token::Number(1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_numbers_with_exponent() {
+        assert_eq!(
+            lex_str("1.5e2 + 2E-3 * 7e+10 - .5"),
+            vec![
+                (LexemeKind::Number, "1.5e2"),
+                (LexemeKind::Symbol, "+"),
+                (LexemeKind::Number, "2E-3"),
+                (LexemeKind::Symbol, "*"),
+                (LexemeKind::Number, "7e+10"),
+                (LexemeKind::Symbol, "-"),
+                (LexemeKind::Number, ".5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_positional_variables() {
+        assert_eq!(
+            lex_str("$1*$12 + $ x"),
+            vec![
+                (LexemeKind::Identifier, "$1"),
+                (LexemeKind::Symbol, "*"),
+                (LexemeKind::Identifier, "$12"),
+                (LexemeKind::Symbol, "+"),
+                (LexemeKind::Symbol, "$"),
+                (LexemeKind::Identifier, "x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_result_references() {
+        assert_eq!(
+            lex_str("%1 * f(%12) + 10 %3 - (2)%4"),
+            vec![
+                (LexemeKind::Identifier, "%1"),
+                (LexemeKind::Symbol, "*"),
+                (LexemeKind::Identifier, "f"),
+                (LexemeKind::Symbol, "("),
+                (LexemeKind::Identifier, "%12"),
+                (LexemeKind::Symbol, ")"),
+                (LexemeKind::Symbol, "+"),
+                (LexemeKind::Number, "10"),
+                (LexemeKind::Symbol, "%"),
+                (LexemeKind::Number, "3"),
+                (LexemeKind::Symbol, "-"),
+                (LexemeKind::Symbol, "("),
+                (LexemeKind::Number, "2"),
+                (LexemeKind::Symbol, ")"),
+                (LexemeKind::Symbol, "%"),
+                (LexemeKind::Number, "4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_number_followed_by_identifier() {
+        assert_eq!(
+            lex_str("2e + e2"),
+            vec![
+                (LexemeKind::Number, "2"),
+                (LexemeKind::Identifier, "e"),
+                (LexemeKind::Symbol, "+"),
+                (LexemeKind::Identifier, "e2"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1211 @@
+use crate::expression::{self, Expression};
+use crate::token::{self, IdentifierPredicate, Lexeme, LexemeKind, Lexer};
+use crate::{CalculatorState, VariableRange};
+
+use std::ops::Range;
+
+/// Check that brackets of expression are balanced and correctly nested
+///
+/// Parenthesis `()`, square brackets `[]` and curly brackets `{}` are considered as grouping,
+/// thus a mismatch as `(a]` or an unclosed bracket as `(a` gives an error indicating its column.
+pub fn check_brackets(expression: &str) -> Result<(), String> {
+    let mut opened_brackets: Vec<(char, usize)> = Vec::new();
+
+    for (id, c) in expression.chars().enumerate() {
+        let column: usize = id + 1;
+
+        match c {
+            '(' | '[' | '{' => opened_brackets.push((c, column)),
+            ')' | ']' | '}' => match opened_brackets.pop() {
+                Some((opening_bracket, _)) if closing_bracket(opening_bracket) == c => {}
+                Some((opening_bracket, opening_column)) => {
+                    return Err(format!(
+                        "The bracket '{}' at column {} does not match bracket '{}' at column {}",
+                        c, column, opening_bracket, opening_column
+                    ));
+                }
+                None => {
+                    return Err(format!(
+                        "The bracket '{}' at column {} is not opened",
+                        c, column
+                    ));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    return match opened_brackets.pop() {
+        Some((opening_bracket, opening_column)) => Err(format!(
+            "The bracket '{}' at column {} is not closed",
+            opening_bracket, opening_column
+        )),
+        None => Ok(()),
+    };
+}
+
+/// Check that no token is left over after a complete operand, as `oops` in `2 + 2 oops`
+///
+/// An operand ends with a number, an identifier or a closing bracket and it must be followed
+/// by an operator or a separator, not by another operand. A function call, as `f(2)`,
+/// and an index after an identifier or a closing bracket, as `v[1]`, are allowed.
+/// The escape character before an identifier is ignored.
+pub fn check_trailing_tokens(expression: &str) -> Result<(), String> {
+    let lexeme_str = |lexeme: &Lexeme| -> &str { &expression[lexeme.range.clone()] };
+
+    let ends_operand = |lexeme: &Lexeme| -> bool {
+        return lexeme.kind != LexemeKind::Symbol || matches!(lexeme_str(lexeme), ")" | "]" | "}");
+    };
+
+    let starts_operand = |lexeme: &Lexeme| -> bool {
+        return lexeme.kind != LexemeKind::Symbol || matches!(lexeme_str(lexeme), "(" | "[" | "{");
+    };
+
+    let mut previous_lexeme: Option<Lexeme> = None;
+
+    for lexeme in Lexer::new(expression).filter(|lexeme| lexeme_str(lexeme) != "`") {
+        if let Some(previous_lexeme) = &previous_lexeme {
+            let is_call: bool =
+                previous_lexeme.kind == LexemeKind::Identifier && lexeme_str(&lexeme) == "(";
+
+            let is_index: bool = lexeme_str(&lexeme) == "["
+                && (previous_lexeme.kind == LexemeKind::Identifier
+                    || matches!(lexeme_str(previous_lexeme), ")" | "]"));
+
+            if ends_operand(previous_lexeme) && starts_operand(&lexeme) && !is_call && !is_index {
+                return Err(format!(
+                    "The token '{}' at column {} is unexpected",
+                    lexeme_str(&lexeme),
+                    expression[..lexeme.range.start].chars().count() + 1
+                ));
+            }
+        }
+
+        previous_lexeme = Some(lexeme);
+    }
+
+    return Ok(());
+}
+
+/// Check that each binary operator of expression has its operands, as `/` in `2 * / 3`
+///
+/// A binary operator cannot follow another operator, an opening bracket or a separator,
+/// and it cannot end expression. Signs `+` and `-` are also unary operators, thus `+5`, `--3`,
+/// `2 - -3` and `2 * +x` are allowed, as in built-in evaluator. Consecutive symbols of a comparison
+/// or logical operator, as `<=` or `&&`, form one operator. The escape character is ignored.
+pub fn check_operators(expression: &str) -> Result<(), String> {
+    let is_operator_part = |c: char| -> bool { return "<>=!&|".contains(c) };
+    let column = |position: usize| -> usize { expression[..position].chars().count() + 1 };
+
+    let mut lexemes = Lexer::new(expression)
+        .filter(|lexeme| &expression[lexeme.range.clone()] != "`")
+        .peekable();
+
+    let mut is_operand_expected: bool = true;
+    let mut last_operator: Option<(&str, usize)> = None;
+
+    while let Some(lexeme) = lexemes.next() {
+        let mut end: usize = lexeme.range.end;
+        let text: &str = &expression[lexeme.range.clone()];
+
+        if lexeme.kind == LexemeKind::Symbol && text.chars().all(is_operator_part) {
+            if let Some(next_lexeme) = lexemes.next_if(|next_lexeme| {
+                return next_lexeme.range.start == end
+                    && expression[next_lexeme.range.clone()]
+                        .chars()
+                        .all(is_operator_part);
+            }) {
+                end = next_lexeme.range.end;
+            }
+        }
+
+        let text: &str = &expression[lexeme.range.start..end];
+        let is_binary_operator: bool = lexeme.kind == LexemeKind::Symbol
+            && !matches!(text, "+" | "-")
+            && expression::BINARY_OPERATORS.contains(&text);
+
+        if is_binary_operator && is_operand_expected {
+            return Err(format!(
+                "The operator '{}' at column {} is missing its left operand",
+                text,
+                column(lexeme.range.start)
+            ));
+        }
+
+        is_operand_expected =
+            lexeme.kind == LexemeKind::Symbol && !matches!(text, ")" | "]" | "}" | "!");
+
+        last_operator = match is_operand_expected && expression::BINARY_OPERATORS.contains(&text) {
+            true => Some((text, lexeme.range.start)),
+            false => None,
+        };
+    }
+
+    if let Some((operator, position)) = last_operator {
+        return Err(format!(
+            "The operator '{}' at column {} is missing its right operand",
+            operator,
+            column(position)
+        ));
+    }
+
+    return Ok(());
+}
+
+/// Check that each token of expression, as a number or an identifier, is not longer than maximal length
+///
+/// The error indicates the column where the oversized token starts. Expression is read only once,
+/// so that a huge token is rejected quickly.
+pub fn check_token_lengths(expression: &str, max_token_length: usize) -> Result<(), String> {
+    return match find_too_long_token(expression, max_token_length) {
+        Some(column) => Err(format!(
+            "The token is too long at column {} (maximal length is {})",
+            column, max_token_length
+        )),
+        None => Ok(()),
+    };
+}
+
+/// Find column of the first token of expression whose number of bytes exceeds maximal token length
+pub(crate) fn find_too_long_token(expression: &str, max_token_length: usize) -> Option<usize> {
+    return Lexer::new(expression)
+        .find(|lexeme| lexeme.range.len() > max_token_length)
+        .map(|lexeme| expression[..lexeme.range.start].chars().count() + 1);
+}
+
+/// Check if a name is a valid identifier for a variable or a function
+///
+/// An identifier starts with a letter or an underscore followed by letters, digits or underscores,
+/// as `x`, `velocity` or `_x2`.
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    return chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+}
+
+/// Check if a name is a valid identifier when identifiers are made of characters accepted by predicate
+///
+/// A valid identifier, see `is_identifier`, is always accepted. Otherwise name must start with
+/// a character accepted by predicate which is not a digit, followed by characters accepted by predicate,
+/// thus `$rate` is an identifier with a predicate accepting `$`. A positional variable, as `$1`, is not.
+pub fn is_identifier_with_predicate(
+    name: &str,
+    is_identifier_character: IdentifierPredicate,
+) -> bool {
+    if is_identifier(name) {
+        return true;
+    }
+
+    let is_positional: bool = name
+        .strip_prefix('$')
+        .is_some_and(|id| id.starts_with(|c: char| c.is_ascii_digit()));
+
+    let mut chars = name.chars();
+
+    return !is_positional
+        && chars
+            .next()
+            .is_some_and(|c| is_identifier_character(c) && !c.is_ascii_digit())
+        && chars.all(is_identifier_character);
+}
+
+/// Check that name of a variable or function definition is a valid identifier
+///
+/// Thus a typo as `2 + 2 = 4`, which defines variable `2 + 2`, gives an error.
+/// The freeze marker at end of function name, as in `g!: x = f(x)`, is allowed.
+pub fn check_definition_name(expression: &Expression) -> Result<(), String> {
+    return check_definition_name_with_predicate(expression, token::is_identifier_character);
+}
+
+/// Check that name of a variable or function definition is a valid identifier as `check_definition_name`,
+/// identifiers being made of characters accepted by predicate, see `is_identifier_with_predicate`
+pub fn check_definition_name_with_predicate(
+    expression: &Expression,
+    is_identifier_character: IdentifierPredicate,
+) -> Result<(), String> {
+    return match expression {
+        Expression::Raw(_) => Ok(()),
+        Expression::Variable(name, _)
+            if !is_identifier_with_predicate(name, is_identifier_character) =>
+        {
+            Err(format!("The variable name {} is not valid", name))
+        }
+        Expression::Variable(_, _) => Ok(()),
+        Expression::Function(name, _, _) => {
+            let unmarked_name: &str = name
+                .strip_suffix(expression::FREEZE_MARKER)
+                .unwrap_or(name)
+                .trim_end();
+
+            if !is_identifier_with_predicate(unmarked_name, is_identifier_character) {
+                return Err(format!("The function name {} is not valid", name));
+            }
+
+            Ok(())
+        }
+    };
+}
+
+/// Check that a definition does not define a name reserved by evaluator
+///
+/// Name of defined variable or function and names of variables of function are checked,
+/// thus with reserved name `i`, both `i = 2` and `f: i = i + 1` are rejected.
+pub fn check_reserved_names(
+    expression: &Expression,
+    reserved_names: &[&str],
+) -> Result<(), String> {
+    let is_reserved = |name: &str| -> bool {
+        return reserved_names.contains(&name);
+    };
+
+    return match expression {
+        Expression::Raw(_) => Ok(()),
+        Expression::Variable(name, _) if is_reserved(name) => Err(format!(
+            "The variable name {} is reserved by evaluator and cannot be defined",
+            name
+        )),
+        Expression::Variable(_, _) => Ok(()),
+        Expression::Function(name, variables, _) => {
+            let unmarked_name: &str = name
+                .strip_suffix(expression::FREEZE_MARKER)
+                .unwrap_or(name)
+                .trim_end();
+
+            if is_reserved(unmarked_name) {
+                return Err(format!(
+                    "The function name {} is reserved by evaluator and cannot be defined",
+                    unmarked_name
+                ));
+            }
+
+            match variables.iter().find(|variable| is_reserved(variable)) {
+                Some(variable) => Err(format!(
+                    "The variable name {} is reserved by evaluator and cannot be defined",
+                    variable
+                )),
+                None => Ok(()),
+            }
+        }
+    };
+}
+
+/// Check that variables of a function definition are valid and either all named or all positional
+///
+/// A named variable must be an identifier, see `is_identifier`, whitespaces around it being ignored.
+/// Positional variables `$1`, `$2`, ... are given by a number as in `f: 2 = $1 * $1 + $2`,
+/// thus a definition mixing them with named variables, as `f: x = x + $1`, is rejected
+/// as well as a positional variable greater than number of variables.
+pub fn check_function_variables(
+    name: &str,
+    variables: &[String],
+    definition: &str,
+) -> Result<(), String> {
+    return check_function_variables_with_predicate(
+        name,
+        variables,
+        definition,
+        token::is_identifier_character,
+    );
+}
+
+/// Check variables of a function definition as `check_function_variables`,
+/// named variables being identifiers made of characters accepted by predicate
+///
+/// With a predicate accepting `$`, `$rate` is a valid named variable.
+pub fn check_function_variables_with_predicate(
+    name: &str,
+    variables: &[String],
+    definition: &str,
+    is_identifier_character: IdentifierPredicate,
+) -> Result<(), String> {
+    let is_positional = |variable: &str| -> bool {
+        return variable.strip_prefix('$').is_some_and(|id| {
+            return !id.is_empty() && id.chars().all(|c| c.is_ascii_digit());
+        });
+    };
+
+    let are_positional: bool = variables
+        .iter()
+        .enumerate()
+        .all(|(id, variable)| *variable == format!("${}", id + 1));
+
+    let positional_references: Vec<&str> = Lexer::new(definition)
+        .filter(|lexeme| lexeme.kind == LexemeKind::Identifier)
+        .map(|lexeme| &definition[lexeme.range])
+        .filter(|identifier| is_positional(identifier))
+        .collect();
+
+    if !are_positional {
+        if let Some(variable) = variables.iter().find(|variable| {
+            let variable: &str = variable.trim();
+
+            return !is_positional(variable)
+                && !is_identifier_with_predicate(variable, is_identifier_character);
+        }) {
+            return Err(format!(
+                "The variable name {} of function {} is not valid",
+                variable, name
+            ));
+        }
+
+        if variables.iter().any(|variable| is_positional(variable))
+            || !positional_references.is_empty()
+        {
+            return Err(format!(
+                "The function {} mixes named and positional variables",
+                name
+            ));
+        }
+
+        return Ok(());
+    }
+
+    if let Some(reference) = positional_references
+        .into_iter()
+        .find(|reference| !variables.iter().any(|variable| variable == reference))
+    {
+        return Err(format!(
+            "The positional variable {} is greater than number of variables of function {}",
+            reference, name
+        ));
+    }
+
+    return Ok(());
+}
+
+/// Check that arguments of function calls and variables of a function definition
+/// are separated by given separator, `,` or `;`
+///
+/// The other separator is rejected at these places, thus with `;` as separator,
+/// `f(1, 2)` gives an error indicating column of `,`. Elsewhere, as in `(1, 2)`, it is left to evaluator.
+pub fn check_argument_separator(expression: &str, argument_separator: char) -> Result<(), String> {
+    let lexeme_str = |lexeme: &Lexeme| -> &str { &expression[lexeme.range.clone()] };
+
+    let other_separator: &str = if argument_separator == ';' { "," } else { ";" };
+
+    // Variables of a function definition are between `:` and `=`
+    let variables_range: Range<usize> = expression::assignment_split(expression)
+        .and_then(|(position, _)| {
+            return expression[..position]
+                .find(':')
+                .map(|colon_position| (colon_position + 1)..position);
+        })
+        .unwrap_or(0..0);
+
+    // Stack of opened brackets, each one is true if it starts arguments of a function call,
+    // thus elements of a vector, as `[1, 2]`, can be separated by `,` whatever the argument separator
+    let mut opened_brackets: Vec<bool> = Vec::new();
+    let mut previous_lexeme: Option<Lexeme> = None;
+
+    for lexeme in Lexer::new(expression) {
+        match lexeme_str(&lexeme) {
+            "(" => opened_brackets.push(
+                previous_lexeme
+                    .as_ref()
+                    .is_some_and(|previous_lexeme| previous_lexeme.kind == LexemeKind::Identifier),
+            ),
+            "[" => opened_brackets.push(false),
+            ")" | "]" => {
+                opened_brackets.pop();
+            }
+            separator
+                if separator == other_separator
+                    && (variables_range.contains(&lexeme.range.start)
+                        || opened_brackets.last() == Some(&true)) =>
+            {
+                return Err(format!(
+                    "The argument separator '{}' at column {} is not valid, arguments are separated by '{}'",
+                    separator,
+                    expression[..lexeme.range.start].chars().count() + 1,
+                    argument_separator
+                ));
+            }
+            _ => {}
+        }
+
+        previous_lexeme = Some(lexeme);
+    }
+
+    return Ok(());
+}
+
+/// Way to handle unsafe characters of an input, see `is_unsafe_character`
+///
+/// Reject gives an error indicating first unsafe character and its column, Strip removes them all.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UnsafeCharacterPolicy {
+    #[default]
+    Reject,
+    Strip,
+}
+
+/// Check if a character is unsafe in an input, since it could spoof display of definitions
+///
+/// They are control characters except tab and newline, i.e. C0 controls, DEL and C1 controls,
+/// and bidirectional control characters: marks U+061C, U+200E and U+200F, embeddings and overrides
+/// from U+202A to U+202E and isolates from U+2066 to U+2069.
+pub fn is_unsafe_character(c: char) -> bool {
+    return (c.is_control() && c != '\t' && c != '\n')
+        || matches!(c, '\u{061c}' | '\u{200e}' | '\u{200f}')
+        || ('\u{202a}'..='\u{202e}').contains(&c)
+        || ('\u{2066}'..='\u{2069}').contains(&c);
+}
+
+/// Find first unsafe character of an input with its column, none if input is safe
+pub fn find_unsafe_character(input: &str) -> Option<(char, usize)> {
+    return input
+        .chars()
+        .enumerate()
+        .find(|(_, c)| is_unsafe_character(*c))
+        .map(|(id, c)| (c, id + 1));
+}
+
+/// Check that a state imported from outside of calculator, as a binary state or a fragment,
+/// only contains definitions which calculator could have stored
+///
+/// Names of variables and functions must be identifiers according to identifier predicate,
+/// see `is_identifier_with_predicate`, variables of functions must be all named or all positional,
+/// see `check_function_variables`, and no name or definition can contain an unsafe character.
+/// A reference cannot have the name of a variable and must lead to a variable without cycle,
+/// a lazy variable or a constant must be defined as a variable and ranges are checked by `check_range`.
+pub fn check_state(
+    state: &CalculatorState,
+    is_identifier_character: IdentifierPredicate,
+) -> Result<(), String> {
+    let check_characters = |text: &str, name: &str| -> Result<(), String> {
+        return match text.chars().find(|c| is_unsafe_character(*c)) {
+            Some(c) => Err(format!(
+                "The character U+{:04X} in definition of {} is not allowed",
+                u32::from(c),
+                name
+            )),
+            None => Ok(()),
+        };
+    };
+
+    for name in state.variables.keys() {
+        check_characters(name, name)?;
+
+        if !is_identifier_with_predicate(name, is_identifier_character) {
+            return Err(format!("The variable name {} is not valid", name));
+        }
+    }
+
+    for (name, (variables, definition)) in &state.functions {
+        check_characters(name, name)?;
+
+        if !is_identifier_with_predicate(name, is_identifier_character) {
+            return Err(format!("The function name {} is not valid", name));
+        }
+
+        for variable in variables {
+            check_characters(variable, name)?;
+        }
+
+        check_characters(definition, name)?;
+
+        check_function_variables_with_predicate(
+            name,
+            variables,
+            definition,
+            is_identifier_character,
+        )?;
+    }
+
+    let mut references: Vec<(&String, &String)> = state.references.iter().collect();
+    references.sort_unstable();
+
+    for (name, target) in references.iter() {
+        for identifier in [name, target] {
+            check_characters(identifier, name)?;
+
+            if !is_identifier_with_predicate(identifier, is_identifier_character) {
+                return Err(format!("The variable name {} is not valid", identifier));
+            }
+        }
+
+        if state.variables.contains_key(*name) {
+            return Err(format!(
+                "The reference {} is also defined as a variable",
+                name
+            ));
+        }
+    }
+
+    for (name, target) in references {
+        let mut current_target: &str = target;
+
+        for _ in 0..state.references.len() {
+            match state.references.get(current_target) {
+                Some(next_target) => current_target = next_target.as_str(),
+                None => break,
+            }
+        }
+
+        if !state.variables.contains_key(current_target) {
+            return Err(format!(
+                "The reference {} does not lead to a defined variable",
+                name
+            ));
+        }
+    }
+
+    for (name, definition) in &state.lazy_definitions {
+        if !state.variables.contains_key(name) {
+            return Err(format!("The lazy variable {} is not defined", name));
+        }
+
+        check_characters(definition, name)?;
+    }
+
+    if let Some(name) = state
+        .constants
+        .iter()
+        .find(|name| !state.variables.contains_key(*name))
+    {
+        return Err(format!("The constant {} is not defined", name));
+    }
+
+    for (name, range) in &state.variable_ranges {
+        check_characters(name, name)?;
+        check_range(name, range, is_identifier_character)?;
+    }
+
+    return Ok(());
+}
+
+/// Check that a range can be declared for a variable, see `Calculator::set_range`
+///
+/// Name must be an identifier according to identifier predicate, minimum cannot be greater
+/// than maximum and step, if any, must be positive and finite.
+pub fn check_range(
+    name: &str,
+    range: &VariableRange,
+    is_identifier_character: IdentifierPredicate,
+) -> Result<(), String> {
+    if !is_identifier_with_predicate(name, is_identifier_character) {
+        return Err(format!("The variable name {} is not valid", name));
+    }
+
+    if range.min.is_nan() || range.max.is_nan() || range.min > range.max {
+        return Err(format!(
+            "The range [{}, {}] of variable {} is not valid",
+            range.min, range.max, name
+        ));
+    }
+
+    if let Some(step) = range.step.filter(|step| *step <= 0.0 || !step.is_finite()) {
+        return Err(format!(
+            "The step {} of variable {} is not valid",
+            step, name
+        ));
+    }
+
+    return Ok(());
+}
+
+/// Find numbers of an expression which cannot be stored exactly as 64-bit float, with their stored value
+///
+/// A number is imprecise when its significant digits differ from the ones of the shortest writing
+/// of its stored value, as `9007199254740993` stored as `9007199254740992` or a decimal number
+/// with more significant digits than a 64-bit float can hold. Thus `0.1` is not imprecise,
+/// since it is written again `0.1`, whereas an overflowing number as `1e400` is.
+pub fn find_imprecise_numbers(expression: &str) -> Vec<(&str, f64)> {
+    return Lexer::new(expression)
+        .filter(|lexeme| lexeme.kind == LexemeKind::Number)
+        .map(|lexeme| &expression[lexeme.range])
+        .filter_map(|number_str| {
+            let value: f64 = number_str.parse::<f64>().ok()?;
+
+            if significant_digits(number_str) == significant_digits(format!("{:e}", value).as_str())
+            {
+                return None;
+            }
+
+            return Some((number_str, value));
+        })
+        .collect();
+}
+
+/// Get significant digits of a decimal number and exponent of its last digit, as `("15", -1)` for `1.50`,
+/// none if it is not a decimal number
+fn significant_digits(number_str: &str) -> Option<(String, i64)> {
+    let (mantissa, exponent_str): (&str, &str) = match number_str.split_once(['e', 'E']) {
+        Some(parts) => parts,
+        None => (number_str, "0"),
+    };
+
+    let (integer_part, decimal_part): (&str, &str) =
+        mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let mut exponent: i64 = exponent_str.parse::<i64>().ok()? - decimal_part.len() as i64;
+
+    let digits: String = format!("{}{}", integer_part, decimal_part);
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut significant_digits: &str = digits.trim_start_matches('0');
+
+    while let Some(remaining_digits) = significant_digits.strip_suffix('0') {
+        significant_digits = remaining_digits;
+        exponent += 1;
+    }
+
+    if significant_digits.is_empty() {
+        return Some((String::from("0"), 0));
+    }
+
+    return Some((String::from(significant_digits), exponent));
+}
+
+/// Get closing bracket corresponding to opening bracket given in argument
+fn closing_bracket(opening_bracket: char) -> char {
+    return match opening_bracket {
+        '[' => ']',
+        '{' => '}',
+        _ => ')',
+    };
+}
+
+/// Check if an expression is well-formed for an evaluator, independently of any calculator
+///
+/// Brackets must be balanced, each operator must have its operands, each number must be valid
+/// and each identifier must be a valid name, function calls as `f(x, 2)` and vectors as `[1, 2][0]`
+/// being allowed. Defined names are not checked, thus `foo(1)` is well-formed, as well as `-x`
+/// and `!b` with unary operators `+`, `-` and `!`. A definition, as `x = 1`, is not an expression.
+/// As in built-in evaluator, a binary operator can be followed by unary operators, as `2 - -3`
+/// or `2 * +x`, but not by another binary operator, as `2 * / 3`.
+pub fn is_syntactically_valid(expression: &str) -> bool {
+    let is_operator_part = |c: char| -> bool { return "<>=!&|".contains(c) };
+
+    // Openings of brackets not yet closed, with a flag indicating if they accept separators
+    let mut openings: Vec<(&str, bool)> = Vec::new();
+    let mut previous_text: &str = "";
+    let mut previous_kind: Option<LexemeKind> = None;
+    let mut previous_end: usize = 0;
+    let mut is_operand_expected: bool = true;
+
+    let mut lexemes = Lexer::new(expression).peekable();
+
+    while let Some(lexeme) = lexemes.next() {
+        let mut end: usize = lexeme.range.end;
+        let text: &str = &expression[lexeme.range.start..end];
+
+        // Consecutive symbols of a comparison or logical operator, as `<=` or `&&`, form one operator
+        if lexeme.kind == LexemeKind::Symbol && text.chars().all(is_operator_part) {
+            if let Some(next_lexeme) = lexemes.next_if(|next_lexeme| {
+                return next_lexeme.range.start == end
+                    && expression[next_lexeme.range.clone()]
+                        .chars()
+                        .all(is_operator_part);
+            }) {
+                end = next_lexeme.range.end;
+            }
+        }
+
+        let text: &str = &expression[lexeme.range.start..end];
+
+        let is_valid: bool = match (lexeme.kind, text) {
+            (LexemeKind::Number, _) => is_operand_expected && text.parse::<f64>().is_ok(),
+            (LexemeKind::Identifier, _) => is_operand_expected && is_identifier(text),
+            (_, "(") => {
+                let is_call: bool =
+                    !is_operand_expected && previous_kind == Some(LexemeKind::Identifier);
+                openings.push(("(", is_call));
+                is_operand_expected || is_call
+            }
+            (_, "[") => {
+                openings.push(("[", is_operand_expected));
+                true
+            }
+            (_, ")" | "]") => {
+                let is_empty_call: bool =
+                    previous_text == "(" && openings.last() == Some(&("(", true)) && text == ")";
+
+                let is_closed: bool = matches!(
+                    (openings.pop(), text),
+                    (Some(("(", _)), ")") | (Some(("[", _)), "]")
+                );
+
+                is_closed && (!is_operand_expected || is_empty_call)
+            }
+            (_, ",") => {
+                !is_operand_expected && openings.last().is_some_and(|(_, is_list)| *is_list)
+            }
+            (_, "+" | "-") => true,
+            (_, "!") => is_operand_expected,
+            _ => {
+                !is_operand_expected && text != "=" && expression::BINARY_OPERATORS.contains(&text)
+            }
+        };
+
+        if !is_valid {
+            return false;
+        }
+
+        is_operand_expected = lexeme.kind == LexemeKind::Symbol && !matches!(text, ")" | "]");
+        previous_text = text;
+        previous_kind = Some(lexeme.kind);
+        previous_end = end;
+    }
+
+    return previous_end > 0 && !is_operand_expected && openings.is_empty();
+}
+
+/// Validate an expression before its process
+///
+/// It is used by calculator in strict mode to report syntax errors
+/// instead of confusing errors from evaluator.
+pub fn validate(expression: &str) -> Result<(), String> {
+    check_trailing_tokens(expression)?;
+    check_brackets(expression)?;
+    check_operators(expression)?;
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_brackets_with_nested_brackets() {
+        assert!(check_brackets("([a])").is_ok());
+        assert!(check_brackets("{x * [y + (z - 1)]} / 2").is_ok());
+        assert!(check_brackets("1 + 1").is_ok());
+    }
+
+    #[test]
+    fn test_check_brackets_with_mismatched_brackets() {
+        assert_eq!(
+            check_brackets("(a]"),
+            Err(String::from(
+                "The bracket ']' at column 3 does not match bracket '(' at column 1"
+            ))
+        );
+
+        assert_eq!(
+            check_brackets("[a)"),
+            Err(String::from(
+                "The bracket ')' at column 3 does not match bracket '[' at column 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_check_brackets_with_unbalanced_brackets() {
+        assert_eq!(
+            check_brackets("(a + [b]"),
+            Err(String::from("The bracket '(' at column 1 is not closed"))
+        );
+
+        assert_eq!(
+            check_brackets("a + b)"),
+            Err(String::from("The bracket ')' at column 6 is not opened"))
+        );
+    }
+
+    #[test]
+    fn test_check_token_lengths() {
+        let identifier: String = "a".repeat(5000);
+        let number: String = "7".repeat(5000);
+
+        assert!(check_token_lengths("velocity * 2.5e3", 8).is_ok());
+
+        assert_eq!(
+            check_token_lengths(format!("é + {}", identifier).as_str(), 4096),
+            Err(String::from(
+                "The token is too long at column 5 (maximal length is 4096)"
+            ))
+        );
+
+        assert_eq!(
+            check_token_lengths(format!("1 + ({}.5)", number).as_str(), 4096),
+            Err(String::from(
+                "The token is too long at column 6 (maximal length is 4096)"
+            ))
+        );
+
+        assert!(check_token_lengths(number.as_str(), 5000).is_ok());
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("x"));
+        assert!(is_identifier("velocity_2"));
+        assert!(is_identifier("_x"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("2x"));
+        assert!(!is_identifier("y - 2"));
+    }
+
+    #[test]
+    fn test_is_identifier_with_predicate() {
+        let is_extended_character = |c: char| -> bool { c.is_alphanumeric() || "_$.".contains(c) };
+
+        assert!(is_identifier_with_predicate("x", is_extended_character));
+        assert!(is_identifier_with_predicate(
+            "$rate.year",
+            is_extended_character
+        ));
+        assert!(!is_identifier_with_predicate("$1", is_extended_character));
+        assert!(!is_identifier_with_predicate("2$", is_extended_character));
+        assert!(!is_identifier_with_predicate(
+            "$rate",
+            crate::token::is_identifier_character
+        ));
+    }
+
+    #[test]
+    fn test_check_trailing_tokens() {
+        assert!(check_trailing_tokens("distance(2, 3) * -x").is_ok());
+        assert!(check_trailing_tokens("f: x, y = x * (y + 1.5e-3)").is_ok());
+        assert!(check_trailing_tokens("`x + [y] / {2}").is_ok());
+
+        assert_eq!(
+            check_trailing_tokens("2 + 2 oops"),
+            Err(String::from("The token 'oops' at column 7 is unexpected"))
+        );
+
+        assert_eq!(
+            check_trailing_tokens("distance(2,3) extra)"),
+            Err(String::from("The token 'extra' at column 15 is unexpected"))
+        );
+
+        assert_eq!(
+            check_trailing_tokens("(1 + 2) (3)"),
+            Err(String::from("The token '(' at column 9 is unexpected"))
+        );
+    }
+
+    #[test]
+    fn test_validate_with_stray_closing_parenthesis() {
+        assert_eq!(
+            validate("2 + 2)"),
+            Err(String::from("The bracket ')' at column 6 is not opened"))
+        );
+
+        assert!(validate("x = (2 + 2) * y").is_ok());
+    }
+
+    #[test]
+    fn test_check_operators() {
+        let valid_expressions: [&str; 9] = [
+            "+5",
+            "--3",
+            "2 - -3",
+            "2 * +x",
+            "x = -(2 + 2)",
+            "f: x, y = x * -y",
+            "a <= b && !c",
+            "g!: x = x ^ 2",
+            "[1, -2][0] % 2",
+        ];
+
+        for expression in valid_expressions {
+            assert!(check_operators(expression).is_ok(), "{}", expression);
+        }
+
+        assert_eq!(
+            validate("2 * / 3"),
+            Err(String::from(
+                "The operator '/' at column 5 is missing its left operand"
+            ))
+        );
+
+        assert_eq!(
+            validate("2 + * 3"),
+            Err(String::from(
+                "The operator '*' at column 5 is missing its left operand"
+            ))
+        );
+
+        assert_eq!(
+            validate("* 2"),
+            Err(String::from(
+                "The operator '*' at column 1 is missing its left operand"
+            ))
+        );
+
+        assert_eq!(
+            validate("x = (== 1)"),
+            Err(String::from(
+                "The operator '==' at column 6 is missing its left operand"
+            ))
+        );
+
+        assert_eq!(
+            validate("2 - -"),
+            Err(String::from(
+                "The operator '-' at column 5 is missing its right operand"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_is_syntactically_valid() {
+        let valid_expressions: [&str; 13] = [
+            "1 + 2 * 3",
+            "-(x ^ 2) / .5e-3",
+            "f(x, 2) - g()",
+            "a <= b && !c",
+            "[1, 2][0] * 3",
+            "((1))",
+            "sin(-x) % 2",
+            "1 - -1",
+            "unknown_name",
+            "+5",
+            "--3",
+            "2 * +x",
+            "2 ^ -+1",
+        ];
+
+        for expression in valid_expressions {
+            assert!(is_syntactically_valid(expression), "{}", expression);
+        }
+
+        let invalid_expressions: [&str; 15] = [
+            "", "(1 + 2", "1 + 2)", "1 +", "* 2", "2 3", "f(1,)", "(1, 2)", "x = 1", "1 # 2", "()",
+            "(1]", "2 * / 3", "2 + * 3", "2 - -",
+        ];
+
+        for expression in invalid_expressions {
+            assert!(!is_syntactically_valid(expression), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn test_find_imprecise_numbers() {
+        assert_eq!(
+            find_imprecise_numbers("x + 9007199254740993 * 2"),
+            vec![("9007199254740993", 9007199254740992.0)]
+        );
+
+        assert_eq!(
+            find_imprecise_numbers("0.12345678901234567890"),
+            vec![("0.12345678901234567890", 0.12345678901234568)]
+        );
+
+        assert!(
+            find_imprecise_numbers("9007199254740992 + 0.1 - 1.50e2 * 000.0 + 2E-3").is_empty()
+        );
+    }
+
+    #[test]
+    fn test_find_unsafe_character() {
+        assert_eq!(find_unsafe_character("x = 1\t+ 2\n"), None);
+        assert_eq!(find_unsafe_character("é = 1 + 2"), None);
+        assert_eq!(find_unsafe_character("é = 1 \u{7f}"), Some(('\u{7f}', 7)));
+        assert_eq!(find_unsafe_character("x\u{85}"), Some(('\u{85}', 2)));
+        assert_eq!(find_unsafe_character("a\u{200f}"), Some(('\u{200f}', 2)));
+        assert_eq!(find_unsafe_character("\u{200e}"), Some(('\u{200e}', 1)));
+        assert_eq!(find_unsafe_character("1\u{61c}"), Some(('\u{61c}', 2)));
+        assert_eq!(find_unsafe_character("ab\u{202e}c"), Some(('\u{202e}', 3)));
+        assert_eq!(find_unsafe_character("\u{2069}"), Some(('\u{2069}', 1)));
+        assert_eq!(find_unsafe_character("1 +\r2"), Some(('\r', 4)));
+        assert_eq!(find_unsafe_character("\0"), Some(('\0', 1)));
+    }
+
+    #[test]
+    fn test_check_state() {
+        let is_identifier_character: IdentifierPredicate = crate::token::is_identifier_character;
+
+        let mut state: CalculatorState = CalculatorState::default();
+        state
+            .variables
+            .insert(String::from("x"), crate::value::Value::Number(2.0));
+        state.functions.insert(
+            String::from("f"),
+            (vec![String::from("$1")], String::from("$1 * x")),
+        );
+
+        assert!(check_state(&state, is_identifier_character).is_ok());
+
+        let invalid_states: Vec<(&str, Vec<String>, &str, &str)> = vec![
+            (
+                "g\u{202e}",
+                Vec::new(),
+                "1",
+                "The character U+202E in definition of g\u{202e} is not allowed",
+            ),
+            (
+                "g",
+                vec![String::from("a")],
+                "a\u{7f}",
+                "The character U+007F in definition of g is not allowed",
+            ),
+            ("2g", Vec::new(), "1", "The function name 2g is not valid"),
+            (
+                "g",
+                vec![String::from("a b")],
+                "1",
+                "The variable name a b of function g is not valid",
+            ),
+            (
+                "g",
+                vec![String::from("a")],
+                "a + $1",
+                "The function g mixes named and positional variables",
+            ),
+        ];
+
+        for (name, variables, definition, message) in invalid_states {
+            let mut invalid_state: CalculatorState = state.clone();
+            invalid_state
+                .functions
+                .insert(String::from(name), (variables, String::from(definition)));
+
+            assert_eq!(
+                check_state(&invalid_state, is_identifier_character),
+                Err(String::from(message))
+            );
+        }
+
+        let mut invalid_state: CalculatorState = state.clone();
+        invalid_state
+            .variables
+            .insert(String::from("y - 2"), crate::value::Value::Number(1.0));
+
+        assert_eq!(
+            check_state(&invalid_state, is_identifier_character),
+            Err(String::from("The variable name y - 2 is not valid"))
+        );
+
+        let mut state_with_references: CalculatorState = state.clone();
+        state_with_references
+            .references
+            .insert(String::from("a"), String::from("x"));
+        state_with_references
+            .references
+            .insert(String::from("b"), String::from("a"));
+
+        assert!(check_state(&state_with_references, is_identifier_character).is_ok());
+
+        let invalid_references: Vec<(&str, &str, &str)> = vec![
+            (
+                "c",
+                "z",
+                "The reference c does not lead to a defined variable",
+            ),
+            (
+                "c",
+                "d",
+                "The reference c does not lead to a defined variable",
+            ),
+            ("x", "a", "The reference x is also defined as a variable"),
+            ("c", "1z", "The variable name 1z is not valid"),
+        ];
+
+        let mut invalid_state: CalculatorState = state.clone();
+        invalid_state
+            .lazy_definitions
+            .insert(String::from("w"), String::from("x * 2"));
+
+        assert_eq!(
+            check_state(&invalid_state, is_identifier_character),
+            Err(String::from("The lazy variable w is not defined"))
+        );
+
+        let mut invalid_state: CalculatorState = state.clone();
+        invalid_state.constants.insert(String::from("w"));
+
+        assert_eq!(
+            check_state(&invalid_state, is_identifier_character),
+            Err(String::from("The constant w is not defined"))
+        );
+
+        for (name, target, message) in invalid_references {
+            let mut invalid_state: CalculatorState = state_with_references.clone();
+            invalid_state
+                .references
+                .insert(String::from(name), String::from(target));
+
+            if target == "d" {
+                invalid_state
+                    .references
+                    .insert(String::from("d"), String::from("c"));
+            }
+
+            assert_eq!(
+                check_state(&invalid_state, is_identifier_character),
+                Err(String::from(message))
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_function_variables() {
+        let named: Vec<String> = vec![String::from("x"), String::from("y")];
+        let positional: Vec<String> = vec![String::from("$1"), String::from("$2")];
+
+        assert!(check_function_variables("f", &named, "x * y").is_ok());
+        assert!(check_function_variables("f", &positional, "$1 * $2 + $1").is_ok());
+        assert!(check_function_variables("f", &[String::from(" x\t")], "x * 2").is_ok());
+
+        assert_eq!(
+            check_function_variables("f", &[String::from("x y")], "x * y"),
+            Err(String::from(
+                "The variable name x y of function f is not valid"
+            ))
+        );
+        assert!(check_function_variables("f", &[String::from("$rate")], "$rate").is_err());
+        assert!(check_function_variables_with_predicate(
+            "f",
+            &[String::from("$rate")],
+            "$rate * 2",
+            |c| c.is_alphanumeric() || c == '_' || c == '$'
+        )
+        .is_ok());
+
+        assert_eq!(
+            check_function_variables("f", &named, "x * $1"),
+            Err(String::from(
+                "The function f mixes named and positional variables"
+            ))
+        );
+
+        assert_eq!(
+            check_function_variables("f", &[String::from("x"), String::from("$2")], "x"),
+            Err(String::from(
+                "The function f mixes named and positional variables"
+            ))
+        );
+
+        assert_eq!(
+            check_function_variables("f", &positional, "$1 * $3"),
+            Err(String::from(
+                "The positional variable $3 is greater than number of variables of function f"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_check_argument_separator() {
+        assert!(check_argument_separator("f(1, g(2, 3)) + (4; 5)", ',').is_ok());
+        assert!(check_argument_separator("f: x; y = max(x; y) + (1, 2)", ';').is_ok());
+        assert!(check_argument_separator("f([1, 2]; g([3, 4][0]))", ';').is_ok());
+
+        assert_eq!(
+            check_argument_separator("f(1; g(2, 3))", ';'),
+            Err(String::from(
+                "The argument separator ',' at column 9 is not valid, arguments are separated by ';'"
+            ))
+        );
+
+        assert_eq!(
+            check_argument_separator("f: x, y = x * y", ';'),
+            Err(String::from(
+                "The argument separator ',' at column 5 is not valid, arguments are separated by ';'"
+            ))
+        );
+
+        assert_eq!(
+            check_argument_separator("f(1; 2)", ','),
+            Err(String::from(
+                "The argument separator ';' at column 4 is not valid, arguments are separated by ','"
+            ))
+        );
+    }
+}
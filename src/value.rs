@@ -0,0 +1,255 @@
+use std::fmt;
+
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::ToPrimitive;
+
+/// Value given by evaluation of an expression
+///
+/// Number is a 64-bit float, Bool is a boolean as result of a comparison
+/// and Integer is a 64-bit signed integer. Vector is a list of 64-bit floats, as given by
+/// `evaluator::BuiltinEvaluator`, it is written between brackets as `[1, 2, 3]`.
+///
+/// With feature `decimal`, Decimal is an exact decimal number, as given by `evaluator::DecimalEvaluator`.
+/// It is written without trailing zeros, so that it is read again exactly when it replaces a variable.
+///
+/// With feature `complex`, Complex is a complex number, as given by `evaluator::ComplexEvaluator`.
+/// It is written `a + bi`, or only its real part when imaginary part is zero,
+/// and it is written between parenthesis when it replaces a variable.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+    Integer(i64),
+    Vector(Vec<f64>),
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal),
+    #[cfg(feature = "complex")]
+    Complex(num_complex::Complex64),
+}
+
+/// Way to write a boolean value when it replaces a variable in an expression
+///
+/// Literal writes `true` or `false` and Numeric writes `1` or `0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BooleanSubstitution {
+    #[default]
+    Literal,
+    Numeric,
+}
+
+impl Value {
+    /// Convert value to 64-bit float, a boolean gives 1 or 0
+    ///
+    /// A vector gives NaN, a complex number gives its real part if its imaginary part is zero, else NaN.
+    pub fn to_f64(&self) -> f64 {
+        return match self {
+            Self::Number(number) => *number,
+            Self::Bool(boolean) => f64::from(u8::from(*boolean)),
+            Self::Integer(integer) => *integer as f64,
+            Self::Vector(_) => f64::NAN,
+            #[cfg(feature = "decimal")]
+            Self::Decimal(decimal) => decimal.to_f64().unwrap_or(f64::NAN),
+            #[cfg(feature = "complex")]
+            Self::Complex(complex) if complex.im == 0.0 => complex.re,
+            #[cfg(feature = "complex")]
+            Self::Complex(_) => f64::NAN,
+        };
+    }
+
+    /// Convert value to 64-bit float as `to_f64`, a vector or a complex number
+    /// whose imaginary part is not zero gives an error instead of NaN
+    pub fn try_to_f64(&self) -> Result<f64, String> {
+        return match self {
+            Self::Vector(_) => Err(format!(
+                "The value {} cannot be converted to a real number",
+                self
+            )),
+            #[cfg(feature = "complex")]
+            Self::Complex(complex) if complex.im != 0.0 => Err(format!(
+                "The value {} cannot be converted to a real number",
+                self
+            )),
+            _ => Ok(self.to_f64()),
+        };
+    }
+
+    /// Get string replacing a variable with this value in an expression
+    pub fn substitution(&self, boolean_substitution: BooleanSubstitution) -> String {
+        return match (self, boolean_substitution) {
+            (Self::Bool(boolean), BooleanSubstitution::Numeric) => {
+                format!("{}", u8::from(*boolean))
+            }
+            #[cfg(feature = "complex")]
+            (Self::Complex(_), _) => format!("({})", self),
+            _ => format!("{}", self),
+        };
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::Number(number) => write!(f, "{}", number),
+            Self::Bool(boolean) => write!(f, "{}", boolean),
+            Self::Integer(integer) => write!(f, "{}", integer),
+            Self::Vector(elements) => write!(f, "{}", format_vector(elements, f64::to_string)),
+            #[cfg(feature = "decimal")]
+            Self::Decimal(decimal) => write!(f, "{}", decimal.normalize()),
+            #[cfg(feature = "complex")]
+            Self::Complex(complex) => {
+                write!(f, "{}", format_complex(complex, |part| part.to_string()))
+            }
+        };
+    }
+}
+
+/// Write a vector between brackets as `[1, 2, 3]` where each element is written by function given in argument
+pub(crate) fn format_vector<FormatElement>(
+    elements: &[f64],
+    format_element: FormatElement,
+) -> String
+where
+    FormatElement: Fn(&f64) -> String,
+{
+    let written_elements: Vec<String> = elements.iter().map(format_element).collect();
+
+    return format!("[{}]", written_elements.join(", "));
+}
+
+/// Write a complex number as `a + bi` where each part is written by function given in argument
+///
+/// Only real part is written if imaginary part is zero.
+#[cfg(feature = "complex")]
+pub(crate) fn format_complex<FormatPart>(
+    complex: &num_complex::Complex64,
+    format_part: FormatPart,
+) -> String
+where
+    FormatPart: Fn(f64) -> String,
+{
+    if complex.im == 0.0 {
+        return format_part(complex.re);
+    }
+
+    let sign: char = if complex.im.is_sign_negative() {
+        '-'
+    } else {
+        '+'
+    };
+
+    return format!(
+        "{} {} {}i",
+        format_part(complex.re),
+        sign,
+        format_part(complex.im.abs())
+    );
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        return Self::Number(number);
+    }
+}
+
+/// A value is equal to a 64-bit float if it is a number or an integer with the same numeric value
+impl PartialEq<f64> for Value {
+    fn eq(&self, other: &f64) -> bool {
+        return match self {
+            Self::Bool(_) => false,
+            _ => self.to_f64() == *other,
+        };
+    }
+}
+
+/// Evaluator of mathematical expression giving a value of any kind
+///
+/// Any function taking a string and returning a 64-bit float, as `fn(&str) -> Result<f64, String>`,
+/// is an evaluator giving numbers.
+///
+/// Evaluation takes `&self` rather than `&mut self`, since calculator evaluates expressions through
+/// shared reference, as in `Calculator::evaluate`, `Calculator::expand` or a calculator shared behind
+/// a `RwLock`. An evaluator needing to modify its state during evaluation uses interior mutability,
+/// as a `Cell` or a `Mutex`.
+///
+/// Error of evaluator can be any type which can be written, as `String` or an enumeration,
+/// calculator writes it in its error message and keeps it as `error::Cause::Evaluator`.
+pub trait EvaluateValue {
+    /// Error given when evaluation fails
+    type Error: fmt::Display + Send + Sync + 'static;
+
+    /// Evaluate expression given in argument
+    fn evaluate(&self, expression: &str) -> Result<Value, Self::Error>;
+
+    /// Get names which cannot be defined as variable or function, as `i` for imaginary unit
+    fn reserved_names(&self) -> &[&str] {
+        return &[];
+    }
+
+    /// Get syntax provided by evaluator, as operators, functions and constants, each one with its description
+    ///
+    /// It is shown by command `:help operators`, thus it should reflect configuration of evaluator.
+    fn syntax_help(&self) -> Vec<(String, String)> {
+        return Vec::new();
+    }
+}
+
+impl<Evaluator, EvaluatorError> EvaluateValue for Evaluator
+where
+    Evaluator: Fn(&str) -> Result<f64, EvaluatorError>,
+    EvaluatorError: fmt::Display + Send + Sync + 'static,
+{
+    type Error = EvaluatorError;
+
+    fn evaluate(&self, expression: &str) -> Result<Value, EvaluatorError> {
+        return self(expression).map(Value::Number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_display() {
+        assert_eq!(format!("{}", Value::Number(2.5)), "2.5");
+        assert_eq!(format!("{}", Value::Number(3.0)), "3");
+        assert_eq!(format!("{}", Value::Bool(true)), "true");
+        assert_eq!(format!("{}", Value::Integer(-42)), "-42");
+    }
+
+    #[test]
+    fn test_value_substitution() {
+        let values: Vec<(Value, &str, &str)> = vec![
+            (Value::Number(2.5), "2.5", "2.5"),
+            (Value::Bool(true), "true", "1"),
+            (Value::Bool(false), "false", "0"),
+            (Value::Integer(7), "7", "7"),
+            (Value::Vector(vec![1.0, -2.5]), "[1, -2.5]", "[1, -2.5]"),
+        ];
+
+        for (value, literal_substitution, numeric_substitution) in values {
+            assert_eq!(
+                value.substitution(BooleanSubstitution::Literal),
+                literal_substitution
+            );
+
+            assert_eq!(
+                value.substitution(BooleanSubstitution::Numeric),
+                numeric_substitution
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_evaluator_adapter() {
+        let evaluator = |expression: &str| -> Result<f64, String> {
+            return Ok(expression.len() as f64);
+        };
+
+        assert_eq!(evaluator.evaluate("1 + 1"), Ok(Value::Number(5.0)));
+        assert_eq!(Value::Integer(5), 5.0);
+        assert_ne!(Value::Bool(true), 1.0);
+    }
+}
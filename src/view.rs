@@ -0,0 +1,175 @@
+use crate::value::Value;
+use crate::{CalculatorState, VariableRange};
+
+use std::collections::{HashMap, HashSet};
+
+/// Read-only view of variables, functions and history of a calculator
+///
+/// It is given by `Calculator::read_view` and only borrows calculator, thus it can be taken
+/// through read guard of a `RwLock` around calculator while another thread waits to process
+/// inputs through write guard. Everything read from a view belongs to the same state.
+#[derive(Debug, Clone, Copy)]
+pub struct StateView<'a> {
+    variables: &'a HashMap<String, Value>, // variables defined in calculator
+    functions: &'a HashMap<String, (Vec<String>, String)>, // functions defined in calculator
+    references: &'a HashMap<String, String>, // references defined in calculator, value is name of target
+    lazy_definitions: &'a HashMap<String, String>, // definitions of lazy variables defined in calculator
+    constants: &'a HashSet<String>,                // constants defined in calculator
+    variable_ranges: &'a HashMap<String, VariableRange>, // ranges of variables declared in calculator
+    history: &'a [String], // inputs processed successfully by calculator
+}
+
+impl<'a> StateView<'a> {
+    /// Construct a view from parts of calculator
+    pub(crate) fn new(
+        variables: &'a HashMap<String, Value>,
+        functions: &'a HashMap<String, (Vec<String>, String)>,
+        references: &'a HashMap<String, String>,
+        lazy_definitions: &'a HashMap<String, String>,
+        constants: &'a HashSet<String>,
+        variable_ranges: &'a HashMap<String, VariableRange>,
+        history: &'a [String],
+    ) -> Self {
+        return Self {
+            variables,
+            functions,
+            references,
+            lazy_definitions,
+            constants,
+            variable_ranges,
+            history,
+        };
+    }
+
+    /// Get variables defined in calculator
+    pub fn variables(&self) -> &'a HashMap<String, Value> {
+        return self.variables;
+    }
+
+    /// Get functions defined in calculator, value is (variables, definition)
+    pub fn functions(&self) -> &'a HashMap<String, (Vec<String>, String)> {
+        return self.functions;
+    }
+
+    /// Get inputs processed successfully, from oldest to newest
+    pub fn history(&self) -> &'a [String] {
+        return self.history;
+    }
+
+    /// Get value of a variable, none if it is not defined
+    pub fn variable(&self, name: &str) -> Option<&'a Value> {
+        return self.variables.get(name);
+    }
+
+    /// Get variables and definition of a function, none if it is not defined
+    pub fn function(&self, name: &str) -> Option<&'a (Vec<String>, String)> {
+        return self.functions.get(name);
+    }
+
+    /// Get names of variables starting with given prefix, sorted in alphabetical order
+    pub fn variables_with_prefix(&self, prefix: &str) -> Vec<&'a str> {
+        return names_with_prefix(self.variables, prefix);
+    }
+
+    /// Get names of functions starting with given prefix, sorted in alphabetical order
+    pub fn functions_with_prefix(&self, prefix: &str) -> Vec<&'a str> {
+        return names_with_prefix(self.functions, prefix);
+    }
+
+    /// Copy variables, functions, references, lazy variables, constants and ranges of view in a state, see `Calculator::snapshot`
+    pub fn snapshot(&self) -> CalculatorState {
+        return CalculatorState {
+            variables: self.variables.clone(),
+            functions: self.functions.clone(),
+            references: self.references.clone(),
+            lazy_definitions: self.lazy_definitions.clone(),
+            constants: self.constants.clone(),
+            variable_ranges: self.variable_ranges.clone(),
+        };
+    }
+}
+
+/// Get names of definitions starting with given prefix, sorted in alphabetical order
+fn names_with_prefix<'a, Definition>(
+    definitions: &'a HashMap<String, Definition>,
+    prefix: &str,
+) -> Vec<&'a str> {
+    let mut names: Vec<&str> = definitions
+        .keys()
+        .map(|name| name.as_str())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+
+    names.sort_unstable();
+
+    return names;
+}
+
+#[cfg(all(test, feature = "history"))]
+mod tests {
+    use crate::Calculator;
+
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+
+    fn evaluate(expression: &str) -> Result<f64, String> {
+        return match expression.trim().parse::<f64>() {
+            Ok(number) => Ok(number),
+            Err(_) => Err(String::from("Invalid number")),
+        };
+    }
+
+    type StringEvaluator = fn(&str) -> Result<f64, String>;
+
+    #[test]
+    fn test_state_view_with_concurrent_readers() {
+        let calculator: Arc<RwLock<Calculator<StringEvaluator>>> =
+            Arc::new(RwLock::new(Calculator::new(evaluate as StringEvaluator)));
+
+        let writer = {
+            let calculator = Arc::clone(&calculator);
+
+            thread::spawn(move || {
+                for id in 0..200 {
+                    let mut calculator = calculator.write().unwrap();
+
+                    assert!(calculator.process(format!("x = {}", id).as_str()).is_ok());
+                    assert!(calculator.process("y = x").is_ok());
+                }
+            })
+        };
+
+        let readers: Vec<thread::JoinHandle<()>> = (0..4)
+            .map(|_| {
+                let calculator = Arc::clone(&calculator);
+
+                return thread::spawn(move || {
+                    for _ in 0..200 {
+                        let calculator = calculator.read().unwrap();
+                        let view = calculator.read_view();
+
+                        // Both variables are defined by the same write, thus they are always equal
+                        assert_eq!(view.variable("x"), view.variable("y"));
+
+                        let processed_writes: usize =
+                            view.variable("x").map_or(0, |x| x.to_f64() as usize + 1);
+                        assert_eq!(view.history().len(), 2 * processed_writes);
+                        assert_eq!(view.snapshot().variables.len(), view.variables().len());
+                    }
+                });
+            })
+            .collect();
+
+        writer.join().unwrap();
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        let calculator = calculator.read().unwrap();
+        let view = calculator.read_view();
+
+        assert_eq!(view.variables_with_prefix(""), vec!["x", "y"]);
+        assert_eq!(view.history().len(), 400);
+    }
+}